@@ -0,0 +1,74 @@
+// Steps a pile of fast bodies over a bumpy mesh with the ccd pairs tested on one thread and then on the pool,
+// checks that both runs end up bit for bit the same, and prints how long the steps took.
+//
+//     cargo bench --bench narrow_phase
+
+use std::time::Instant;
+
+use gl_engine::{math_structs::Vec3, object::Object, shape::Shape, world::{PhysicsConfig, PhysicsWorld}};
+
+
+static STEPS: usize = 120;
+static GRID: u16 = 40;
+
+
+fn build_world(parallel: bool) -> PhysicsWorld {
+	let mut world = PhysicsWorld::new(PhysicsConfig { parallel_narrow_phase: parallel, ..Default::default() });
+	
+	let size = 20.0;
+	let mut vertices = Vec::new();
+	for j in 0..=GRID {
+		for i in 0..=GRID {
+			let (x, z) = (size * (i as f32 / GRID as f32 - 0.5), size * (j as f32 / GRID as f32 - 0.5));
+			vertices.push(Vec3(x, 0.3 * (x * 0.8).sin() * (z * 0.6).cos(), z));
+		}
+	}
+	let index = |i: u16, j: u16| j * (GRID + 1) + i;
+	let mut indices = Vec::new();
+	for j in 0..GRID {
+		for i in 0..GRID {
+			indices.push((index(i, j), index(i + 1, j), index(i + 1, j + 1)));
+			indices.push((index(i, j), index(i + 1, j + 1), index(i, j + 1)));
+		}
+	}
+	world.add_body(Object::new(&vertices, &indices));
+	
+	let shapes = [
+		Shape::Box { half_extents: Vec3(0.3, 0.3, 0.3) },
+		Shape::Sphere { radius: 0.3 },
+		Shape::Capsule { half_height: 0.2, radius: 0.2 }
+	];
+	for i in 0..150 {
+		let mut body = Object::new(&[], &[]);
+		body.set_shape(shapes[i % shapes.len()].clone());
+		body.transform = body.transform.rotate_y(0.7 * i as f32).translate(Vec3((i % 10) as f32 * 1.6 - 7.2, 2.0 + (i / 50) as f32 * 1.5, (i / 10 % 5) as f32 * 1.6 - 3.2));
+		// fast enough to cross the mesh within a step, so the ccd pass does the catching
+		body.velocity = Vec3(0.0, -60.0 - i as f32 * 0.1, 0.0);
+		world.add_body(body);
+	}
+	world
+}
+
+fn run(parallel: bool) -> (u64, f64) {
+	let mut world = build_world(parallel);
+	let start = Instant::now();
+	for _ in 0..STEPS {
+		world.step(1.0 / 60.0);
+	}
+	(world.state_hash(), start.elapsed().as_secs_f64() * 1000.0 / STEPS as f64)
+}
+
+
+fn main() {
+	// once each to warm up the caches and the pool
+	run(false);
+	run(true);
+	
+	let (serial_hash, serial_time) = run(false);
+	let (parallel_hash, parallel_time) = run(true);
+	assert_eq!(serial_hash, parallel_hash, "parallel narrow phase diverged from the serial one");
+	
+	println!("threads: {}", rayon::current_num_threads());
+	println!("serial:   {:.3} ms/step", serial_time);
+	println!("parallel: {:.3} ms/step ({:.2}x)", parallel_time, serial_time / parallel_time);
+}
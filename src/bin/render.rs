@@ -0,0 +1,131 @@
+// Renders the scene to a PNG without a window, after letting the physics run for a while.
+//
+//     cargo run --bin render -- --ticks 120 --size 1024x768 --output frame.png
+//
+// Needs nothing but an EGL driver, Mesa's software one does.
+
+use gl_engine::{headless::HeadlessDisplay, math_structs::Vec3, render::{Camera, Renderer, ShadowConfig, ShadowFilter}, world::{PhysicsConfig, PhysicsWorld}};
+
+
+static USAGE: &str = "usage: render [--ticks N] [--size WIDTHxHEIGHT] [--camera X,Y,Z] [--angles HORIZONTAL,VERTICAL] [--light ANGLE] [--no-post-process] [--debug] [--show-cascades] [--shadow-filter hard|kernel|noise|pcss] [--shadow-resolution N] [--output PATH]";
+
+
+struct Options {
+	ticks: u64,
+	size: (u32, u32),
+	camera: Camera,
+	light_angle: f32,
+	post_process: bool,
+	debug: bool,
+	show_cascades: bool,
+	shadows: ShadowConfig,
+	output: String
+}
+
+
+fn parse_list<const N: usize>(arg: &str, value: &str) -> Result<[f32; N], String> {
+	let values = value.split(',').map(|v| v.trim().parse::<f32>()).collect::<Result<Vec<f32>, _>>().map_err(|e| format!("{}: {}", arg, e))?;
+	values.try_into().map_err(|_| format!("{} takes {} comma separated numbers", arg, N))
+}
+
+fn parse_options() -> Result<Options, String> {
+	// the same view the window opens with
+	let camera = Camera { position: Vec3(4.0, 6.0, -11.0), horizontal_angle: 0.3, vertical_angle: 0.0 };
+	let mut options = Options { ticks: 0, size: (1024, 768), camera, light_angle: 0.0, post_process: true, debug: false, show_cascades: false, shadows: ShadowConfig::default(), output: "frame.png".to_string() };
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+		match arg.as_str() {
+			"--ticks" => options.ticks = value()?.parse().map_err(|e| format!("--ticks: {}", e))?,
+			"--size" => {
+				let size = value()?;
+				let (width, height) = size.split_once('x').ok_or("--size looks like 1024x768")?;
+				options.size = (width.parse().map_err(|e| format!("--size: {}", e))?, height.parse().map_err(|e| format!("--size: {}", e))?);
+			}
+			"--camera" => {
+				let [x, y, z] = parse_list::<3>(&arg, &value()?)?;
+				options.camera.position = Vec3(x, y, z);
+			}
+			"--angles" => [options.camera.horizontal_angle, options.camera.vertical_angle] = parse_list::<2>(&arg, &value()?)?,
+			"--light" => options.light_angle = value()?.parse().map_err(|e| format!("--light: {}", e))?,
+			"--no-post-process" => options.post_process = false,
+			"--debug" => options.debug = true,
+			"--show-cascades" => options.show_cascades = true,
+			"--shadow-filter" => options.shadows.filter = match value()?.as_str() {
+				"hard" => ShadowFilter::Hard,
+				"kernel" => ShadowFilter::Kernel,
+				"noise" => ShadowFilter::Noise,
+				"pcss" => ShadowFilter::Pcss,
+				other => return Err(format!("unknown shadow filter {}", other))
+			},
+			"--shadow-resolution" => options.shadows.resolution = value()?.parse().map_err(|e| format!("--shadow-resolution: {}", e))?,
+			"--output" => options.output = value()?,
+			"--help" | "-h" => return Err(String::new()),
+			other => return Err(format!("unknown argument {}", other))
+		}
+	}
+	match (options.size.0 > 0 && options.size.1 > 0, options.shadows.resolution > 0) {
+		(true, true) => Ok(options),
+		(false, _) => Err("--size has to be at least a pixel each way".to_string()),
+		(_, false) => Err("--shadow-resolution has to be at least a texel".to_string())
+	}
+}
+
+
+fn main() {
+	let options = match parse_options() {
+		Ok(options) => options,
+		Err(message) => {
+			if !message.is_empty() { eprintln!("{}", message); }
+			eprintln!("{}", USAGE);
+			std::process::exit(2);
+		}
+	};
+	let display = match HeadlessDisplay::new() {
+		Ok(display) => display,
+		Err(message) => {
+			eprintln!("no headless OpenGL: {}", message);
+			std::process::exit(1);
+		}
+	};
+	
+	let (mut objects, vertex_buffers, index_buffers) = gl_engine::scene::initialize_scene(&display);
+	let (soft_bodies, soft_vertex_buffers, soft_index_buffers) = gl_engine::scene::initialize_soft_bodies(&display);
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	for object in objects.iter() {
+		world.add_body(object.clone());
+	}
+	for soft_body in soft_bodies.into_iter() {
+		world.add_soft_body(soft_body);
+	}
+	let dt = 1.0 / world.config.tick_rate;
+	for _ in 0..options.ticks {
+		world.step(dt);
+	}
+	for (object, &state) in objects.iter_mut().zip(world.dynamic_states().iter()) {
+		object.set_dynamic_state(state);
+	}
+	for (buffer, positions) in soft_vertex_buffers.iter().zip(world.soft_body_positions()) {
+		buffer.write(&positions);
+	}
+	
+	let (width, height) = options.size;
+	let mut renderer = Renderer::new(&display, width, height, 75.0, 0.01, 1000.0);
+	renderer.shadowmap.show_cascades = options.show_cascades;
+	renderer.shadowmap.set_config(&display, options.shadows);
+	let debug_frame = world.debug_frame();
+	let image = match display.capture(width, height, |target| {
+		renderer.render(&display, target, &options.camera, &objects, &vertex_buffers, &index_buffers, &soft_vertex_buffers, &soft_index_buffers, &[], options.debug.then_some(&debug_frame), options.post_process, false, &gl_engine::scene::build_lights(options.light_angle));
+	}) {
+		Ok(image) => image,
+		Err(message) => {
+			eprintln!("couldn't render: {}", message);
+			std::process::exit(1);
+		}
+	};
+	if let Err(e) = image.save_with_format(&options.output, image::ImageFormat::Png) {
+		eprintln!("couldn't write {}: {}", options.output, e);
+		std::process::exit(1);
+	}
+	eprintln!("{}x{} after {} ticks to {}", width, height, options.ticks, options.output);
+}
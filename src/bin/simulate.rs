@@ -0,0 +1,203 @@
+// Runs a scene without a window and writes what every body did on every tick. The scene is the builtin one from
+// scene::build_scene and build_soft_bodies unless a scene file is given, see scene::SceneDescription for what goes in it.
+// The tick rate and integrator given here override the ones in the file.
+//
+//     cargo run --bin simulate -- --ticks 600 --format csv --output run.csv
+//     cargo run --bin simulate -- --scene stack.scene --integrator rk4
+//
+// Trajectories go to stdout unless an output file is given, the summary always goes to stderr. Numbers that aren't
+// finite are written as null in jsonl, which has no way to spell them, and as NaN or inf in csv.
+
+use std::{fs::File, io::{self, BufWriter, Write}, path::Path, time::Instant};
+
+use gl_engine::{integrator::Integrator, math_structs::{Mat3, Vec3}, physics::{ContactEvent, ContactEventKind}, scene::SceneDescription, world::PhysicsWorld};
+
+
+static USAGE: &str = "usage: simulate [--scene PATH] [--ticks N] [--tick-rate HZ] [--format jsonl|csv] [--integrator euler|verlet|rk4] [--output PATH] [--no-soft-bodies]\n\
+	simulates the builtin scene unless a scene file is given";
+
+static CSV_HEADER: &str = "tick,time,record,a,b,px,py,pz,r00,r01,r02,r10,r11,r12,r20,r21,r22,vx,vy,vz,wx,wy,wz,sleeping,nx,ny,nz,impulse,trigger";
+
+
+#[derive(Copy, Clone, PartialEq)]
+enum Format {
+	JsonLines,
+	Csv
+}
+
+struct Options {
+	scene: Option<String>,
+	ticks: u64,
+	tick_rate: Option<f32>, // the scene's own unless given
+	format: Format,
+	integrator: Option<Integrator>,
+	output: Option<String>,
+	soft_bodies: bool
+}
+
+
+fn parse_options() -> Result<Options, String> {
+	let mut options = Options { scene: None, ticks: 600, tick_rate: None, format: Format::JsonLines, integrator: None, output: None, soft_bodies: true };
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+		match arg.as_str() {
+			"--scene" => options.scene = Some(value()?),
+			"--ticks" => options.ticks = value()?.parse().map_err(|e| format!("--ticks: {}", e))?,
+			"--tick-rate" => options.tick_rate = Some(value()?.parse().map_err(|e| format!("--tick-rate: {}", e))?),
+			"--format" => options.format = match value()?.as_str() {
+				"jsonl" => Format::JsonLines,
+				"csv" => Format::Csv,
+				other => return Err(format!("unknown format {}", other))
+			},
+			"--integrator" => {
+				let name = value()?;
+				options.integrator = Some(Integrator::from_name(&name).ok_or(format!("unknown integrator {}", name))?);
+			}
+			"--output" => options.output = Some(value()?),
+			"--no-soft-bodies" => options.soft_bodies = false,
+			"--help" | "-h" => return Err(String::new()),
+			other => return Err(format!("unknown argument {}", other))
+		}
+	}
+	match options.tick_rate.is_none_or(|hz| hz > 0.0) {
+		true => Ok(options),
+		false => Err("--tick-rate has to be positive".to_string())
+	}
+}
+
+
+fn main() {
+	let options = match parse_options() {
+		Ok(options) => options,
+		Err(message) => {
+			if !message.is_empty() { eprintln!("{}", message); }
+			eprintln!("{}", USAGE);
+			std::process::exit(2);
+		}
+	};
+	
+	let output: Box<dyn Write> = match &options.output {
+		Some(path) => Box::new(File::create(path).unwrap_or_else(|e| {
+			eprintln!("can't create {}: {}", path, e);
+			std::process::exit(1);
+		})),
+		None => Box::new(io::stdout().lock())
+	};
+	let mut output = BufWriter::new(output);
+	
+	let mut scene = match &options.scene {
+		Some(path) => SceneDescription::load(Path::new(path)).unwrap_or_else(|message| {
+			eprintln!("{}", message);
+			std::process::exit(1);
+		}),
+		None => SceneDescription::builtin()
+	};
+	if let Some(tick_rate) = options.tick_rate { scene.config.tick_rate = tick_rate; }
+	if let Some(integrator) = options.integrator { scene.config.integrator = integrator; }
+	if !options.soft_bodies { scene.soft_bodies.clear(); }
+	let mut world = scene.build_world();
+	
+	if options.format == Format::Csv {
+		writeln!(output, "{}", CSV_HEADER).unwrap();
+	}
+	
+	let dt = 1.0 / world.config.tick_rate;
+	let first = world.measure();
+	let mut max_penetration = 0.0f32;
+	let mut event_counts = [0usize; 3];
+	let (mut total_tick_time, mut max_tick_time) = (0.0f64, 0.0f64);
+	
+	for tick in 1..=options.ticks {
+		let start = Instant::now();
+		world.step(dt);
+		let tick_time = start.elapsed().as_secs_f64();
+		total_tick_time += tick_time;
+		max_tick_time = max_tick_time.max(tick_time);
+		
+		max_penetration = max_penetration.max(world.diagnostics().max_penetration);
+		for event in world.events() {
+			event_counts[event.kind as usize] += 1;
+		}
+		
+		write_tick(&mut output, options.format, tick, &world).unwrap();
+	}
+	output.flush().unwrap();
+	
+	let last = world.measure();
+	let ticks = options.ticks.max(1) as f64;
+	eprintln!("ticks: {} at {} Hz, {:.3} s simulated", options.ticks, world.config.tick_rate, world.time());
+	eprintln!("bodies: {}, soft bodies: {}", world.len(), world.soft_bodies().len());
+	eprintln!("energy: {:.4} J at the start, {:.4} J at the end (kinetic {:.4}, potential {:.4})", first.total_energy(), last.total_energy(), last.kinetic_energy, last.potential_energy);
+	eprintln!("momentum at the end: linear {}, angular {}", json_vec3(last.linear_momentum), json_vec3(last.angular_momentum));
+	eprintln!("max penetration: {:.5} m", max_penetration);
+	eprintln!("contact events: {} begin, {} persist, {} end", event_counts[0], event_counts[1], event_counts[2]);
+	eprintln!("tick time: {:.3} ms average, {:.3} ms max", 1000.0 * total_tick_time / ticks, 1000.0 * max_tick_time);
+}
+
+
+fn write_tick(output: &mut impl Write, format: Format, tick: u64, world: &PhysicsWorld) -> io::Result<()> {
+	let time = world.time();
+	for (i, object) in world.bodies().iter().enumerate() {
+		let p = object.transform.get_position();
+		let r = object.transform.get_rotation();
+		let (v, w) = (object.velocity, object.angular_velocity);
+		match format {
+			Format::JsonLines => writeln!(output,
+				"{{\"tick\":{},\"time\":{},\"record\":\"body\",\"body\":{},\"position\":{},\"rotation\":{},\"velocity\":{},\"angular_velocity\":{},\"sleeping\":{}}}",
+				tick, time, i, json_vec3(p), json_mat3(&r), json_vec3(v), json_vec3(w), object.sleeping
+			)?,
+			Format::Csv => writeln!(output,
+				"{},{},body,{},,{},{},{},{},{},,,,,",
+				tick, time, i, csv_vec3(p), csv_mat3(&r), csv_vec3(v), csv_vec3(w), object.sleeping as u8
+			)?
+		}
+	}
+	
+	for event in world.events() {
+		let ContactEvent { kind, bodies: (a, b), point, normal, impulse, trigger } = *event;
+		let kind = match kind {
+			ContactEventKind::Begin => "begin",
+			ContactEventKind::Persist => "persist",
+			ContactEventKind::End => "end"
+		};
+		match format {
+			Format::JsonLines => writeln!(output,
+				"{{\"tick\":{},\"time\":{},\"record\":\"{}\",\"bodies\":[{},{}],\"point\":{},\"normal\":{},\"impulse\":{},\"trigger\":{}}}",
+				tick, time, kind, a, b, json_vec3(point), json_vec3(normal), json_f32(impulse), trigger
+			)?,
+			Format::Csv => writeln!(output,
+				"{},{},{},{},{},{},,,,,,,,,,,,,,,,,{},{},{}",
+				tick, time, kind, a, b, csv_vec3(point), csv_vec3(normal), impulse, trigger as u8
+			)?
+		}
+	}
+	Ok(())
+}
+
+// JSON has no NaN or infinity
+fn json_f32(x: f32) -> String {
+	match x.is_finite() {
+		true => x.to_string(),
+		false => "null".to_string()
+	}
+}
+
+fn json_vec3(v: Vec3) -> String {
+	format!("[{},{},{}]", json_f32(v.0), json_f32(v.1), json_f32(v.2))
+}
+
+// rows of the rotation
+fn json_mat3(m: &Mat3) -> String {
+	let row = |i: usize| json_vec3(Vec3(m.0[0][i], m.0[1][i], m.0[2][i]));
+	format!("[{},{},{}]", row(0), row(1), row(2))
+}
+
+fn csv_vec3(v: Vec3) -> String {
+	format!("{},{},{}", v.0, v.1, v.2)
+}
+
+// row by row, like the header
+fn csv_mat3(m: &Mat3) -> String {
+	(0..3).map(|i| csv_vec3(Vec3(m.0[0][i], m.0[1][i], m.0[2][i]))).collect::<Vec<String>>().join(",")
+}
@@ -0,0 +1,136 @@
+use std::{path::{Path, PathBuf}, sync::mpsc, thread::JoinHandle, time::{SystemTime, UNIX_EPOCH}};
+
+use glium::{backend::Facade, framebuffer::SimpleFrameBuffer, texture::{RawImage2d, SrgbTexture2d}};
+
+
+// Frames waiting on the writer before record blocks, a second or so at 60 fps. Encoding a png takes
+// longer than drawing the frame, so the queue is what lets the app keep going while it catches up.
+static MAX_QUEUED_FRAMES: usize = 64;
+
+
+// Dumps frames to a numbered png sequence, frame_00000.png and on, as fast as they come.
+// Whoever feeds it is meant to step the world by frame_dt per frame rather than by the wall clock,
+// so the sequence plays back smoothly at frame_rate however long each frame took to make.
+pub struct Recorder {
+	pub directory: PathBuf,
+	pub frame_rate: f32,
+	frames: usize,
+	frame_tx: Option<mpsc::SyncSender<(PathBuf, image::RgbaImage)>>, // dropped to tell the writer there are no more
+	writer: Option<JoinHandle<Result<(), String>>>
+}
+
+
+// Runs draw against an offscreen target of the given size and reads back what it drew, top row first.
+// What is on screen can't be read back reliably once it's swapped, so a window copies the target onto its frame instead.
+pub fn render_offscreen(facade: &impl Facade, width: u32, height: u32, draw: impl FnOnce(&mut SimpleFrameBuffer)) -> Result<image::RgbaImage, String> {
+	let texture = SrgbTexture2d::empty(facade, width, height).map_err(|e| format!("couldn't make a {}x{} capture texture: {:?}", width, height, e))?;
+	draw(&mut SimpleFrameBuffer::new(facade, &texture).map_err(|e| format!("couldn't draw into the capture texture: {:?}", e))?);
+	
+	let pixels: RawImage2d<u8> = texture.read();
+	let mut image = image::RgbaImage::from_raw(width, height, pixels.data.into_owned()).ok_or("capture texture of the wrong size")?;
+	// rows come bottom first out of gl
+	image::imageops::flip_vertical_in_place(&mut image);
+	Ok(image)
+}
+
+// UTC date and time down to milliseconds, 2024-05-01_13-45-12-345, which sorts the same as by time
+pub fn timestamp() -> String {
+	let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+	let seconds = since_epoch.as_secs();
+	let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+	let time = seconds % 86400;
+	format!("{:04}-{:02}-{:02}_{:02}-{:02}-{:02}-{:03}", year, month, day, time / 3600, time / 60 % 60, time % 60, since_epoch.subsec_millis())
+}
+
+// Howard Hinnant's days to proleptic gregorian date
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+	let z = days + 719468;
+	let era = z.div_euclid(146097);
+	let day_of_era = z.rem_euclid(146097);
+	let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let month_index = (5 * day_of_year + 2) / 153;
+	let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+	let month = match month_index < 10 {
+		true => month_index + 3,
+		false => month_index - 9
+	} as u32;
+	(year_of_era + era * 400 + (month <= 2) as i64, month, day)
+}
+
+// Saves the image as screenshot_<timestamp>.png in directory, making the directory if it has to.
+pub fn save_screenshot(image: &image::RgbaImage, directory: &Path) -> Result<PathBuf, String> {
+	std::fs::create_dir_all(directory).map_err(|e| format!("couldn't create {}: {}", directory.display(), e))?;
+	let path = directory.join(format!("screenshot_{}.png", timestamp()));
+	image.save_with_format(&path, image::ImageFormat::Png).map_err(|e| format!("couldn't write {}: {}", path.display(), e))?;
+	Ok(path)
+}
+
+
+impl Recorder {
+	// Starts a sequence in directory, which can't already have one in it.
+	pub fn new(directory: &Path, frame_rate: f32) -> Result<Self, String> {
+		std::fs::create_dir_all(directory).map_err(|e| format!("couldn't create {}: {}", directory.display(), e))?;
+		if directory.join(Self::frame_name(0)).exists() {
+			return Err(format!("{} already has a recording in it", directory.display()));
+		}
+		
+		let (frame_tx, frame_rx) = mpsc::sync_channel::<(PathBuf, image::RgbaImage)>(MAX_QUEUED_FRAMES);
+		let writer = std::thread::spawn(move || {
+			for (path, image) in frame_rx.iter() {
+				image.save_with_format(&path, image::ImageFormat::Png).map_err(|e| format!("couldn't write {}: {}", path.display(), e))?;
+			}
+			Ok(())
+		});
+		Ok(Self { directory: directory.to_path_buf(), frame_rate, frames: 0, frame_tx: Some(frame_tx), writer: Some(writer) })
+	}
+	
+	fn frame_name(frame: usize) -> String {
+		format!("frame_{:05}.png", frame)
+	}
+	
+	// how far the world moves between two frames of the sequence
+	pub fn frame_dt(&self) -> f32 {
+		1.0 / self.frame_rate
+	}
+	
+	pub fn frames(&self) -> usize {
+		self.frames
+	}
+	
+	// Queues the next frame to be written. Only fails once the writer has, with the reason it did.
+	pub fn record(&mut self, image: image::RgbaImage) -> Result<(), String> {
+		let path = self.directory.join(Self::frame_name(self.frames));
+		let sent = match &self.frame_tx {
+			Some(frame_tx) => frame_tx.send((path, image)).is_ok(),
+			None => false
+		};
+		match sent {
+			true => {
+				self.frames += 1;
+				Ok(())
+			}
+			false => Err(self.stop_writer().err().unwrap_or("the recording was already finished".to_string()))
+		}
+	}
+	
+	// Waits for the queued frames to be written and says how many there are in all.
+	pub fn finish(mut self) -> Result<usize, String> {
+		self.stop_writer()?;
+		Ok(self.frames)
+	}
+	
+	fn stop_writer(&mut self) -> Result<(), String> {
+		self.frame_tx = None;
+		match self.writer.take() {
+			Some(writer) => writer.join().map_err(|_| "the frame writer panicked".to_string())?,
+			None => Ok(())
+		}
+	}
+}
+
+impl Drop for Recorder {
+	fn drop(&mut self) {
+		let _ = self.stop_writer();
+	}
+}
@@ -0,0 +1,200 @@
+use crate::{math_structs::{Mat4, Vec3}, narrow_phase, object::Object, query::{self, CastHit, QueryFilter}, shape::{Convex, Shape}};
+
+
+static UP: Vec3 = Vec3(0.0, 1.0, 0.0);
+static SKIN: f32 = 0.01; // gap kept to every surface, so casts never start out touching
+static MAX_SLIDES: usize = 4;
+static DEPENETRATION_ITERATIONS: usize = 4;
+
+
+// A capsule moved by shape casts rather than forces. It collides with every body but doesn't push them,
+// and moving bodies that run into it shove it out of the way. Up is +y.
+#[derive(Clone, Debug)]
+pub struct CharacterController {
+	pub position: Vec3, // center of the capsule
+	pub velocity: Vec3,
+	pub radius: f32,
+	pub half_height: f32, // to the cap centers, like Shape::Capsule
+	pub eye_height: f32, // above the center
+	pub max_slope: f32, // steepest walkable ground, radians from level
+	pub step_height: f32,
+	pub jump_speed: f32,
+	pub gravity_scale: f32,
+	pub filter: QueryFilter,
+	pub grounded: bool,
+	pub ground_normal: Vec3
+}
+
+
+impl CharacterController {
+	pub fn new(position: Vec3, radius: f32, half_height: f32) -> Self {
+		Self {
+			position,
+			velocity: Vec3(0.0, 0.0, 0.0),
+			radius,
+			half_height,
+			eye_height: half_height,
+			max_slope: 0.25 * std::f32::consts::PI,
+			step_height: 0.3,
+			jump_speed: 5.0,
+			gravity_scale: 1.0,
+			filter: QueryFilter::default(),
+			grounded: false,
+			ground_normal: UP
+		}
+	}
+	
+	pub fn eye_position(&self) -> Vec3 {
+		self.position + UP * self.eye_height
+	}
+	
+	pub fn feet_position(&self) -> Vec3 {
+		self.position - UP * (self.half_height + self.radius)
+	}
+	
+	pub fn shape(&self) -> Shape {
+		Shape::Capsule { half_height: self.half_height, radius: self.radius }
+	}
+	
+	pub fn is_walkable(&self, normal: Vec3) -> bool {
+		normal.dot(UP) >= self.max_slope.cos()
+	}
+	
+	// Walks at the given velocity, only its level part counts. Jumping only works from the ground.
+	pub fn update(&mut self, objects: &[Object], walk_velocity: Vec3, jump: bool, gravity: Vec3, dt: f32) {
+		if dt <= 0.0 { return; }
+		self.depenetrate(objects);
+		
+		let start = self.position;
+		let was_grounded = self.grounded;
+		let jumping = jump && was_grounded;
+		let vertical_speed = match (jumping, was_grounded) {
+			(true, _) => self.jump_speed,
+			(false, true) => 0.0,
+			(false, false) => self.velocity.dot(UP) + gravity.dot(UP) * (self.gravity_scale * dt)
+		};
+		
+		// level movement, going over a step when that gets further than sliding along it
+		let walk = (walk_velocity - UP * walk_velocity.dot(UP)) * dt;
+		let mut position = self.slide(objects, start, walk, true).0;
+		if was_grounded && !jumping && walk.length_squared() > 0.0 {
+			let raised = self.slide(objects, start, UP * self.step_height, false).0;
+			let moved = self.slide(objects, raised, walk, true).0;
+			let drop = moved.dot(UP) - start.dot(UP) + SKIN;
+			if let Some(hit) = self.cast(objects, moved, -UP, drop).filter(|hit| self.ground_normal(objects, hit).is_some()) {
+				let stepped = moved - UP * (hit.distance - SKIN).max(0.0);
+				let level = |p: Vec3| { let d = p - start; (d - UP * d.dot(UP)).length() };
+				if level(stepped) > level(position) + 1e-4 {
+					position = stepped;
+				}
+			}
+		}
+		
+		// falling, rising and landing
+		let (moved, normals) = self.slide(objects, position, UP * (vertical_speed * dt), false);
+		position = moved;
+		let hit_ceiling = vertical_speed > 0.0 && normals.iter().any(|n| n.dot(UP) < 0.0);
+		
+		// stick to the ground when walking down slopes and stairs, otherwise just check for it
+		self.grounded = false;
+		self.ground_normal = UP;
+		if vertical_speed <= 0.0 {
+			let probe = match was_grounded { true => self.step_height, false => 2.0 * SKIN };
+			if let Some(hit) = self.cast(objects, position, -UP, probe + SKIN) {
+				if let Some(normal) = self.ground_normal(objects, &hit) {
+					position -= UP * (hit.distance - SKIN).max(0.0);
+					self.grounded = true;
+					self.ground_normal = normal;
+				}
+			}
+		}
+		
+		self.position = position;
+		self.velocity = (position - start) / dt;
+		// the vertical motion of steps and slopes isn't kept, and a bumped head stops the jump
+		if self.grounded || hit_ceiling {
+			self.velocity -= UP * self.velocity.dot(UP).max(0.0);
+		}
+	}
+	
+	
+	fn cast(&self, objects: &[Object], from: Vec3, direction: Vec3, distance: f32) -> Option<CastHit> {
+		query::shape_cast(objects, &self.shape(), &Mat4::identity().translate(from), direction, distance, &self.filter)
+	}
+	
+	// The walkable surface under a downward cast hit, if there is one. A capsule resting on the edge of a step
+	// touches it with its rounded bottom and gets a slanted normal, so that looks at the face just inside the edge.
+	fn ground_normal(&self, objects: &[Object], hit: &CastHit) -> Option<Vec3> {
+		if self.is_walkable(hit.normal) { return Some(hit.normal); }
+		let level = hit.normal - UP * hit.normal.dot(UP);
+		if level.length_squared() < 1e-6 { return None; }
+		
+		// on a steep slope that point is inside the body, which a ray reports as a hit at zero
+		let origin = hit.point - level.normalize() * SKIN + UP * SKIN;
+		query::ray_cast(objects, origin, -UP, 2.0 * SKIN, &self.filter).filter(|ray| ray.distance > 0.0 && self.is_walkable(ray.normal)).map(|ray| ray.normal)
+	}
+	
+	// Moves as far as it can, then along whatever was hit. Returns where it ended up and the normals it hit.
+	// Walls that are too steep to walk up are treated as vertical when flatten is on, so walking can't climb them.
+	fn slide(&self, objects: &[Object], from: Vec3, motion: Vec3, flatten: bool) -> (Vec3, Vec<Vec3>) {
+		let mut position = from;
+		let mut motion = motion;
+		let mut normals = Vec::new();
+		
+		for _ in 0..MAX_SLIDES {
+			let distance = motion.length();
+			if distance < 1e-6 { break; }
+			let hit = match self.cast(objects, position, motion, distance + SKIN) {
+				Some(hit) => hit,
+				None => {
+					position += motion;
+					break;
+				}
+			};
+			
+			let travel = (hit.distance - SKIN).clamp(0.0, distance);
+			position += motion * (travel / distance);
+			normals.push(hit.normal);
+			
+			let mut normal = hit.normal;
+			if flatten && !self.is_walkable(normal) {
+				let level = normal - UP * normal.dot(UP);
+				if level.length_squared() > 1e-6 { normal = level.normalize(); }
+			}
+			let rest = motion * (1.0 - travel / distance);
+			motion = rest - normal * rest.dot(normal).min(0.0);
+		}
+		(position, normals)
+	}
+	
+	// pushes the capsule out of anything that moved into it, deepest first
+	fn depenetrate(&mut self, objects: &[Object]) {
+		for _ in 0..DEPENETRATION_ITERATIONS {
+			let capsule = Convex { points: vec![self.position - UP * self.half_height, self.position + UP * self.half_height], radius: self.radius };
+			let bounds = capsule.aabb();
+			
+			let mut deepest: Option<(Vec3, f32)> = None;
+			for (i, object) in objects.iter().enumerate() {
+				if !self.filter.accepts(i, object) || !object.world_aabb().overlaps(&bounds) { continue; }
+				
+				let pieces = match object.shape.world_convex(&object.transform) {
+					Some(convex) => vec![convex],
+					None => query::world_triangles(object, &bounds).into_iter().map(|(_, a, b, c)| Convex { points: vec![a, b, c], radius: 0.0 }).collect()
+				};
+				for piece in pieces.iter() {
+					if let Some((normal, separation)) = narrow_phase::separating_normal(&capsule, piece) {
+						let depth = capsule.radius + piece.radius - separation;
+						if depth > 0.0 && deepest.is_none_or(|d| depth > d.1) {
+							deepest = Some((normal, depth));
+						}
+					}
+				}
+			}
+			
+			match deepest {
+				Some((normal, depth)) => self.position += normal * (depth + SKIN),
+				None => break
+			}
+		}
+	}
+}
@@ -0,0 +1,98 @@
+use crate::{math_structs::Vec3, physics::Aabb};
+
+
+static CONTACT_COLOR: Vec3 = Vec3(1.0, 0.2, 0.2);
+static SPECULATIVE_CONTACT_COLOR: Vec3 = Vec3(1.0, 0.6, 0.2);
+static AWAKE_COLOR: Vec3 = Vec3(0.2, 1.0, 0.2);
+static SLEEPING_COLOR: Vec3 = Vec3(0.2, 0.4, 1.0);
+static STATIC_COLOR: Vec3 = Vec3(0.4, 0.4, 0.4);
+static VELOCITY_COLOR: Vec3 = Vec3(1.0, 1.0, 0.2);
+static ANGULAR_VELOCITY_COLOR: Vec3 = Vec3(1.0, 0.2, 1.0);
+static CENTER_COLOR: Vec3 = Vec3(1.0, 1.0, 1.0);
+
+static NORMAL_LENGTH: f32 = 0.5;
+static VELOCITY_SCALE: f32 = 0.2; // seconds of motion an arrow shows
+static MARKER_SIZE: f32 = 0.05;
+
+
+#[derive(Copy, Clone, Debug)]
+pub struct DebugBody {
+	pub aabb: Aabb,
+	pub center: Vec3, // center of mass, which is the model origin
+	pub velocity: Vec3,
+	pub angular_velocity: Vec3,
+	pub sleeping: bool,
+	pub is_static: bool
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct DebugContact {
+	pub point: Vec3,
+	pub normal: Vec3,
+	pub separation: f32
+}
+
+// What the physics step looked like, for drawing on top of the scene. Doesn't need a GL context.
+#[derive(Clone, Debug, Default)]
+pub struct DebugFrame {
+	pub bodies: Vec<DebugBody>,
+	pub contacts: Vec<DebugContact>
+}
+
+
+impl DebugFrame {
+	// line segments as (start, end, color)
+	pub fn lines(&self) -> Vec<(Vec3, Vec3, Vec3)> {
+		let mut lines = Vec::new();
+		
+		for contact in self.contacts.iter() {
+			let color = match contact.separation > 0.0 {
+				true => SPECULATIVE_CONTACT_COLOR,
+				false => CONTACT_COLOR
+			};
+			cross(&mut lines, contact.point, MARKER_SIZE, color);
+			lines.push((contact.point, contact.point + contact.normal * NORMAL_LENGTH, color));
+		}
+		
+		for body in self.bodies.iter() {
+			let color = match (body.is_static, body.sleeping) {
+				(true, _) => STATIC_COLOR,
+				(false, true) => SLEEPING_COLOR,
+				(false, false) => AWAKE_COLOR
+			};
+			aabb(&mut lines, &body.aabb, color);
+			if body.is_static { continue; }
+			
+			cross(&mut lines, body.center, MARKER_SIZE * 2.0, CENTER_COLOR);
+			if !body.sleeping {
+				lines.push((body.center, body.center + body.velocity * VELOCITY_SCALE, VELOCITY_COLOR));
+				lines.push((body.center, body.center + body.angular_velocity * VELOCITY_SCALE, ANGULAR_VELOCITY_COLOR));
+			}
+		}
+		
+		lines
+	}
+}
+
+
+fn cross(lines: &mut Vec<(Vec3, Vec3, Vec3)>, center: Vec3, size: f32, color: Vec3) {
+	for axis in [Vec3(size, 0.0, 0.0), Vec3(0.0, size, 0.0), Vec3(0.0, 0.0, size)] {
+		lines.push((center - axis, center + axis, color));
+	}
+}
+
+fn aabb(lines: &mut Vec<(Vec3, Vec3, Vec3)>, aabb: &Aabb, color: Vec3) {
+	let corner = |i: usize| Vec3(
+		if i & 4 == 0 { aabb.min.0 } else { aabb.max.0 },
+		if i & 2 == 0 { aabb.min.1 } else { aabb.max.1 },
+		if i & 1 == 0 { aabb.min.2 } else { aabb.max.2 }
+	);
+	// corners one bit apart share an edge
+	for i in 0..8 {
+		for bit in [1, 2, 4] {
+			if i & bit == 0 {
+				lines.push((corner(i), corner(i | bit), color));
+			}
+		}
+	}
+}
@@ -0,0 +1,73 @@
+use crate::{math_structs::Vec3, object::Object, physics::Aabb};
+
+
+// Adds forces to every dynamic body through Object::apply_force and apply_torque, once per step before contacts are solved.
+// Sleeping bodies are only looked at to see if they're pushed, and woken if it's enough to get them moving.
+pub trait ForceField: Send {
+	fn apply(&self, object: &mut Object, gravity: Vec3);
+}
+
+// pulls bodies towards a point with an inverse square falloff, repels with a negative strength
+#[derive(Copy, Clone, Debug)]
+pub struct PointAttractor {
+	pub position: Vec3,
+	pub strength: f32, // acceleration at unit distance
+	pub min_distance: f32 // keeps the force finite near the center
+}
+
+impl ForceField for PointAttractor {
+	fn apply(&self, object: &mut Object, _gravity: Vec3) {
+		let offset = self.position - object.transform.get_position();
+		if offset.length_squared() < 1e-12 { return; }
+		let distance_squared = f32::max(offset.length_squared(), self.min_distance * self.min_distance);
+		object.apply_force(offset.normalize() * (self.strength * object.mass() / distance_squared), Vec3(0.0, 0.0, 0.0));
+	}
+}
+
+
+// drags bodies whose center is inside the volume towards the wind velocity
+#[derive(Copy, Clone, Debug)]
+pub struct WindVolume {
+	pub bounds: Aabb,
+	pub velocity: Vec3,
+	pub drag: f32 // force per unit of relative velocity
+}
+
+impl ForceField for WindVolume {
+	fn apply(&self, object: &mut Object, _gravity: Vec3) {
+		let position = object.transform.get_position();
+		if !self.bounds.overlaps(&Aabb { min: position, max: position }) { return; }
+		let relative_velocity = self.velocity - object.velocity;
+		object.apply_force(relative_velocity * self.drag, Vec3(0.0, 0.0, 0.0));
+	}
+}
+
+
+// Water below a horizontal plane. The submerged fraction is estimated from the body's bounding box,
+// which is rough for rotated bodies but cheap and stable.
+#[derive(Copy, Clone, Debug)]
+pub struct Buoyancy {
+	pub surface_height: f32,
+	pub fluid_density: f32,
+	pub linear_drag: f32, // per second, scaled by how much of the body is under
+	pub angular_drag: f32
+}
+
+impl ForceField for Buoyancy {
+	fn apply(&self, object: &mut Object, gravity: Vec3) {
+		let aabb = object.world_aabb();
+		let height = aabb.max.1 - aabb.min.1;
+		let submerged = match height > 1e-6 {
+			true => ((self.surface_height - aabb.min.1) / height).clamp(0.0, 1.0),
+			false => match aabb.min.1 < self.surface_height { true => 1.0, false => 0.0 }
+		};
+		if submerged == 0.0 { return; }
+		
+		let buoyant_force = -gravity * (self.fluid_density * object.volume * submerged);
+		let drag_force = -object.velocity * (self.linear_drag * submerged * object.mass());
+		object.apply_force(buoyant_force + drag_force, Vec3(0.0, 0.0, 0.0));
+		
+		let inertia = object.world_inertia();
+		object.apply_torque(-inertia.mult_vec3(object.angular_velocity) * (self.angular_drag * submerged));
+	}
+}
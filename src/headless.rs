@@ -0,0 +1,130 @@
+use std::{cell::RefCell, ffi::c_void, rc::Rc};
+
+use glium::{backend::{Backend, Context, Facade}, debug::DebugCallbackBehavior, framebuffer::SimpleFrameBuffer, SwapBuffersError};
+use khronos_egl as egl;
+
+use crate::capture;
+
+
+// from EGL_MESA_platform_surfaceless, which needs neither a display server nor a gpu
+static PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+
+// An OpenGL context without a window, made straight through EGL so it works on machines with no display at all,
+// like a GPU-less Linux box running Mesa's llvmpipe. Anything that takes a Display to make its buffers takes this too,
+// and frames are drawn into textures and read back with capture.
+pub struct HeadlessDisplay {
+	context: Rc<Context>,
+	failure: Rc<RefCell<Option<String>>>
+}
+
+struct EglBackend {
+	egl: egl::DynamicInstance<egl::EGL1_5>,
+	display: egl::Display,
+	context: egl::Context,
+	surface: egl::Surface, // a pixel of pbuffer to be current on, everything real is drawn into textures
+	failure: Rc<RefCell<Option<String>>> // the last make_current error, which glium's Backend has no way to return
+}
+
+
+impl HeadlessDisplay {
+	pub fn new() -> Result<Self, String> {
+		let backend = EglBackend::new()?;
+		backend.try_make_current()?;
+		let failure = backend.failure.clone();
+		let context = unsafe { Context::new(backend, true, DebugCallbackBehavior::default()) }.map_err(|e| format!("unusable OpenGL context: {:?}", e))?;
+		Ok(Self { context, failure })
+	}
+	
+	// Runs draw against an offscreen target of the given size and reads back what it drew, top row first.
+	// Failing to make the context current while drawing is an error too, what was read back then isn't the frame.
+	pub fn capture(&self, width: u32, height: u32, draw: impl FnOnce(&mut SimpleFrameBuffer)) -> Result<image::RgbaImage, String> {
+		let image = capture::render_offscreen(self, width, height, draw);
+		match self.failure.borrow_mut().take() {
+			Some(message) => Err(message),
+			None => image
+		}
+	}
+}
+
+
+impl Facade for HeadlessDisplay {
+	fn get_context(&self) -> &Rc<Context> {
+		&self.context
+	}
+}
+
+
+impl EglBackend {
+	fn new() -> Result<Self, String> {
+		let egl = unsafe { egl::DynamicInstance::<egl::EGL1_5>::load_required() }.map_err(|e| format!("couldn't load libEGL: {}", e))?;
+		
+		// Mesa's surfaceless platform first, whatever the default display is otherwise
+		let display = unsafe { egl.get_platform_display(PLATFORM_SURFACELESS_MESA, egl::DEFAULT_DISPLAY, &[egl::ATTRIB_NONE]) }.ok()
+			.or_else(|| unsafe { egl.get_display(egl::DEFAULT_DISPLAY) })
+			.ok_or("no EGL display")?;
+		egl.initialize(display).map_err(|e| format!("couldn't initialize EGL: {}", e))?;
+		
+		let config = egl.choose_first_config(display, &[
+			egl::SURFACE_TYPE, egl::PBUFFER_BIT,
+			egl::RENDERABLE_TYPE, egl::OPENGL_BIT,
+			egl::RED_SIZE, 8,
+			egl::GREEN_SIZE, 8,
+			egl::BLUE_SIZE, 8,
+			egl::ALPHA_SIZE, 8,
+			egl::DEPTH_SIZE, 24,
+			egl::NONE
+		]).map_err(|e| format!("couldn't choose an EGL config: {}", e))?.ok_or("no EGL config for desktop OpenGL")?;
+		
+		egl.bind_api(egl::OPENGL_API).map_err(|e| format!("no desktop OpenGL through EGL: {}", e))?;
+		// the shaders are glsl 150
+		let context = egl.create_context(display, config, None, &[
+			egl::CONTEXT_MAJOR_VERSION, 3,
+			egl::CONTEXT_MINOR_VERSION, 3,
+			egl::CONTEXT_OPENGL_PROFILE_MASK, egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+			egl::NONE
+		]).map_err(|e| format!("couldn't create an OpenGL 3.3 context: {}", e))?;
+		let surface = egl.create_pbuffer_surface(display, config, &[egl::WIDTH, 1, egl::HEIGHT, 1, egl::NONE]).map_err(|e| format!("couldn't create a pbuffer: {}", e))?;
+		
+		Ok(Self { egl, display, context, surface, failure: Rc::new(RefCell::new(None)) })
+	}
+	
+	fn try_make_current(&self) -> Result<(), String> {
+		self.egl.make_current(self.display, Some(self.surface), Some(self.surface), Some(self.context)).map_err(|e| format!("couldn't make the OpenGL context current: {}", e))
+	}
+}
+
+unsafe impl Backend for EglBackend {
+	fn swap_buffers(&self) -> Result<(), SwapBuffersError> {
+		Ok(())
+	}
+	
+	unsafe fn get_proc_address(&self, symbol: &str) -> *const c_void {
+		self.egl.get_proc_address(symbol).map_or(std::ptr::null(), |f| f as *const c_void)
+	}
+	
+	fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+		(1, 1)
+	}
+	
+	fn is_current(&self) -> bool {
+		self.egl.get_current_context() == Some(self.context)
+	}
+	
+	unsafe fn make_current(&self) {
+		if let Err(message) = self.try_make_current() {
+			*self.failure.borrow_mut() = Some(message);
+		}
+	}
+}
+
+impl Drop for EglBackend {
+	// The display is left initialized, every context in the process shares it and terminating it would take the others with it.
+	fn drop(&mut self) {
+		if self.is_current() {
+			let _ = self.egl.make_current(self.display, None, None, None);
+		}
+		let _ = self.egl.destroy_surface(self.display, self.surface);
+		let _ = self.egl.destroy_context(self.display, self.context);
+	}
+}
@@ -0,0 +1,213 @@
+use crate::{math_structs::{Mat4, Vec3}, physics::Aabb, query::ray_triangle};
+
+
+// A grid of heights over the xz plane of its model space, centered on the origin. Columns run along x and rows along z,
+// every cell is split into two triangles facing up. Collision only ever looks at the cells under what it is testing,
+// so large terrain costs about the same as a small floor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heightfield {
+	pub columns: usize, // points along x
+	pub rows: usize, // points along z
+	pub spacing: f32, // between neighbouring points
+	pub heights: Box<[f32]> // row by row
+}
+
+
+impl Heightfield {
+	pub fn new(columns: usize, rows: usize, spacing: f32, heights: &[f32]) -> Self {
+		assert!(columns >= 2 && rows >= 2, "a heightfield needs at least one cell");
+		assert_eq!(heights.len(), columns * rows, "one height per grid point");
+		Self { columns, rows, spacing, heights: heights.to_vec().into_boxed_slice() }
+	}
+	
+	// One point per pixel, black at zero and white at height_scale. The top of the image is at -z.
+	pub fn from_png(bytes: &[u8], spacing: f32, height_scale: f32) -> Result<Self, image::ImageError> {
+		let image = image::load_from_memory_with_format(bytes, image::ImageFormat::Png)?.into_luma16();
+		let (columns, rows) = (image.width() as usize, image.height() as usize);
+		if columns < 2 || rows < 2 {
+			return Err(image::ImageError::Parameter(image::error::ParameterError::from_kind(image::error::ParameterErrorKind::DimensionMismatch)));
+		}
+		let heights = image.pixels().map(|p| p.0[0] as f32 / u16::MAX as f32 * height_scale).collect::<Vec<f32>>();
+		Ok(Self::new(columns, rows, spacing, &heights))
+	}
+	
+	pub fn load(path: &str, spacing: f32, height_scale: f32) -> Result<Self, image::ImageError> {
+		Self::from_png(&std::fs::read(path)?, spacing, height_scale)
+	}
+	
+	
+	pub fn height(&self, column: usize, row: usize) -> f32 {
+		self.heights[row * self.columns + column]
+	}
+	
+	pub fn point(&self, column: usize, row: usize) -> Vec3 {
+		Vec3(
+			(column as f32 - 0.5 * (self.columns - 1) as f32) * self.spacing,
+			self.height(column, row),
+			(row as f32 - 0.5 * (self.rows - 1) as f32) * self.spacing
+		)
+	}
+	
+	pub fn triangle_count(&self) -> usize {
+		2 * (self.columns - 1) * (self.rows - 1)
+	}
+	
+	// model space corners, wound like the render meshes so (c - a) x (b - a) faces up
+	pub fn triangle(&self, index: usize) -> (Vec3, Vec3, Vec3) {
+		let cell = index / 2;
+		let (column, row) = (cell % (self.columns - 1), cell / (self.columns - 1));
+		match index % 2 {
+			0 => (self.point(column, row), self.point(column + 1, row), self.point(column, row + 1)),
+			_ => (self.point(column + 1, row), self.point(column + 1, row + 1), self.point(column, row + 1))
+		}
+	}
+	
+	// height of the surface above a model space point, none off the edge of the grid
+	pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+		let u = x / self.spacing + 0.5 * (self.columns - 1) as f32;
+		let v = z / self.spacing + 0.5 * (self.rows - 1) as f32;
+		if u < 0.0 || v < 0.0 || u > (self.columns - 1) as f32 || v > (self.rows - 1) as f32 { return None; }
+		
+		let column = (u.floor() as usize).min(self.columns - 2);
+		let row = (v.floor() as usize).min(self.rows - 2);
+		let (s, t) = (u - column as f32, v - row as f32);
+		let (h00, h10, h01, h11) = (self.height(column, row), self.height(column + 1, row), self.height(column, row + 1), self.height(column + 1, row + 1));
+		Some(match s + t <= 1.0 {
+			true => h00 + (h10 - h00) * s + (h01 - h00) * t,
+			false => h11 + (h01 - h11) * (1.0 - s) + (h10 - h11) * (1.0 - t)
+		})
+	}
+	
+	pub fn aabb(&self) -> Aabb {
+		let (low, high) = self.heights.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), &h| (low.min(h), high.max(h)));
+		let (corner_a, corner_b) = (self.point(0, 0), self.point(self.columns - 1, self.rows - 1));
+		Aabb { min: Vec3(corner_a.0, low, corner_a.2), max: Vec3(corner_b.0, high, corner_b.2) }
+	}
+	
+	pub fn world_aabb(&self, transform: &Mat4) -> Aabb {
+		let Aabb { min, max } = self.aabb();
+		let corners = (0..8).map(|i| Vec3(
+			if i & 4 == 0 { min.0 } else { max.0 },
+			if i & 2 == 0 { min.1 } else { max.1 },
+			if i & 1 == 0 { min.2 } else { max.2 }
+		).apply_transform(transform)).collect::<Vec<Vec3>>();
+		Aabb::from_points(&corners)
+	}
+	
+	// The triangles of the cells under a model space box whose heights reach into it.
+	pub fn triangles_in(&self, bounds: &Aabb) -> Vec<usize> {
+		let to_cell = |x: f32, count: usize| (x / self.spacing + 0.5 * (count - 1) as f32).floor().clamp(0.0, (count - 2) as f32) as usize;
+		let Aabb { min, max } = self.aabb();
+		if bounds.max.0 < min.0 || bounds.min.0 > max.0 || bounds.max.2 < min.2 || bounds.min.2 > max.2 { return Vec::new(); }
+		
+		let mut triangles = Vec::new();
+		for row in to_cell(bounds.min.2, self.rows)..=to_cell(bounds.max.2, self.rows) {
+			for column in to_cell(bounds.min.0, self.columns)..=to_cell(bounds.max.0, self.columns) {
+				let heights = [self.height(column, row), self.height(column + 1, row), self.height(column, row + 1), self.height(column + 1, row + 1)];
+				let low = heights.iter().copied().fold(f32::INFINITY, f32::min);
+				let high = heights.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+				if high < bounds.min.1 || low > bounds.max.1 { continue; }
+				
+				let cell = row * (self.columns - 1) + column;
+				triangles.extend([2 * cell, 2 * cell + 1]);
+			}
+		}
+		triangles
+	}
+	
+	// the same for a world space box, with the triangles' corners in world space
+	pub fn world_triangles_in(&self, transform: &Mat4, bounds: &Aabb) -> Vec<(usize, Vec3, Vec3, Vec3)> {
+		self.triangles_in(&to_model(transform, bounds)).into_iter().map(|l| {
+			let (a, b, c) = self.triangle(l);
+			(l, a.apply_transform(transform), b.apply_transform(transform), c.apply_transform(transform))
+		}).collect()
+	}
+	
+	// Walks the cells the ray passes over in order, so a long ray over a big field only looks at the cells it crosses.
+	// Model space, returns the distance along the normalized direction and the triangle.
+	pub fn ray_cast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<(f32, usize)> {
+		let Aabb { min, max } = self.aabb();
+		
+		// clip the ray to the grid so the walk starts and ends inside it
+		let (mut near, mut far) = (0.0f32, max_distance);
+		for (o, d, low, high) in [(origin.0, direction.0, min.0, max.0), (origin.2, direction.2, min.2, max.2)] {
+			if d.abs() < 1e-12 {
+				if o < low || o > high { return None; }
+				continue;
+			}
+			let (t0, t1) = ((low - o) / d, (high - o) / d);
+			near = near.max(t0.min(t1));
+			far = far.min(t0.max(t1));
+		}
+		if near > far { return None; }
+		
+		let start = origin + direction * near;
+		let cell_of = |x: f32, count: usize| (x / self.spacing + 0.5 * (count - 1) as f32).floor().clamp(0.0, (count - 2) as f32) as i64;
+		let (mut column, mut row) = (cell_of(start.0, self.columns), cell_of(start.2, self.rows));
+		let step = |d: f32| if d > 0.0 { 1 } else { -1 };
+		let (step_column, step_row) = (step(direction.0), step(direction.2));
+		
+		// distance along the ray to the next cell boundary on each axis, and between boundaries
+		let boundary = |cell: i64, step: i64, count: usize, o: f32, d: f32| match d.abs() < 1e-12 {
+			true => f32::INFINITY,
+			false => {
+				let edge = ((cell + (step > 0) as i64) as f32 - 0.5 * (count - 1) as f32) * self.spacing;
+				(edge - o) / d
+			}
+		};
+		let mut next_column = boundary(column, step_column, self.columns, origin.0, direction.0);
+		let mut next_row = boundary(row, step_row, self.rows, origin.2, direction.2);
+		let delta_column = if direction.0.abs() < 1e-12 { f32::INFINITY } else { self.spacing / direction.0.abs() };
+		let delta_row = if direction.2.abs() < 1e-12 { f32::INFINITY } else { self.spacing / direction.2.abs() };
+		
+		loop {
+			let cell = row as usize * (self.columns - 1) + column as usize;
+			let mut best: Option<(f32, usize)> = None;
+			for l in [2 * cell, 2 * cell + 1] {
+				let (a, b, c) = self.triangle(l);
+				if let Some(t) = ray_triangle(origin, direction, a, b, c) {
+					if t <= max_distance && best.is_none_or(|(best_t, _)| t < best_t) { best = Some((t, l)); }
+				}
+			}
+			if best.is_some() { return best; }
+			
+			let exit = next_column.min(next_row);
+			if exit > far { return None; }
+			match next_column < next_row {
+				true => {
+					column += step_column;
+					next_column += delta_column;
+				}
+				false => {
+					row += step_row;
+					next_row += delta_row;
+				}
+			}
+			if column < 0 || row < 0 || column as usize >= self.columns - 1 || row as usize >= self.rows - 1 { return None; }
+		}
+	}
+	
+	// the grid as a render mesh, with 32 bit indices since large terrain has more points than 16 bits reach
+	pub fn mesh(&self) -> (Vec<Vec3>, Vec<(u32, u32, u32)>) {
+		let vertices = (0..self.rows).flat_map(|row| (0..self.columns).map(move |column| (column, row))).map(|(column, row)| self.point(column, row)).collect();
+		let index = |column: usize, row: usize| (row * self.columns + column) as u32;
+		let indices = (0..self.rows - 1).flat_map(|row| (0..self.columns - 1).map(move |column| (column, row))).flat_map(|(column, row)| [
+			(index(column, row), index(column + 1, row), index(column, row + 1)),
+			(index(column + 1, row), index(column + 1, row + 1), index(column, row + 1))
+		]).collect();
+		(vertices, indices)
+	}
+}
+
+
+// a world space box around the same region in the model space of a rigid transform
+fn to_model(transform: &Mat4, bounds: &Aabb) -> Aabb {
+	let rotation = transform.get_rotation().transpose();
+	let position = transform.get_position();
+	let corners = (0..8).map(|i| rotation.mult_vec3(Vec3(
+		if i & 4 == 0 { bounds.min.0 } else { bounds.max.0 },
+		if i & 2 == 0 { bounds.min.1 } else { bounds.max.1 },
+		if i & 1 == 0 { bounds.min.2 } else { bounds.max.2 }
+	) - position)).collect::<Vec<Vec3>>();
+	Aabb::from_points(&corners)
+}
@@ -0,0 +1,148 @@
+use crate::math_structs::{Mat3, Mat4, Vec3};
+
+
+static MIDPOINT_ITERATIONS: usize = 3;
+
+// How a free body's velocities and transform are stepped under the forces on it, before contacts and joints
+// are solved. Higher orders cost an evaluation of the force fields per stage.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Integrator {
+	#[default]
+	SemiImplicitEuler, // velocities first, then the transform with the new ones. One evaluation, symplectic, tumbling slowly damps
+	VelocityVerlet, // half a kick, the whole drift, half a kick at the new transform. Two evaluations, symplectic
+	Rk4 // classic fourth order Runge-Kutta. Four evaluations, the most accurate over a step but slowly loses energy
+}
+
+// What the forces depend on, in world space.
+#[derive(Copy, Clone, Debug)]
+pub struct State {
+	pub transform: Mat4,
+	pub velocity: Vec3,
+	pub angular_velocity: Vec3
+}
+
+// The result of a step. The transform isn't moved here: contacts change the velocities afterwards, and the
+// difference between the mean and end velocities is carried over on top of whatever they end up as.
+#[derive(Copy, Clone, Debug)]
+pub struct Motion {
+	pub velocity: Vec3, // at the end of the step
+	pub angular_velocity: Vec3,
+	pub mean_velocity: Vec3, // moves the transform from the start of the step to the end
+	pub mean_angular_velocity: Vec3
+}
+
+
+impl Integrator {
+	// the short names scene files and the simulate binary use
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"euler" => Some(Integrator::SemiImplicitEuler),
+			"verlet" => Some(Integrator::VelocityVerlet),
+			"rk4" => Some(Integrator::Rk4),
+			_ => None
+		}
+	}
+	
+	// Steps a body with the given model space inertia and its inverse. forces returns the linear acceleration and the torque at a state,
+	// and is called once per stage. Spinning bodies that aren't round get the gyroscopic torque on top.
+	pub fn step(self, start: &State, inertia: &Mat3, inverse_inertia: &Mat3, dt: f32, mut forces: impl FnMut(&State) -> (Vec3, Vec3)) -> Motion {
+		match self {
+			Integrator::SemiImplicitEuler => {
+				let (acceleration, torque) = forces(start);
+				let velocity = start.velocity + acceleration * dt;
+				let angular_velocity = start.angular_velocity + world_inertia(&start.transform, inverse_inertia).mult_vec3(torque) * dt;
+				let angular_velocity = gyroscopic_implicit(angular_velocity, &start.transform.get_rotation(), inertia, dt);
+				Motion { velocity, angular_velocity, mean_velocity: velocity, mean_angular_velocity: angular_velocity }
+			}
+			Integrator::VelocityVerlet => {
+				// Kicks angular momentum rather than angular velocity, which keeps it exactly when nothing twists the body.
+				// The body turns at the angular velocity that momentum gives halfway through the turn.
+				let (acceleration, torque) = forces(start);
+				let momentum = world_inertia(&start.transform, inertia).mult_vec3(start.angular_velocity) + torque * (0.5 * dt);
+				let mut angular_velocity = world_inertia(&start.transform, inverse_inertia).mult_vec3(momentum);
+				for _ in 0..MIDPOINT_ITERATIONS {
+					let midpoint = drift(&start.transform, Vec3(0.0, 0.0, 0.0), angular_velocity, 0.5 * dt);
+					angular_velocity = world_inertia(&midpoint, inverse_inertia).mult_vec3(momentum);
+				}
+				let half = State { transform: start.transform, velocity: start.velocity + acceleration * (0.5 * dt), angular_velocity };
+				
+				let transform = drift(&start.transform, half.velocity, half.angular_velocity, dt);
+				let moved = State { transform, angular_velocity: world_inertia(&transform, inverse_inertia).mult_vec3(momentum), ..half };
+				let (acceleration, torque) = forces(&moved);
+				Motion {
+					velocity: moved.velocity + acceleration * (0.5 * dt),
+					angular_velocity: world_inertia(&transform, inverse_inertia).mult_vec3(momentum + torque * (0.5 * dt)),
+					mean_velocity: half.velocity,
+					mean_angular_velocity: half.angular_velocity
+				}
+			}
+			Integrator::Rk4 => {
+				// each stage is the state's velocities and the accelerations at it
+				let mut derivative = |state: &State| {
+					let (acceleration, torque) = forces(state);
+					(state.velocity, state.angular_velocity, acceleration, angular_acceleration(state, torque, inverse_inertia, inertia))
+				};
+				let offset = |(v, w, a, alpha): (Vec3, Vec3, Vec3, Vec3), dt: f32| State {
+					transform: drift(&start.transform, v, w, dt),
+					velocity: start.velocity + a * dt,
+					angular_velocity: start.angular_velocity + alpha * dt
+				};
+				
+				let k1 = derivative(start);
+				let k2 = derivative(&offset(k1, 0.5 * dt));
+				let k3 = derivative(&offset(k2, 0.5 * dt));
+				let k4 = derivative(&offset(k3, dt));
+				let mean = |f: fn(&(Vec3, Vec3, Vec3, Vec3)) -> Vec3| (f(&k1) + (f(&k2) + f(&k3)) * 2.0 + f(&k4)) / 6.0;
+				Motion {
+					velocity: start.velocity + mean(|k| k.2) * dt,
+					angular_velocity: start.angular_velocity + mean(|k| k.3) * dt,
+					mean_velocity: mean(|k| k.0),
+					mean_angular_velocity: mean(|k| k.1)
+				}
+			}
+		}
+	}
+}
+
+
+// moves a transform along at constant velocities, turning it about its own position
+pub fn drift(transform: &Mat4, velocity: Vec3, angular_velocity: Vec3, dt: f32) -> Mat4 {
+	let new_position = transform.get_position() + velocity * dt;
+	let mut new_transform = *transform;
+	if angular_velocity.length_squared() > 0.0 {
+		new_transform = new_transform.rotate_axis(angular_velocity.normalize(), angular_velocity.length() * dt);
+	}
+	new_transform.set_position(new_position)
+}
+
+
+// a model space inertia or its inverse, turned the way the transform is
+fn world_inertia(transform: &Mat4, m: &Mat3) -> Mat3 {
+	let rotation = transform.get_rotation();
+	rotation.mult_mat3(m).mult_mat3(&rotation.transpose())
+}
+
+// Euler's equations in world space, the torque plus the gyroscopic one over the inertia.
+fn angular_acceleration(state: &State, torque: Vec3, inverse_inertia: &Mat3, inertia: &Mat3) -> Vec3 {
+	let momentum = world_inertia(&state.transform, inertia).mult_vec3(state.angular_velocity);
+	world_inertia(&state.transform, inverse_inertia).mult_vec3(torque - state.angular_velocity.cross(momentum))
+}
+
+// The gyroscopic torque taken implicitly with one Newton step in model space. Done explicitly it feeds energy
+// into anything tumbling, which one evaluation per step can't take back.
+fn gyroscopic_implicit(angular_velocity: Vec3, rotation: &Mat3, inertia: &Mat3, dt: f32) -> Vec3 {
+	let w = rotation.transpose().mult_vec3(angular_velocity);
+	let momentum = inertia.mult_vec3(w);
+	let residual = w.cross(momentum) * dt;
+	let jacobian = *inertia + (cross_matrix(w).mult_mat3(inertia) - cross_matrix(momentum)) * dt;
+	
+	// scaled first so the inverse of a small body's tiny inertia isn't taken for singular
+	let scale = jacobian.trace();
+	if scale <= 0.0 { return angular_velocity; }
+	rotation.mult_vec3(w - (jacobian * (1.0 / scale)).inverse().mult_vec3(residual / scale))
+}
+
+// the matrix that does v.cross(x)
+fn cross_matrix(v: Vec3) -> Mat3 {
+	Mat3::from_columns(Vec3(0.0, v.2, -v.1), Vec3(-v.2, 0.0, v.0), Vec3(v.1, -v.0, 0.0))
+}
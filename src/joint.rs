@@ -0,0 +1,419 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{math_structs::{Mat3, Vec3}, object::Object, physics::tangent_basis};
+
+
+static JOINT_BAUMGARTE_FACTOR: f32 = 0.2;
+
+// impulse slots, kept per joint for warm starting
+static LINEAR_SLOT: usize = 0;
+static ANGULAR_SLOT: usize = 3;
+static LOWER_LIMIT_SLOT: usize = 6;
+static UPPER_LIMIT_SLOT: usize = 7;
+static MOTOR_SLOT: usize = 8;
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JointId(usize);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Motor {
+	pub speed: f32,
+	pub max_torque: f32
+}
+
+// Axes and reference vectors are stored in the model space of their own body,
+// rotations are the rotation of b relative to a when the joint was made.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JointKind {
+	BallSocket,
+	Hinge { axis_a: Vec3, axis_b: Vec3, reference_a: Vec3, reference_b: Vec3, limits: Option<(f32, f32)>, motor: Option<Motor> },
+	Slider { axis_a: Vec3, rotation: Mat3, limits: Option<(f32, f32)> },
+	Fixed { rotation: Mat3 },
+	Distance { min_length: f32, max_length: f32 }
+}
+
+// Connects body a to body b, or to the world when b is None. Anchors are in the model space
+// of their body, or in world space for the world.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Joint {
+	pub a: usize,
+	pub b: Option<usize>,
+	pub anchor_a: Vec3,
+	pub anchor_b: Vec3,
+	pub kind: JointKind,
+	pub collide_connected: bool,
+	pub impulses: [f32; 9]
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct JointSet {
+	joints: BTreeMap<JointId, Joint>,
+	next_id: usize
+}
+
+
+fn frame(objects: &[Object], body: Option<usize>) -> (Vec3, Mat3) {
+	match body {
+		Some(i) => (objects[i].transform.get_position(), objects[i].transform.get_rotation()),
+		None => (Vec3(0.0, 0.0, 0.0), Mat3::identity())
+	}
+}
+
+fn to_local(objects: &[Object], body: Option<usize>, point: Vec3) -> Vec3 {
+	let (position, rotation) = frame(objects, body);
+	rotation.transpose().mult_vec3(point - position)
+}
+
+fn relative_rotation(objects: &[Object], a: usize, b: Option<usize>) -> Mat3 {
+	frame(objects, Some(a)).1.transpose().mult_mat3(&frame(objects, b).1)
+}
+
+
+impl Joint {
+	fn new(objects: &[Object], a: usize, b: Option<usize>, anchor: Vec3, kind: JointKind) -> Self {
+		Self {
+			a,
+			b,
+			anchor_a: to_local(objects, Some(a), anchor),
+			anchor_b: to_local(objects, b, anchor),
+			kind,
+			collide_connected: false,
+			impulses: [0.0; 9]
+		}
+	}
+	
+	// anchor is a world space point
+	pub fn ball_socket(objects: &[Object], a: usize, b: Option<usize>, anchor: Vec3) -> Self {
+		Self::new(objects, a, b, anchor, JointKind::BallSocket)
+	}
+	
+	// rotation about a world space axis through the anchor
+	pub fn hinge(objects: &[Object], a: usize, b: Option<usize>, anchor: Vec3, axis: Vec3) -> Self {
+		let (_, rotation_a) = frame(objects, Some(a));
+		let (_, rotation_b) = frame(objects, b);
+		let axis = axis.normalize();
+		let reference = tangent_basis(axis).0;
+		Self::new(objects, a, b, anchor, JointKind::Hinge {
+			axis_a: rotation_a.transpose().mult_vec3(axis),
+			axis_b: rotation_b.transpose().mult_vec3(axis),
+			reference_a: rotation_a.transpose().mult_vec3(reference),
+			reference_b: rotation_b.transpose().mult_vec3(reference),
+			limits: None,
+			motor: None
+		})
+	}
+	
+	// translation along a world space axis, no relative rotation
+	pub fn slider(objects: &[Object], a: usize, b: Option<usize>, anchor: Vec3, axis: Vec3) -> Self {
+		let axis_a = frame(objects, Some(a)).1.transpose().mult_vec3(axis.normalize());
+		Self::new(objects, a, b, anchor, JointKind::Slider { axis_a, rotation: relative_rotation(objects, a, b), limits: None })
+	}
+	
+	// welds the bodies together where they are now
+	pub fn fixed(objects: &[Object], a: usize, b: Option<usize>) -> Self {
+		Self::new(objects, a, b, objects[a].transform.get_position(), JointKind::Fixed { rotation: relative_rotation(objects, a, b) })
+	}
+	
+	// keeps two world space anchors at their current distance
+	pub fn distance(objects: &[Object], a: usize, b: Option<usize>, anchor_a: Vec3, anchor_b: Vec3) -> Self {
+		let length = (anchor_b - anchor_a).length();
+		let mut joint = Self::new(objects, a, b, anchor_a, JointKind::Distance { min_length: length, max_length: length });
+		joint.anchor_b = to_local(objects, b, anchor_b);
+		joint
+	}
+	
+	// angle range for hinges, translation range for sliders, length range for distance joints
+	pub fn with_limits(mut self, lower: f32, upper: f32) -> Self {
+		match &mut self.kind {
+			JointKind::Hinge { limits, .. } | JointKind::Slider { limits, .. } => *limits = Some((lower, upper)),
+			JointKind::Distance { min_length, max_length } => (*min_length, *max_length) = (lower, upper),
+			JointKind::BallSocket | JointKind::Fixed { .. } => ()
+		}
+		self
+	}
+	
+	// drives b relative to a about the hinge axis at a target angular velocity, with a bounded torque
+	pub fn with_motor(mut self, speed: f32, max_torque: f32) -> Self {
+		if let JointKind::Hinge { motor, .. } = &mut self.kind {
+			*motor = Some(Motor { speed, max_torque });
+		}
+		self
+	}
+	
+	pub fn with_collide_connected(mut self, collide_connected: bool) -> Self {
+		self.collide_connected = collide_connected;
+		self
+	}
+	
+	// current hinge angle, zero where the joint was made
+	pub fn hinge_angle(&self, objects: &[Object]) -> Option<f32> {
+		match self.kind {
+			JointKind::Hinge { axis_a, reference_a, reference_b, .. } => {
+				let rotation_a = frame(objects, Some(self.a)).1;
+				let rotation_b = frame(objects, self.b).1;
+				let axis = rotation_a.mult_vec3(axis_a);
+				let (reference_a, reference_b) = (rotation_a.mult_vec3(reference_a), rotation_b.mult_vec3(reference_b));
+				Some(f32::atan2(reference_a.cross(reference_b).dot(axis), reference_a.dot(reference_b)))
+			}
+			_ => None
+		}
+	}
+}
+
+
+impl JointSet {
+	pub fn new() -> Self {
+		Self::default()
+	}
+	
+	pub fn add(&mut self, joint: Joint) -> JointId {
+		let id = JointId(self.next_id);
+		self.next_id += 1;
+		self.joints.insert(id, joint);
+		id
+	}
+	
+	pub fn remove(&mut self, id: JointId) -> Option<Joint> {
+		self.joints.remove(&id)
+	}
+	
+	pub fn get(&self, id: JointId) -> Option<&Joint> {
+		self.joints.get(&id)
+	}
+	
+	pub fn get_mut(&mut self, id: JointId) -> Option<&mut Joint> {
+		self.joints.get_mut(&id)
+	}
+	
+	pub fn iter(&self) -> impl Iterator<Item = (JointId, &Joint)> {
+		self.joints.iter().map(|(&id, joint)| (id, joint))
+	}
+	
+	pub fn iter_mut(&mut self) -> impl Iterator<Item = (JointId, &mut Joint)> {
+		self.joints.iter_mut().map(|(&id, joint)| (id, joint))
+	}
+	
+	pub fn len(&self) -> usize {
+		self.joints.len()
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.joints.is_empty()
+	}
+	
+	pub fn clear(&mut self) {
+		self.joints.clear();
+	}
+	
+	// drops the joints on a removed body and shifts the bodies after it down
+	pub fn remove_body(&mut self, index: usize) {
+		let shift = |i: usize| if i > index { i - 1 } else { i };
+		self.joints.retain(|_, joint| joint.a != index && joint.b != Some(index));
+		for joint in self.joints.values_mut() {
+			joint.a = shift(joint.a);
+			joint.b = joint.b.map(shift);
+		}
+	}
+	
+	// body pairs that shouldn't generate contacts, lower index first
+	pub fn ignored_pairs(&self) -> BTreeSet<(usize, usize)> {
+		self.joints.values().filter(|j| !j.collide_connected).filter_map(|j| j.b.map(|b| (j.a.min(b), j.a.max(b)))).collect()
+	}
+}
+
+
+
+#[derive(Copy, Clone)]
+enum Jacobian {
+	Linear { r_a: Vec3, r_b: Vec3, n: Vec3 },
+	Angular { u: Vec3 }
+}
+
+// One scalar velocity constraint, solved as J v + bias = 0 with the accumulated impulse clamped to range.
+pub(crate) struct JointRow {
+	joint: JointId,
+	slot: usize,
+	a: usize,
+	b: Option<usize>,
+	jacobian: Jacobian,
+	mass: f32,
+	bias: f32,
+	range: (f32, f32),
+	impulse: f32
+}
+
+
+fn equality_bias(c: f32, dt: f32) -> f32 {
+	JOINT_BAUMGARTE_FACTOR * c / dt
+}
+
+// limits act speculatively while they aren't violated, like separated contacts
+fn limit_bias(c: f32, dt: f32) -> f32 {
+	match c < 0.0 {
+		true => JOINT_BAUMGARTE_FACTOR * c / dt,
+		false => c / dt
+	}
+}
+
+// small rotation that takes `from` onto `to`, both world space
+fn rotation_error(from: &Mat3, to: &Mat3) -> Vec3 {
+	let e = to.mult_mat3(&from.transpose());
+	Vec3(e.0[1][2] - e.0[2][1], e.0[2][0] - e.0[0][2], e.0[0][1] - e.0[1][0]) * 0.5
+}
+
+
+pub(crate) fn prepare_rows(joints: &JointSet, objects: &[Object], masses: &[(f32, Mat3)], dt: f32) -> Vec<JointRow> {
+	let mut rows = Vec::new();
+	
+	for (&id, joint) in joints.joints.iter() {
+		let (position_a, rotation_a) = frame(objects, Some(joint.a));
+		let (position_b, rotation_b) = frame(objects, joint.b);
+		let r_a = rotation_a.mult_vec3(joint.anchor_a);
+		let r_b = rotation_b.mult_vec3(joint.anchor_b);
+		let d = position_b + r_b - position_a - r_a;
+		
+		let mut push = |slot: usize, jacobian: Jacobian, bias: f32, range: (f32, f32)| {
+			let (mass_a, inertia_a) = masses[joint.a];
+			let (mass_b, inertia_b) = joint.b.map_or((0.0, Mat3::zero()), |b| masses[b]);
+			let k = match jacobian {
+				Jacobian::Linear { r_a, r_b, n } => mass_a + mass_b
+					+ r_a.cross(n).dot(inertia_a.mult_vec3(r_a.cross(n)))
+					+ r_b.cross(n).dot(inertia_b.mult_vec3(r_b.cross(n))),
+				Jacobian::Angular { u } => u.dot(inertia_a.mult_vec3(u)) + u.dot(inertia_b.mult_vec3(u))
+			};
+			if k <= 0.0 { return; }
+			rows.push(JointRow { joint: id, slot, a: joint.a, b: joint.b, jacobian, mass: 1.0 / k, bias, range, impulse: joint.impulses[slot] });
+		};
+		
+		let point = |push: &mut dyn FnMut(usize, Jacobian, f32, (f32, f32))| {
+			for (i, n) in [Vec3(1.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0), Vec3(0.0, 0.0, 1.0)].into_iter().enumerate() {
+				push(LINEAR_SLOT + i, Jacobian::Linear { r_a, r_b, n }, equality_bias(d.dot(n), dt), (f32::NEG_INFINITY, f32::INFINITY));
+			}
+		};
+		
+		let lock_rotation = |push: &mut dyn FnMut(usize, Jacobian, f32, (f32, f32)), rotation: &Mat3| {
+			let error = rotation_error(&rotation_b, &rotation_a.mult_mat3(rotation));
+			for (i, u) in [Vec3(1.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0), Vec3(0.0, 0.0, 1.0)].into_iter().enumerate() {
+				push(ANGULAR_SLOT + i, Jacobian::Angular { u }, equality_bias(-error.dot(u), dt), (f32::NEG_INFINITY, f32::INFINITY));
+			}
+		};
+		
+		match &joint.kind {
+			JointKind::BallSocket => point(&mut push),
+			JointKind::Fixed { rotation } => {
+				point(&mut push);
+				lock_rotation(&mut push, rotation);
+			}
+			JointKind::Hinge { axis_a, axis_b, reference_a, reference_b, limits, motor } => {
+				point(&mut push);
+				
+				let axis = rotation_a.mult_vec3(*axis_a);
+				let axis_b = rotation_b.mult_vec3(*axis_b);
+				let (t1, t2) = tangent_basis(axis);
+				for (i, t) in [t1, t2].into_iter().enumerate() {
+					push(ANGULAR_SLOT + i, Jacobian::Angular { u: axis_b.cross(t) }, equality_bias(axis_b.dot(t), dt), (f32::NEG_INFINITY, f32::INFINITY));
+				}
+				
+				if let Some((lower, upper)) = limits {
+					let (reference_a, reference_b) = (rotation_a.mult_vec3(*reference_a), rotation_b.mult_vec3(*reference_b));
+					let angle = f32::atan2(reference_a.cross(reference_b).dot(axis), reference_a.dot(reference_b));
+					push(LOWER_LIMIT_SLOT, Jacobian::Angular { u: axis }, limit_bias(angle - lower, dt), (0.0, f32::INFINITY));
+					push(UPPER_LIMIT_SLOT, Jacobian::Angular { u: -axis }, limit_bias(upper - angle, dt), (0.0, f32::INFINITY));
+				}
+				
+				if let Some(motor) = motor {
+					let max_impulse = motor.max_torque * dt;
+					push(MOTOR_SLOT, Jacobian::Angular { u: axis }, -motor.speed, (-max_impulse, max_impulse));
+				}
+			}
+			JointKind::Slider { axis_a, rotation, limits } => {
+				lock_rotation(&mut push, rotation);
+				
+				// a's lever arm reaches to b's anchor so rotating a drags b's anchor along
+				let axis = rotation_a.mult_vec3(*axis_a);
+				let (t1, t2) = tangent_basis(axis);
+				for (i, t) in [t1, t2].into_iter().enumerate() {
+					push(LINEAR_SLOT + i, Jacobian::Linear { r_a: r_a + d, r_b, n: t }, equality_bias(d.dot(t), dt), (f32::NEG_INFINITY, f32::INFINITY));
+				}
+				
+				if let Some((lower, upper)) = limits {
+					let s = d.dot(axis);
+					push(LOWER_LIMIT_SLOT, Jacobian::Linear { r_a: r_a + d, r_b, n: axis }, limit_bias(s - lower, dt), (0.0, f32::INFINITY));
+					push(UPPER_LIMIT_SLOT, Jacobian::Linear { r_a: r_a + d, r_b, n: -axis }, limit_bias(upper - s, dt), (0.0, f32::INFINITY));
+				}
+			}
+			JointKind::Distance { min_length, max_length } => {
+				let length = d.length();
+				if length < 1e-6 { continue; }
+				let n = d / length;
+				match min_length == max_length {
+					true => push(LINEAR_SLOT, Jacobian::Linear { r_a, r_b, n }, equality_bias(length - min_length, dt), (f32::NEG_INFINITY, f32::INFINITY)),
+					false => {
+						push(LOWER_LIMIT_SLOT, Jacobian::Linear { r_a, r_b, n }, limit_bias(length - min_length, dt), (0.0, f32::INFINITY));
+						push(UPPER_LIMIT_SLOT, Jacobian::Linear { r_a, r_b, n: -n }, limit_bias(max_length - length, dt), (0.0, f32::INFINITY));
+					}
+				}
+			}
+		}
+	}
+	
+	rows
+}
+
+
+fn row_velocity(objects: &[Object], row: &JointRow) -> f32 {
+	let (velocity_a, angular_a) = (objects[row.a].velocity, objects[row.a].angular_velocity);
+	let (velocity_b, angular_b) = row.b.map_or((Vec3(0.0, 0.0, 0.0), Vec3(0.0, 0.0, 0.0)), |b| (objects[b].velocity, objects[b].angular_velocity));
+	match row.jacobian {
+		Jacobian::Linear { r_a, r_b, n } => (velocity_b + angular_b.cross(r_b) - velocity_a - angular_a.cross(r_a)).dot(n),
+		Jacobian::Angular { u } => (angular_b - angular_a).dot(u)
+	}
+}
+
+fn apply_row_impulse(objects: &mut [Object], masses: &[(f32, Mat3)], row: &JointRow, lambda: f32) {
+	let (mass_a, inertia_a) = masses[row.a];
+	match row.jacobian {
+		Jacobian::Linear { r_a, r_b, n } => {
+			objects[row.a].velocity -= n * (lambda * mass_a);
+			objects[row.a].angular_velocity -= inertia_a.mult_vec3(r_a.cross(n)) * lambda;
+			if let Some(b) = row.b {
+				objects[b].velocity += n * (lambda * masses[b].0);
+				objects[b].angular_velocity += masses[b].1.mult_vec3(r_b.cross(n)) * lambda;
+			}
+		}
+		Jacobian::Angular { u } => {
+			objects[row.a].angular_velocity -= inertia_a.mult_vec3(u) * lambda;
+			if let Some(b) = row.b {
+				objects[b].angular_velocity += masses[b].1.mult_vec3(u) * lambda;
+			}
+		}
+	}
+}
+
+pub(crate) fn warm_start(rows: &[JointRow], objects: &mut [Object], masses: &[(f32, Mat3)]) {
+	for row in rows {
+		apply_row_impulse(objects, masses, row, row.impulse);
+	}
+}
+
+pub(crate) fn solve_rows(rows: &mut [JointRow], objects: &mut [Object], masses: &[(f32, Mat3)]) {
+	for row in rows.iter_mut() {
+		let lambda = -(row_velocity(objects, row) + row.bias) * row.mass;
+		let new_impulse = (row.impulse + lambda).clamp(row.range.0, row.range.1);
+		let change = new_impulse - row.impulse;
+		row.impulse = new_impulse;
+		apply_row_impulse(objects, masses, row, change);
+	}
+}
+
+pub(crate) fn store_impulses(rows: &[JointRow], joints: &mut JointSet) {
+	for joint in joints.joints.values_mut() {
+		joint.impulses = [0.0; 9];
+	}
+	for row in rows {
+		if let Some(joint) = joints.joints.get_mut(&row.joint) {
+			joint.impulses[row.slot] = row.impulse;
+		}
+	}
+}
@@ -0,0 +1,21 @@
+pub mod capture;
+pub mod character;
+pub mod debug;
+pub mod force;
+pub mod headless;
+pub mod heightfield;
+pub mod integrator;
+pub mod joint;
+pub mod light;
+pub mod math_structs;
+mod narrow_phase;
+pub mod object;
+pub mod particles;
+pub mod physics;
+pub mod query;
+pub mod render;
+pub mod scene;
+pub mod shape;
+pub mod snapshot;
+pub mod soft_body;
+pub mod world;
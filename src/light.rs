@@ -0,0 +1,147 @@
+use crate::{math_structs::{Mat4, Vec3}, physics::Aabb};
+
+
+// the most lights the main shader takes for one object, the size of its light arrays
+pub static MAX_LIGHTS_PER_OBJECT: usize = 8;
+// shadowed point lights get a cube map each and spot lights a layer each, the ones past these light without shadows
+pub static MAX_POINT_SHADOWS: usize = 2;
+pub static MAX_SPOT_SHADOWS: usize = 4;
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightKind {
+	Directional { direction: Vec3 }, // toward the light, from everywhere
+	Point { position: Vec3 },
+	Spot { position: Vec3, direction: Vec3, inner_angle: f32, outer_angle: f32 } // direction is where it shines, full inside inner_angle of it and dark past outer_angle
+}
+
+// Range and attenuation only matter to point and spot lights. Their light falls off as
+// 1 / (constant + linear d + quadratic d^2), then smoothly down to nothing at range, so whatever is past it skips the light.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Light {
+	pub kind: LightKind,
+	pub color: Vec3,
+	pub intensity: f32,
+	pub range: f32,
+	pub attenuation: Vec3, // constant, linear and quadratic
+	pub casts_shadows: bool
+}
+
+// Which shadow map a light draws into. The first shadowed directional light gets the cascades,
+// and the shadowed point and spot lights the cube maps and layers in order while they last.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShadowSlot {
+	None,
+	Cascades,
+	Cube(usize),
+	Spot(usize)
+}
+
+
+impl Light {
+	// casting shadows, with the falloff that suits the default range of 20
+	pub fn new(kind: LightKind, color: Vec3, intensity: f32) -> Self {
+		Self { kind, color, intensity, range: 20.0, attenuation: Vec3(1.0, 0.22, 0.2), casts_shadows: true }
+	}
+	
+	// how much of the light is left this far from it, matching the shader
+	pub fn attenuation_at(&self, distance: f32) -> f32 {
+		match self.kind {
+			LightKind::Directional { .. } => 1.0,
+			_ => {
+				let Vec3(constant, linear, quadratic) = self.attenuation;
+				let window = (1.0 - (distance / self.range).powi(4)).clamp(0.0, 1.0).powi(2);
+				window / (constant + linear * distance + quadratic * distance * distance)
+			}
+		}
+	}
+	
+	// How brightly it could light anything in the box, 0 for boxes out of range. Ignores spot cones and shadows.
+	pub fn influence(&self, bounds: &Aabb) -> f32 {
+		match self.kind {
+			LightKind::Directional { .. } => self.intensity,
+			LightKind::Point { position } | LightKind::Spot { position, .. } => {
+				let closest = position.max(bounds.min).min(bounds.max);
+				self.intensity * self.attenuation_at((closest - position).length())
+			}
+		}
+	}
+}
+
+
+// The lights to draw something in bounds with, strongest first and no more than the shader takes.
+// Without bounds, for meshes that are only on the gpu, the first ones in the list.
+pub fn select_lights(lights: &[Light], bounds: Option<&Aabb>) -> Vec<usize> {
+	let mut selected = match bounds {
+		Some(bounds) => {
+			let mut influences = lights.iter().enumerate().map(|(i, light)| (i, light.influence(bounds))).filter(|&(_, influence)| influence > 0.0).collect::<Vec<(usize, f32)>>();
+			influences.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+			influences.into_iter().map(|(i, _)| i).collect::<Vec<usize>>()
+		}
+		None => (0..lights.len()).collect()
+	};
+	selected.truncate(MAX_LIGHTS_PER_OBJECT);
+	selected
+}
+
+pub fn assign_shadow_slots(lights: &[Light]) -> Vec<ShadowSlot> {
+	let (mut cascades, mut cubes, mut spots) = (false, 0, 0);
+	lights.iter().map(|light| {
+		if !light.casts_shadows {
+			return ShadowSlot::None;
+		}
+		match light.kind {
+			LightKind::Directional { .. } if !cascades => {
+				cascades = true;
+				ShadowSlot::Cascades
+			}
+			LightKind::Point { .. } if cubes < MAX_POINT_SHADOWS => {
+				cubes += 1;
+				ShadowSlot::Cube(cubes - 1)
+			}
+			LightKind::Spot { .. } if spots < MAX_SPOT_SHADOWS => {
+				spots += 1;
+				ShadowSlot::Spot(spots - 1)
+			}
+			_ => ShadowSlot::None
+		}
+	}).collect()
+}
+
+
+// rows (s, t, 0, w) of a projection from position, with s and t the directions across the image and w the one into it
+fn projection(position: Vec3, s: Vec3, t: Vec3, w: Vec3) -> Mat4 {
+	Mat4([
+		[s.0, t.0, 0.0, w.0],
+		[s.1, t.1, 0.0, w.1],
+		[s.2, t.2, 0.0, w.2],
+		[-s.dot(position), -t.dot(position), 0.0, -w.dot(position)]
+	])
+}
+
+// What each face of a cube map around position sees, in the order of glium's CubeLayer, +x -x +y -y +z -z,
+// turned the way the gl looks them up. Clip z is left at 0, the depth written is the distance from the light.
+pub fn cube_face_transforms(position: Vec3) -> [Mat4; 6] {
+	let (x, y, z) = (Vec3(1.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0), Vec3(0.0, 0.0, 1.0));
+	[
+		projection(position, -z, -y, x),
+		projection(position, z, -y, -x),
+		projection(position, x, z, y),
+		projection(position, x, -z, -y),
+		projection(position, x, -y, z),
+		projection(position, -x, -y, -z)
+	]
+}
+
+// The perspective a spot light's shadow map is drawn with, just wide enough for its cone.
+pub fn spot_shadow_transform(position: Vec3, direction: Vec3, outer_angle: f32) -> Mat4 {
+	let forward = direction.normalize();
+	let reference = match forward.1.abs() < 0.9 {
+		true => Vec3(0.0, 1.0, 0.0),
+		false => Vec3(1.0, 0.0, 0.0)
+	};
+	let across = forward.cross(reference).normalize();
+	let up = across.cross(forward);
+	let slope = 1.0 / outer_angle.min(1.55).tan();
+	projection(position, across * slope, up * slope, forward)
+}
@@ -1,46 +1,13 @@
 extern crate glium;
 
-mod math_structs;
-mod object;
-mod physics;
-mod render;
-mod scene;
+use std::{path::Path, sync::mpsc::{self, Receiver, TryRecvError}, time::{Duration, Instant}};
 
-use std::{sync::mpsc::{self, Receiver, TryRecvError}, time::{Duration, Instant}};
+use gl_engine::{capture::{self, Recorder}, character::CharacterController, debug::DebugFrame, math_structs::{Mat4, Vec3}, object::Object, physics::Diagnostics, render::{Camera, Renderer, ShadowFilter}, world::{PhysicsConfig, PhysicsWorld}};
 
-use math_structs::{Mat4, Vec2, Vec3};
+use glium::{glutin::{event::{Event, WindowEvent, ElementState, VirtualKeyCode}, event_loop::{ControlFlow, EventLoop}, dpi::{PhysicalPosition, PhysicalSize, LogicalSize}, window::{CursorGrabMode, WindowBuilder}, ContextBuilder}, uniforms::MagnifySamplerFilter, Display, Surface};
 
-use glium::{glutin::{event::{Event, WindowEvent, ElementState, VirtualKeyCode}, event_loop::{ControlFlow, EventLoop}, dpi::{PhysicalPosition, PhysicalSize, LogicalSize}, window::{CursorGrabMode, WindowBuilder}, ContextBuilder}, vertex::Attribute, Display, Vertex, VertexFormat};
-use object::Object;
-use render::Renderer;
 
 
-
-impl Vertex for Vec2 {
-	fn build_bindings() -> VertexFormat {
-		std::borrow::Cow::Owned(vec![(std::borrow::Cow::Borrowed("position"), 0, -1, <(f32, f32)>::get_type(), false)])
-	}
-}
-
-impl Vertex for Vec3 {
-	fn build_bindings() -> VertexFormat {
-		std::borrow::Cow::Owned(vec![(std::borrow::Cow::Borrowed("position"), 0, -1, <(f32, f32, f32)>::get_type(), false)])
-	}
-}
-
-
-pub struct Camera {
-	pub position: Vec3,
-	pub horizontal_angle: f32,
-	pub vertical_angle: f32
-}
-
-impl Camera {
-	pub fn get_transform(&self) -> Mat4 {
-		Mat4::identity().translate(-self.position).rotate_y(self.horizontal_angle).rotate_x(self.vertical_angle)
-	}
-}
-
 pub fn get_latest_value<T>(rx: &Receiver<T>) -> Option<T> {
 	let mut value = None;
 	loop {
@@ -56,6 +23,9 @@ pub fn get_latest_value<T>(rx: &Receiver<T>) -> Option<T> {
 
 static TARGET_TPS: f32 = 60.0;
 static TIMING_UPDATE_RATE: f32 = 1.0;
+static RECORDING_FRAME_RATE: f32 = 60.0;
+static SCREENSHOT_DIRECTORY: &str = "screenshots";
+static RECORDING_DIRECTORY: &str = "recordings"; // each recording gets a timestamped directory in here
 
 
 
@@ -86,6 +56,9 @@ fn main() {
 	let mut capture = false;
 	let mut do_post_process = true;
 	let mut show_shadowmap = false;
+	let mut show_debug = false;
+	let mut take_screenshot = false;
+	let mut recorder: Option<Recorder> = None;
 	
 	let mut previous_mouse_pos = PhysicalPosition::<f64>::new(0.0, 0.0);
 	
@@ -96,14 +69,14 @@ fn main() {
 		vertical_angle: 0.0
 	};
 	
+	// walks the camera around on the scene instead of flying it when there is one
+	let mut character: Option<CharacterController> = None;
+	
 	let look_sensitivity = 0.00025;
 	let movement_speed = 4.0;
 	
 	
-	let g = 9.8;
-	
-	
-	let mut dummy = 0.0f32;
+	let mut sun_angle = 0.0f32;
 	
 	
 	
@@ -112,58 +85,95 @@ fn main() {
 	let mut avg_frame_process_time = 0.0;
 	let mut avg_tick_time = 0.0;
 	let mut avg_tick_process_time = 0.0;
+	let mut diagnostics = Diagnostics::default();
 	
 	let mut renderer = Renderer::new(&display, width, height, 75.0, 0.01, 1000.0);
+	let mut debug_frame = DebugFrame::default();
+	let mut particles = gl_engine::scene::initialize_particles();
 	
 	
 	
 	
-	let (physics_tx, main_rx) = mpsc::channel::<Vec<(Mat4, Vec3, Vec3)>>();
-	let (main_tx, physics_rx) = mpsc::channel::<Vec<(Mat4, Vec3, Vec3)>>();
-	let (tps_tx, tps_rx) = mpsc::channel::<(f32, f32)>();
+	let (physics_tx, main_rx) = mpsc::channel::<(Vec<(Mat4, Vec3, Vec3)>, Vec<Vec<Vec3>>, Option<DebugFrame>)>(); // the debug frame only while it's shown
+	let (main_tx, physics_rx) = mpsc::channel::<(Vec<(Mat4, Vec3, Vec3)>, Vec<Vec<Vec3>>)>();
+	let (tps_tx, tps_rx) = mpsc::channel::<(f32, f32, Diagnostics)>(); // process time, tick time and the last step's diagnostics
 	let (control_tx, control_rx) = mpsc::channel::<bool>();
+	let (debug_tx, debug_rx) = mpsc::channel::<bool>(); // whether to build debug frames
+	let (snapshot_tx, snapshot_rx) = mpsc::channel::<bool>(); // true takes a snapshot, false goes back to it
+	let (step_tx, step_rx) = mpsc::channel::<Option<f32>>(); // steps exactly that far for a recorded frame, None goes back to the wall clock
+	let (recorded_tx, recorded_rx) = mpsc::channel::<(Vec<(Mat4, Vec3, Vec3)>, Vec<Vec<Vec3>>, Option<DebugFrame>)>(); // the answer to each step
 	
-	let (mut objects, vertex_buffers, index_buffers) = crate::scene::initialize_scene(&display);
-	let objects_physics = objects.clone();
+	let (mut objects, vertex_buffers, index_buffers) = gl_engine::scene::initialize_scene(&display);
+	let mut world = PhysicsWorld::new(PhysicsConfig { tick_rate: TARGET_TPS, ..Default::default() });
+	let gravity = world.config.gravity;
+	for object in objects.iter() {
+		world.add_body(object.clone());
+	}
+	let (soft_bodies, soft_vertex_buffers, soft_index_buffers) = gl_engine::scene::initialize_soft_bodies(&display);
+	for soft_body in soft_bodies.into_iter() {
+		world.add_soft_body(soft_body);
+	}
 	
 	let _physics_thread = std::thread::spawn(move || {
-		let mut objects = objects_physics;
 		let mut previous_tick_time = Instant::now();
+		let mut saved = None;
+		let mut recording = false;
+		let mut build_debug = false;
 		
 		loop {
 			let start_time = Instant::now();
 			let tick_dt = start_time.duration_since(previous_tick_time).as_secs_f32()	;
 			previous_tick_time = start_time;
 			
-			if let Some(dynamic_states) = get_latest_value(&physics_rx) {
-				for i in 0..objects.len() {
-					objects[i].set_dynamic_state(dynamic_states[i]);
-				}
+			if let Some((dynamic_states, soft_body_positions)) = get_latest_value(&physics_rx) {
+				world.set_dynamic_states(&dynamic_states);
+				world.set_soft_body_positions(&soft_body_positions);
 			}
 			
 			if let Some(control) = get_latest_value(&control_rx) {
 				run = control;
 			}
 			
-			if run {
-				let dt = 1.0 / TARGET_TPS;
-				
-				for i in 0..objects.len() {
-					objects[i].velocity += Vec3(0.0, -g * dt, 0.0);
+			if let Some(show_debug) = get_latest_value(&debug_rx) {
+				build_debug = show_debug;
+			}
+			
+			while let Ok(take) = snapshot_rx.try_recv() {
+				match (take, &saved) {
+					(true, _) => saved = Some(world.snapshot()),
+					(false, Some(snapshot)) => {
+						if let Err(message) = world.restore(snapshot) {
+							eprintln!("couldn't restore the snapshot: {}", message);
+							continue;
+						}
+						physics_tx.send((world.dynamic_states(), world.soft_body_positions(), build_debug.then(|| world.debug_frame()))).unwrap();
+					}
+					(false, None) => ()
+				}
+			}
+			
+			while let Ok(step) = step_rx.try_recv() {
+				match step {
+					Some(frame_dt) => {
+						recording = true;
+						if run {
+							world.advance(frame_dt);
+						}
+						recorded_tx.send((world.dynamic_states(), world.soft_body_positions(), build_debug.then(|| world.debug_frame()))).unwrap();
+					}
+					None => recording = false
 				}
-				objects[1].velocity = Vec3(0.0, 0.0, 0.0);
-				
-				
-				crate::physics::run(&mut objects, dt);
-				
-				physics_tx.send(objects.iter().map(Object::get_dynamic_state).collect::<Vec<_>>()).unwrap();
+			}
+			
+			if run && !recording && world.advance(tick_dt) > 0 {
+				physics_tx.send((world.dynamic_states(), world.soft_body_positions(), build_debug.then(|| world.debug_frame()))).unwrap();
 			}
 			
 			let process_time = start_time.elapsed();
 			
-			tps_tx.send((process_time.as_secs_f32(), tick_dt)).unwrap();
+			tps_tx.send((process_time.as_secs_f32(), tick_dt, world.diagnostics())).unwrap();
 			
-			let sleep_duration = Duration::from_secs_f32(1.0 / TARGET_TPS).checked_sub(process_time).unwrap_or(Duration::ZERO);
+			let sleep_duration = Duration::from_secs_f32(1.0 / world.config.tick_rate).checked_sub(process_time).unwrap_or(Duration::ZERO);
 			spin_sleep::sleep(sleep_duration);
 		}
 	});
@@ -191,19 +201,70 @@ fn main() {
 							VirtualKeyCode::Space => space = state,
 							VirtualKeyCode::LShift => shift = state,
 							
-							VirtualKeyCode::P => if state { run = !run; control_tx.send(run).unwrap(); }
-							VirtualKeyCode::M => if state { show_shadowmap = !show_shadowmap; }
-							VirtualKeyCode::N => if state { do_post_process = !do_post_process; }
-							VirtualKeyCode::Comma => if state { dummy -= 0.1; }
-							VirtualKeyCode::Period => if state { dummy += 0.1; }
-							VirtualKeyCode::Slash => if state { dummy = 0.0; }
+							VirtualKeyCode::P if state => { run = !run; control_tx.send(run).unwrap(); }
+							VirtualKeyCode::M if state => { show_shadowmap = !show_shadowmap; }
+							VirtualKeyCode::V if state => { renderer.shadowmap.show_cascades = !renderer.shadowmap.show_cascades; }
+							VirtualKeyCode::F if state => {
+								let mut config = *renderer.shadowmap.config();
+								config.filter = match config.filter {
+									ShadowFilter::Hard => ShadowFilter::Kernel,
+									ShadowFilter::Kernel => ShadowFilter::Noise,
+									ShadowFilter::Noise => ShadowFilter::Pcss,
+									ShadowFilter::Pcss => ShadowFilter::Hard
+								};
+								renderer.shadowmap.set_config(&display, config);
+							}
+							VirtualKeyCode::N if state => { do_post_process = !do_post_process; }
+							VirtualKeyCode::B if state => { show_debug = !show_debug; debug_tx.send(show_debug).unwrap(); }
+							VirtualKeyCode::E if state => { particles.burst(0, 200); }
+							VirtualKeyCode::K if state => { snapshot_tx.send(true).unwrap(); }
+							VirtualKeyCode::L if state => { snapshot_tx.send(false).unwrap(); }
+							VirtualKeyCode::F12 if state => { take_screenshot = true; }
+							VirtualKeyCode::F9 if state => {
+								recorder = match recorder.take() {
+									Some(recorder) => {
+										step_tx.send(None).unwrap();
+										match recorder.finish() {
+											Ok(frames) => eprintln!("recorded {} frames", frames),
+											Err(message) => eprintln!("recording failed: {}", message)
+										}
+										None
+									}
+									None => match Recorder::new(&Path::new(RECORDING_DIRECTORY).join(capture::timestamp()), RECORDING_FRAME_RATE) {
+										Ok(recorder) => {
+											eprintln!("recording to {}", recorder.directory.display());
+											Some(recorder)
+										}
+										Err(message) => {
+											eprintln!("couldn't start recording: {}", message);
+											None
+										}
+									}
+								};
+							}
+							VirtualKeyCode::C if state => {
+								character = match character {
+									Some(_) => None,
+									None => {
+										let mut walker = CharacterController::new(camera.position, 0.3, 0.6);
+										walker.eye_height = 0.5;
+										walker.position -= Vec3(0.0, walker.eye_height, 0.0);
+										Some(walker)
+									}
+								};
+							}
+							VirtualKeyCode::Comma if state => { sun_angle -= 0.1; }
+							VirtualKeyCode::Period if state => { sun_angle += 0.1; }
+							VirtualKeyCode::Slash if state => { sun_angle = 0.0; }
 							
-							VirtualKeyCode::R => if state {
-								objects = crate::scene::initialize_scene(&display).0;
-								main_tx.send(objects.iter().map(Object::get_dynamic_state).collect::<Vec<_>>()).unwrap();
+							VirtualKeyCode::R if state => {
+								objects = gl_engine::scene::build_scene();
+								let soft_body_positions = gl_engine::scene::build_soft_bodies().iter().map(|s| s.positions.to_vec()).collect();
+								main_tx.send((objects.iter().map(Object::get_dynamic_state).collect::<Vec<_>>(), soft_body_positions)).unwrap();
+								particles.clear();
 							}
 							
-							VirtualKeyCode::Escape => if state && capture {
+							VirtualKeyCode::Escape if state && capture => {
 								capture = false;
 								display.gl_window().window().set_cursor_grab(CursorGrabMode::None).unwrap();
 								display.gl_window().window().set_cursor_visible(true);
@@ -214,12 +275,10 @@ fn main() {
 						
 					}
 				}
-				WindowEvent::MouseInput { device_id: _, state, button: _, .. } => {
-					if let ElementState::Pressed = state {
-						capture = true;
-						display.gl_window().window().set_cursor_grab(CursorGrabMode::Confined).unwrap();
-						display.gl_window().window().set_cursor_visible(false);
-					}
+				WindowEvent::MouseInput { device_id: _, state: ElementState::Pressed, button: _, .. } => {
+					capture = true;
+					display.gl_window().window().set_cursor_grab(CursorGrabMode::Confined).unwrap();
+					display.gl_window().window().set_cursor_visible(false);
 				}
 				WindowEvent::CursorMoved { device_id: _, position, .. } => {
 					let _dx = position.x - previous_mouse_pos.x;
@@ -240,6 +299,12 @@ fn main() {
 					renderer.resize(&display, width, height);
 				}
 				WindowEvent::CloseRequested => {
+					if let Some(recorder) = recorder.take() {
+						match recorder.finish() {
+							Ok(frames) => eprintln!("recorded {} frames", frames),
+							Err(message) => eprintln!("recording failed: {}", message)
+						}
+					}
 					*control_flow = ControlFlow::Exit;
 				}
 				_ => ()
@@ -249,18 +314,21 @@ fn main() {
 			}
 			Event::RedrawRequested(_) => {
 				let start_time = Instant::now();
-				let dt = start_time.duration_since(previous_frame_time).as_secs_f32();
+				let wall_dt = start_time.duration_since(previous_frame_time).as_secs_f32();
 				previous_frame_time = start_time;
+				// while recording everything moves by whole frames of the recording, however long they take to make
+				let dt = recorder.as_ref().map_or(wall_dt, Recorder::frame_dt);
 				
-				if dt < 1.0 / TIMING_UPDATE_RATE {
-					avg_frame_time += TIMING_UPDATE_RATE * dt * (dt - avg_frame_time);
+				if wall_dt < 1.0 / TIMING_UPDATE_RATE {
+					avg_frame_time += TIMING_UPDATE_RATE * wall_dt * (wall_dt - avg_frame_time);
 				} else {
-					avg_frame_time = dt;
+					avg_frame_time = wall_dt;
 				}
 				
 				loop {
 					match tps_rx.try_recv() {
-						Ok((tick_process_time, tick_dt)) => {
+						Ok((tick_process_time, tick_dt, tick_diagnostics)) => {
+							diagnostics = tick_diagnostics;
 							if tick_dt < 1.0 / TIMING_UPDATE_RATE {
 								avg_tick_time += TIMING_UPDATE_RATE * tick_dt * (tick_dt - avg_tick_time);
 								avg_tick_process_time += TIMING_UPDATE_RATE * tick_dt * (tick_process_time - avg_tick_process_time);
@@ -274,7 +342,7 @@ fn main() {
 					}
 				}
 				
-				display.gl_window().window().set_title(&format!("3d things: {} fps, {:.3} mspf, {} tps, {:.3} mspt", (1.0 / avg_frame_time) as u32, 1000.0 * avg_frame_process_time, (1.0 / avg_tick_time) as u32, 1000.0 * avg_tick_process_time));
+				display.gl_window().window().set_title(&format!("3d things: {} fps, {:.3} mspf, {} tps, {:.3} mspt, {:.2} J, p {:.2}, L {:.2}, {:.4} m deepest", (1.0 / avg_frame_time) as u32, 1000.0 * avg_frame_process_time, (1.0 / avg_tick_time) as u32, 1000.0 * avg_tick_process_time, diagnostics.total_energy(), diagnostics.linear_momentum.length(), diagnostics.angular_momentum.length(), diagnostics.max_penetration));
 				
 				
 				let asin = camera.horizontal_angle.sin();
@@ -286,12 +354,21 @@ fn main() {
 				if dkey { mov.0 -= 1.0 }
 				if space { mov.1 += 1.0 }
 				if shift { mov.1 -= 1.0 }
-				if mov.length_squared() > 0.0 {
-					mov = mov.normalize();
-					let ds = dt * movement_speed;
-					camera.position.0 += (mov.0*acos - mov.2*asin)*ds;
-					camera.position.1 += mov.1*ds;
-					camera.position.2 += (mov.2*acos + mov.0*asin)*ds;
+				match &mut character {
+					Some(character) => {
+						mov.1 = 0.0;
+						if mov.length_squared() > 0.0 { mov = mov.normalize(); }
+						let walk = Vec3(mov.0*acos - mov.2*asin, 0.0, mov.2*acos + mov.0*asin) * movement_speed;
+						character.update(&objects, walk, space, gravity, dt.min(0.1));
+						camera.position = character.eye_position();
+					}
+					None => if mov.length_squared() > 0.0 {
+						mov = mov.normalize();
+						let ds = dt * movement_speed;
+						camera.position.0 += (mov.0*acos - mov.2*asin)*ds;
+						camera.position.1 += mov.1*ds;
+						camera.position.2 += (mov.2*acos + mov.0*asin)*ds;
+					}
 				}
 				
 				if right { camera.horizontal_angle += look_sensitivity }
@@ -300,29 +377,78 @@ fn main() {
 				if down { camera.vertical_angle -= look_sensitivity }
 				
 				
-				let mut dynamic_states = None;
+				if let Some(recorder) = &recorder {
+					step_tx.send(Some(recorder.frame_dt())).unwrap();
+				}
+				
+				let mut latest = None;
 				loop {
 					match main_rx.try_recv() {
-						Ok(states) => dynamic_states = Some(states),
+						Ok(physics_frame) => latest = Some(physics_frame),
 						Err(TryRecvError::Empty) => break,
 						Err(TryRecvError::Disconnected) => panic!()
 					}
 				}
+				if recorder.is_some() {
+					// the world one step of the recording on, whatever came from its own clock before that
+					latest = Some(recorded_rx.recv().unwrap());
+				}
 				
-				if let Some(dynamic_states) = dynamic_states {
+				if let Some((dynamic_states, soft_body_positions, frame)) = latest {
 					for i in 0..objects.len() {
 						objects[i].set_dynamic_state(dynamic_states[i]);
 					}
+					for (buffer, positions) in soft_vertex_buffers.iter().zip(soft_body_positions) {
+						buffer.write(&positions);
+					}
+					if let Some(frame) = frame {
+						debug_frame = frame;
+					}
 				}
 				
 				
 				
-				renderer.render(&display, &camera, &objects, &vertex_buffers, &index_buffers, do_post_process, show_shadowmap, dummy);
+				if run {
+					particles.update(dt.min(0.1), gravity, &objects);
+				}
+				
+				let mut target = display.draw();
+				let lights = gl_engine::scene::build_lights(sun_angle);
+				if take_screenshot || recorder.is_some() {
+					// what's on screen can't be read back reliably once it's swapped, so the frame is drawn offscreen and copied over
+					let (width, height) = target.get_dimensions();
+					let captured = capture::render_offscreen(&display, width, height, |offscreen| {
+						renderer.render(&display, offscreen, &camera, &objects, &vertex_buffers, &index_buffers, &soft_vertex_buffers, &soft_index_buffers, &particles.particles, show_debug.then_some(&debug_frame), do_post_process, show_shadowmap, &lights);
+						offscreen.fill(&target, MagnifySamplerFilter::Nearest);
+					});
+					target.finish().unwrap();
+					
+					match captured {
+						Ok(image) => {
+							if take_screenshot {
+								match capture::save_screenshot(&image, Path::new(SCREENSHOT_DIRECTORY)) {
+									Ok(path) => eprintln!("saved {}", path.display()),
+									Err(message) => eprintln!("{}", message)
+								}
+							}
+							if let Some(Err(message)) = recorder.as_mut().map(|recorder| recorder.record(image)) {
+								eprintln!("recording stopped: {}", message);
+								recorder = None;
+								step_tx.send(None).unwrap();
+							}
+						}
+						Err(message) => eprintln!("{}", message)
+					}
+					take_screenshot = false;
+				} else {
+					renderer.render(&display, &mut target, &camera, &objects, &vertex_buffers, &index_buffers, &soft_vertex_buffers, &soft_index_buffers, &particles.particles, show_debug.then_some(&debug_frame), do_post_process, show_shadowmap, &lights);
+					target.finish().unwrap();
+				}
 				
 				
 				let process_time = start_time.elapsed().as_secs_f32();
-				if dt < 1.0 / TIMING_UPDATE_RATE {
-					avg_frame_process_time += TIMING_UPDATE_RATE * dt * (process_time - avg_frame_process_time);
+				if wall_dt < 1.0 / TIMING_UPDATE_RATE {
+					avg_frame_process_time += TIMING_UPDATE_RATE * wall_dt * (process_time - avg_frame_process_time);
 				} else {
 					avg_frame_process_time = process_time;
 				}
@@ -5,6 +5,8 @@ pub struct Vec2(pub f32, pub f32);
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct Vec3(pub f32, pub f32, pub f32);
 #[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mat3(pub [[f32; 3]; 3]); // [column][row], same layout as Mat4
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Mat4(pub [[f32; 4]; 4]); // [column][row], [x][y], inner list is a column
 
 #[allow(dead_code)]
@@ -88,6 +90,14 @@ impl Mat4 {
 		]).mult_mat4(self)
 	}
 	
+	pub fn get_rotation(&self) -> Mat3 {
+		Mat3([
+			[self.0[0][0], self.0[0][1], self.0[0][2]],
+			[self.0[1][0], self.0[1][1], self.0[1][2]],
+			[self.0[2][0], self.0[2][1], self.0[2][2]],
+		])
+	}
+	
 	pub fn get_position(&self) -> Vec3 {
 		Vec3(self.0[3][0], self.0[3][1], self.0[3][2])
 	}
@@ -122,6 +132,72 @@ impl Mat4 {
 	}
 }
 
+impl Mat3 {
+	pub fn identity() -> Mat3 {
+		Mat3([
+			[1.0, 0.0, 0.0],
+			[0.0, 1.0, 0.0],
+			[0.0, 0.0, 1.0],
+		])
+	}
+	
+	pub fn zero() -> Mat3 {
+		Mat3([[0.0; 3]; 3])
+	}
+	
+	pub fn diagonal(d: Vec3) -> Mat3 {
+		Mat3([
+			[d.0, 0.0, 0.0],
+			[0.0, d.1, 0.0],
+			[0.0, 0.0, d.2],
+		])
+	}
+	
+	pub fn from_columns(x: Vec3, y: Vec3, z: Vec3) -> Mat3 {
+		Mat3([[x.0, x.1, x.2], [y.0, y.1, y.2], [z.0, z.1, z.2]])
+	}
+	
+	pub fn outer(a: Vec3, b: Vec3) -> Mat3 {
+		Mat3::from_columns(a * b.0, a * b.1, a * b.2)
+	}
+	
+	pub fn column(&self, i: usize) -> Vec3 {
+		Vec3(self.0[i][0], self.0[i][1], self.0[i][2])
+	}
+	
+	pub fn mult_vec3(&self, v: Vec3) -> Vec3 {
+		self.column(0) * v.0 + self.column(1) * v.1 + self.column(2) * v.2
+	}
+	
+	pub fn mult_mat3(&self, m: &Mat3) -> Mat3 {
+		Mat3::from_columns(self.mult_vec3(m.column(0)), self.mult_vec3(m.column(1)), self.mult_vec3(m.column(2)))
+	}
+	
+	pub fn transpose(&self) -> Mat3 {
+		Mat3([
+			[self.0[0][0], self.0[1][0], self.0[2][0]],
+			[self.0[0][1], self.0[1][1], self.0[2][1]],
+			[self.0[0][2], self.0[1][2], self.0[2][2]],
+		])
+	}
+	
+	pub fn trace(&self) -> f32 {
+		self.0[0][0] + self.0[1][1] + self.0[2][2]
+	}
+	
+	pub fn determinant(&self) -> f32 {
+		self.column(0).dot(self.column(1).cross(self.column(2)))
+	}
+	
+	// returns the zero matrix for singular input, which is what an infinite mass wants anyway
+	pub fn inverse(&self) -> Mat3 {
+		let det = self.determinant();
+		if det.abs() < 1e-12 { return Mat3::zero(); }
+		let (x, y, z) = (self.column(0), self.column(1), self.column(2));
+		Mat3::from_columns(y.cross(z), z.cross(x), x.cross(y)).transpose() * (1.0 / det)
+	}
+}
+
 
 #[allow(dead_code)]
 impl Vec2 {
@@ -144,6 +220,13 @@ impl Vec3 {
 		m.0[0][1]*self.0 + m.0[1][1]*self.1 + m.0[2][1]*self.2 + m.0[3][1],
 		m.0[0][2]*self.0 + m.0[1][2]*self.1 + m.0[2][2]*self.2 + m.0[3][2],
 	) }
+	#[inline] pub fn apply_rotation(self, m: &Mat4) -> Self { Self(
+		m.0[0][0]*self.0 + m.0[1][0]*self.1 + m.0[2][0]*self.2,
+		m.0[0][1]*self.0 + m.0[1][1]*self.1 + m.0[2][1]*self.2,
+		m.0[0][2]*self.0 + m.0[1][2]*self.1 + m.0[2][2]*self.2,
+	) }
+	#[inline] pub fn min(self, v: Self) -> Self { Self(self.0.min(v.0), self.1.min(v.1), self.2.min(v.2)) }
+	#[inline] pub fn max(self, v: Self) -> Self { Self(self.0.max(v.0), self.1.max(v.1), self.2.max(v.2)) }
 }
 
 impl std::ops::Add for Vec2 { type Output = Self; #[inline] fn add(self, rhs: Self) -> Self::Output { Self(self.0 + rhs.0, self.1 + rhs.1) } }
@@ -168,5 +251,7 @@ impl std::ops::Div<f32> for Vec3 { type Output = Self; #[inline] fn div(self, rh
 impl std::ops::DivAssign<f32> for Vec3 { #[inline] fn div_assign(&mut self, rhs: f32) { *self = *self / rhs; } }
 impl std::ops::Mul<Vec3> for f32 { type Output = Vec3; #[inline] fn mul(self, rhs: Vec3) -> Self::Output { rhs * self } }
 
-
-
+impl std::ops::Add for Mat3 { type Output = Self; #[inline] fn add(self, rhs: Self) -> Self::Output { Mat3::from_columns(self.column(0) + rhs.column(0), self.column(1) + rhs.column(1), self.column(2) + rhs.column(2)) } }
+impl std::ops::AddAssign for Mat3 { #[inline] fn add_assign(&mut self, rhs: Self) { *self = *self + rhs; } }
+impl std::ops::Sub for Mat3 { type Output = Self; #[inline] fn sub(self, rhs: Self) -> Self::Output { Mat3::from_columns(self.column(0) - rhs.column(0), self.column(1) - rhs.column(1), self.column(2) - rhs.column(2)) } }
+impl std::ops::Mul<f32> for Mat3 { type Output = Self; #[inline] fn mul(self, rhs: f32) -> Self::Output { Mat3::from_columns(self.column(0) * rhs, self.column(1) * rhs, self.column(2) * rhs) } }
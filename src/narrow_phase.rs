@@ -0,0 +1,579 @@
+use crate::{math_structs::{Mat4, Vec2, Vec3}, physics::{point_in_triangle, tangent_basis, Aabb, ContactPoint, CONTACT_MARGIN, MAX_CONTACT_DEPTH}, shape::{Convex, Shape}};
+
+
+static GJK_ITERATIONS: usize = 64;
+static EPA_ITERATIONS: usize = 64;
+static EPA_TOLERANCE: f32 = 1e-4;
+static CONVEX_FEATURE: u64 = 3 << 48;
+
+
+#[derive(Copy, Clone)]
+struct SupportPoint {
+	w: Vec3, // a - b
+	a: Vec3,
+	b: Vec3
+}
+
+enum Gjk {
+	Separated { point_a: Vec3, point_b: Vec3 },
+	Overlapping(Vec<SupportPoint>)
+}
+
+
+// Contacts between two convex shapes, with normals pointing from b towards a.
+pub(crate) fn convex_contacts(shape_a: &Shape, transform_a: &Mat4, a: &Convex, shape_b: &Shape, transform_b: &Mat4, b: &Convex) -> Vec<ContactPoint> {
+	let normal = match (shape_a, shape_b) {
+		(Shape::Sphere { radius }, Shape::Box { half_extents }) => return sphere_box(a.points[0], *radius, transform_b, *half_extents, false),
+		(Shape::Box { half_extents }, Shape::Sphere { radius }) => return sphere_box(b.points[0], *radius, transform_a, *half_extents, true),
+		(Shape::Box { half_extents: half_a }, Shape::Box { half_extents: half_b }) => box_box_normal(transform_a, *half_a, transform_b, *half_b),
+		_ => match separating_normal(a, b) {
+			Some(normal) => normal,
+			None => return Vec::new()
+		}
+	};
+	manifold(a, b, normal.0, normal.1)
+}
+
+// Contacts between a convex shape and the triangles of a mesh near it, with normals pointing from the mesh towards the shape.
+// Triangles come with their index in the mesh, which goes into the features.
+pub(crate) fn convex_mesh_contacts(convex: &Convex, triangles: &[(usize, Vec3, Vec3, Vec3)]) -> Vec<ContactPoint> {
+	let bounds = convex.aabb().expand(CONTACT_MARGIN);
+	let mut points = Vec::new();
+	
+	for &(l, a, b, c) in triangles.iter() {
+		let triangle = Convex { points: vec![a, b, c], radius: 0.0 };
+		if !Aabb::from_points(&triangle.points).overlaps(&bounds) { continue; }
+		
+		let (ta, tb, tc) = (triangle.points[0], triangle.points[1], triangle.points[2]);
+		let normal = (tc - ta).cross(tb - ta);
+		if normal.length_squared() < 1e-12 { continue; }
+		let normal = normal.normalize();
+		
+		// Triangles only push out along their face normal, which keeps shapes rolling across the seams
+		// between flat triangles from catching on edge normals. Anything deep behind a face is left to the ccd.
+		let separation = convex.points.iter().map(|&p| (p - ta).dot(normal)).fold(f32::INFINITY, f32::min);
+		if separation - convex.radius < -MAX_CONTACT_DEPTH { continue; }
+		
+		for mut point in manifold(convex, &triangle, normal, separation) {
+			if !point_in_triangle(point.position - normal * (point.position - ta).dot(normal), ta, tb, tc) { continue; }
+			point.feature |= (l as u64) << 8;
+			points.push(point);
+		}
+	}
+	
+	points
+}
+
+
+// Closest points between the cores of two convex shapes, none when the cores overlap.
+pub(crate) fn closest_points(a: &Convex, b: &Convex) -> Option<(Vec3, Vec3)> {
+	match a.points.len() <= 2 && b.points.len() <= 2 {
+		true => Some(closest_segment_points(a.points[0], a.points[a.points.len() - 1], b.points[0], b.points[b.points.len() - 1])),
+		false => match gjk(a, b) {
+			Gjk::Separated { point_a, point_b } => Some((point_a, point_b)),
+			Gjk::Overlapping(_) => None
+		}
+	}
+}
+
+
+// normal from b to a and the distance between the cores along it, negative when they overlap
+pub(crate) fn separating_normal(a: &Convex, b: &Convex) -> Option<(Vec3, f32)> {
+	let fallback = || {
+		let offset = a.center() - b.center();
+		match offset.length_squared() > 1e-12 {
+			true => offset.normalize(),
+			false => Vec3(0.0, 1.0, 0.0)
+		}
+	};
+	
+	let (normal, separation) = match a.points.len() <= 2 && b.points.len() <= 2 {
+		// spheres and capsules are points and segments, which only need their closest points
+		true => {
+			let (a0, a1) = (a.points[0], a.points[a.points.len() - 1]);
+			let (b0, b1) = (b.points[0], b.points[b.points.len() - 1]);
+			let (point_a, point_b) = closest_segment_points(a0, a1, b0, b1);
+			let distance = (point_a - point_b).length();
+			match distance > 1e-6 {
+				true => ((point_a - point_b) / distance, distance),
+				false => (fallback(), 0.0)
+			}
+		}
+		false => match gjk(a, b) {
+			Gjk::Separated { point_a, point_b } => {
+				let distance = (point_a - point_b).length();
+				match distance > 1e-6 {
+					true => ((point_a - point_b) / distance, distance),
+					false => (fallback(), 0.0)
+				}
+			}
+			Gjk::Overlapping(simplex) => epa(a, b, simplex).unwrap_or((fallback(), 0.0))
+		}
+	};
+	
+	match separation - a.radius - b.radius > CONTACT_MARGIN {
+		true => None,
+		false => Some((normal, separation))
+	}
+}
+
+
+// Clips the features of a and b facing each other along the normal against one another,
+// which gives whole faces of contact for resting boxes and hulls instead of a single point.
+fn manifold(a: &Convex, b: &Convex, normal: Vec3, core_separation: f32) -> Vec<ContactPoint> {
+	let radius = a.radius + b.radius;
+	if core_separation - radius > CONTACT_MARGIN { return Vec::new(); }
+	
+	let lowest_a = a.points.iter().map(|p| p.dot(normal)).fold(f32::INFINITY, f32::min);
+	let highest_b = b.points.iter().map(|p| p.dot(normal)).fold(f32::NEG_INFINITY, f32::max);
+	let feature_a = a.points.iter().copied().filter(|p| p.dot(normal) <= lowest_a + CONTACT_MARGIN).collect::<Vec<Vec3>>();
+	let feature_b = b.points.iter().copied().filter(|p| p.dot(normal) >= highest_b - CONTACT_MARGIN).collect::<Vec<Vec3>>();
+	
+	// the contact sits halfway between the two surfaces
+	let contact = |point_a: Vec3, point_b: Vec3, index: usize| {
+		let separation = (point_a - point_b).dot(normal) - radius;
+		ContactPoint {
+			feature: CONVEX_FEATURE | index as u64,
+			position: (point_a + point_b) * 0.5 + normal * (0.5 * (b.radius - a.radius)),
+			normal,
+			separation,
+			normal_impulse: 0.0,
+			tangent_impulse: (0.0, 0.0)
+		}
+	};
+	
+	let mut points = Vec::new();
+	
+	if feature_a.len() >= 3 || feature_b.len() >= 3 {
+		let a_is_reference = feature_a.len() >= feature_b.len();
+		let (reference, incident) = match a_is_reference {
+			true => (order_polygon(&feature_a, normal), order_polygon(&feature_b, normal)),
+			false => (order_polygon(&feature_b, normal), order_polygon(&feature_a, normal))
+		};
+		
+		for (index, q) in clip_polygon(incident, &reference, normal).into_iter().enumerate() {
+			let point = match a_is_reference {
+				true => contact(q + normal * (lowest_a - q.dot(normal)), q, index),
+				false => contact(q, q - normal * (q.dot(normal) - highest_b), index)
+			};
+			if point.separation <= CONTACT_MARGIN { points.push(point); }
+		}
+	}
+	
+	if points.is_empty() {
+		let segment_a = match feature_a.len() {
+			1 | 2 => (feature_a[0], feature_a[feature_a.len() - 1]),
+			_ => { let p = a.support(-normal); (p, p) }
+		};
+		let segment_b = match feature_b.len() {
+			1 | 2 => (feature_b[0], feature_b[feature_b.len() - 1]),
+			_ => { let p = b.support(normal); (p, p) }
+		};
+		
+		// parallel edges, like a capsule lying along a box edge, touch along an interval
+		let (da, db) = (segment_a.1 - segment_a.0, segment_b.1 - segment_b.0);
+		let parallel = da.length_squared() > 1e-8 && db.length_squared() > 1e-8 && da.cross(db).length_squared() < 1e-4 * da.length_squared() * db.length_squared();
+		let mut pairs = Vec::new();
+		if parallel {
+			let direction = da.normalize();
+			let (t0, t1) = ((segment_b.0 - segment_a.0).dot(direction), (segment_b.1 - segment_a.0).dot(direction));
+			let (lower, upper) = (t0.min(t1).max(0.0), t0.max(t1).min(da.length()));
+			if upper - lower > 1e-4 {
+				for t in [lower, upper] {
+					let point_a = segment_a.0 + direction * t;
+					pairs.push((point_a, closest_segment_points(point_a, point_a, segment_b.0, segment_b.1).1));
+				}
+			}
+		}
+		if pairs.is_empty() {
+			// a face's support point is an arbitrary corner of it, so project the other side onto the face plane instead
+			match (feature_a.len() >= 3, feature_b.len() >= 3) {
+				(false, true) => for &point_a in feature_a.iter() {
+					pairs.push((point_a, point_a - normal * (point_a.dot(normal) - highest_b)));
+				}
+				(true, false) => for &point_b in feature_b.iter() {
+					pairs.push((point_b + normal * (lowest_a - point_b.dot(normal)), point_b));
+				}
+				_ => pairs.push(closest_segment_points(segment_a.0, segment_a.1, segment_b.0, segment_b.1))
+			}
+		}
+		
+		for (index, (point_a, point_b)) in pairs.into_iter().enumerate() {
+			let point = contact(point_a, point_b, index);
+			if point.separation <= CONTACT_MARGIN { points.push(point); }
+		}
+	}
+	
+	points
+}
+
+
+// convex hull of the points projected onto the plane of the normal, in winding order
+fn order_polygon(points: &[Vec3], normal: Vec3) -> Vec<Vec3> {
+	if points.len() < 3 { return points.to_vec(); }
+	
+	let (t1, t2) = tangent_basis(normal);
+	let mut projected = points.iter().map(|&p| (Vec2(p.dot(t1), p.dot(t2)), p)).collect::<Vec<(Vec2, Vec3)>>();
+	projected.sort_by(|a, b| a.0.0.total_cmp(&b.0.0).then(a.0.1.total_cmp(&b.0.1)));
+	
+	// monotone chain
+	let mut hull: Vec<(Vec2, Vec3)> = Vec::new();
+	for pass in 0..2 {
+		let start = hull.len();
+		let iter: Box<dyn Iterator<Item = &(Vec2, Vec3)>> = match pass {
+			0 => Box::new(projected.iter()),
+			_ => Box::new(projected.iter().rev())
+		};
+		for &p in iter {
+			while hull.len() >= start + 2 && (hull[hull.len() - 1].0 - hull[hull.len() - 2].0).cross(p.0 - hull[hull.len() - 2].0) <= 1e-9 {
+				hull.pop();
+			}
+			hull.push(p);
+		}
+		hull.pop();
+	}
+	
+	hull.into_iter().map(|(_, p)| p).collect()
+}
+
+// Sutherland-Hodgman against the side planes of the reference polygon
+fn clip_polygon(mut polygon: Vec<Vec3>, reference: &[Vec3], normal: Vec3) -> Vec<Vec3> {
+	let center = reference.iter().fold(Vec3(0.0, 0.0, 0.0), |sum, &p| sum + p) / reference.len() as f32;
+	
+	for i in 0..reference.len() {
+		if polygon.is_empty() { break; }
+		let (r0, r1) = (reference[i], reference[(i + 1) % reference.len()]);
+		let mut side = (r1 - r0).cross(normal);
+		if side.dot(center - r0) > 0.0 { side = -side; }
+		
+		let mut clipped = Vec::new();
+		for j in 0..polygon.len() {
+			let (p, q) = (polygon[j], polygon[(j + 1) % polygon.len()]);
+			let (dp, dq) = ((p - r0).dot(side), (q - r0).dot(side));
+			if dp <= 0.0 { clipped.push(p); }
+			if (dp < 0.0 && dq > 0.0) || (dp > 0.0 && dq < 0.0) {
+				clipped.push(p + (q - p) * (dp / (dp - dq)));
+			}
+		}
+		clipped.dedup_by(|p, q| (*p - *q).length_squared() < 1e-10);
+		polygon = clipped;
+	}
+	
+	if polygon.len() > 1 && (polygon[0] - polygon[polygon.len() - 1]).length_squared() < 1e-10 { polygon.pop(); }
+	polygon
+}
+
+
+// closest points between segments p0-p1 and q0-q1, either of which may be a single point
+fn closest_segment_points(p0: Vec3, p1: Vec3, q0: Vec3, q1: Vec3) -> (Vec3, Vec3) {
+	let (d1, d2, r) = (p1 - p0, q1 - q0, p0 - q0);
+	let (a, e, f) = (d1.dot(d1), d2.dot(d2), d2.dot(r));
+	
+	let (s, t) = if a <= 1e-12 && e <= 1e-12 {
+		(0.0, 0.0)
+	} else if a <= 1e-12 {
+		(0.0, (f / e).clamp(0.0, 1.0))
+	} else {
+		let c = d1.dot(r);
+		if e <= 1e-12 {
+			((-c / a).clamp(0.0, 1.0), 0.0)
+		} else {
+			let b = d1.dot(d2);
+			let denominator = a * e - b * b;
+			let mut s = match denominator > 1e-12 {
+				true => ((b * f - c * e) / denominator).clamp(0.0, 1.0),
+				false => 0.0
+			};
+			let mut t = (b * s + f) / e;
+			if t < 0.0 {
+				t = 0.0;
+				s = (-c / a).clamp(0.0, 1.0);
+			} else if t > 1.0 {
+				t = 1.0;
+				s = ((b - c) / a).clamp(0.0, 1.0);
+			}
+			(s, t)
+		}
+	};
+	
+	(p0 + d1 * s, q0 + d2 * t)
+}
+
+
+fn sphere_box(center: Vec3, radius: f32, transform: &Mat4, half_extents: Vec3, flip: bool) -> Vec<ContactPoint> {
+	let rotation = transform.get_rotation();
+	let position = transform.get_position();
+	let local = rotation.transpose().mult_vec3(center - position);
+	let clamped = Vec3(local.0.clamp(-half_extents.0, half_extents.0), local.1.clamp(-half_extents.1, half_extents.1), local.2.clamp(-half_extents.2, half_extents.2));
+	
+	let (normal, separation, closest) = match (local - clamped).length_squared() > 1e-12 {
+		true => {
+			let closest = position + rotation.mult_vec3(clamped);
+			let distance = (center - closest).length();
+			((center - closest) / distance, distance - radius, closest)
+		}
+		// the center is inside, push out through the nearest face
+		false => {
+			let depths = [half_extents.0 - local.0.abs(), half_extents.1 - local.1.abs(), half_extents.2 - local.2.abs()];
+			let axis = (0..3).min_by(|&i, &j| depths[i].total_cmp(&depths[j])).unwrap();
+			let sign = match [local.0, local.1, local.2][axis] < 0.0 { true => -1.0, false => 1.0 };
+			let normal = rotation.column(axis) * sign;
+			(normal, -depths[axis] - radius, center + normal * depths[axis])
+		}
+	};
+	if separation > CONTACT_MARGIN { return Vec::new(); }
+	
+	vec![ContactPoint {
+		feature: CONVEX_FEATURE,
+		position: (center - normal * radius + closest) * 0.5,
+		normal: if flip { -normal } else { normal },
+		separation,
+		normal_impulse: 0.0,
+		tangent_impulse: (0.0, 0.0)
+	}]
+}
+
+// separating axis test over the face normals of both boxes and the cross products of their edges
+fn box_box_normal(transform_a: &Mat4, half_a: Vec3, transform_b: &Mat4, half_b: Vec3) -> (Vec3, f32) {
+	let (rotation_a, rotation_b) = (transform_a.get_rotation(), transform_b.get_rotation());
+	let axes_a = [rotation_a.column(0), rotation_a.column(1), rotation_a.column(2)];
+	let axes_b = [rotation_b.column(0), rotation_b.column(1), rotation_b.column(2)];
+	let offset = transform_a.get_position() - transform_b.get_position();
+	let project = |half: Vec3, axes: &[Vec3; 3], l: Vec3| half.0 * axes[0].dot(l).abs() + half.1 * axes[1].dot(l).abs() + half.2 * axes[2].dot(l).abs();
+	
+	let mut best = (Vec3(0.0, 1.0, 0.0), f32::NEG_INFINITY);
+	let mut test = |l: Vec3, bias: f32| {
+		let s = offset.dot(l);
+		let separation = s.abs() - project(half_a, &axes_a, l) - project(half_b, &axes_b, l);
+		if separation > best.1 + bias {
+			best = (if s < 0.0 { -l } else { l }, separation);
+		}
+	};
+	
+	for l in axes_a.iter().chain(axes_b.iter()) {
+		test(*l, 0.0);
+	}
+	// edge axes have to be clearly better, otherwise resting faces flicker onto edges
+	for u in axes_a.iter() {
+		for v in axes_b.iter() {
+			let l = u.cross(*v);
+			if l.length_squared() > 1e-6 { test(l.normalize(), 1e-3); }
+		}
+	}
+	
+	best
+}
+
+
+
+fn support(a: &Convex, b: &Convex, direction: Vec3) -> SupportPoint {
+	let (point_a, point_b) = (a.support(direction), b.support(-direction));
+	SupportPoint { w: point_a - point_b, a: point_a, b: point_b }
+}
+
+
+// closest points between the cores of two convex shapes, or the simplex enclosing the origin if they overlap
+fn gjk(a: &Convex, b: &Convex) -> Gjk {
+	let mut direction = a.center() - b.center();
+	if direction.length_squared() < 1e-12 { direction = Vec3(1.0, 0.0, 0.0); }
+	
+	let mut simplex = vec![support(a, b, direction)];
+	let mut witness = (simplex[0].a, simplex[0].b);
+	let mut best_distance = f32::INFINITY;
+	
+	for _ in 0..GJK_ITERATIONS {
+		let weights = match closest_on_simplex(&simplex.iter().map(|s| s.w).collect::<Vec<Vec3>>()) {
+			Some(weights) => weights,
+			None => return Gjk::Overlapping(simplex)
+		};
+		simplex = weights.iter().map(|&(i, _)| simplex[i]).collect();
+		
+		let mut v = Vec3(0.0, 0.0, 0.0);
+		let (mut point_a, mut point_b) = (Vec3(0.0, 0.0, 0.0), Vec3(0.0, 0.0, 0.0));
+		for (s, &(_, weight)) in simplex.iter().zip(weights.iter()) {
+			v += s.w * weight;
+			point_a += s.a * weight;
+			point_b += s.b * weight;
+		}
+		
+		let distance = v.length_squared();
+		if distance < 1e-10 { return Gjk::Overlapping(simplex); }
+		if distance >= best_distance { break; }
+		best_distance = distance;
+		witness = (point_a, point_b);
+		
+		let next = support(a, b, -v);
+		if distance - v.dot(next.w) <= 1e-6 * distance { break; }
+		simplex.push(next);
+	}
+	
+	Gjk::Separated { point_a: witness.0, point_b: witness.1 }
+}
+
+
+// barycentric weights of the point of the simplex closest to the origin, none if a tetrahedron contains it
+fn closest_on_simplex(points: &[Vec3]) -> Option<Vec<(usize, f32)>> {
+	match points.len() {
+		1 => Some(vec![(0, 1.0)]),
+		2 => {
+			let ab = points[1] - points[0];
+			let t = match ab.length_squared() > 1e-12 {
+				true => (-points[0].dot(ab) / ab.length_squared()).clamp(0.0, 1.0),
+				false => 0.0
+			};
+			Some(match t {
+				t if t <= 0.0 => vec![(0, 1.0)],
+				t if t >= 1.0 => vec![(1, 1.0)],
+				t => vec![(0, 1.0 - t), (1, t)]
+			})
+		}
+		3 => Some(closest_on_triangle(points[0], points[1], points[2]).into_iter().filter(|&(_, w)| w > 0.0).collect()),
+		_ => {
+			let faces = [(0, 1, 2, 3), (0, 1, 3, 2), (0, 2, 3, 1), (1, 2, 3, 0)];
+			let mut best: Option<(f32, Vec<(usize, f32)>)> = None;
+			let mut inside = true;
+			for (i, j, k, opposite) in faces {
+				let n = (points[j] - points[i]).cross(points[k] - points[i]);
+				let (origin_side, opposite_side) = (-points[i].dot(n), (points[opposite] - points[i]).dot(n));
+				// flat tetrahedra can't contain anything, so every face counts
+				if origin_side * opposite_side >= 0.0 && opposite_side.abs() > 1e-9 { continue; }
+				inside = false;
+				
+				let weights = closest_on_triangle(points[i], points[j], points[k]);
+				let closest = weights.iter().fold(Vec3(0.0, 0.0, 0.0), |sum, &(l, w)| sum + [points[i], points[j], points[k]][l] * w);
+				let distance = closest.length_squared();
+				if best.as_ref().is_none_or(|(d, _)| distance < *d) {
+					let indices = [i, j, k];
+					best = Some((distance, weights.into_iter().filter(|&(_, w)| w > 0.0).map(|(l, w)| (indices[l], w)).collect()));
+				}
+			}
+			match inside {
+				true => None,
+				false => best.map(|(_, weights)| weights)
+			}
+		}
+	}
+}
+
+// the point of the triangle nearest to p
+pub(crate) fn closest_point_on_triangle(p: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+	let corners = [a, b, c];
+	closest_on_triangle(a - p, b - p, c - p).iter().fold(Vec3(0.0, 0.0, 0.0), |sum, &(i, weight)| sum + corners[i] * weight)
+}
+
+// from Real-Time Collision Detection, with the origin as the query point
+fn closest_on_triangle(a: Vec3, b: Vec3, c: Vec3) -> [(usize, f32); 3] {
+	let (ab, ac) = (b - a, c - a);
+	
+	let (d1, d2) = (-ab.dot(a), -ac.dot(a));
+	if d1 <= 0.0 && d2 <= 0.0 { return [(0, 1.0), (1, 0.0), (2, 0.0)]; }
+	
+	let (d3, d4) = (-ab.dot(b), -ac.dot(b));
+	if d3 >= 0.0 && d4 <= d3 { return [(0, 0.0), (1, 1.0), (2, 0.0)]; }
+	
+	let vc = d1 * d4 - d3 * d2;
+	if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+		let v = d1 / (d1 - d3);
+		return [(0, 1.0 - v), (1, v), (2, 0.0)];
+	}
+	
+	let (d5, d6) = (-ab.dot(c), -ac.dot(c));
+	if d6 >= 0.0 && d5 <= d6 { return [(0, 0.0), (1, 0.0), (2, 1.0)]; }
+	
+	let vb = d5 * d2 - d1 * d6;
+	if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+		let w = d2 / (d2 - d6);
+		return [(0, 1.0 - w), (1, 0.0), (2, w)];
+	}
+	
+	let va = d3 * d6 - d5 * d4;
+	if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+		let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+		return [(0, 0.0), (1, 1.0 - w), (2, w)];
+	}
+	
+	let denominator = va + vb + vc;
+	if denominator.abs() < 1e-18 { return [(0, 1.0), (1, 0.0), (2, 0.0)]; }
+	let (v, w) = (vb / denominator, vc / denominator);
+	[(0, 1.0 - v - w), (1, v), (2, w)]
+}
+
+
+// Expanding polytope over the minkowski difference, returns the normal from b to a and the (negative) separation.
+// None when the difference is flat and has no interior to expand from.
+fn epa(a: &Convex, b: &Convex, mut vertices: Vec<SupportPoint>) -> Option<(Vec3, f32)> {
+	let directions = [Vec3(1.0, 0.0, 0.0), Vec3(-1.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0), Vec3(0.0, -1.0, 0.0), Vec3(0.0, 0.0, 1.0), Vec3(0.0, 0.0, -1.0)];
+	
+	// grow whatever gjk stopped with into a tetrahedron
+	if vertices.len() == 1 {
+		if let Some(p) = directions.iter().map(|&d| support(a, b, d)).find(|p| (p.w - vertices[0].w).length_squared() > 1e-10) {
+			vertices.push(p);
+		}
+	}
+	if vertices.len() == 2 {
+		let ab = vertices[1].w - vertices[0].w;
+		let found = directions.iter().map(|&d| ab.cross(d)).filter(|d| d.length_squared() > 1e-10)
+			.flat_map(|d| [d, -d]).map(|d| support(a, b, d))
+			.find(|p| ab.cross(p.w - vertices[0].w).length_squared() > 1e-10 * ab.length_squared());
+		if let Some(p) = found { vertices.push(p); }
+	}
+	if vertices.len() == 3 {
+		let n = (vertices[1].w - vertices[0].w).cross(vertices[2].w - vertices[0].w);
+		if n.length_squared() < 1e-12 { return None; }
+		let n = n.normalize();
+		let found = [n, -n].into_iter().map(|d| support(a, b, d)).find(|p| (p.w - vertices[0].w).dot(n).abs() > 1e-5);
+		if let Some(p) = found { vertices.push(p); }
+	}
+	if vertices.len() < 4 { return None; }
+	
+	let center = vertices.iter().fold(Vec3(0.0, 0.0, 0.0), |sum, v| sum + v.w) * 0.25;
+	if (vertices[1].w - vertices[0].w).cross(vertices[2].w - vertices[0].w).dot(vertices[3].w - vertices[0].w).abs() < 1e-9 { return None; }
+	
+	let mut faces = Vec::new();
+	for (i, j, k) in [(0, 1, 2), (0, 3, 1), (0, 2, 3), (1, 3, 2)] {
+		let n = (vertices[j].w - vertices[i].w).cross(vertices[k].w - vertices[i].w);
+		faces.push(match n.dot(vertices[i].w - center) >= 0.0 {
+			true => [i, j, k],
+			false => [i, k, j]
+		});
+	}
+	
+	let face_plane = |vertices: &[SupportPoint], face: &[usize; 3]| {
+		let n = (vertices[face[1]].w - vertices[face[0]].w).cross(vertices[face[2]].w - vertices[face[0]].w);
+		match n.length_squared() > 1e-18 {
+			true => { let n = n.normalize(); Some((n, n.dot(vertices[face[0]].w))) }
+			false => None
+		}
+	};
+	
+	let mut closest = None;
+	for _ in 0..EPA_ITERATIONS {
+		closest = faces.iter().filter_map(|f| face_plane(&vertices, f)).min_by(|x, y| x.1.total_cmp(&y.1));
+		let (normal, distance) = closest?;
+		
+		let w = support(a, b, normal);
+		if w.w.dot(normal) - distance < EPA_TOLERANCE { break; }
+		
+		// remove every face the new point can see and patch the hole from the horizon
+		let mut horizon: Vec<(usize, usize)> = Vec::new();
+		faces.retain(|face| {
+			let visible = face_plane(&vertices, face).is_none_or(|(n, d)| n.dot(w.w) - d > 0.0);
+			if visible {
+				for e in 0..3 {
+					let edge = (face[e], face[(e + 1) % 3]);
+					match horizon.iter().position(|&(p, q)| (p, q) == (edge.1, edge.0)) {
+						Some(index) => { horizon.swap_remove(index); }
+						None => horizon.push(edge)
+					}
+				}
+			}
+			!visible
+		});
+		
+		vertices.push(w);
+		let new_index = vertices.len() - 1;
+		faces.extend(horizon.into_iter().map(|(p, q)| [p, q, new_index]));
+	}
+	
+	closest.map(|(normal, distance)| (-normal, -distance))
+}
@@ -1,6 +1,6 @@
-use glium::{index::PrimitiveType, Display, IndexBuffer, VertexBuffer};
+use glium::{backend::Facade, index::PrimitiveType, IndexBuffer, VertexBuffer};
 
-use crate::math_structs::{Mat4, Vec3};
+use crate::{heightfield::Heightfield, integrator, math_structs::{Mat3, Mat4, Vec3}, physics::{Aabb, Ccd}, shape::{self, Shape}};
 
 
 #[derive(Clone)]
@@ -8,43 +8,182 @@ pub struct Object {
 	pub vertices: Box<[Vec3]>,
 	pub indices: Box<[(u16, u16, u16)]>,
 	pub edges: Box<[(u16, u16)]>,
+	pub shape: Shape,
 	pub transform: Mat4,
 	pub velocity: Vec3,
 	pub angular_velocity: Vec3,
+	pub inverse_mass: f32,
+	pub inverse_inertia: Mat3, // model space, about the center of mass, which set_density puts at the model origin
+	pub inertia: Mat3, // the same, kept as well since heavy bodies' inverse is too small to invert back
+	pub volume: f32,
+	pub force: Vec3, // accumulated over a step, then cleared
+	pub torque: Vec3,
+	pub gravity_scale: f32,
+	pub linear_damping: f32,
+	pub angular_damping: f32,
+	pub restitution: f32,
+	pub friction: f32,
+	pub is_trigger: bool, // reports overlaps as contact events but never collides
+	pub ccd: Ccd, // how impacts with meshes are found over a step
+	pub sleeping: bool,
+	pub sleep_timer: f32,
 }
 
 impl Object {
-	pub fn new_with_buffers(display: &Display, vertices: &[Vec3], indices: &[(u16, u16, u16)]) -> (Self, VertexBuffer<Vec3>, IndexBuffer<u16>) {
-		let vertex_buffer = VertexBuffer::new(display, &vertices).unwrap(); // might switch to dynamic later
-		let index_buffer = IndexBuffer::new(display, PrimitiveType::TrianglesList, unsafe {
-			core::slice::from_raw_parts(indices.as_ptr() as *const u16, indices.len() * 3)
-		}).unwrap();
-		
-		let mut edges = std::collections::HashSet::new();
-		for t in indices {
-			let (a, b, c) = match (t.0 < t.1, t.0 < t.2, t.1 < t.2) {
-				(true, true, true) => (t.0, t.1, t.2),
-				(true, true, false) => (t.0, t.2, t.1),
-				(true, false, true) => unreachable!(),
-				(true, false, false) => (t.2, t.0, t.1),
-				(false, true, true) => (t.1, t.0, t.2),
-				(false, true, false) => unreachable!(),
-				(false, false, true) => (t.1, t.2, t.0),
-				(false, false, false) => (t.2, t.1, t.0)
-			};
-			edges.insert((a, b));
-			edges.insert((a, c));
-			edges.insert((b, c));
-		}
+	pub fn new(vertices: &[Vec3], indices: &[(u16, u16, u16)]) -> Self {
+		Self::with_shape(vertices, indices, Shape::Mesh)
+	}
+	
+	// Static terrain. It has no mesh of its own, create_buffers draws the field's grid.
+	pub fn from_heightfield(field: Heightfield) -> Self {
+		Self::with_shape(&[], &[], Shape::Heightfield { field: Box::new(field) })
+	}
+	
+	// the shape is set before the mass properties, which a mesh object would otherwise be recentered for
+	fn with_shape(vertices: &[Vec3], indices: &[(u16, u16, u16)], shape: Shape) -> Self {
+		let edges = unique_edges(indices);
 		
-		(Self {
+		let mut object = Self {
 			vertices: vertices.to_vec().into_boxed_slice(),
 			indices: indices.to_vec().into_boxed_slice(),
-			edges: edges.into_iter().collect::<Vec<(u16, u16)>>().into_boxed_slice(),
+			edges,
+			shape,
 			transform: Mat4::identity(),
 			velocity: Vec3(0.0, 0.0, 0.0),
-			angular_velocity: Vec3(0.0, 0.0, 0.0)
-		}, vertex_buffer, index_buffer)
+			angular_velocity: Vec3(0.0, 0.0, 0.0),
+			inverse_mass: 0.0,
+			inverse_inertia: Mat3::zero(),
+			inertia: Mat3::zero(),
+			volume: 0.0,
+			force: Vec3(0.0, 0.0, 0.0),
+			torque: Vec3(0.0, 0.0, 0.0),
+			gravity_scale: 1.0,
+			linear_damping: 0.0,
+			angular_damping: 0.0,
+			restitution: 0.3,
+			friction: 0.5,
+			is_trigger: false,
+			ccd: Ccd::Linear,
+			sleeping: false,
+			sleep_timer: 0.0
+		};
+		object.set_density(1.0);
+		object
+	}
+	
+	pub fn new_with_buffers(display: &impl Facade, vertices: &[Vec3], indices: &[(u16, u16, u16)]) -> (Self, VertexBuffer<Vec3>, IndexBuffer<u32>) {
+		let object = Self::new(vertices, indices);
+		let (vertex_buffer, index_buffer) = object.create_buffers(display);
+		(object, vertex_buffer, index_buffer)
+	}
+	
+	// the render mesh in model space, or a heightfield's grid, for bodies made without a display
+	pub fn create_buffers(&self, display: &impl Facade) -> (VertexBuffer<Vec3>, IndexBuffer<u32>) {
+		let (vertices, indices) = match &self.shape {
+			Shape::Heightfield { field } => field.mesh(),
+			_ => (self.vertices.to_vec(), self.indices.iter().map(|&(a, b, c)| (a as u32, b as u32, c as u32)).collect())
+		};
+		let vertex_buffer = VertexBuffer::new(display, &vertices).unwrap(); // might switch to dynamic later
+		let index_buffer = IndexBuffer::new(display, PrimitiveType::TrianglesList, &indices.iter().flat_map(|&(a, b, c)| [a, b, c]).collect::<Vec<u32>>()).unwrap();
+		(vertex_buffer, index_buffer)
+	}
+	
+	// collides as the given shape instead of the render mesh, with mass properties at unit density
+	pub fn set_shape(&mut self, shape: Shape) {
+		self.shape = shape;
+		self.set_density(1.0);
+	}
+	
+	// Analytic shapes have exact mass properties, the rest sum signed tetrahedra from the origin to each
+	// triangle of the render mesh. Open meshes (like a floor quad) enclose no volume and end up static.
+	// Dynamic bodies are moved onto their center of mass, see recenter.
+	pub fn set_density(&mut self, density: f32) {
+		let (volume, center, inertia) = match self.shape.mass_properties() {
+			Some(properties) => properties,
+			None => shape::mesh_mass_properties(self.indices.iter().map(|&(a, b, c)| (self.vertices[a as usize], self.vertices[b as usize], self.vertices[c as usize])))
+		};
+		
+		self.volume = volume;
+		if volume < 1e-6 || density <= 0.0 {
+			self.set_static();
+			return;
+		}
+		
+		self.recenter(center);
+		self.inverse_mass = 1.0 / (volume * density);
+		self.inertia = inertia * density;
+		self.inverse_inertia = self.inertia.inverse();
+	}
+	
+	// Gravity, contacts and the integrator all take the model origin for the center of mass, so the mesh is
+	// shifted to put it there and the transform the other way. Nothing moves in the world.
+	fn recenter(&mut self, center: Vec3) {
+		if center == Vec3(0.0, 0.0, 0.0) { return; }
+		for vertex in self.vertices.iter_mut() {
+			*vertex -= center;
+		}
+		if let Shape::ConvexHull { points } = &mut self.shape {
+			for point in points.iter_mut() {
+				*point -= center;
+			}
+		}
+		self.transform = self.transform.set_position(center.apply_transform(&self.transform));
+	}
+	
+	pub fn set_static(&mut self) {
+		self.inverse_mass = 0.0;
+		self.inverse_inertia = Mat3::zero();
+		self.inertia = Mat3::zero();
+	}
+	
+	pub fn is_static(&self) -> bool {
+		self.inverse_mass == 0.0
+	}
+	
+	// dynamic and not sleeping
+	pub fn is_active(&self) -> bool {
+		!self.is_static() && !self.sleeping
+	}
+	
+	pub fn wake(&mut self) {
+		self.sleeping = false;
+		self.sleep_timer = 0.0;
+	}
+	
+	pub fn mass(&self) -> f32 {
+		match self.is_static() {
+			true => f32::INFINITY,
+			false => 1.0 / self.inverse_mass
+		}
+	}
+	
+	pub fn world_aabb(&self) -> Aabb {
+		match (&self.shape, self.shape.world_convex(&self.transform)) {
+			(_, Some(convex)) => convex.aabb(),
+			(Shape::Heightfield { field }, None) => field.world_aabb(&self.transform),
+			(_, None) => Aabb::from_points(&self.vertices.iter().map(|v| v.apply_transform(&self.transform)).collect::<Vec<Vec3>>())
+		}
+	}
+	
+	// a collision triangle of a mesh or heightfield in model space
+	pub fn triangle(&self, index: usize) -> (Vec3, Vec3, Vec3) {
+		match &self.shape {
+			Shape::Heightfield { field } => field.triangle(index),
+			_ => {
+				let (a, b, c) = self.indices[index];
+				(self.vertices[a as usize], self.vertices[b as usize], self.vertices[c as usize])
+			}
+		}
+	}
+	
+	pub fn world_inverse_inertia(&self) -> Mat3 {
+		let rotation = self.transform.get_rotation();
+		rotation.mult_mat3(&self.inverse_inertia).mult_mat3(&rotation.transpose())
+	}
+	
+	pub fn world_inertia(&self) -> Mat3 {
+		let rotation = self.transform.get_rotation();
+		rotation.mult_mat3(&self.inertia).mult_mat3(&rotation.transpose())
 	}
 	
 	pub fn get_dynamic_state(&self) -> (Mat4, Vec3, Vec3) {
@@ -55,22 +194,57 @@ impl Object {
 		self.transform = transform;
 		self.velocity = velocity;
 		self.angular_velocity = angular_velocity;
+		self.wake();
 	}
 	
 	pub fn future_transform(&self, dt: f32) -> Mat4 {
-		let new_position = self.transform.get_position() + self.velocity * dt;
-		let mut new_transform = self.transform;
-		if self.angular_velocity.length_squared() > 0.0 {
-			new_transform = new_transform.rotate_axis(self.angular_velocity.normalize(), self.angular_velocity.length() * dt);
-		}
-		new_transform.set_position(new_position)
+		integrator::drift(&self.transform, self.velocity, self.angular_velocity, dt)
+	}
+	
+	// location is relative to the object origin, in world space
+	pub fn apply_force(&mut self, force: Vec3, location: Vec3) {
+		self.force += force;
+		self.torque += location.cross(force);
+		if self.sleeping { self.wake(); }
+	}
+	
+	pub fn apply_torque(&mut self, torque: Vec3) {
+		self.torque += torque;
+		if self.sleeping { self.wake(); }
+	}
+	
+	pub fn clear_forces(&mut self) {
+		self.force = Vec3(0.0, 0.0, 0.0);
+		self.torque = Vec3(0.0, 0.0, 0.0);
 	}
 	
+	// location is relative to the object origin, in world space
 	pub fn apply_impulse(&mut self, impulse: Vec3, location: Vec3) {
-		// todo: make this physically accurate
-		self.angular_velocity += location.cross(impulse) * 0.01;
-		self.velocity += impulse;
-		
-		dbg!(self.angular_velocity, self.velocity);
+		self.velocity += impulse * self.inverse_mass;
+		self.angular_velocity += self.world_inverse_inertia().mult_vec3(location.cross(impulse));
 	}
-}
\ No newline at end of file
+}
+
+
+// every edge of the triangles once, lower vertex index first and sorted
+pub fn unique_edges(indices: &[(u16, u16, u16)]) -> Box<[(u16, u16)]> {
+	let mut edges = std::collections::HashSet::new();
+	for t in indices {
+		let (a, b, c) = match (t.0 < t.1, t.0 < t.2, t.1 < t.2) {
+			(true, true, true) => (t.0, t.1, t.2),
+			(true, true, false) => (t.0, t.2, t.1),
+			(true, false, true) => unreachable!(),
+			(true, false, false) => (t.2, t.0, t.1),
+			(false, true, true) => (t.1, t.0, t.2),
+			(false, true, false) => unreachable!(),
+			(false, false, true) => (t.1, t.2, t.0),
+			(false, false, false) => (t.2, t.1, t.0)
+		};
+		edges.insert((a, b));
+		edges.insert((a, c));
+		edges.insert((b, c));
+	}
+	let mut edges = edges.into_iter().collect::<Vec<(u16, u16)>>();
+	edges.sort_unstable();
+	edges.into_boxed_slice()
+}
@@ -0,0 +1,233 @@
+use crate::{math_structs::{Mat4, Vec3}, object::Object, query::{self, QueryFilter}};
+
+
+static COLLISION_OFFSET: f32 = 1e-3; // how far off a surface a particle is put back after hitting it
+
+
+// Where new particles appear, in the emitter's model space.
+#[derive(Clone, Debug)]
+pub enum EmitterShape {
+	Point,
+	Sphere { radius: f32 }, // anywhere inside the ball
+	MeshSurface { vertices: Box<[Vec3]>, indices: Box<[(u16, u16, u16)]> } // evenly over the area of the triangles, nothing without any
+}
+
+#[derive(Clone, Debug)]
+pub struct Emitter {
+	pub shape: EmitterShape,
+	pub transform: Mat4,
+	pub rate: f32, // particles per second, zero for one that only bursts
+	pub lifetime: (f32, f32), // seconds, picked evenly between the two
+	pub velocity: Vec3, // world space
+	pub velocity_randomness: f32, // a random vector up to this long is added to the velocity
+	pub outward_speed: f32, // away from the sphere's center or along the triangle's normal
+	pub size: f32, // radius at birth, shrinks to nothing over the lifetime
+	pub color: Vec3,
+	accumulator: f32
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Particle {
+	pub position: Vec3,
+	pub velocity: Vec3,
+	pub age: f32,
+	pub lifetime: f32,
+	pub size: f32,
+	pub color: Vec3
+}
+
+// Short lived points for sparks, dust and debris. They don't push on anything and don't touch each other,
+// so they are stepped with the frame rather than the physics ticks.
+#[derive(Clone, Debug)]
+pub struct ParticleSystem {
+	pub emitters: Vec<Emitter>,
+	pub particles: Vec<Particle>,
+	pub gravity_scale: f32,
+	pub drag: f32, // fraction of the velocity lost per second
+	pub collide: bool, // bounce off the bodies, which costs a ray cast per particle
+	pub restitution: f32,
+	pub friction: f32, // fraction of the sliding velocity lost per bounce
+	pub max_particles: usize,
+	seed: u64
+}
+
+
+impl Emitter {
+	pub fn new(shape: EmitterShape, transform: Mat4) -> Self {
+		Self {
+			shape,
+			transform,
+			rate: 0.0,
+			lifetime: (1.0, 1.0),
+			velocity: Vec3(0.0, 0.0, 0.0),
+			velocity_randomness: 0.0,
+			outward_speed: 0.0,
+			size: 0.05,
+			color: Vec3(1.0, 1.0, 1.0),
+			accumulator: 0.0
+		}
+	}
+	
+	// none from a mesh surface without any area to spawn on
+	fn spawn(&self, seed: &mut u64) -> Option<Particle> {
+		let (position, outward) = match &self.shape {
+			EmitterShape::Point => (Vec3(0.0, 0.0, 0.0), Vec3(0.0, 0.0, 0.0)),
+			EmitterShape::Sphere { radius } => {
+				let offset = random_in_ball(seed) * *radius;
+				let outward = match offset.length_squared() > 1e-12 {
+					true => offset.normalize(),
+					false => Vec3(0.0, 0.0, 0.0)
+				};
+				(offset, outward)
+			}
+			EmitterShape::MeshSurface { vertices, indices } => {
+				let corners = |&(a, b, c): &(u16, u16, u16)| (vertices[a as usize], vertices[b as usize], vertices[c as usize]);
+				let areas = indices.iter().map(|t| {
+					let (a, b, c) = corners(t);
+					(c - a).cross(b - a).length()
+				}).collect::<Vec<f32>>();
+				
+				let total = areas.iter().sum::<f32>();
+				if total <= 0.0 { return None; }
+				let mut pick = random(seed) * total;
+				let mut index = indices.len() - 1;
+				for (l, &area) in areas.iter().enumerate() {
+					if pick < area { index = l; break; }
+					pick -= area;
+				}
+				
+				// folding the square onto the triangle keeps the spread even
+				let (a, b, c) = corners(&indices[index]);
+				let (mut u, mut v) = (random(seed), random(seed));
+				if u + v > 1.0 { (u, v) = (1.0 - u, 1.0 - v); }
+				(a + (b - a) * u + (c - a) * v, (c - a).cross(b - a).normalize())
+			}
+		};
+		
+		let outward = match outward.length_squared() > 0.0 {
+			true => outward.apply_rotation(&self.transform).normalize(),
+			false => outward
+		};
+		let lifetime = self.lifetime.0 + (self.lifetime.1 - self.lifetime.0) * random(seed);
+		Some(Particle {
+			position: position.apply_transform(&self.transform),
+			velocity: self.velocity + outward * self.outward_speed + random_in_ball(seed) * self.velocity_randomness,
+			age: 0.0,
+			lifetime,
+			size: self.size,
+			color: self.color
+		})
+	}
+}
+
+
+impl Particle {
+	// what is drawn, shrinking as it ages
+	pub fn current_size(&self) -> f32 {
+		self.size * (1.0 - self.age / self.lifetime).max(0.0)
+	}
+}
+
+
+impl Default for ParticleSystem {
+	fn default() -> Self {
+		Self {
+			emitters: Vec::new(),
+			particles: Vec::new(),
+			gravity_scale: 1.0,
+			drag: 0.0,
+			collide: false,
+			restitution: 0.3,
+			friction: 0.2,
+			max_particles: 10000,
+			seed: 0x2545_f491_4f6c_dd1d
+		}
+	}
+}
+
+
+impl ParticleSystem {
+	pub fn new() -> Self {
+		Self::default()
+	}
+	
+	pub fn add_emitter(&mut self, emitter: Emitter) -> usize {
+		self.emitters.push(emitter);
+		self.emitters.len() - 1
+	}
+	
+	// spawns a number of particles at once from an emitter, on top of its steady rate, none for an unknown emitter
+	pub fn burst(&mut self, emitter: usize, count: usize) {
+		let emitter = match self.emitters.get(emitter) {
+			Some(emitter) => emitter,
+			None => return
+		};
+		for _ in 0..count.min(self.max_particles.saturating_sub(self.particles.len())) {
+			if let Some(particle) = emitter.spawn(&mut self.seed) {
+				self.particles.push(particle);
+			}
+		}
+	}
+	
+	pub fn clear(&mut self) {
+		self.particles.clear();
+		for emitter in self.emitters.iter_mut() {
+			emitter.accumulator = 0.0;
+		}
+	}
+	
+	// The bodies are only needed when collide is on. Particles spawned this frame move for the whole frame.
+	pub fn update(&mut self, dt: f32, gravity: Vec3, objects: &[Object]) {
+		for i in 0..self.emitters.len() {
+			let emitter = &mut self.emitters[i];
+			emitter.accumulator += emitter.rate * dt;
+			let count = emitter.accumulator.floor();
+			emitter.accumulator -= count;
+			self.burst(i, count as usize);
+		}
+		
+		for particle in self.particles.iter_mut() {
+			particle.age += dt;
+		}
+		self.particles.retain(|p| p.age < p.lifetime);
+		
+		let damping = (-self.drag * dt).exp();
+		let filter = QueryFilter::default();
+		for particle in self.particles.iter_mut() {
+			particle.velocity = (particle.velocity + gravity * (self.gravity_scale * dt)) * damping;
+			let motion = particle.velocity * dt;
+			let distance = motion.length();
+			
+			// the hit point is where the particle ends up, the rest of the step is dropped
+			let hit = match self.collide && distance > 1e-9 {
+				true => query::ray_cast(objects, particle.position, motion, distance, &filter),
+				false => None
+			};
+			match hit {
+				Some(hit) => {
+					let normal_speed = particle.velocity.dot(hit.normal);
+					let tangential = particle.velocity - hit.normal * normal_speed;
+					particle.position = hit.point + hit.normal * COLLISION_OFFSET;
+					particle.velocity = tangential * (1.0 - self.friction) - hit.normal * (normal_speed * self.restitution);
+				}
+				None => particle.position += motion
+			}
+		}
+	}
+}
+
+
+// xorshift, so the system needs no dependencies and replays the same way from the same seed
+fn random(seed: &mut u64) -> f32 {
+	*seed ^= *seed << 13;
+	*seed ^= *seed >> 7;
+	*seed ^= *seed << 17;
+	(*seed >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn random_in_ball(seed: &mut u64) -> Vec3 {
+	loop {
+		let v = Vec3(random(seed) * 2.0 - 1.0, random(seed) * 2.0 - 1.0, random(seed) * 2.0 - 1.0);
+		if v.length_squared() <= 1.0 { return v; }
+	}
+}
@@ -1,192 +1,1163 @@
-use crate::{math_structs::{Mat4, Vec3}, object::Object};
+use std::collections::{BTreeMap, BTreeSet};
 
-pub fn run(objects: &mut [Object], dt: f32) {
-	let mut collision = None;
-	let mut dt_remaining = dt;
+use rayon::prelude::*;
+
+use crate::{force::ForceField, integrator::{self, State}, joint::{self, JointSet}, math_structs::{Mat3, Mat4, Vec3}, narrow_phase, object::Object, query, shape::{Convex, Shape}, world::PhysicsConfig};
+
+
+static MAX_TOI_ITERATIONS: usize = 8;
+static MAX_ADVANCEMENT_ITERATIONS: usize = 32;
+static MAX_MANIFOLD_POINTS: usize = 4;
+pub(crate) static CONTACT_MARGIN: f32 = 0.05;
+pub(crate) static MAX_CONTACT_DEPTH: f32 = 0.25;
+static PENETRATION_SLOP: f32 = 0.005;
+static BAUMGARTE_FACTOR: f32 = 0.2;
+static RESTITUTION_THRESHOLD: f32 = 1.0;
+pub(crate) static SLEEP_LINEAR_VELOCITY: f32 = 0.05;
+static SLEEP_ANGULAR_VELOCITY: f32 = 0.05;
+static SLEEP_TIME: f32 = 0.5;
+static CONTAINED_FEATURE: u64 = 4 << 48;
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+	pub min: Vec3,
+	pub max: Vec3
+}
+
+impl Aabb {
+	pub fn from_points(points: &[Vec3]) -> Self {
+		let mut aabb = Self { min: Vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY), max: Vec3(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY) };
+		for &p in points {
+			aabb.min = aabb.min.min(p);
+			aabb.max = aabb.max.max(p);
+		}
+		aabb
+	}
+	
+	pub fn expand(self, margin: f32) -> Self {
+		let m = Vec3(margin, margin, margin);
+		Self { min: self.min - m, max: self.max + m }
+	}
+	
+	pub fn union(self, other: Self) -> Self {
+		Self { min: self.min.min(other.min), max: self.max.max(other.max) }
+	}
+	
+	pub fn overlaps(&self, other: &Self) -> bool {
+		self.min.0 <= other.max.0 && self.max.0 >= other.min.0 &&
+		self.min.1 <= other.max.1 && self.max.1 >= other.min.1 &&
+		self.min.2 <= other.max.2 && self.max.2 >= other.min.2
+	}
+}
+
+
+// How the ccd pass finds a body's impacts with mesh and heightfield triangles within a step.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Ccd {
+	#[default]
+	Linear, // exact for points moving in straight lines, but the corners of a spinning body cut across their arcs
+	ConservativeAdvancement // steps along the real motion by how far it is safe to go, for projectiles and spinners
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ContactPoint {
+	pub feature: u64,
+	pub position: Vec3,
+	pub normal: Vec3, // points from the second body of the pair towards the first
+	pub separation: f32, // negative when penetrating
+	pub normal_impulse: f32,
+	pub tangent_impulse: (f32, f32)
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Manifold {
+	pub points: Vec<ContactPoint>
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ContactEventKind {
+	Begin,
+	Persist,
+	End
+}
+
+// One touching pair for one step. Ends repeat the last point and normal the pair had.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ContactEvent {
+	pub kind: ContactEventKind,
+	pub bodies: (usize, usize), // lower index first
+	pub point: Vec3,
+	pub normal: Vec3, // points from the second body towards the first
+	pub impulse: f32, // total normal impulse over the step, zero for triggers and ends
+	pub trigger: bool
+}
+
+// Manifolds keyed by body index pair (lower index first), kept between steps so impulses can be warm started.
+// Pairs with a trigger in them go to triggers instead and are never solved.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ContactCache {
+	pub manifolds: BTreeMap<(usize, usize), Manifold>,
+	pub triggers: BTreeMap<(usize, usize), Manifold>,
+	pub events: Vec<ContactEvent>, // from the last step
+	pub(crate) touching: BTreeMap<(usize, usize), (Vec3, Vec3, bool)> // point, normal and whether it's a trigger pair, as of the last step
+}
+
+// Totals over the dynamic bodies at the end of a step, for spotting energy gained or momentum lost.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Diagnostics {
+	pub kinetic_energy: f32, // linear plus rotational
+	pub potential_energy: f32, // under the configured gravity, zero at the origin
+	pub linear_momentum: Vec3,
+	pub angular_momentum: Vec3, // about the origin
+	pub max_penetration: f32 // deepest solid contact, zero when nothing overlaps
+}
+
+impl ContactCache {
+	pub fn new() -> Self {
+		Self::default()
+	}
+	
+	// Drops the cached manifolds, but not which pairs were touching, so events carry on over a teleport.
+	pub fn clear(&mut self) {
+		self.manifolds.clear();
+		self.triggers.clear();
+	}
+	
+	// forgets a removed body and shifts the pairs of the bodies after it down
+	pub fn remove_body(&mut self, index: usize) {
+		fn remap<T>(map: &mut BTreeMap<(usize, usize), T>, index: usize) {
+			let shift = |i: usize| if i > index { i - 1 } else { i };
+			*map = std::mem::take(map).into_iter()
+				.filter(|&((a, b), _)| a != index && b != index)
+				.map(|((a, b), value)| ((shift(a), shift(b)), value))
+				.collect();
+		}
+		remap(&mut self.manifolds, index);
+		remap(&mut self.triggers, index);
+		remap(&mut self.touching, index);
+	}
+	
+	// Whether the cache could have come from stepping a world of this many bodies: every pair names two of them,
+	// lower index first, manifolds hold no more points than get kept, and features carry a kind the narrow phase
+	// makes. Used on snapshots read back from bytes, where a bad pair would index past the bodies on the next step.
+	pub(crate) fn check(&self, bodies: usize) -> Result<(), String> {
+		let pairs = self.manifolds.keys().chain(self.triggers.keys()).chain(self.touching.keys()).chain(self.events.iter().map(|e| &e.bodies));
+		for &(a, b) in pairs {
+			if a >= b || b >= bodies {
+				return Err(format!("the contacts have a pair ({}, {}) that isn't two of the {} bodies", a, b, bodies));
+			}
+		}
+		for (&(a, b), manifold) in self.manifolds.iter() {
+			if manifold.points.len() > MAX_MANIFOLD_POINTS {
+				return Err(format!("the manifold of ({}, {}) has {} points, more than {}", a, b, manifold.points.len(), MAX_MANIFOLD_POINTS));
+			}
+		}
+		for (&(a, b), manifold) in self.manifolds.iter().chain(self.triggers.iter()) {
+			if let Some(point) = manifold.points.iter().find(|p| p.feature >> 48 > CONTAINED_FEATURE >> 48) {
+				return Err(format!("a contact point of ({}, {}) has an unknown feature {:#x}", a, b, point.feature));
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Diagnostics {
+	pub fn total_energy(&self) -> f32 {
+		self.kinetic_energy + self.potential_energy
+	}
+}
+
+
+struct ContactConstraint {
+	pair: (usize, usize),
+	point: usize,
+	r_a: Vec3,
+	r_b: Vec3,
+	normal: Vec3,
+	tangents: (Vec3, Vec3),
+	normal_mass: f32,
+	tangent_mass: (f32, f32),
+	bias: f32,
+	friction: f32,
+	normal_impulse: f32,
+	tangent_impulse: (f32, f32)
+}
+
+
+
+pub fn run(objects: &mut [Object], contacts: &mut ContactCache, joints: &mut JointSet, fields: &[Box<dyn ForceField>], config: &PhysicsConfig, dt: f32) {
+	let mut drifts = apply_forces(objects, fields, config, dt);
+	
+	// a heightfield's grid is only for drawing, its triangles are looked up where they are needed
+	let world_vertices = objects.iter().map(|o| match o.shape {
+		Shape::Heightfield { .. } => Vec::new(),
+		_ => o.vertices.iter().map(|v| v.apply_transform(&o.transform)).collect()
+	}).collect::<Vec<Vec<Vec3>>>();
+	let convexes = objects.iter().map(|o| o.shape.world_convex(&o.transform)).collect::<Vec<Option<Convex>>>();
+	let aabbs = objects.iter().zip(world_vertices.iter()).zip(convexes.iter()).map(|((o, v), c)| match (c, &o.shape) {
+		(Some(convex), _) => convex.aabb(),
+		(None, Shape::Heightfield { field }) => field.world_aabb(&o.transform),
+		(None, _) => Aabb::from_points(v)
+	}.expand(CONTACT_MARGIN)).collect::<Vec<Aabb>>();
+	
+	let ignored_pairs = joints.ignored_pairs();
+	
+	update_contacts(objects, contacts, &world_vertices, &convexes, &aabbs, &ignored_pairs);
+	wake_islands(objects, contacts, joints);
+	solve_velocities(objects, contacts, joints, config.solver_iterations, dt);
+	hold_drifts(&mut drifts, contacts, joints);
+	let impacts = integrate(objects, &drifts, &ignored_pairs, config.parallel_narrow_phase, dt);
+	update_sleep(objects, contacts, joints, dt);
+	update_events(contacts, &impacts);
+}
+
+
+// Gravity, force fields and the accumulated forces become velocity here through the configured integrator,
+// then the accumulators are cleared. Sleeping bodies keep whatever was applied to them for when they wake.
+// Returns what has to be added to each body's velocities when moving it, for integrators whose transform
+// doesn't just follow the end velocities.
+fn apply_forces(objects: &mut [Object], fields: &[Box<dyn ForceField>], config: &PhysicsConfig, dt: f32) -> Vec<(Vec3, Vec3)> {
+	let gravity = config.gravity;
+	objects.iter_mut().map(|object| {
+		if object.is_static() { return (Vec3(0.0, 0.0, 0.0), Vec3(0.0, 0.0, 0.0)); }
+		if object.sleeping {
+			// A sleeping body a field pushes hard enough gets a step to move in. Its sleep timer is left alone, so it
+			// doesn't wake its island and falls back asleep at the end of the step unless it got going.
+			match pushed_by_fields(object, fields, gravity, dt) {
+				true => object.sleeping = false,
+				false => return (Vec3(0.0, 0.0, 0.0), Vec3(0.0, 0.0, 0.0))
+			}
+		}
+		let start = State { transform: object.transform, velocity: object.velocity, angular_velocity: object.angular_velocity };
+		let (force, torque) = (object.force, object.torque);
+		let (inertia, inverse_inertia) = (object.inertia, object.inverse_inertia);
+		
+		// the fields look at the body itself, so it's put in each stage's state for them
+		let motion = config.integrator.step(&start, &inertia, &inverse_inertia, dt, |state| {
+			object.transform = state.transform;
+			object.velocity = state.velocity;
+			object.angular_velocity = state.angular_velocity;
+			object.force = force;
+			object.torque = torque;
+			for field in fields.iter() {
+				field.apply(object, gravity);
+			}
+			(gravity * object.gravity_scale + object.force * object.inverse_mass, object.torque)
+		});
+		object.transform = start.transform;
+		
+		object.velocity = motion.velocity * (1.0 / (1.0 + object.linear_damping * dt));
+		object.angular_velocity = motion.angular_velocity * (1.0 / (1.0 + object.angular_damping * dt));
+		
+		object.clear_forces();
+		(motion.mean_velocity - motion.velocity, motion.mean_angular_velocity - motion.angular_velocity)
+	}).collect()
+}
+
+
+// Whether the fields where the body is would change its velocities over the step by more than a body moves while
+// it sleeps, leaving its forces and sleep as they were. A gentle steady push like a distant attractor leaves it asleep.
+fn pushed_by_fields(object: &mut Object, fields: &[Box<dyn ForceField>], gravity: Vec3, dt: f32) -> bool {
+	let (force, torque, sleeping, sleep_timer) = (object.force, object.torque, object.sleeping, object.sleep_timer);
+	for field in fields.iter() {
+		field.apply(object, gravity);
+	}
+	let linear = (object.force - force) * (object.inverse_mass * dt);
+	let angular = object.world_inverse_inertia().mult_vec3(object.torque - torque) * dt;
+	(object.force, object.torque, object.sleeping, object.sleep_timer) = (force, torque, sleeping, sleep_timer);
+	linear.length() > SLEEP_LINEAR_VELOCITY || angular.length() > SLEEP_ANGULAR_VELOCITY
+}
+
+
+// Static bodies have no energy or momentum to speak of and are left out.
+pub fn diagnostics(objects: &[Object], contacts: &ContactCache, gravity: Vec3) -> Diagnostics {
+	let mut diagnostics = Diagnostics::default();
+	for object in objects.iter().filter(|o| !o.is_static()) {
+		let mass = object.mass();
+		let position = object.transform.get_position();
+		let spin = object.world_inertia().mult_vec3(object.angular_velocity);
+		
+		diagnostics.kinetic_energy += 0.5 * (mass * object.velocity.length_squared() + object.angular_velocity.dot(spin));
+		diagnostics.potential_energy -= mass * object.gravity_scale * gravity.dot(position);
+		diagnostics.linear_momentum += object.velocity * mass;
+		diagnostics.angular_momentum += position.cross(object.velocity * mass) + spin;
+	}
+	diagnostics.max_penetration = contacts.manifolds.values().flat_map(|m| m.points.iter()).fold(0.0, |deepest, p| f32::max(deepest, -p.separation));
+	diagnostics
+}
+
+
+// The solver's impulses don't spread over the step the way forces do, so a body that is resting on something
+// or hanging from a joint moves with its solved velocities alone. Otherwise the drift would lift it off its support.
+fn hold_drifts(drifts: &mut [(Vec3, Vec3)], contacts: &ContactCache, joints: &JointSet) {
+	let jointed = joints.iter().flat_map(|(_, j)| std::iter::once(j.a).chain(j.b));
+	for i in contacts.manifolds.keys().flat_map(|&(a, b)| [a, b]).chain(jointed) {
+		drifts[i] = (Vec3(0.0, 0.0, 0.0), Vec3(0.0, 0.0, 0.0));
+	}
+}
+
+
+// sweep and prune along x, returns overlapping pairs with the lower index first
+pub fn broad_phase(aabbs: &[Aabb]) -> Vec<(usize, usize)> {
+	let mut order = (0..aabbs.len()).collect::<Vec<usize>>();
+	order.sort_by(|&a, &b| aabbs[a].min.0.total_cmp(&aabbs[b].min.0));
 	
-	while dt_remaining > 0.0 {
-		
-		let new_transforms = (0..objects.len()).map(|i| objects[i].future_transform(dt_remaining)).collect::<Vec<Mat4>>();
-		let transformed_vertices = (0..objects.len()).map(|i| objects[i].vertices.iter().map(|v| (v.apply_transform(&objects[i].transform), v.apply_transform(&new_transforms[i]))).collect::<Vec<(Vec3, Vec3)>>()).collect::<Vec<Vec<(Vec3, Vec3)>>>();
-		
-		for i in 0..objects.len() { for j in 0..objects.len() {
-			if i == j { continue; }
-			for k in 0..transformed_vertices[i].len() {
-				let (this_v, next_v) = transformed_vertices[i][k];
-				for l in 0..objects[j].indices.len() {
-					let (a_index, b_index, c_index) = objects[j].indices[l];
-					let (this_a, next_a) = transformed_vertices[j][a_index as usize];
-					let (this_b, next_b) = transformed_vertices[j][b_index as usize];
-					let (this_c, next_c) = transformed_vertices[j][c_index as usize];
-					
-					
-					let p0 = this_v - this_a;
-					let g0 = this_b - this_a;
-					let h0 = this_c - this_a;
-					let dp = next_v - next_a - p0;
-					let dg = next_b - next_a - g0;
-					let dh = next_c - next_a - h0;
-					
-					let cubic_a = dg.cross(dh).dot(dp);
-					let cubic_b = dg.cross(dh).dot(p0) + (dg.cross(h0) + g0.cross(dh)).dot(dp);
-					let cubic_c = (dg.cross(h0) + g0.cross(dh)).dot(p0) + g0.cross(h0).dot(dp);
-					let cubic_d = g0.cross(h0).dot(p0);
-					
-					let time_to_beat = match collision { Some((t, _, _, _, _)) => t, None => 1.0 };
-					
-					
-					let t = if cubic_a.abs() > 1e-7 {
-						let b = cubic_b / cubic_a;
-						let c = cubic_c / cubic_a;
-						let d = cubic_d / cubic_a;
-						
-						let q = (3.0*c - b*b) / 9.0;
-						let r = b * (9.0*c - 2.0*b*b) / 54.0 - 0.5 * d;
-						
-						let discriminant = q*q*q + r*r;
-						if discriminant >= 0.0 {
-							let sqrtd = f32::sqrt(discriminant);
-							let t = -b / 3.0 + (r + sqrtd).cbrt() + (r - sqrtd).cbrt();
-							match t >= 0.0 && t <= time_to_beat {
-								true => Some(t),
-								false => None
-							}
-						} else {
-							let dum1 = f32::acos(r / f32::sqrt(-q*q*q));
-							let r13 = 2.0 * f32::sqrt(-q);
-							let t1 = -b / 3.0 + r13 * f32::cos(dum1 / 3.0);
-							let t2 = -b / 3.0 + r13 * f32::cos((dum1 + 2.0*std::f32::consts::PI) / 3.0);
-							let t3 = -b / 3.0 + r13 * f32::cos((dum1 + 4.0*std::f32::consts::PI) / 3.0);
-							
-							match (t1 >= 0.0 && t1 <= time_to_beat, t2 >= 0.0 && t2 <= time_to_beat, t3 >= 0.0 && t3 <= time_to_beat, t1 < t2, t1 < t3, t2 < t3) {
-								(true, true, true, true, true, _) => Some(t1),
-								(true, true, true, false, _, true) => Some(t2),
-								(true, true, true, _, false, false) => Some(t3),
-								(true, true, false, true, _, _) => Some(t1),
-								(true, true, false, false, _, _) => Some(t2),
-								(true, false, true, _, true, _) => Some(t1),
-								(true, false, true, _, false, _) => Some(t3),
-								(false, true, true, _, _, true) => Some(t2),
-								(false, true, true, _, _, false) => Some(t3),
-								(true, false, false, _, _, _) => Some(t1),
-								(false, true, false, _, _, _) => Some(t1),
-								(false, false, true, _, _, _) => Some(t1),
-								(false, false, false, _, _, _) => None,
-								(true, true, true, false, true, false) | (true, true, true, true, false, true) => unreachable!()
-							}
-						}
-					} else if cubic_b.abs() > 1e-7 {
-						let b = cubic_c / cubic_b;
-						let c = cubic_d / cubic_b;
-						
-						let discriminant = b*b - 4.0*c;
-						if discriminant >= 0.0 {
-							let sqrtd = discriminant.sqrt();
-							let t1 = (-b + sqrtd) * 0.5;
-							let t2 = (-b - sqrtd) * 0.5;
-							
-							match (t1 >= 0.0 && t1 <= time_to_beat, t2 >= 0.0 && t2 <= time_to_beat, t1 < t2) {
-								(true, true, true) => Some(t1),
-								(true, true, false) => Some(t2),
-								(true, false, _) => Some(t1),
-								(false, true, _) => Some(t2),
-								(false, false, _) => None
-							}
-						} else {
-							None
-						}
-					} else {
-						let t = -cubic_d / cubic_c;
-						match t >= 0.0 && t <= time_to_beat {
-							true => Some(t),
-							false => None
-						}
-					};
-					
-					
-					if let Some(t) = t {
-						let p = p0 + dp * t;
-						let g = g0 + dg * t;
-						let h = h0 + dh * t;
-						
-						let (larger, smaller) = match f32::max(g.0*g.1, f32::max(g.0*g.2, g.1*g.2)) > f32::max(h.0*h.1, f32::max(h.0*h.2, h.1*h.2)) {
-							true => (g, h),
-							false => (h, g)
-						};
-						
-						let (la, lb, sa, sb, pa, pb) = match (larger.0 < larger.1, larger.0 < larger.2, larger.1 < larger.2) {
-							(true, true, true) => (larger.2, larger.1, smaller.2, smaller.1, p.2, p.1),
-							(true, true, false) => (larger.1, larger.2, smaller.1, smaller.2, p.1, p.2),
-							(true, false, true) => unreachable!(),
-							(true, false, false) => (larger.1, larger.0, smaller.1, smaller.0, p.1, p.0),
-							(false, true, true) => (larger.2, larger.0, smaller.2, smaller.0, p.2, p.0),
-							(false, true, false) => unreachable!(),
-							(false, false, true) => (larger.0, larger.2, smaller.0, smaller.2, p.0, p.2),
-							(false, false, false) => (larger.0, larger.1, smaller.0, smaller.1, p.0, p.1)
-						};
-						
-						let s = (pa / la - pb / lb) / (sa / la - sb / lb);
-						let r = (pa - sa * s) / la;
-						// larger * r + smaller * s = p
-						
-						if r >= 0.0 && s >= 0.0 && r + s <= 1.0 {
-							collision = Some((t, i, j, k, l));
-						}
-					}
+	let mut pairs = Vec::new();
+	for (n, &i) in order.iter().enumerate() {
+		for &j in &order[n+1..] {
+			if aabbs[j].min.0 > aabbs[i].max.0 { break; }
+			if aabbs[i].overlaps(&aabbs[j]) {
+				pairs.push((i.min(j), i.max(j)));
+			}
+		}
+	}
+	pairs.sort_unstable();
+	pairs
+}
+
+
+fn update_contacts(objects: &mut [Object], contacts: &mut ContactCache, world_vertices: &[Vec<Vec3>], convexes: &[Option<Convex>], aabbs: &[Aabb], ignored_pairs: &BTreeSet<(usize, usize)>) {
+	let mut manifolds = BTreeMap::new();
+	let mut triggers = BTreeMap::new();
+	
+	for (i, j) in broad_phase(aabbs) {
+		if objects[i].is_static() && objects[j].is_static() { continue; }
+		if ignored_pairs.contains(&(i, j)) { continue; }
+		
+		// nothing moves between two resting bodies, so their manifold stays as it was
+		if !objects[i].is_active() && !objects[j].is_active() {
+			if let Some(manifold) = contacts.manifolds.remove(&(i, j)) {
+				manifolds.insert((i, j), manifold);
+			}
+			if let Some(manifold) = contacts.triggers.remove(&(i, j)) {
+				triggers.insert((i, j), manifold);
+			}
+			continue;
+		}
+		
+		let mut points = match (&convexes[i], &convexes[j]) {
+			(Some(a), Some(b)) => narrow_phase::convex_contacts(&objects[i].shape, &objects[i].transform, a, &objects[j].shape, &objects[j].transform, b),
+			(Some(a), None) => narrow_phase::convex_mesh_contacts(a, &world_triangles(&objects[j], &world_vertices[j], &a.aabb().expand(CONTACT_MARGIN))),
+			(None, Some(b)) => narrow_phase::convex_mesh_contacts(b, &world_triangles(&objects[i], &world_vertices[i], &b.aabb().expand(CONTACT_MARGIN))).into_iter().map(|p| ContactPoint { normal: -p.normal, ..p }).collect(),
+			(None, None) => match (&objects[i].shape, &objects[j].shape) {
+				(Shape::Heightfield { .. }, _) => heightfield_contacts(objects, j, i, world_vertices).into_iter().map(|p| ContactPoint { normal: -p.normal, ..p }).collect(),
+				(_, Shape::Heightfield { .. }) => heightfield_contacts(objects, i, j, world_vertices),
+				_ => mesh_contacts(objects, i, j, world_vertices)
+			}
+		};
+		
+		// triggers only need to know about actual overlap, not the speculative points
+		if objects[i].is_trigger || objects[j].is_trigger {
+			points.retain(|p| p.separation <= 0.0);
+			if points.is_empty() {
+				points.extend(containment(objects, i, j));
+			}
+			if !points.is_empty() {
+				triggers.insert((i, j), Manifold { points });
+			}
+			continue;
+		}
+		if points.is_empty() { continue; }
+		reduce_manifold(&mut points);
+		
+		if let Some(old) = contacts.manifolds.get(&(i, j)) {
+			for point in points.iter_mut() {
+				// features flicker between vertex and edge contacts at the same spot, so fall back to distance
+				let matching = old.points.iter().find(|p| p.feature == point.feature)
+					.or_else(|| old.points.iter().find(|p| (p.position - point.position).length_squared() < CONTACT_MARGIN * CONTACT_MARGIN));
+				if let Some(old_point) = matching {
+					point.normal_impulse = old_point.normal_impulse;
+					point.tangent_impulse = old_point.tangent_impulse;
 				}
 			}
-		}}
+		}
 		
-		if let Some((t, i, j, k, l)) = collision {
-			let t_step = dt_remaining * f32::max(t - 0.001, t * 0.5);
-			for i in 0..objects.len() {
-				objects[i].transform = objects[i].future_transform(t_step);
+		manifolds.insert((i, j), Manifold { points });
+	}
+	
+	contacts.manifolds = manifolds;
+	contacts.triggers = triggers;
+}
+
+
+// Contacts against meshes stop at MAX_CONTACT_DEPTH, so a body that has sunk all the way into a mesh trigger
+// has none. It still overlaps while its center is inside the other body, or the other's center inside it.
+fn containment(objects: &[Object], i: usize, j: usize) -> Option<ContactPoint> {
+	let (center_i, center_j) = (objects[i].transform.get_position(), objects[j].transform.get_position());
+	let position = match (query::contains_point(&objects[j], center_i), query::contains_point(&objects[i], center_j)) {
+		(true, _) => center_i,
+		(false, true) => center_j,
+		(false, false) => return None
+	};
+	let normal = match (center_i - center_j).length_squared() > 1e-12 {
+		true => (center_i - center_j).normalize(),
+		false => Vec3(0.0, 1.0, 0.0)
+	};
+	Some(ContactPoint { feature: CONTAINED_FEATURE, position, normal, separation: -MAX_CONTACT_DEPTH, normal_impulse: 0.0, tangent_impulse: (0.0, 0.0) })
+}
+
+
+// The collision triangles of a mesh or heightfield with their index, in world space. Meshes give all of them,
+// heightfields only the cells under the bounds.
+fn world_triangles(object: &Object, world_vertices: &[Vec3], bounds: &Aabb) -> Vec<(usize, Vec3, Vec3, Vec3)> {
+	match &object.shape {
+		Shape::Heightfield { field } => field.world_triangles_in(&object.transform, bounds),
+		_ => object.indices.iter().enumerate().map(|(l, &(a, b, c))| (l, world_vertices[a as usize], world_vertices[b as usize], world_vertices[c as usize])).collect()
+	}
+}
+
+
+// Vertex against face proximity in both directions, plus edge against edge. Every vertex keeps at
+// most one contact, against the face it penetrates least, which is the right one for closed convex-ish meshes.
+fn mesh_contacts(objects: &[Object], a: usize, b: usize, world_vertices: &[Vec<Vec3>]) -> Vec<ContactPoint> {
+	let mut points = Vec::new();
+	
+	for (side, p, q) in [(0u64, a, b), (1u64, b, a)] {
+		let triangles = world_triangles(&objects[q], &world_vertices[q], &Aabb::from_points(&world_vertices[q]));
+		for (k, &v) in world_vertices[p].iter().enumerate() {
+			points.extend(vertex_contact(side, k, v, &triangles));
+		}
+	}
+	
+	// edges crossing each other without either having a vertex inside the other body
+	let center_offset = objects[a].transform.get_position() - objects[b].transform.get_position();
+	for (m, &(a0, a1)) in objects[a].edges.iter().enumerate() {
+		let p1 = world_vertices[a][a0 as usize];
+		let d1 = world_vertices[a][a1 as usize] - p1;
+		for (l, &(b0, b1)) in objects[b].edges.iter().enumerate() {
+			let p2 = world_vertices[b][b0 as usize];
+			let d2 = world_vertices[b][b1 as usize] - p2;
+			
+			let n = d1.cross(d2);
+			let length_squared = n.length_squared();
+			if length_squared < 1e-6 * d1.length_squared() * d2.length_squared() { continue; }
+			
+			let r = p1 - p2;
+			let (aa, bb, ee) = (d1.dot(d1), d1.dot(d2), d2.dot(d2));
+			let (c, f) = (d1.dot(r), d2.dot(r));
+			let denominator = aa * ee - bb * bb;
+			let s = (bb * f - c * ee) / denominator;
+			let t = (aa * f - bb * c) / denominator;
+			if s <= 1e-3 || s >= 1.0 - 1e-3 || t <= 1e-3 || t >= 1.0 - 1e-3 { continue; }
+			
+			// for convex bodies the separating direction points from b towards a
+			let mut n = n / length_squared.sqrt();
+			if n.dot(center_offset) < 0.0 { n = -n; }
+			
+			let (closest_a, closest_b) = (p1 + d1 * s, p2 + d2 * t);
+			let d = (closest_a - closest_b).dot(n);
+			if d > CONTACT_MARGIN || d < -MAX_CONTACT_DEPTH { continue; }
+			
+			points.push(ContactPoint {
+				feature: 2 << 48 | (m as u64) << 24 | l as u64,
+				position: (closest_a + closest_b) * 0.5,
+				normal: n,
+				separation: d,
+				normal_impulse: 0.0,
+				tangent_impulse: (0.0, 0.0)
+			});
+		}
+	}
+	
+	points
+}
+
+
+// A mesh's vertices against the cells of a heightfield right under each of them. The grid is open and seen from
+// above only, so there are no edge contacts, and normals point from the heightfield towards the mesh.
+fn heightfield_contacts(objects: &[Object], mesh: usize, field: usize, world_vertices: &[Vec<Vec3>]) -> Vec<ContactPoint> {
+	let mut points = Vec::new();
+	for (k, &v) in world_vertices[mesh].iter().enumerate() {
+		let bounds = Aabb { min: v, max: v }.expand(CONTACT_MARGIN.max(MAX_CONTACT_DEPTH));
+		points.extend(vertex_contact(0, k, v, &world_triangles(&objects[field], &[], &bounds)));
+	}
+	points
+}
+
+// the face a vertex is within the margin of and penetrates least, normal flipped for the second body's vertices
+fn vertex_contact(side: u64, k: usize, v: Vec3, triangles: &[(usize, Vec3, Vec3, Vec3)]) -> Option<ContactPoint> {
+	let mut best: Option<(f32, usize, Vec3)> = None;
+	for &(l, ta, tb, tc) in triangles.iter() {
+		let n = (tc - ta).cross(tb - ta);
+		let length = n.length();
+		if length < 1e-9 { continue; }
+		let n = n / length;
+		
+		let d = (v - ta).dot(n);
+		if d > CONTACT_MARGIN || d < -MAX_CONTACT_DEPTH { continue; }
+		if best.is_some_and(|(best_d, _, _)| best_d >= d) { continue; }
+		if !point_in_triangle(v - n * d, ta, tb, tc) { continue; }
+		
+		best = Some((d, l, n));
+	}
+	
+	best.map(|(d, l, n)| ContactPoint {
+		feature: side << 48 | (k as u64) << 24 | l as u64,
+		position: v - n * (0.5 * d),
+		normal: if side == 0 { n } else { -n },
+		separation: d,
+		normal_impulse: 0.0,
+		tangent_impulse: (0.0, 0.0)
+	})
+}
+
+
+pub(crate) fn point_in_triangle(p: Vec3, a: Vec3, b: Vec3, c: Vec3) -> bool {
+	let (v0, v1, v2) = (b - a, c - a, p - a);
+	let d00 = v0.dot(v0);
+	let d01 = v0.dot(v1);
+	let d11 = v1.dot(v1);
+	let d20 = v2.dot(v0);
+	let d21 = v2.dot(v1);
+	let denominator = d00 * d11 - d01 * d01;
+	if denominator.abs() < 1e-12 { return false; }
+	
+	let s = (d11 * d20 - d01 * d21) / denominator;
+	let t = (d00 * d21 - d01 * d20) / denominator;
+	s >= -1e-4 && t >= -1e-4 && s + t <= 1.0 + 1e-4
+}
+
+
+// keeps the deepest point and then whichever points spread the manifold out the most
+fn reduce_manifold(points: &mut Vec<ContactPoint>) {
+	if points.len() <= MAX_MANIFOLD_POINTS { return; }
+	
+	let argmax = |score: &dyn Fn(&ContactPoint) -> f32| {
+		let mut best = 0;
+		for i in 1..points.len() {
+			if score(&points[i]) > score(&points[best]) { best = i; }
+		}
+		best
+	};
+	
+	let first = argmax(&|p| -p.separation);
+	let p1 = points[first].position;
+	let second = argmax(&|p| (p.position - p1).length_squared());
+	let p2 = points[second].position;
+	let third = argmax(&|p| (p.position - p1).cross(p.position - p2).length_squared());
+	let p3 = points[third].position;
+	let fourth = argmax(&|p| {
+		(p.position - p1).cross(p.position - p2).length() +
+		(p.position - p2).cross(p.position - p3).length() +
+		(p.position - p3).cross(p.position - p1).length()
+	});
+	
+	*points = [first, second, third, fourth].iter().map(|&i| points[i]).collect();
+	points.dedup_by(|a, b| a.feature == b.feature);
+}
+
+
+fn is_moving(object: &Object) -> bool {
+	object.velocity.length_squared() > SLEEP_LINEAR_VELOCITY * SLEEP_LINEAR_VELOCITY ||
+	object.angular_velocity.length_squared() > SLEEP_ANGULAR_VELOCITY * SLEEP_ANGULAR_VELOCITY
+}
+
+
+pub(crate) fn tangent_basis(n: Vec3) -> (Vec3, Vec3) {
+	let t1 = match n.0.abs() > 0.57 {
+		true => Vec3(n.1, -n.0, 0.0),
+		false => Vec3(0.0, n.2, -n.1)
+	}.normalize();
+	(t1, n.cross(t1))
+}
+
+
+fn velocity_at(object: &Object, r: Vec3) -> Vec3 {
+	object.velocity + object.angular_velocity.cross(r)
+}
+
+
+fn effective_mass(masses: &[(f32, Mat3)], a: usize, b: usize, r_a: Vec3, r_b: Vec3, direction: Vec3) -> f32 {
+	let k = masses[a].0 + masses[b].0
+		+ masses[a].1.mult_vec3(r_a.cross(direction)).cross(r_a).dot(direction)
+		+ masses[b].1.mult_vec3(r_b.cross(direction)).cross(r_b).dot(direction);
+	if k > 0.0 { 1.0 / k } else { 0.0 }
+}
+
+
+fn apply_pair_impulse(objects: &mut [Object], masses: &[(f32, Mat3)], (a, b): (usize, usize), r_a: Vec3, r_b: Vec3, impulse: Vec3) {
+	objects[a].velocity += impulse * masses[a].0;
+	objects[a].angular_velocity += masses[a].1.mult_vec3(r_a.cross(impulse));
+	objects[b].velocity -= impulse * masses[b].0;
+	objects[b].angular_velocity -= masses[b].1.mult_vec3(r_b.cross(impulse));
+}
+
+
+// Sequential impulses with warm starting, for contacts and joints together. Separated contacts act speculatively
+// and only stop the bodies from closing more than the gap in one step; penetration is fed back as a velocity bias.
+fn solve_velocities(objects: &mut [Object], contacts: &mut ContactCache, joints: &mut JointSet, iterations: usize, dt: f32) {
+	// sleeping bodies behave as if they were static until something wakes them
+	let masses = objects.iter().map(|o| match o.sleeping {
+		true => (0.0, Mat3::zero()),
+		false => (o.inverse_mass, o.world_inverse_inertia())
+	}).collect::<Vec<(f32, Mat3)>>();
+	
+	let mut constraints = Vec::new();
+	for (&(a, b), manifold) in contacts.manifolds.iter() {
+		if !objects[a].is_active() && !objects[b].is_active() { continue; }
+		
+		let restitution = f32::max(objects[a].restitution, objects[b].restitution);
+		let friction = f32::sqrt(objects[a].friction * objects[b].friction);
+		
+		for (n, point) in manifold.points.iter().enumerate() {
+			let r_a = point.position - objects[a].transform.get_position();
+			let r_b = point.position - objects[b].transform.get_position();
+			let tangents = tangent_basis(point.normal);
+			
+			let normal_velocity = (velocity_at(&objects[a], r_a) - velocity_at(&objects[b], r_b)).dot(point.normal);
+			let mut bias = match point.separation > 0.0 {
+				true => -point.separation / dt,
+				false => BAUMGARTE_FACTOR * f32::max(-point.separation - PENETRATION_SLOP, 0.0) / dt
+			};
+			if normal_velocity < -RESTITUTION_THRESHOLD {
+				bias = f32::max(bias, -restitution * normal_velocity);
 			}
-			dt_remaining -= t_step;
 			
+			constraints.push(ContactConstraint {
+				pair: (a, b),
+				point: n,
+				r_a,
+				r_b,
+				normal: point.normal,
+				tangents,
+				normal_mass: effective_mass(&masses, a, b, r_a, r_b, point.normal),
+				tangent_mass: (effective_mass(&masses, a, b, r_a, r_b, tangents.0), effective_mass(&masses, a, b, r_a, r_b, tangents.1)),
+				bias,
+				friction,
+				normal_impulse: point.normal_impulse,
+				tangent_impulse: point.tangent_impulse
+			});
+		}
+	}
+	
+	let mut joint_rows = joint::prepare_rows(joints, objects, &masses, dt);
+	
+	for c in constraints.iter() {
+		let impulse = c.normal * c.normal_impulse + c.tangents.0 * c.tangent_impulse.0 + c.tangents.1 * c.tangent_impulse.1;
+		apply_pair_impulse(objects, &masses, c.pair, c.r_a, c.r_b, impulse);
+	}
+	joint::warm_start(&joint_rows, objects, &masses);
+	
+	for _ in 0..iterations {
+		joint::solve_rows(&mut joint_rows, objects, &masses);
+		
+		for c in constraints.iter_mut() {
+			let (a, b) = c.pair;
 			
-			let position = objects[i].vertices[k].apply_transform(&objects[i].transform);
+			let max_friction = c.friction * c.normal_impulse;
+			for (tangent, tangent_mass, accumulated) in [(c.tangents.0, c.tangent_mass.0, &mut c.tangent_impulse.0), (c.tangents.1, c.tangent_mass.1, &mut c.tangent_impulse.1)] {
+				let relative_velocity = velocity_at(&objects[a], c.r_a) - velocity_at(&objects[b], c.r_b);
+				let lambda = -relative_velocity.dot(tangent) * tangent_mass;
+				let new_impulse = (*accumulated + lambda).clamp(-max_friction, max_friction);
+				let change = new_impulse - *accumulated;
+				*accumulated = new_impulse;
+				apply_pair_impulse(objects, &masses, c.pair, c.r_a, c.r_b, tangent * change);
+			}
 			
-			let (a_index, b_index, c_index) = objects[j].indices[l];
-			let a = objects[j].vertices[a_index as usize];
-			let b = objects[j].vertices[b_index as usize];
-			let c = objects[j].vertices[c_index as usize];
-			let normal = (b - a).cross(c - a).apply_transform(&objects[j].transform.set_position(Vec3(0.0, 0.0, 0.0))).normalize();
+			let relative_velocity = velocity_at(&objects[a], c.r_a) - velocity_at(&objects[b], c.r_b);
+			let lambda = -(relative_velocity.dot(c.normal) - c.bias) * c.normal_mass;
+			let new_impulse = f32::max(c.normal_impulse + lambda, 0.0);
+			let change = new_impulse - c.normal_impulse;
+			c.normal_impulse = new_impulse;
+			apply_pair_impulse(objects, &masses, c.pair, c.r_a, c.r_b, c.normal * change);
+		}
+	}
+	
+	for c in constraints.iter() {
+		if let Some(manifold) = contacts.manifolds.get_mut(&c.pair) {
+			manifold.points[c.point].normal_impulse = c.normal_impulse;
+			manifold.points[c.point].tangent_impulse = c.tangent_impulse;
+		}
+	}
+	joint::store_impulses(&joint_rows, joints);
+}
+
+
+// Moves everything forward while catching fast impacts the manifolds missed.
+// Bounded by MAX_TOI_ITERATIONS, after which the rest of the step is taken without ccd.
+// Returns the impacts as (body, mesh body, point, normal towards the body, normal impulse).
+fn integrate(objects: &mut [Object], drifts: &[(Vec3, Vec3)], ignored_pairs: &BTreeSet<(usize, usize)>, parallel: bool, dt: f32) -> Vec<(usize, usize, Vec3, Vec3, f32)> {
+	let mut dt_remaining = dt;
+	let mut impacts = Vec::new();
+	
+	for _ in 0..MAX_TOI_ITERATIONS {
+		let new_transforms = objects.iter().zip(drifts).map(|(o, drift)| future_transform(o, drift, dt_remaining)).collect::<Vec<Mat4>>();
+		
+		if let Some((t, i, j, k, l)) = find_earliest_impact(objects, drifts, &new_transforms, ignored_pairs, parallel, dt_remaining) {
+			let t_step = dt_remaining * f32::max(t - 0.001, t * 0.5);
+			advance(objects, drifts, t_step);
+			dt_remaining -= t_step;
 			
-			collide(objects, i, j, position, normal);
+			let (a, b, c) = objects[j].triangle(l);
+			let normal = (c - a).cross(b - a).apply_rotation(&objects[j].transform).normalize();
 			
-			collision = None;
+			// a face landing flat hits with several points at once, a lone corner would send it spinning
+			let (points, radius) = sweep_points(&objects[i], &objects[i].transform);
+			let plane = a.apply_transform(&objects[j].transform);
+			let height = |p: Vec3| (p - plane).dot(normal);
+			let touching = points.iter().copied().filter(|&p| height(p) <= height(points[k]) + CONTACT_MARGIN).collect::<Vec<Vec3>>();
+			let position = touching.iter().fold(Vec3(0.0, 0.0, 0.0), |sum, &p| sum + p) / touching.len() as f32 - normal * radius;
 			
+			let impulse = collide(objects, i, j, position, normal);
+			impacts.push((i, j, position, normal, impulse));
 		} else {
-			for i in 0..objects.len() {
-				objects[i].transform = new_transforms[i];
+			for (object, transform) in objects.iter_mut().zip(new_transforms) {
+				object.transform = transform;
 			}
-			break;
+			return impacts;
 		}
 	}
+	
+	advance(objects, drifts, dt_remaining);
+	impacts
 }
 
 
-fn collide(objects: &mut [Object], i: usize, j: usize, p: Vec3, n: Vec3) {
+// the points that sweep against mesh triangles and how far the surface sits out from them, none for heightfields
+fn sweep_points(object: &Object, transform: &Mat4) -> (Vec<Vec3>, f32) {
+	match (object.shape.world_convex(transform), &object.shape) {
+		(Some(convex), _) => (convex.points, convex.radius),
+		(None, Shape::Heightfield { .. }) => (Vec::new(), 0.0),
+		(None, _) => (object.vertices.iter().map(|v| v.apply_transform(transform)).collect(), 0.0)
+	}
+}
+
+
+fn advance(objects: &mut [Object], drifts: &[(Vec3, Vec3)], dt: f32) {
+	for (object, drift) in objects.iter_mut().zip(drifts) {
+		object.transform = future_transform(object, drift, dt);
+	}
+}
+
+// where the body's velocities and the integrator's drift take it
+fn future_transform(object: &Object, &(linear, angular): &(Vec3, Vec3), dt: f32) -> Mat4 {
+	match object.sleeping {
+		true => object.transform,
+		false => integrator::drift(&object.transform, object.velocity + linear, object.angular_velocity + angular, dt)
+	}
+}
+
+
+// Earliest vertex-triangle crossing over the step, as a fraction of it. Vertices that start
+// within the contact margin of a face are left to the manifolds, which keeps resting contact out of here.
+// Pairs are tested on the thread pool when asked, then reduced in pair order, so ties go the same way either way.
+fn find_earliest_impact(objects: &[Object], drifts: &[(Vec3, Vec3)], new_transforms: &[Mat4], ignored_pairs: &BTreeSet<(usize, usize)>, parallel: bool, dt: f32) -> Option<(f32, usize, usize, usize, usize)> {
+	let (transformed_vertices, radii): (Vec<Vec<(Vec3, Vec3)>>, Vec<f32>) = (0..objects.len()).map(|i| {
+		let ((this, radius), (next, _)) = (sweep_points(&objects[i], &objects[i].transform), sweep_points(&objects[i], &new_transforms[i]));
+		(this.into_iter().zip(next).collect(), radius)
+	}).unzip();
+	// how far a body's surface reaches from its position, which is what it turns about
+	let reaches = (0..objects.len()).map(|i| {
+		let position = objects[i].transform.get_position();
+		transformed_vertices[i].iter().map(|&(p, _)| (p - position).length()).fold(0.0, f32::max) + radii[i]
+	}).collect::<Vec<f32>>();
+	let swept_aabbs = (0..objects.len()).map(|i| match (&objects[i].shape, objects[i].ccd) {
+		(Shape::Heightfield { field }, _) => field.world_aabb(&objects[i].transform),
+		// its position moves in a straight line and the rest of it stays within reach, however it turns
+		(_, Ccd::ConservativeAdvancement) => Aabb::from_points(&[objects[i].transform.get_position(), new_transforms[i].get_position()]).expand(reaches[i]),
+		(_, Ccd::Linear) => {
+			let (this, next): (Vec<Vec3>, Vec<Vec3>) = transformed_vertices[i].iter().copied().unzip();
+			Aabb::from_points(&this).union(Aabb::from_points(&next)).expand(radii[i])
+		}
+	}).collect::<Vec<Aabb>>();
 	
-	let v1 = objects[i].velocity + objects[i].angular_velocity.cross(p - objects[i].transform.get_position());
-	let v2 = objects[j].velocity + objects[j].angular_velocity.cross(p - objects[j].transform.get_position());
+	let pairs = broad_phase(&swept_aabbs).into_iter().filter(|&(a, b)| {
+		if new_transforms[a] == objects[a].transform && new_transforms[b] == objects[b].transform { return false; }
+		if ignored_pairs.contains(&(a, b)) { return false; }
+		if objects[a].is_trigger || objects[b].is_trigger { return false; }
+		// convex shapes are kept apart by their manifolds, only mesh triangles stop fast bodies here
+		!(objects[a].shape.is_convex() && objects[b].shape.is_convex())
+	}).collect::<Vec<(usize, usize)>>();
 	
-	let relative_velocity = v2 - v1;
+	let pair_impact = |&(a, b): &(usize, usize)| match objects[a].ccd == Ccd::ConservativeAdvancement || objects[b].ccd == Ccd::ConservativeAdvancement {
+		true => pair_advancement_impact(objects, drifts, &reaches, &swept_aabbs, a, b, dt),
+		false => pair_earliest_impact(objects, &transformed_vertices, &radii, &swept_aabbs, a, b)
+	};
+	let impacts = match parallel {
+		true => pairs.par_iter().map(pair_impact).collect::<Vec<_>>(),
+		false => pairs.iter().map(pair_impact).collect::<Vec<_>>()
+	};
+	impacts.into_iter().flatten().fold(None, |earliest, impact| match earliest {
+		Some((t, _, _, _, _)) if impact.0 > t => earliest,
+		_ => Some(impact)
+	})
+}
+
+fn pair_earliest_impact(objects: &[Object], transformed_vertices: &[Vec<(Vec3, Vec3)>], radii: &[f32], swept_aabbs: &[Aabb], a: usize, b: usize) -> Option<(f32, usize, usize, usize, usize)> {
+	let mut collision = None;
 	
-	let normal_velocity = n * n.dot(relative_velocity);
-	let perpendicular_velocity = relative_velocity - normal_velocity;
+	for (i, j) in [(a, b), (b, a)] {
+		if objects[j].shape.is_convex() { continue; }
+		// heightfields are static, so their triangles are where they are at both ends of the step
+		let triangles = match &objects[j].shape {
+			Shape::Heightfield { field } => field.world_triangles_in(&objects[j].transform, &swept_aabbs[i]).into_iter().map(|(l, a, b, c)| (l, (a, a), (b, b), (c, c))).collect(),
+			_ => objects[j].indices.iter().enumerate().map(|(l, &(a, b, c))| (l, transformed_vertices[j][a as usize], transformed_vertices[j][b as usize], transformed_vertices[j][c as usize])).collect::<Vec<_>>()
+		};
+		for (k, &(this_v, next_v)) in transformed_vertices[i].iter().enumerate() {
+			for &(l, (this_a, next_a), (this_b, next_b), (this_c, next_c)) in triangles.iter() {
+				
+				// rounded shapes sweep their core against the face pushed out by their radius
+				let (this_v, next_v) = match radii[i] > 0.0 {
+					true => (
+						this_v + (this_b - this_a).cross(this_c - this_a).normalize() * radii[i],
+						next_v + (next_b - next_a).cross(next_c - next_a).normalize() * radii[i]
+					),
+					false => (this_v, next_v)
+				};
+				
+				let p0 = this_v - this_a;
+				let g0 = this_b - this_a;
+				let h0 = this_c - this_a;
+				let dp = next_v - next_a - p0;
+				let dg = next_b - next_a - g0;
+				let dh = next_c - next_a - h0;
+				
+				// the face normal g0 x h0 points inward, so this is the distance in front of the face
+				let area = g0.cross(h0).length();
+				if area < 1e-9 || -g0.cross(h0).dot(p0) / area <= CONTACT_MARGIN { continue; }
+				
+				let time_to_beat = match collision { Some((t, _, _, _, _)) => t, None => 1.0 };
+				
+				if let Some(t) = vertex_triangle_impact(p0, g0, h0, dp, dg, dh, time_to_beat) {
+					collision = Some((t, i, j, k, l));
+				}
+			}
+		}
+	}
 	
-	objects[i].apply_impulse(2.0 * normal_velocity, p - objects[i].transform.get_position());
+	collision
+}
+
+
+// Conservative advancement along the motion the bodies really take. The gap between one side's points and the other's triangles
+// can't close faster than the relative speed of their positions plus each angular speed times its reach, so moving on by the gap
+// over that bound never steps past an impact. Gives up unconverged after MAX_ADVANCEMENT_ITERATIONS, which only happens
+// to points sliding past a face far from it.
+fn pair_advancement_impact(objects: &[Object], drifts: &[(Vec3, Vec3)], reaches: &[f32], swept_aabbs: &[Aabb], a: usize, b: usize, dt: f32) -> Option<(f32, usize, usize, usize, usize)> {
+	let velocities = |i: usize| match objects[i].sleeping {
+		true => (Vec3(0.0, 0.0, 0.0), Vec3(0.0, 0.0, 0.0)),
+		false => (objects[i].velocity + drifts[i].0, objects[i].angular_velocity + drifts[i].1)
+	};
+	let ((velocity_a, angular_a), (velocity_b, angular_b)) = (velocities(a), velocities(b));
+	let bound = ((velocity_a - velocity_b).length() + angular_a.length() * reaches[a] + angular_b.length() * reaches[b]) * dt;
+	if bound < 1e-9 { return None; }
+	let transform_at = |i: usize, t: f32| future_transform(&objects[i], &drifts[i], t * dt);
 	
+	// Which points go against which triangles, the same as the linear sweep: points starting within the contact
+	// margin of a face's plane, or behind it, are left to the manifolds.
+	let sides = [(a, b), (b, a)].into_iter().filter(|&(_, j)| !objects[j].shape.is_convex()).map(|(i, j)| {
+		let (points, radius) = sweep_points(&objects[i], &objects[i].transform);
+		let triangles = query::world_triangles(&objects[j], &swept_aabbs[i]);
+		let mut candidates = Vec::new();
+		for (m, &(_, x, y, z)) in triangles.iter().enumerate() {
+			let normal = (z - x).cross(y - x);
+			if normal.length_squared() < 1e-12 { continue; }
+			let normal = normal.normalize();
+			candidates.extend(points.iter().enumerate().filter(|&(_, &p)| (p - x).dot(normal) - radius > CONTACT_MARGIN).map(|(k, _)| (k, m)));
+		}
+		(i, j, radius, triangles.into_iter().map(|(l, _, _, _)| l).collect::<Vec<usize>>(), candidates)
+	}).collect::<Vec<_>>();
 	
-	objects[i].velocity = -objects[i].velocity;
+	let mut t = 0.0;
+	for _ in 0..MAX_ADVANCEMENT_ITERATIONS {
+		// the closest point and triangle as (gap, i, j, k, l)
+		let mut closest: Option<(f32, usize, usize, usize, usize)> = None;
+		for (i, j, radius, triangles, candidates) in sides.iter() {
+			let (points, _) = sweep_points(&objects[*i], &transform_at(*i, t));
+			let transform = transform_at(*j, t);
+			let corners = triangles.iter().map(|&l| {
+				let (x, y, z) = objects[*j].triangle(l);
+				(x.apply_transform(&transform), y.apply_transform(&transform), z.apply_transform(&transform))
+			}).collect::<Vec<(Vec3, Vec3, Vec3)>>();
+			
+			for &(k, m) in candidates.iter() {
+				let (x, y, z) = corners[m];
+				let gap = (narrow_phase::closest_point_on_triangle(points[k], x, y, z) - points[k]).length() - radius;
+				if closest.is_none_or(|(closest_gap, _, _, _, _)| gap < closest_gap) { closest = Some((gap, *i, *j, k, triangles[m])); }
+			}
+		}
+		
+		let (gap, i, j, k, l) = closest?;
+		if gap <= 0.5 * CONTACT_MARGIN { return Some((t, i, j, k, l)); }
+		t += gap / bound;
+		if t > 1.0 { return None; }
+	}
+	None
+}
+
+
+// Solves for the first time in [0, time_to_beat] where the moving point p lies inside the moving triangle (0, g, h).
+fn vertex_triangle_impact(p0: Vec3, g0: Vec3, h0: Vec3, dp: Vec3, dg: Vec3, dh: Vec3, time_to_beat: f32) -> Option<f32> {
+	let cubic_a = dg.cross(dh).dot(dp);
+	let cubic_b = dg.cross(dh).dot(p0) + (dg.cross(h0) + g0.cross(dh)).dot(dp);
+	let cubic_c = (dg.cross(h0) + g0.cross(dh)).dot(p0) + g0.cross(h0).dot(dp);
+	let cubic_d = g0.cross(h0).dot(p0);
 	
+	let t = if cubic_a.abs() > 1e-7 {
+		let b = cubic_b / cubic_a;
+		let c = cubic_c / cubic_a;
+		let d = cubic_d / cubic_a;
+		
+		let q = (3.0*c - b*b) / 9.0;
+		let r = b * (9.0*c - 2.0*b*b) / 54.0 - 0.5 * d;
+		
+		let discriminant = q*q*q + r*r;
+		if discriminant >= 0.0 {
+			let sqrtd = f32::sqrt(discriminant);
+			let t = -b / 3.0 + (r + sqrtd).cbrt() + (r - sqrtd).cbrt();
+			match (0.0..=time_to_beat).contains(&t) {
+				true => Some(t),
+				false => None
+			}
+		} else {
+			let dum1 = f32::acos(r / f32::sqrt(-q*q*q));
+			let r13 = 2.0 * f32::sqrt(-q);
+			let t1 = -b / 3.0 + r13 * f32::cos(dum1 / 3.0);
+			let t2 = -b / 3.0 + r13 * f32::cos((dum1 + 2.0*std::f32::consts::PI) / 3.0);
+			let t3 = -b / 3.0 + r13 * f32::cos((dum1 + 4.0*std::f32::consts::PI) / 3.0);
+			
+			match ((0.0..=time_to_beat).contains(&t1), (0.0..=time_to_beat).contains(&t2), (0.0..=time_to_beat).contains(&t3), t1 < t2, t1 < t3, t2 < t3) {
+				(true, true, true, true, true, _) => Some(t1),
+				(true, true, true, false, _, true) => Some(t2),
+				(true, true, true, _, false, false) => Some(t3),
+				(true, true, false, true, _, _) => Some(t1),
+				(true, true, false, false, _, _) => Some(t2),
+				(true, false, true, _, true, _) => Some(t1),
+				(true, false, true, _, false, _) => Some(t3),
+				(false, true, true, _, _, true) => Some(t2),
+				(false, true, true, _, _, false) => Some(t3),
+				(true, false, false, _, _, _) => Some(t1),
+				(false, true, false, _, _, _) => Some(t2),
+				(false, false, true, _, _, _) => Some(t3),
+				(false, false, false, _, _, _) => None,
+				(true, true, true, false, true, false) | (true, true, true, true, false, true) => unreachable!()
+			}
+		}
+	} else if cubic_b.abs() > 1e-7 {
+		let b = cubic_c / cubic_b;
+		let c = cubic_d / cubic_b;
+		
+		let discriminant = b*b - 4.0*c;
+		if discriminant >= 0.0 {
+			let sqrtd = discriminant.sqrt();
+			let t1 = (-b + sqrtd) * 0.5;
+			let t2 = (-b - sqrtd) * 0.5;
+			
+			match ((0.0..=time_to_beat).contains(&t1), (0.0..=time_to_beat).contains(&t2), t1 < t2) {
+				(true, true, true) => Some(t1),
+				(true, true, false) => Some(t2),
+				(true, false, _) => Some(t1),
+				(false, true, _) => Some(t2),
+				(false, false, _) => None
+			}
+		} else {
+			None
+		}
+	} else {
+		let t = -cubic_d / cubic_c;
+		match (0.0..=time_to_beat).contains(&t) {
+			true => Some(t),
+			false => None
+		}
+	};
+	
+	
+	let t = t?;
+	let p = p0 + dp * t;
+	let g = g0 + dg * t;
+	let h = h0 + dh * t;
+	
+	let (larger, smaller) = match f32::max(g.0*g.1, f32::max(g.0*g.2, g.1*g.2)) > f32::max(h.0*h.1, f32::max(h.0*h.2, h.1*h.2)) {
+		true => (g, h),
+		false => (h, g)
+	};
+	
+	let (la, lb, sa, sb, pa, pb) = match (larger.0 < larger.1, larger.0 < larger.2, larger.1 < larger.2) {
+		(true, true, true) => (larger.2, larger.1, smaller.2, smaller.1, p.2, p.1),
+		(true, true, false) => (larger.1, larger.2, smaller.1, smaller.2, p.1, p.2),
+		(true, false, true) => unreachable!(),
+		(true, false, false) => (larger.1, larger.0, smaller.1, smaller.0, p.1, p.0),
+		(false, true, true) => (larger.2, larger.0, smaller.2, smaller.0, p.2, p.0),
+		(false, true, false) => unreachable!(),
+		(false, false, true) => (larger.0, larger.2, smaller.0, smaller.2, p.0, p.2),
+		(false, false, false) => (larger.0, larger.1, smaller.0, smaller.1, p.0, p.1)
+	};
+	
+	let s = (pa / la - pb / lb) / (sa / la - sb / lb);
+	let r = (pa - sa * s) / la;
+	// larger * r + smaller * s = p
+	
+	match r >= 0.0 && s >= 0.0 && r + s <= 1.0 {
+		true => Some(t),
+		false => None
+	}
 }
 
 
+// Impulse response for an impact found by the ccd pass, with restitution and Coulomb friction.
+// Returns the normal impulse, zero if the bodies were already separating.
+fn collide(objects: &mut [Object], i: usize, j: usize, p: Vec3, n: Vec3) -> f32 {
+	let r_i = p - objects[i].transform.get_position();
+	let r_j = p - objects[j].transform.get_position();
+	
+	let relative_velocity = velocity_at(&objects[i], r_i) - velocity_at(&objects[j], r_j);
+	let normal_velocity = n.dot(relative_velocity);
+	if normal_velocity >= 0.0 { return 0.0; }
+	
+	objects[i].wake();
+	objects[j].wake();
+	
+	let masses = [(objects[i].inverse_mass, objects[i].world_inverse_inertia()), (objects[j].inverse_mass, objects[j].world_inverse_inertia())];
+	let normal_mass = effective_mass(&masses, 0, 1, r_i, r_j, n);
+	if normal_mass == 0.0 { return 0.0; }
+	
+	let restitution = f32::max(objects[i].restitution, objects[j].restitution);
+	let normal_impulse = -(1.0 + restitution) * normal_velocity * normal_mass;
+	let mut impulse = n * normal_impulse;
+	
+	let perpendicular_velocity = relative_velocity - n * normal_velocity;
+	if perpendicular_velocity.length_squared() > 1e-12 {
+		let tangent = perpendicular_velocity.normalize();
+		let friction = f32::sqrt(objects[i].friction * objects[j].friction);
+		let tangent_impulse = f32::min(perpendicular_velocity.length() * effective_mass(&masses, 0, 1, r_i, r_j, tangent), friction * normal_impulse);
+		impulse -= tangent * tangent_impulse;
+	}
+	
+	objects[i].apply_impulse(impulse, r_i);
+	objects[j].apply_impulse(-impulse, r_j);
+	normal_impulse
+}
 
 
+// Groups dynamic bodies that touch or are jointed, directly or through other dynamic bodies. Static bodies
+// don't join islands, otherwise everything on the floor would be one island.
+fn islands(objects: &[Object], contacts: &ContactCache, joints: &JointSet) -> Vec<Vec<usize>> {
+	let mut parent = (0..objects.len()).collect::<Vec<usize>>();
+	fn find(parent: &mut [usize], mut i: usize) -> usize {
+		while parent[i] != i {
+			parent[i] = parent[parent[i]];
+			i = parent[i];
+		}
+		i
+	}
+	
+	let jointed = joints.iter().filter_map(|(_, j)| j.b.map(|b| (j.a, b)));
+	for (a, b) in contacts.manifolds.keys().copied().chain(jointed) {
+		if objects[a].is_static() || objects[b].is_static() { continue; }
+		let (root_a, root_b) = (find(&mut parent, a), find(&mut parent, b));
+		parent[root_a.max(root_b)] = root_a.min(root_b);
+	}
+	
+	let mut islands = BTreeMap::<usize, Vec<usize>>::new();
+	for (i, object) in objects.iter().enumerate() {
+		if object.is_static() { continue; }
+		let root = find(&mut parent, i);
+		islands.entry(root).or_default().push(i);
+	}
+	islands.into_values().collect()
+}
+
+
+// an island is only as asleep as its most restless body
+fn wake_islands(objects: &mut [Object], contacts: &ContactCache, joints: &JointSet) {
+	for island in islands(objects, contacts, joints) {
+		if island.iter().any(|&i| objects[i].is_active() && objects[i].sleep_timer < SLEEP_TIME) {
+			for &i in island.iter() {
+				if objects[i].sleeping { objects[i].wake(); }
+			}
+		}
+	}
+}
+
+
+fn update_sleep(objects: &mut [Object], contacts: &ContactCache, joints: &JointSet, dt: f32) {
+	for object in objects.iter_mut() {
+		if !object.is_active() { continue; }
+		match is_moving(object) {
+			true => object.sleep_timer = 0.0,
+			false => object.sleep_timer += dt
+		}
+	}
+	
+	for island in islands(objects, contacts, joints) {
+		if island.iter().all(|&i| objects[i].sleeping || objects[i].sleep_timer >= SLEEP_TIME) {
+			for &i in island.iter() {
+				objects[i].sleeping = true;
+				objects[i].velocity = Vec3(0.0, 0.0, 0.0);
+				objects[i].angular_velocity = Vec3(0.0, 0.0, 0.0);
+			}
+		}
+	}
+}
+
 
+// Compares the pairs touching now with the last step's. Solid pairs count as touching once a point
+// penetrates or pushes, so speculative points alone don't begin a contact.
+fn update_events(contacts: &mut ContactCache, impacts: &[(usize, usize, Vec3, Vec3, f32)]) {
+	let mut touching = BTreeMap::new();
+	
+	for (&pair, manifold) in contacts.manifolds.iter() {
+		let active = manifold.points.iter().filter(|p| p.separation <= 0.0 || p.normal_impulse > 0.0).collect::<Vec<&ContactPoint>>();
+		if active.is_empty() { continue; }
+		let point = active.iter().fold(Vec3(0.0, 0.0, 0.0), |sum, p| sum + p.position) / active.len() as f32;
+		let normal = match active.iter().fold(Vec3(0.0, 0.0, 0.0), |sum, p| sum + p.normal) {
+			sum if sum.length_squared() > 1e-12 => sum.normalize(),
+			_ => active[0].normal
+		};
+		let impulse = active.iter().map(|p| p.normal_impulse).sum::<f32>();
+		touching.insert(pair, (point, normal, impulse, false));
+	}
+	
+	for &(i, j, point, normal, impulse) in impacts {
+		if impulse == 0.0 { continue; }
+		let normal = match i < j { true => normal, false => -normal };
+		let entry = touching.entry((i.min(j), i.max(j))).or_insert((point, normal, 0.0, false));
+		entry.2 += impulse;
+	}
+	
+	for (&pair, manifold) in contacts.triggers.iter() {
+		let point = manifold.points.iter().fold(Vec3(0.0, 0.0, 0.0), |sum, p| sum + p.position) / manifold.points.len() as f32;
+		touching.insert(pair, (point, manifold.points[0].normal, 0.0, true));
+	}
+	
+	contacts.events.clear();
+	for (&bodies, &(point, normal, trigger)) in contacts.touching.iter() {
+		if !touching.contains_key(&bodies) {
+			contacts.events.push(ContactEvent { kind: ContactEventKind::End, bodies, point, normal, impulse: 0.0, trigger });
+		}
+	}
+	for (&bodies, &(point, normal, impulse, trigger)) in touching.iter() {
+		let kind = match contacts.touching.contains_key(&bodies) {
+			true => ContactEventKind::Persist,
+			false => ContactEventKind::Begin
+		};
+		contacts.events.push(ContactEvent { kind, bodies, point, normal, impulse, trigger });
+	}
+	
+	contacts.touching = touching.into_iter().map(|(pair, (point, normal, _, trigger))| (pair, (point, normal, trigger))).collect();
+}
 
 
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	fn impacts_come_from_whichever_root_is_in_the_step() {
+		// g = (t - x, 1, 0) and h = (0, t - y, 0) span the triangle, and p sits a quarter of the way along each of them,
+		// (t - z) above it. p is in the triangle's plane when (t - x)(t - y)(t - z) is zero, but only inside it at z.
+		// With only z in the step it has to be the root found, whichever of the three it comes out of the cubic as.
+		let turn = Mat4::identity().rotate_x(0.3).rotate_y(0.5);
+		let roots = [(3.0, -2.0, 0.5), (-2.0, 3.0, 0.5), (1.5, -1.0, 0.25), (-1.0, 1.5, 0.25), (2.5, 4.0, 0.75), (-3.0, -0.5, 0.75)];
+		for (x, y, z) in roots {
+			let (g0, dg) = (Vec3(-x, 1.0, 0.0), Vec3(1.0, 0.0, 0.0));
+			let (h0, dh) = (Vec3(0.0, -y, 0.0), Vec3(0.0, 1.0, 0.0));
+			let (p0, dp) = ((g0 + h0) * 0.25 - Vec3(0.0, 0.0, z), (dg + dh) * 0.25 + Vec3(0.0, 0.0, 1.0));
+			let [g0, h0, p0, dg, dh, dp] = [g0, h0, p0, dg, dh, dp].map(|v| v.apply_transform(&turn));
+			let t = vertex_triangle_impact(p0, g0, h0, dp, dg, dh, 1.0);
+			assert!(t.is_some_and(|t| (t - z).abs() < 1e-4), "roots {:?} gave {:?}", (x, y, z), t);
+		}
+	}
+}
@@ -0,0 +1,381 @@
+use crate::{math_structs::{Mat4, Vec3}, narrow_phase, object::Object, physics::Aabb, shape::{Convex, Shape}};
+
+
+static CAST_ITERATIONS: usize = 32;
+static CAST_TOLERANCE: f32 = 1e-4;
+
+
+// Which bodies a query looks at. Triggers are skipped unless asked for, so they don't block line of sight.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct QueryFilter {
+	pub exclude: Option<usize>, // usually the body doing the query
+	pub include_triggers: bool
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CastHit {
+	pub body: usize,
+	pub point: Vec3, // on the surface that was hit
+	pub normal: Vec3, // out of the hit surface, back towards the caster
+	pub distance: f32, // travelled along the normalized direction
+	pub triangle: Option<usize> // index into the body's triangles when it is a mesh
+}
+
+// A bounding volume hierarchy over the bodies' bounds. The world builds one on the first query after its bodies
+// change and every query until the next change walks it, instead of going over every body's bounds again.
+pub struct BoundsTree {
+	nodes: Vec<Node> // the root first, none without bodies
+}
+
+struct Node {
+	bounds: Aabb,
+	content: NodeContent
+}
+
+enum NodeContent {
+	Body(usize),
+	Children(usize, usize)
+}
+
+
+impl QueryFilter {
+	pub(crate) fn accepts(&self, index: usize, object: &Object) -> bool {
+		self.exclude != Some(index) && (self.include_triggers || !object.is_trigger)
+	}
+}
+
+
+impl BoundsTree {
+	pub fn new(objects: &[Object]) -> Self {
+		let bounds = objects.iter().map(Object::world_aabb).collect::<Vec<Aabb>>();
+		let mut tree = Self { nodes: Vec::with_capacity(2 * objects.len()) };
+		if !objects.is_empty() {
+			tree.build(&bounds, &mut (0..objects.len()).collect::<Vec<usize>>());
+		}
+		tree
+	}
+	
+	// splits the bodies in half at the median center along the axis their centers spread out most on,
+	// returns the index of the node holding them
+	fn build(&mut self, bounds: &[Aabb], bodies: &mut [usize]) -> usize {
+		let node = self.nodes.len();
+		let all = bodies.iter().map(|&i| bounds[i]).reduce(Aabb::union).unwrap();
+		if let [body] = bodies {
+			self.nodes.push(Node { bounds: all, content: NodeContent::Body(*body) });
+			return node;
+		}
+		self.nodes.push(Node { bounds: all, content: NodeContent::Children(0, 0) });
+		
+		let center = |i: usize| (bounds[i].min + bounds[i].max) * 0.5;
+		let centers = Aabb::from_points(&bodies.iter().map(|&i| center(i)).collect::<Vec<Vec3>>());
+		let Vec3(x, y, z) = centers.max - centers.min;
+		let key = |i: usize| match (x >= y && x >= z, y >= z) {
+			(true, _) => center(i).0,
+			(false, true) => center(i).1,
+			(false, false) => center(i).2
+		};
+		let half = bodies.len() / 2;
+		bodies.select_nth_unstable_by(half, |&a, &b| key(a).total_cmp(&key(b)));
+		let (left, right) = bodies.split_at_mut(half);
+		let children = (self.build(bounds, left), self.build(bounds, right));
+		self.nodes[node].content = NodeContent::Children(children.0, children.1);
+		node
+	}
+	
+	// the bodies whose bounds pass the test, with their bounds, only going into nodes whose bounds pass it too
+	pub fn bodies_where(&self, test: impl Fn(&Aabb) -> bool) -> Vec<(usize, Aabb)> {
+		let mut bodies = Vec::new();
+		let mut stack = match self.nodes.is_empty() { true => vec![], false => vec![0] };
+		while let Some(node) = stack.pop() {
+			let node = &self.nodes[node];
+			if !test(&node.bounds) { continue; }
+			match node.content {
+				NodeContent::Body(i) => bodies.push((i, node.bounds)),
+				NodeContent::Children(left, right) => stack.extend([right, left])
+			}
+		}
+		bodies
+	}
+}
+
+
+// First hit along the ray, none for a zero direction. Bodies are visited in order of where the ray enters
+// their bounds, so everything behind the closest hit so far is skipped.
+pub fn ray_cast(objects: &[Object], origin: Vec3, direction: Vec3, max_distance: f32, filter: &QueryFilter) -> Option<CastHit> {
+	ray_cast_in(objects, None, origin, direction, max_distance, filter)
+}
+
+// the same, finding the bodies through the tree when there is one rather than looking at every body's bounds
+pub fn ray_cast_in(objects: &[Object], tree: Option<&BoundsTree>, origin: Vec3, direction: Vec3, max_distance: f32, filter: &QueryFilter) -> Option<CastHit> {
+	if direction.length_squared() < 1e-12 { return None; }
+	let direction = direction.normalize();
+	
+	let mut best: Option<CastHit> = None;
+	for (entry, i, _) in candidates(objects, tree, origin, direction, Vec3(0.0, 0.0, 0.0), max_distance, filter) {
+		if best.is_some_and(|hit| hit.distance <= entry) { break; }
+		let limit = best.map_or(max_distance, |hit| hit.distance);
+		if let Some(hit) = ray_body(&objects[i], i, origin, direction, limit) {
+			best = Some(hit);
+		}
+	}
+	best
+}
+
+// the closest hit on every body the ray passes through, nearest first
+pub fn ray_cast_all(objects: &[Object], origin: Vec3, direction: Vec3, max_distance: f32, filter: &QueryFilter) -> Vec<CastHit> {
+	ray_cast_all_in(objects, None, origin, direction, max_distance, filter)
+}
+
+pub fn ray_cast_all_in(objects: &[Object], tree: Option<&BoundsTree>, origin: Vec3, direction: Vec3, max_distance: f32, filter: &QueryFilter) -> Vec<CastHit> {
+	if direction.length_squared() < 1e-12 { return Vec::new(); }
+	let direction = direction.normalize();
+	let mut hits = candidates(objects, tree, origin, direction, Vec3(0.0, 0.0, 0.0), max_distance, filter).into_iter()
+		.filter_map(|(_, i, _)| ray_body(&objects[i], i, origin, direction, max_distance))
+		.collect::<Vec<CastHit>>();
+	hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+	hits
+}
+
+// Moves a convex shape from the transform along the direction and returns the first thing it touches.
+// Shapes that start out touching something hit it at distance zero. Meshes can't be cast, and nothing is
+// hit along a zero direction. Bodies are visited like for ray_cast, with their bounds grown by the shape's.
+pub fn shape_cast(objects: &[Object], shape: &Shape, transform: &Mat4, direction: Vec3, max_distance: f32, filter: &QueryFilter) -> Option<CastHit> {
+	shape_cast_in(objects, None, shape, transform, direction, max_distance, filter)
+}
+
+pub fn shape_cast_in(objects: &[Object], tree: Option<&BoundsTree>, shape: &Shape, transform: &Mat4, direction: Vec3, max_distance: f32, filter: &QueryFilter) -> Option<CastHit> {
+	if direction.length_squared() < 1e-12 { return None; }
+	let caster = shape.world_convex(transform)?;
+	let direction = direction.normalize();
+	let start = caster.aabb();
+	let (center, half_size) = ((start.min + start.max) * 0.5, (start.max - start.min) * 0.5);
+	
+	let mut best: Option<CastHit> = None;
+	for (entry, i, bounds) in candidates(objects, tree, center, direction, half_size, max_distance, filter) {
+		if best.is_some_and(|hit| hit.distance <= entry) { break; }
+		let object = &objects[i];
+		let reach = cast_reach(center, half_size, &bounds, max_distance);
+		let motion = direction * reach;
+		let swept = start.union(Aabb { min: start.min + motion, max: start.max + motion });
+		
+		let hit = match object.shape.world_convex(&object.transform) {
+			Some(target) => cast_convex(&caster, motion, &target).map(|(t, point, normal)| (t, point, normal, None)),
+			None => {
+				let mut nearest: Option<(f32, Vec3, Vec3, Option<usize>)> = None;
+				for (l, a, b, c) in world_triangles(object, &swept) {
+					let triangle = Convex { points: vec![a, b, c], radius: 0.0 };
+					if !Aabb::from_points(&triangle.points).overlaps(&swept) { continue; }
+					if let Some((t, point, normal)) = cast_convex(&caster, motion, &triangle) {
+						if nearest.is_none_or(|n| t < n.0) { nearest = Some((t, point, normal, Some(l))); }
+					}
+				}
+				nearest
+			}
+		};
+		
+		if let Some((t, point, normal, triangle)) = hit {
+			if best.is_none_or(|b| t * reach < b.distance) {
+				best = Some(CastHit { body: i, point, normal, distance: t * reach, triangle });
+			}
+		}
+	}
+	best
+}
+
+// The bodies whose bounds, grown by half_size, the ray from origin along the normalized direction enters
+// within max_distance, with where it enters them and their bounds, nearest first.
+fn candidates(objects: &[Object], tree: Option<&BoundsTree>, origin: Vec3, direction: Vec3, half_size: Vec3, max_distance: f32, filter: &QueryFilter) -> Vec<(f32, usize, Aabb)> {
+	let enters = |bounds: &Aabb| ray_aabb(origin, direction, &Aabb { min: bounds.min - half_size, max: bounds.max + half_size }).filter(|&t| t <= max_distance);
+	let bodies = match tree {
+		Some(tree) => tree.bodies_where(|bounds| enters(bounds).is_some()),
+		None => objects.iter().map(Object::world_aabb).enumerate().collect()
+	};
+	let mut candidates = bodies.into_iter()
+		.filter(|&(i, _)| filter.accepts(i, &objects[i]))
+		.filter_map(|(i, bounds)| enters(&bounds).map(|t| (t, i, bounds)))
+		.collect::<Vec<(f32, usize, Aabb)>>();
+	candidates.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+	candidates
+}
+
+// How far to cast at a body, no further than past the far side of its bounds grown by half_size. Casting
+// convex shapes works on the whole motion, which an unlimited max_distance would fill with infinities.
+fn cast_reach(origin: Vec3, half_size: Vec3, bounds: &Aabb, max_distance: f32) -> f32 {
+	let far = (origin - (bounds.min + bounds.max) * 0.5).length() + ((bounds.max - bounds.min) * 0.5 + half_size).length();
+	max_distance.min(far)
+}
+
+// bodies containing the point, meshes count as solid if they are closed and heightfields below their surface
+pub fn point_overlaps(objects: &[Object], point: Vec3, filter: &QueryFilter) -> Vec<usize> {
+	point_overlaps_in(objects, None, point, filter)
+}
+
+pub fn point_overlaps_in(objects: &[Object], tree: Option<&BoundsTree>, point: Vec3, filter: &QueryFilter) -> Vec<usize> {
+	let mut bodies = match tree {
+		Some(tree) => tree.bodies_where(|bounds| bounds.overlaps(&Aabb { min: point, max: point })).into_iter().map(|(i, _)| i).collect(),
+		None => (0..objects.len()).collect::<Vec<usize>>()
+	};
+	bodies.retain(|&i| filter.accepts(i, &objects[i]) && contains_point(&objects[i], point));
+	bodies.sort();
+	bodies
+}
+
+pub(crate) fn contains_point(object: &Object, point: Vec3) -> bool {
+	if !object.world_aabb().overlaps(&Aabb { min: point, max: point }) { return false; }
+	
+	match object.shape.world_convex(&object.transform) {
+		Some(convex) => match narrow_phase::closest_points(&Convex { points: vec![point], radius: 0.0 }, &convex) {
+			Some((p, q)) => (p - q).length() <= convex.radius,
+			None => true
+		},
+		None => match &object.shape {
+			// under the surface counts as inside
+			Shape::Heightfield { field } => {
+				let rotation = object.transform.get_rotation().transpose();
+				let local = rotation.mult_vec3(point - object.transform.get_position());
+				field.height_at(local.0, local.2).is_some_and(|height| local.1 <= height)
+			}
+			// inside a closed mesh a ray leaves through an odd number of triangles
+			_ => {
+				let direction = Vec3(0.5377, 0.6412, 0.5473).normalize();
+				let vertices = object.vertices.iter().map(|v| v.apply_transform(&object.transform)).collect::<Vec<Vec3>>();
+				let crossings = object.indices.iter().filter(|&&(a, b, c)| ray_triangle(point, direction, vertices[a as usize], vertices[b as usize], vertices[c as usize]).is_some()).count();
+				crossings % 2 == 1
+			}
+		}
+	}
+}
+
+
+fn ray_body(object: &Object, index: usize, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<CastHit> {
+	match (object.shape.world_convex(&object.transform), &object.shape) {
+		(Some(target), _) => {
+			let caster = Convex { points: vec![origin], radius: 0.0 };
+			let reach = cast_reach(origin, Vec3(0.0, 0.0, 0.0), &target.aabb(), max_distance);
+			let (t, point, normal) = cast_convex(&caster, direction * reach, &target)?;
+			Some(CastHit { body: index, point, normal, distance: t * reach, triangle: None })
+		}
+		// walked cell by cell rather than testing every triangle
+		(None, Shape::Heightfield { field }) => {
+			let rotation = object.transform.get_rotation().transpose();
+			let local_origin = rotation.mult_vec3(origin - object.transform.get_position());
+			let (t, l) = field.ray_cast(local_origin, rotation.mult_vec3(direction), max_distance)?;
+			let (a, b, c) = field.triangle(l);
+			let normal = (c - a).cross(b - a).apply_rotation(&object.transform).normalize();
+			let normal = match normal.dot(direction) > 0.0 { true => -normal, false => normal };
+			Some(CastHit { body: index, point: origin + direction * t, normal, distance: t, triangle: Some(l) })
+		}
+		(None, _) => {
+			let vertices = object.vertices.iter().map(|v| v.apply_transform(&object.transform)).collect::<Vec<Vec3>>();
+			let mut best: Option<CastHit> = None;
+			for (l, &(a, b, c)) in object.indices.iter().enumerate() {
+				let (a, b, c) = (vertices[a as usize], vertices[b as usize], vertices[c as usize]);
+				let t = match ray_triangle(origin, direction, a, b, c) {
+					Some(t) if t <= best.map_or(max_distance, |hit| hit.distance) => t,
+					_ => continue
+				};
+				
+				// triangles are hit from either side, the normal always faces the ray
+				let normal = (c - a).cross(b - a).normalize();
+				let normal = match normal.dot(direction) > 0.0 { true => -normal, false => normal };
+				best = Some(CastHit { body: index, point: origin + direction * t, normal, distance: t, triangle: Some(l) });
+			}
+			best
+		}
+	}
+}
+
+
+// The collision triangles of a mesh or heightfield that might touch the bounds, in world space.
+pub(crate) fn world_triangles(object: &Object, bounds: &Aabb) -> Vec<(usize, Vec3, Vec3, Vec3)> {
+	match &object.shape {
+		Shape::Heightfield { field } => field.world_triangles_in(&object.transform, bounds),
+		_ => {
+			let vertices = object.vertices.iter().map(|v| v.apply_transform(&object.transform)).collect::<Vec<Vec3>>();
+			object.indices.iter().enumerate().map(|(l, &(a, b, c))| (l, vertices[a as usize], vertices[b as usize], vertices[c as usize])).collect()
+		}
+	}
+}
+
+
+// Moller-Trumbore, distance along the ray to where it crosses the triangle
+pub(crate) fn ray_triangle(origin: Vec3, direction: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+	let (e1, e2) = (b - a, c - a);
+	let p = direction.cross(e2);
+	let determinant = e1.dot(p);
+	if determinant.abs() < 1e-12 { return None; }
+	
+	let s = origin - a;
+	let u = s.dot(p) / determinant;
+	if !(0.0..=1.0).contains(&u) { return None; }
+	let q = s.cross(e1);
+	let v = direction.dot(q) / determinant;
+	if v < 0.0 || u + v > 1.0 { return None; }
+	
+	let t = e2.dot(q) / determinant;
+	match t >= 0.0 {
+		true => Some(t),
+		false => None
+	}
+}
+
+// distance along the ray to where it enters the box, zero if it starts inside
+fn ray_aabb(origin: Vec3, direction: Vec3, aabb: &Aabb) -> Option<f32> {
+	let (mut near, mut far) = (0.0f32, f32::INFINITY);
+	for (o, d, min, max) in [(origin.0, direction.0, aabb.min.0, aabb.max.0), (origin.1, direction.1, aabb.min.1, aabb.max.1), (origin.2, direction.2, aabb.min.2, aabb.max.2)] {
+		if d.abs() < 1e-12 {
+			if o < min || o > max { return None; }
+			continue;
+		}
+		let (t0, t1) = ((min - o) / d, (max - o) / d);
+		near = near.max(t0.min(t1));
+		far = far.min(t0.max(t1));
+	}
+	match near <= far {
+		true => Some(near),
+		false => None
+	}
+}
+
+// Conservative advancement: steps the caster forward by the gap divided by how fast it closes along the
+// separating direction, which never passes through a convex target. Returns the fraction of the motion,
+// the point on the target and its normal.
+fn cast_convex(caster: &Convex, motion: Vec3, target: &Convex) -> Option<(f32, Vec3, Vec3)> {
+	let radius = caster.radius + target.radius;
+	let mut t = 0.0;
+	
+	for _ in 0..CAST_ITERATIONS {
+		let moved = Convex { points: caster.points.iter().map(|&p| p + motion * t).collect(), radius: caster.radius };
+		let (point_a, point_b) = match narrow_phase::closest_points(&moved, target) {
+			Some(points) => points,
+			None => return Some((t, moved.center(), -motion.normalize()))
+		};
+		
+		let offset = point_a - point_b;
+		let distance = offset.length();
+		if distance - radius <= CAST_TOLERANCE {
+			// the direction between nearly touching cores is mostly rounding error, so take it from a little way back
+			let normal = match distance > 1e-3 {
+				true => offset / distance,
+				false => {
+					let back = f32::max(t - 0.01 / motion.length(), 0.0);
+					let moved_back = Convex { points: caster.points.iter().map(|&p| p + motion * back).collect(), radius: caster.radius };
+					match narrow_phase::closest_points(&moved_back, target) {
+						Some((a, b)) if (a - b).length() > 1e-3 => (a - b).normalize(),
+						_ if distance > 1e-6 => offset / distance,
+						_ => -motion.normalize()
+					}
+				}
+			};
+			return Some((t, point_b + normal * target.radius, normal));
+		}
+		
+		let normal = offset / distance;
+		let closing = -motion.dot(normal);
+		if closing <= 0.0 { return None; }
+		// stopping just short keeps the shapes from touching exactly, where gjk can't give closest points
+		t += (distance - radius - 0.5 * CAST_TOLERANCE) / closing;
+		if t > 1.0 { return None; }
+	}
+	None
+}
@@ -1,11 +1,14 @@
-use glium::{framebuffer::{MultiOutputFrameBuffer, SimpleFrameBuffer}, index::PrimitiveType, texture::{DepthTexture2d, RawImage2d, SrgbTexture2d}, uniform, uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerBehavior, SamplerWrapFunction}, BackfaceCullingMode, Depth, DepthTest, Display, DrawParameters, IndexBuffer, Program, Surface, Texture2d, VertexBuffer};
+use glium::{framebuffer::{MultiOutputFrameBuffer, SimpleFrameBuffer}, implement_vertex, index::{NoIndices, PrimitiveType}, texture::{CubeLayer, DepthCubemap, DepthTexture2d, DepthTexture2dArray, RawImage2d, SrgbTexture2d}, uniform, uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerBehavior, SamplerWrapFunction, UniformValue, Uniforms}, vertex::Attribute, backend::Facade, draw_parameters::DepthClamp, BackfaceCullingMode, Depth, DepthTest, DrawParameters, IndexBuffer, Program, Surface, Texture2d, Vertex, VertexBuffer, VertexFormat};
 
-use crate::{math_structs::{Mat4, Vec2, Vec3}, object::Object, Camera};
+use crate::{debug::DebugFrame, light::{self, Light, LightKind, ShadowSlot, MAX_POINT_SHADOWS, MAX_SPOT_SHADOWS}, math_structs::{Mat4, Vec2, Vec3}, object::Object, particles::Particle, physics::Aabb};
 
 
 static POST_VERTEX_BUFFER: [Vec2; 4] = [Vec2(-1.0, -1.0), Vec2(1.0, -1.0), Vec2(1.0, 1.0), Vec2(-1.0, 1.0)];
 static POST_INDEX_BUFFER: [u16; 6] = [0, 1, 2, 0, 2, 3];
 
+// the size of the cascade arrays in the shaders
+pub static MAX_SHADOW_CASCADES: usize = 4;
+
 static POST_VERTEX_SHADER: &str = "#version 150
 in vec2 position;
 out vec2 screen_position;
@@ -30,16 +33,150 @@ void main() {
 	gl_Position = shadowmap_transform * (model_transform * vec4(position, 1.0));
 }";
 
+static LOCAL_SHADOWMAP_VERTEX_SHADER: &str = "#version 150
+in vec3 position;
+out vec3 world_position;
+uniform mat4 shadowmap_transform;
+uniform mat4 model_transform;
+void main() {
+	world_position = vec3(model_transform * vec4(position, 1.0));
+	gl_Position = shadowmap_transform * vec4(world_position, 1.0);
+}";
+
+// the distance from the light rather than the projected depth, so the faces of a cube map all agree
+static LOCAL_SHADOWMAP_FRAG_SHADER: &str = "#version 150
+in vec3 world_position;
+uniform vec3 light_position;
+uniform float light_range;
+void main() {
+	gl_FragDepth = min(length(world_position - light_position) / light_range, 1.0);
+}";
+
+static DEBUG_VERTEX_SHADER: &str = "#version 150
+in vec3 position;
+in vec3 color;
+out vec3 line_color;
+uniform mat4 camera_transform;
+uniform mat4 perspective_matrix;
+void main() {
+	line_color = color;
+	gl_Position = perspective_matrix * (camera_transform * vec4(position, 1.0));
+}";
+
+static DEBUG_FRAG_SHADER: &str = "#version 150
+in vec3 line_color;
+out vec4 color;
+void main() {
+	color = vec4(line_color, 1.0);
+}";
+
 
 
+impl Vertex for Vec2 {
+	fn build_bindings() -> VertexFormat {
+		std::borrow::Cow::Owned(vec![(std::borrow::Cow::Borrowed("position"), 0, -1, <(f32, f32)>::get_type(), false)])
+	}
+}
+
+impl Vertex for Vec3 {
+	fn build_bindings() -> VertexFormat {
+		std::borrow::Cow::Owned(vec![(std::borrow::Cow::Borrowed("position"), 0, -1, <(f32, f32, f32)>::get_type(), false)])
+	}
+}
+
+#[derive(Copy, Clone)]
+pub struct DebugVertex {
+	pub position: [f32; 3],
+	pub color: [f32; 3]
+}
+
+implement_vertex!(DebugVertex, position, color);
+
+#[derive(Copy, Clone)]
+pub struct ParticleVertex {
+	pub center: [f32; 3],
+	pub corner: [f32; 2],
+	pub size: f32,
+	pub color: [f32; 3]
+}
+
+implement_vertex!(ParticleVertex, center, corner, size, color);
+
+
+pub struct Camera {
+	pub position: Vec3,
+	pub horizontal_angle: f32,
+	pub vertical_angle: f32
+}
+
+impl Camera {
+	pub fn get_transform(&self) -> Mat4 {
+		Mat4::identity().translate(-self.position).rotate_y(self.horizontal_angle).rotate_x(self.vertical_angle)
+	}
+}
+
+
+// One orthographic view from the light, covering the slice of the camera's view between the previous
+// cascade's split_distance and its own. Fitted around the sphere that bounds the slice, so it keeps its size
+// as the camera turns, and moved in whole texels, so the shadow edges don't crawl as the camera moves.
+pub struct ShadowCascade {
+	pub split_distance: f32,
+	pub radius: f32,
+	pub transform: Mat4
+}
+
+// How the edge of a shadow is sampled, cheapest first.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ShadowFilter {
+	Hard, // one bilinear comparison, a texel wide edge
+	#[default]
+	Kernel, // sixteen taps in a disc of filter_radius texels, four where they agree
+	Noise, // one tap picked at random from the disc per pixel, grainy but as cheap as Hard
+	Pcss // percentage closer soft shadows, the edge widens with the distance from what casts it as light_angle says
+}
+
+// The view out to max_distance is split between the cascades, evenly with a split_lambda of 0 and growing
+// geometrically with 1, and over the last blend_fraction of each cascade the shadows fade into the next one's.
+// Past max_distance nothing is shadowed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShadowConfig {
+	pub resolution: u32, // of each cascade, both ways
+	pub cascade_count: usize,
+	pub max_distance: f32,
+	pub split_lambda: f32,
+	pub blend_fraction: f32,
+	pub bias_factor: f32, // in texels, which are bigger the further out the cascade is
+	pub filter: ShadowFilter,
+	pub filter_radius: f32, // in texels
+	pub light_angle: f32, // the light's angular radius, in radians
+	pub local_resolution: u32 // of each face of a point light's cube map and of each spot light's map
+}
+
+// Cascaded shadow maps for the sun, one layer of texture per cascade, and the maps of the point and spot lights.
+// The config is only changed through set_config so the textures always have the resolution and layers it says.
 pub struct ShadowMap {
-	pub resolution: (u32, u32),
-	pub size: (f32, f32),
-	pub near_distance: f32,
-	pub far_distance: f32,
-	pub bias_factor: f32,
-	pub transform: Mat4,
-	pub texture: DepthTexture2d
+	config: ShadowConfig,
+	pub show_cascades: bool,
+	pub cascades: Vec<ShadowCascade>,
+	pub texture: DepthTexture2dArray,
+	pub point_textures: Vec<DepthCubemap>, // MAX_POINT_SHADOWS of them
+	pub spot_texture: DepthTexture2dArray, // MAX_SPOT_SHADOWS layers
+	pub spot_transforms: Vec<Mat4> // of the spot lights with a layer this frame, in layer order
+}
+
+// the per cascade uniforms, which uniform! can't name since there are as many as there are cascades
+struct CascadeUniforms<'a, U: Uniforms> {
+	uniforms: U,
+	shadowmap: &'a ShadowMap
+}
+
+// the lights picked for one object and where their shadows are
+struct LightUniforms<'a, U: Uniforms> {
+	uniforms: U,
+	lights: &'a [Light],
+	selected: Vec<usize>,
+	slots: &'a [ShadowSlot],
+	shadowmap: &'a ShadowMap
 }
 
 pub struct Renderer {
@@ -47,7 +184,10 @@ pub struct Renderer {
 	pub post_program: Program,
 	pub post_program_none: Program,
 	pub shadowmap_program: Program,
+	pub local_shadowmap_program: Program,
 	pub shadowmap_render_program: Program,
+	pub debug_program: Program,
+	pub particle_program: Program,
 	pub post_vertex_buffer: VertexBuffer<Vec2>,
 	pub post_index_buffer: IndexBuffer<u16>,
 	pub main_buffer: SrgbTexture2d,
@@ -55,6 +195,7 @@ pub struct Renderer {
 	pub depth_buffer: DepthTexture2d,
 	pub shadowmap: ShadowMap,
 	pub bayer_texture: Texture2d,
+	#[allow(dead_code)]
 	pub fov: f32,
 	pub f: f32,
 	pub z_far: f32,
@@ -62,41 +203,206 @@ pub struct Renderer {
 }
 
 
-impl ShadowMap {
-	pub fn set_up_transform(&mut self, light_direction: Vec3) {
-		self.transform = Mat4::identity()
-			.translate(Vec3(0.0, 0.0, 0.0))
+// Where the view from near to far is split into count cascades, the far end of each.
+// Blends the even split with the geometric one by lambda, the geometric one alone crowds everything up close.
+pub fn split_distances(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+	(1..=count).map(|i| {
+		let t = i as f32 / count as f32;
+		lambda * near * (far / near).powf(t) + (1.0 - lambda) * (near + (far - near) * t)
+	}).collect()
+}
+
+
+impl ShadowCascade {
+	// f and aspect_ratio as in the perspective matrix, the view's slope up and the ratio of height to width
+	pub fn fit(camera: &Camera, light_direction: Vec3, near: f32, far: f32, f: f32, aspect_ratio: f32, resolution: u32) -> Self {
+		// the smallest sphere through the slice's corners is centered on the view axis
+		let corner_slope_squared = (1.0 + 1.0 / (aspect_ratio * aspect_ratio)) / (f * f);
+		let center_distance = (0.5 * (near + far) * (1.0 + corner_slope_squared)).min(far);
+		let radius = ((far - center_distance).powi(2) + far * far * corner_slope_squared).sqrt();
+		
+		let camera_transform = camera.get_transform();
+		let forward = Vec3(camera_transform.0[0][2], camera_transform.0[1][2], camera_transform.0[2][2]);
+		let center = camera.position + forward * center_distance;
+		
+		// the light shines down +z
+		let light_rotation = Mat4::identity()
 			.rotate_y(f32::atan2(light_direction.0, -light_direction.2))
-			.rotate_x(f32::asin(-light_direction.1))
-			.translate(Vec3(0.0, 0.0, -(self.far_distance + self.near_distance)))
-			.scale_xyz(1.0 / self.size.0, 1.0 / self.size.1, 1.0 / (self.far_distance - self.near_distance))
-			;
+			.rotate_x(f32::asin(-light_direction.1));
+		let Vec3(x, y, z) = center.apply_rotation(&light_rotation);
+		let texel = 2.0 * radius / resolution as f32;
+		let snapped = Vec3((x / texel).floor() * texel, (y / texel).floor() * texel, z);
+		
+		Self {
+			split_distance: far,
+			radius,
+			transform: light_rotation.translate(-snapped).scale(1.0 / radius)
+		}
+	}
+}
+
+
+impl Default for ShadowConfig {
+	fn default() -> Self {
+		Self {
+			resolution: 2048,
+			cascade_count: 4,
+			max_distance: 60.0,
+			split_lambda: 0.6,
+			blend_fraction: 0.1,
+			bias_factor: 1.0,
+			filter: ShadowFilter::default(),
+			filter_radius: 1.5,
+			light_angle: 0.03,
+			local_resolution: 512
+		}
+	}
+}
+
+
+impl ShadowMap {
+	pub fn new(display: &impl Facade, config: ShadowConfig) -> Self {
+		Self {
+			config,
+			show_cascades: false,
+			cascades: Vec::new(),
+			texture: Self::create_texture(display, &config),
+			point_textures: Self::create_point_textures(display, &config),
+			spot_texture: Self::create_spot_texture(display, &config),
+			spot_transforms: Vec::new()
+		}
+	}
+	
+	fn create_texture(display: &impl Facade, config: &ShadowConfig) -> DepthTexture2dArray {
+		assert!(config.cascade_count > 0 && config.cascade_count <= MAX_SHADOW_CASCADES, "{} shadow cascades, there can be 1 to {}", config.cascade_count, MAX_SHADOW_CASCADES);
+		DepthTexture2dArray::empty(display, config.resolution, config.resolution, config.cascade_count as u32).unwrap()
+	}
+	
+	fn create_point_textures(display: &impl Facade, config: &ShadowConfig) -> Vec<DepthCubemap> {
+		(0..MAX_POINT_SHADOWS).map(|_| DepthCubemap::empty(display, config.local_resolution).unwrap()).collect()
+	}
+	
+	fn create_spot_texture(display: &impl Facade, config: &ShadowConfig) -> DepthTexture2dArray {
+		DepthTexture2dArray::empty(display, config.local_resolution, config.local_resolution, MAX_SPOT_SHADOWS as u32).unwrap()
+	}
+	
+	pub fn config(&self) -> &ShadowConfig {
+		&self.config
+	}
+	
+	// Only makes new textures when the resolution or number of cascades changed.
+	pub fn set_config(&mut self, display: &impl Facade, config: ShadowConfig) {
+		if (config.resolution, config.cascade_count) != (self.config.resolution, self.config.cascade_count) {
+			self.texture = Self::create_texture(display, &config);
+		}
+		if config.local_resolution != self.config.local_resolution {
+			self.point_textures = Self::create_point_textures(display, &config);
+			self.spot_texture = Self::create_spot_texture(display, &config);
+		}
+		self.config = config;
+	}
+	
+	pub fn set_up_transforms(&mut self, camera: &Camera, light_direction: Vec3, z_near: f32, f: f32, aspect_ratio: f32) {
+		let config = &self.config;
+		let splits = split_distances(z_near, config.max_distance, config.cascade_count, config.split_lambda);
+		let nears = std::iter::once(z_near).chain(splits.iter().copied());
+		self.cascades = nears.zip(splits.iter()).map(|(near, &far)| ShadowCascade::fit(camera, light_direction, near, far, f, aspect_ratio, config.resolution)).collect();
+	}
+	
+	// The bias in depth. A texel's worth of depth is the same in every cascade, the depth range grows with the texels.
+	fn tolerance(&self) -> f32 {
+		self.config.bias_factor / self.config.resolution as f32
+	}
+	
+	// The same for the point and spot maps, of the distance over the light's range. Their texels
+	// grow with the distance, so it is a bit more than a texel's worth where the light reaches furthest.
+	fn local_tolerance(&self) -> f32 {
+		2.0 * self.config.bias_factor / self.config.local_resolution as f32
+	}
+}
+
+impl<U: Uniforms> Uniforms for CascadeUniforms<'_, U> {
+	fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+		self.uniforms.visit_values(&mut output);
+		output("cascade_count", UniformValue::SignedInt(self.shadowmap.cascades.len() as i32));
+		output("cascade_blend", UniformValue::Float(self.shadowmap.config.blend_fraction));
+		output("show_cascades", UniformValue::Bool(self.shadowmap.show_cascades));
+		output("shadowmap_resolution", UniformValue::Float(self.shadowmap.config.resolution as f32));
+		output("shadowmap_tolerance", UniformValue::Float(self.shadowmap.tolerance()));
+		output("shadow_filter", UniformValue::SignedInt(self.shadowmap.config.filter as i32));
+		output("shadow_filter_radius", UniformValue::Float(self.shadowmap.config.filter_radius));
+		output("shadow_penumbra_slope", UniformValue::Float(self.shadowmap.config.light_angle.tan()));
+		for (i, cascade) in self.shadowmap.cascades.iter().enumerate() {
+			output(&format!("cascade_transforms[{}]", i), UniformValue::Mat4(cascade.transform.0));
+			output(&format!("cascade_splits[{}]", i), UniformValue::Float(cascade.split_distance));
+		}
+	}
+}
+
+
+impl<U: Uniforms> Uniforms for LightUniforms<'_, U> {
+	fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+		self.uniforms.visit_values(&mut output);
+		output("light_count", UniformValue::SignedInt(self.selected.len() as i32));
+		for (i, &index) in self.selected.iter().enumerate() {
+			let light = &self.lights[index];
+			let (light_type, position, direction, cone) = match light.kind {
+				LightKind::Directional { direction } => (0, Vec3(0.0, 0.0, 0.0), direction.normalize(), (1.0, 1.0)),
+				LightKind::Point { position } => (1, position, Vec3(0.0, 0.0, 0.0), (1.0, 1.0)),
+				LightKind::Spot { position, direction, inner_angle, outer_angle } => (2, position, direction.normalize(), (inner_angle.cos(), outer_angle.cos()))
+			};
+			let shadow = match self.slots[index] {
+				ShadowSlot::None => -1,
+				ShadowSlot::Cascades => 0,
+				ShadowSlot::Cube(slot) | ShadowSlot::Spot(slot) => slot as i32
+			};
+			let color = light.color * light.intensity;
+			output(&format!("light_types[{}]", i), UniformValue::SignedInt(light_type));
+			output(&format!("light_positions[{}]", i), UniformValue::Vec3([position.0, position.1, position.2]));
+			output(&format!("light_directions[{}]", i), UniformValue::Vec3([direction.0, direction.1, direction.2]));
+			output(&format!("light_colors[{}]", i), UniformValue::Vec3([color.0, color.1, color.2]));
+			output(&format!("light_ranges[{}]", i), UniformValue::Float(light.range));
+			output(&format!("light_attenuations[{}]", i), UniformValue::Vec3([light.attenuation.0, light.attenuation.1, light.attenuation.2]));
+			output(&format!("light_cones[{}]", i), UniformValue::Vec2([cone.0, cone.1]));
+			output(&format!("light_shadows[{}]", i), UniformValue::SignedInt(shadow));
+		}
+		
+		// every sampler gets its texture whether a light uses it or not, an unbound one would clash with the cascades' unit
+		let comparison = SamplerBehavior {
+			minify_filter: MinifySamplerFilter::Linear,
+			magnify_filter: MagnifySamplerFilter::Linear,
+			depth_texture_comparison: Some(glium::uniforms::DepthTextureComparison::Greater),
+			.. Default::default()
+		};
+		for (i, texture) in self.shadowmap.point_textures.iter().enumerate() {
+			output(&format!("point_shadowmaps[{}]", i), UniformValue::DepthCubemap(texture, Some(comparison)));
+		}
+		output("spot_shadowmap", UniformValue::DepthTexture2dArray(&self.shadowmap.spot_texture, Some(comparison)));
+		for (i, transform) in self.shadowmap.spot_transforms.iter().enumerate() {
+			output(&format!("spot_shadow_transforms[{}]", i), UniformValue::Mat4(transform.0));
+		}
+		output("local_shadow_tolerance", UniformValue::Float(self.shadowmap.local_tolerance()));
 	}
 }
 
 
 impl Renderer {
-	pub fn new(display: &Display, width: u32, height: u32, fov: f32, z_near: f32, z_far: f32) -> Self {
+	pub fn new(display: &impl Facade, width: u32, height: u32, fov: f32, z_near: f32, z_far: f32) -> Self {
 		Self {
 			main_program: Program::from_source(display, include_str!("shaders/main.vert"), include_str!("shaders/main.frag"), None).unwrap(),
-			post_program: Program::from_source(display, &POST_VERTEX_SHADER, include_str!("shaders/post_effects.frag"), None).unwrap(),
-			post_program_none: Program::from_source(display, &POST_VERTEX_SHADER, &DEFAULT_FRAG_SHADER, None).unwrap(),
-			shadowmap_program: Program::from_source(display, &SHADOWMAP_VERTEX_SHADER, "#version 150\nvoid main() {}", None).unwrap(),
-			shadowmap_render_program: Program::from_source(display, &POST_VERTEX_SHADER, include_str!("shaders/shadowmap_render.frag"), None).unwrap(),
+			post_program: Program::from_source(display, POST_VERTEX_SHADER, include_str!("shaders/post_effects.frag"), None).unwrap(),
+			post_program_none: Program::from_source(display, POST_VERTEX_SHADER, DEFAULT_FRAG_SHADER, None).unwrap(),
+			shadowmap_program: Program::from_source(display, SHADOWMAP_VERTEX_SHADER, "#version 150\nvoid main() {}", None).unwrap(),
+			local_shadowmap_program: Program::from_source(display, LOCAL_SHADOWMAP_VERTEX_SHADER, LOCAL_SHADOWMAP_FRAG_SHADER, None).unwrap(),
+			shadowmap_render_program: Program::from_source(display, POST_VERTEX_SHADER, include_str!("shaders/shadowmap_render.frag"), None).unwrap(),
+			debug_program: Program::from_source(display, DEBUG_VERTEX_SHADER, DEBUG_FRAG_SHADER, None).unwrap(),
+			particle_program: Program::from_source(display, include_str!("shaders/particle.vert"), include_str!("shaders/particle.frag"), None).unwrap(),
 			post_vertex_buffer: VertexBuffer::new(display, &POST_VERTEX_BUFFER).unwrap(),
 			post_index_buffer: IndexBuffer::new(display, PrimitiveType::TrianglesList, &POST_INDEX_BUFFER).unwrap(),
 			main_buffer: SrgbTexture2d::empty(display, width, height).unwrap(),
 			normals_buffer: SrgbTexture2d::empty(display, width, height).unwrap(),
 			depth_buffer: DepthTexture2d::empty(display, width, height).unwrap(),
-			shadowmap: ShadowMap {
-				resolution: (1024, 1024),
-				size: (10.0, 10.0),
-				near_distance: -10.0,
-				far_distance: 10.0,
-				bias_factor: 0.01,
-				transform: Mat4::identity(),
-				texture: DepthTexture2d::empty(display, 4096, 4096).unwrap()
-			},
+			shadowmap: ShadowMap::new(display, ShadowConfig::default()),
 			bayer_texture: {
 				let img_buffer = image::load_from_memory_with_format(include_bytes!("bayer16.png"), image::ImageFormat::Png).unwrap().to_rgba8();
 				let dimensions = img_buffer.dimensions();
@@ -109,86 +415,185 @@ impl Renderer {
 		}
 	}
 	
-	pub fn resize(&mut self, display: &Display, width: u32, height: u32) {
+	pub fn resize(&mut self, display: &impl Facade, width: u32, height: u32) {
 		self.main_buffer = SrgbTexture2d::empty(display, width, height).unwrap();
 		self.normals_buffer = SrgbTexture2d::empty(display, width, height).unwrap();
 		self.depth_buffer = DepthTexture2d::empty(display, width, height).unwrap();
 	}
 	
 	
-	pub fn render(&mut self, display: &Display, camera: &Camera, objects: &[Object], vertex_buffers: &[VertexBuffer<Vec3>], index_buffers: &[IndexBuffer<u16>], do_post_process: bool, show_shadowmap: bool, dummy: f32) {
-		
-		let light_direction = Vec3(f32::cos(dummy), 2.0, f32::sin(dummy)).normalize();
-		self.shadowmap.set_up_transform(light_direction);
-		
-		let mut target = SimpleFrameBuffer::depth_only(display, &self.shadowmap.texture).unwrap();
-		target.clear_depth(1.0);
-		for i in 0..objects.len() {
-			target.draw(&vertex_buffers[i], &index_buffers[i], &self.shadowmap_program, &uniform! {
-				shadowmap_transform: self.shadowmap.transform.0,
-				model_transform: objects[i].transform.0
-			}, &DrawParameters {
-				depth: Depth {
-					test: DepthTest::IfLess,
-					write: true,
+	#[allow(clippy::too_many_arguments)]
+	pub fn render(&mut self, display: &impl Facade, target: &mut impl Surface, camera: &Camera, objects: &[Object], vertex_buffers: &[VertexBuffer<Vec3>], index_buffers: &[IndexBuffer<u32>], soft_vertex_buffers: &[VertexBuffer<Vec3>], soft_index_buffers: &[IndexBuffer<u32>], particles: &[Particle], debug_frame: Option<&DebugFrame>, do_post_process: bool, show_shadowmap: bool, lights: &[Light]) {
+		
+		let (width, height) = self.main_buffer.dimensions();
+		let aspect_ratio = height as f32 / width as f32;
+		
+		// the cascades follow the first directional light that casts shadows
+		let slots = light::assign_shadow_slots(lights);
+		match lights.iter().zip(slots.iter()).find(|(_, &slot)| slot == ShadowSlot::Cascades) {
+			Some((&Light { kind: LightKind::Directional { direction }, .. }, _)) => self.shadowmap.set_up_transforms(camera, direction.normalize(), self.z_near, self.f, aspect_ratio),
+			_ => self.shadowmap.cascades.clear()
+		}
+		self.shadowmap.spot_transforms = lights.iter().zip(slots.iter()).filter_map(|(light, slot)| match (light.kind, slot) {
+			(LightKind::Spot { position, direction, outer_angle, .. }, ShadowSlot::Spot(_)) => Some(light::spot_shadow_transform(position, direction, outer_angle)),
+			_ => None
+		}).collect();
+		
+		// soft bodies are already in world space, and cloth has no back to cull
+		let rigid_meshes = (0..objects.len()).map(|i| (&vertex_buffers[i], &index_buffers[i], objects[i].transform, true));
+		let soft_meshes = (0..soft_vertex_buffers.len()).map(|i| (&soft_vertex_buffers[i], &soft_index_buffers[i], Mat4::identity(), false));
+		let meshes = rigid_meshes.chain(soft_meshes).collect::<Vec<(&VertexBuffer<Vec3>, &IndexBuffer<u32>, Mat4, bool)>>();
+		// soft meshes have no bounds on this side, they get the first lights in the list
+		let bounds = objects.iter().map(|object| Some(object.world_aabb())).chain(std::iter::repeat(None)).take(meshes.len()).collect::<Vec<_>>();
+		
+		for (i, cascade) in self.shadowmap.cascades.iter().enumerate() {
+			let layer = self.shadowmap.texture.layer(i as u32).unwrap().main_level();
+			let mut shadowmap_target = SimpleFrameBuffer::depth_only(display, layer).unwrap();
+			shadowmap_target.clear_depth(1.0);
+			for &(vertex_buffer, index_buffer, model_transform, cull_backfaces) in meshes.iter() {
+				shadowmap_target.draw(vertex_buffer, index_buffer, &self.shadowmap_program, &uniform! {
+					shadowmap_transform: cascade.transform.0,
+					model_transform: model_transform.0
+				}, &DrawParameters {
+					depth: Depth {
+						test: DepthTest::IfLess,
+						write: true,
+						// casters between the light and the cascade get flattened onto its near side instead of clipped
+						clamp: DepthClamp::Clamp,
+						.. Default::default()
+					},
+					backface_culling: match cull_backfaces {
+						true => BackfaceCullingMode::CullClockwise,
+						false => BackfaceCullingMode::CullingDisabled
+					},
 					.. Default::default()
-				},
-				backface_culling: BackfaceCullingMode::CullClockwise,
+				}).unwrap();
+			}
+		}
+		
+		// point and spot shadows see all around, so there are no back faces to leave out
+		let local_parameters = DrawParameters {
+			depth: Depth {
+				test: DepthTest::IfLess,
+				write: true,
 				.. Default::default()
-			}).unwrap();
+			},
+			.. Default::default()
+		};
+		for (light, &slot) in lights.iter().zip(slots.iter()) {
+			let (position, targets) = match (light.kind, slot) {
+				(LightKind::Point { position }, ShadowSlot::Cube(slot)) => {
+					let texture = &self.shadowmap.point_textures[slot];
+					let faces = [CubeLayer::PositiveX, CubeLayer::NegativeX, CubeLayer::PositiveY, CubeLayer::NegativeY, CubeLayer::PositiveZ, CubeLayer::NegativeZ];
+					let targets = faces.into_iter().zip(light::cube_face_transforms(position)).map(|(face, transform)| {
+						(SimpleFrameBuffer::depth_only(display, texture.main_level().image(face)).unwrap(), transform)
+					}).collect::<Vec<_>>();
+					(position, targets)
+				}
+				(LightKind::Spot { position, .. }, ShadowSlot::Spot(slot)) => {
+					let layer = self.shadowmap.spot_texture.layer(slot as u32).unwrap().main_level();
+					(position, vec![(SimpleFrameBuffer::depth_only(display, layer).unwrap(), self.shadowmap.spot_transforms[slot])])
+				}
+				_ => continue
+			};
+			for (mut shadowmap_target, transform) in targets {
+				shadowmap_target.clear_depth(1.0);
+				for &(vertex_buffer, index_buffer, model_transform, _) in meshes.iter() {
+					shadowmap_target.draw(vertex_buffer, index_buffer, &self.local_shadowmap_program, &uniform! {
+						shadowmap_transform: transform.0,
+						model_transform: model_transform.0,
+						light_position: (position.0, position.1, position.2),
+						light_range: light.range
+					}, &local_parameters).unwrap();
+				}
+			}
 		}
 		
 		
 		
-		let mut target = MultiOutputFrameBuffer::with_depth_buffer(display, [
+		let mut buffers_target = MultiOutputFrameBuffer::with_depth_buffer(display, [
 			("color", &self.main_buffer),
 			("normal_color", &self.normals_buffer)
 		], &self.depth_buffer).unwrap();
 		
-		target.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
+		buffers_target.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
 		
-		let (width, height) = target.get_dimensions();
-		let aspect_ratio = height as f32 / width as f32;
+		let perspective_matrix = [
+			[-self.f * aspect_ratio, 0.0, 0.0, 0.0],
+			[0.0, self.f, 0.0, 0.0],
+			[0.0, 0.0, (self.z_far+self.z_near)/(self.z_far-self.z_near), 1.0],
+			[0.0, 0.0, -(2.0*self.z_far*self.z_near)/(self.z_far-self.z_near), 0.0]
+		];
 		
 		
 		
-		for i in 0..objects.len() {
-			let uniforms = uniform! {
+		for (&(vertex_buffer, index_buffer, model_transform, cull_backfaces), bounds) in meshes.iter().zip(bounds.iter()) {
+			let uniforms = CascadeUniforms { uniforms: uniform! {
 				camera_location: (camera.position.0, camera.position.1, camera.position.2),
 				camera_transform: camera.get_transform().0,
-				model_transform: objects[i].transform.0,
-				perspective_matrix: [
-					[-self.f * aspect_ratio, 0.0, 0.0, 0.0],
-					[0.0, self.f, 0.0, 0.0],
-					[0.0, 0.0, (self.z_far+self.z_near)/(self.z_far-self.z_near), 1.0],
-					[0.0, 0.0, -(2.0*self.z_far*self.z_near)/(self.z_far-self.z_near), 0.0]
-				],
-				shadowmap_transform: self.shadowmap.transform.0,
+				model_transform: model_transform.0,
+				perspective_matrix: perspective_matrix,
 				shadowmap_texture: Sampler(&self.shadowmap.texture, SamplerBehavior {
 					minify_filter: MinifySamplerFilter::Linear,
 					magnify_filter: MagnifySamplerFilter::Linear,
 					depth_texture_comparison: Some(glium::uniforms::DepthTextureComparison::Greater),
 					.. Default::default()
 				}),
-				shadowmap_resolution: (self.shadowmap.resolution.0 as f32, self.shadowmap.resolution.1 as f32),
-				shadowmap_tolerance: self.shadowmap.bias_factor / (self.shadowmap.far_distance - self.shadowmap.near_distance),
-				light_direction: (light_direction.0, light_direction.1, light_direction.2),
-				dummy: dummy
-			};
+				// the same texture without the comparison, for pcss to find how far off the blockers are
+				shadowmap_depth: Sampler(&self.shadowmap.texture, SamplerBehavior {
+					minify_filter: MinifySamplerFilter::Nearest,
+					magnify_filter: MagnifySamplerFilter::Nearest,
+					.. Default::default()
+				})
+			}, shadowmap: &self.shadowmap };
+			let uniforms = LightUniforms { uniforms, lights, selected: light::select_lights(lights, bounds.as_ref()), slots: &slots, shadowmap: &self.shadowmap };
 			
-			target.draw(&vertex_buffers[i], &index_buffers[i], &self.main_program, &uniforms, &DrawParameters {
+			buffers_target.draw(vertex_buffer, index_buffer, &self.main_program, &uniforms, &DrawParameters {
+				depth: Depth {
+					test: DepthTest::IfLess,
+					write: true,
+					.. Default::default()
+				},
+				backface_culling: match cull_backfaces {
+					true => BackfaceCullingMode::CullCounterClockwise,
+					false => BackfaceCullingMode::CullingDisabled
+				},
+				.. Default::default()
+			}).unwrap();
+		}
+		
+		// two triangles per particle, spread into a quad by the vertex shader
+		if !particles.is_empty() {
+			let vertices = particles.iter().flat_map(|p| {
+				let vertex = |x: f32, y: f32| ParticleVertex { center: [p.position.0, p.position.1, p.position.2], corner: [x, y], size: p.current_size(), color: [p.color.0, p.color.1, p.color.2] };
+				[vertex(-1.0, -1.0), vertex(1.0, -1.0), vertex(1.0, 1.0), vertex(-1.0, -1.0), vertex(1.0, 1.0), vertex(-1.0, 1.0)]
+			}).collect::<Vec<ParticleVertex>>();
+			let vertex_buffer = VertexBuffer::new(display, &vertices).unwrap();
+			// one draw for them all, so they share the lights picked for the box around the whole cloud
+			let centers = particles.iter().map(|p| p.position).collect::<Vec<Vec3>>();
+			let bounds = Aabb::from_points(&centers).expand(particles.iter().fold(0.0, |size, p| size.max(p.current_size())));
+			let uniforms = CascadeUniforms { uniforms: uniform! {
+				camera_transform: camera.get_transform().0,
+				perspective_matrix: perspective_matrix,
+				shadowmap_texture: Sampler(&self.shadowmap.texture, SamplerBehavior {
+					minify_filter: MinifySamplerFilter::Linear,
+					magnify_filter: MagnifySamplerFilter::Linear,
+					depth_texture_comparison: Some(glium::uniforms::DepthTextureComparison::Greater),
+					.. Default::default()
+				})
+			}, shadowmap: &self.shadowmap };
+			let uniforms = LightUniforms { uniforms, lights, selected: light::select_lights(lights, Some(&bounds)), slots: &slots, shadowmap: &self.shadowmap };
+			buffers_target.draw(&vertex_buffer, NoIndices(PrimitiveType::TrianglesList), &self.particle_program, &uniforms, &DrawParameters {
 				depth: Depth {
 					test: DepthTest::IfLess,
 					write: true,
 					.. Default::default()
 				},
-				backface_culling: BackfaceCullingMode::CullCounterClockwise,
 				.. Default::default()
 			}).unwrap();
 		}
 		
 		
-		let mut target = display.draw();
 		target.clear_color(0.0, 0.0, 0.0, 1.0);
 		target.draw(
 			&self.post_vertex_buffer,
@@ -216,11 +621,24 @@ impl Renderer {
 				main_buffer: &self.main_buffer,
 				normals_buffer: &self.normals_buffer,
 				depth_buffer: &self.depth_buffer,
-				shadowmap_texture: &self.shadowmap.texture
+				shadowmap_texture: &self.shadowmap.texture,
+				cascade_count: self.shadowmap.config.cascade_count as i32
 			},
 			&DrawParameters::default()
 		).unwrap();
-		target.finish().unwrap();
+		
+		// drawn over everything, so nothing hides the overlay
+		if let Some(debug_frame) = debug_frame {
+			let vertices = debug_frame.lines().into_iter().flat_map(|(start, end, color)| [
+				DebugVertex { position: [start.0, start.1, start.2], color: [color.0, color.1, color.2] },
+				DebugVertex { position: [end.0, end.1, end.2], color: [color.0, color.1, color.2] }
+			]).collect::<Vec<DebugVertex>>();
+			let vertex_buffer = VertexBuffer::new(display, &vertices).unwrap();
+			target.draw(&vertex_buffer, NoIndices(PrimitiveType::LinesList), &self.debug_program, &uniform! {
+				camera_transform: camera.get_transform().0,
+				perspective_matrix: perspective_matrix
+			}, &DrawParameters::default()).unwrap();
+		}
 	}
 }
 
@@ -230,7 +648,7 @@ impl Renderer {
 
 
 
-fn _load_texture(display: &Display, path: &str) -> SrgbTexture2d {
+fn _load_texture(display: &impl Facade, path: &str) -> SrgbTexture2d {
 	let mut osstr = std::env::current_dir().unwrap().as_os_str().to_owned();
 	osstr.push("\\textures\\");
 	osstr.push(path);
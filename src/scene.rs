@@ -1,10 +1,26 @@
-use glium::{Display, IndexBuffer, VertexBuffer};
+use std::path::Path;
 
-use crate::{math_structs::Vec3, object::Object};
+use glium::{backend::Facade, IndexBuffer, VertexBuffer};
 
+use crate::{heightfield::Heightfield, integrator::Integrator, light::{Light, LightKind}, math_structs::{Mat4, Vec3}, object::Object, particles::{Emitter, EmitterShape, ParticleSystem}, shape::Shape, soft_body::SoftBody, world::{PhysicsConfig, PhysicsWorld}};
 
-pub fn initialize_scene(display: &Display) -> (Vec<Object>, Vec<VertexBuffer<Vec3>>, Vec<IndexBuffer<u16>>) {
-	let (mut cube, cube_vbuf, cube_ibuf) = Object::new_with_buffers(&display, &[
+
+pub fn initialize_scene(display: &impl Facade) -> (Vec<Object>, Vec<VertexBuffer<Vec3>>, Vec<IndexBuffer<u32>>) {
+	let objects = build_scene();
+	let (vertex_buffers, index_buffers) = objects.iter().map(|o| o.create_buffers(display)).unzip();
+	(objects, vertex_buffers, index_buffers)
+}
+
+pub fn initialize_soft_bodies(display: &impl Facade) -> (Vec<SoftBody>, Vec<VertexBuffer<Vec3>>, Vec<IndexBuffer<u32>>) {
+	let soft_bodies = build_soft_bodies();
+	let (vertex_buffers, index_buffers) = soft_bodies.iter().map(|s| s.create_buffers(display)).unzip();
+	(soft_bodies, vertex_buffers, index_buffers)
+}
+
+
+// the bodies of the scene without anything to draw them with, for running it headless
+pub fn build_scene() -> Vec<Object> {
+	let mut cube = Object::new(&[
 		Vec3(-1.0, -1.0, -1.0),
 		Vec3(-1.0, -1.0,  1.0),
 		Vec3(-1.0,  1.0, -1.0),
@@ -28,7 +44,7 @@ pub fn initialize_scene(display: &Display) -> (Vec<Object>, Vec<VertexBuffer<Vec
 		(7, 3, 2),
 	]);
 	
-	let (floor, floor_vbuf, floor_ibuf) = Object::new_with_buffers(&display, &[
+	let floor = Object::new(&[
 		Vec3(-10.0, 0.0, -10.0),
 		Vec3(-10.0, 0.0,  10.0),
 		Vec3( 10.0, 0.0, -10.0),
@@ -38,17 +54,284 @@ pub fn initialize_scene(display: &Display) -> (Vec<Object>, Vec<VertexBuffer<Vec
 		(0, 3, 1)
 	]);
 	
+	cube.set_shape(Shape::Box { half_extents: Vec3(1.0, 1.0, 1.0) });
 	cube.transform = cube.transform.rotate_x(0.5).rotate_z(0.5).translate(Vec3(0.0, 10.0, 0.0));
+	
+	// hills all around the floor, dipping under it in the middle
+	let field = Heightfield::from_png(include_bytes!("terrain.png"), 1.0, 6.0).unwrap();
+	let mut terrain = Object::from_heightfield(field);
+	terrain.transform = terrain.transform.translate(Vec3(0.0, -1.0, 0.0));
+	
+	vec![cube, floor, terrain]
+}
+
+pub fn build_soft_bodies() -> Vec<SoftBody> {
+	// a flag hanging from its two top corners
+	let (vertices, indices) = SoftBody::cloth_mesh(3.0, 2.0, 15, 10);
+	let mut flag = SoftBody::new(&vertices, &indices, &Mat4::identity().rotate_x(0.5 * std::f32::consts::PI).translate(Vec3(5.0, 5.0, 0.0)), 0.5, 400.0, 1.0).unwrap();
+	flag.pin(0);
+	flag.pin(15);
+	
+	vec![flag]
+}
+
+
+pub fn initialize_particles() -> ParticleSystem {
+	// sparks spraying up from beside the cube and bouncing off everything
+	let mut particles = ParticleSystem::new();
+	particles.collide = true;
+	particles.drag = 0.2;
+	let mut sparks = Emitter::new(EmitterShape::Sphere { radius: 0.1 }, Mat4::identity().translate(Vec3(-4.0, 0.2, 0.0)));
+	sparks.rate = 60.0;
+	sparks.lifetime = (1.5, 3.0);
+	sparks.velocity = Vec3(0.0, 6.0, 0.0);
+	sparks.velocity_randomness = 2.0;
+	sparks.size = 0.06;
+	sparks.color = Vec3(1.0, 0.7, 0.3);
+	particles.add_emitter(sparks);
+	
+	particles
+}
+
+// The sun, sun_angle around the sky, with a warm glow over where the sparks come from and a spot light
+// shining down past the flag onto the middle of the floor.
+pub fn build_lights(sun_angle: f32) -> Vec<Light> {
+	let sun = Light::new(LightKind::Directional { direction: Vec3(sun_angle.cos(), 2.0, sun_angle.sin()).normalize() }, Vec3(1.0, 1.0, 1.0), 1.0);
+	let glow = Light { range: 8.0, ..Light::new(LightKind::Point { position: Vec3(-4.0, 1.5, 0.0) }, Vec3(1.0, 0.6, 0.3), 1.5) };
+	let spot = Light { attenuation: Vec3(1.0, 0.07, 0.017), ..Light::new(LightKind::Spot { position: Vec3(6.0, 8.0, -4.0), direction: Vec3(-6.0, -8.0, 4.0), inner_angle: 0.25, outer_angle: 0.35 }, Vec3(0.4, 0.6, 1.0), 2.0) };
+	vec![sun, glow, spot]
+}
+
+
+// A scene file, a line per setting with # starting a comment. A body or cloth line starts a new one and the lines
+// after it, up to the next one, set its properties. Angles are in radians, rotations are about x, then y, then z,
+// and heightfield paths are relative to the file.
+//
+//     gravity 0 -9.8 0             # also tick_rate HZ, solver_iterations N, integrator euler|verlet|rk4
+//     body floor 10                # a static square of that half size facing up, also sphere R, box HX HY HZ,
+//     body sphere 0.5              # capsule HALF_HEIGHT R and heightfield PNG SPACING HEIGHT_SCALE
+//     position 0 5 0               # also rotation, velocity, angular_velocity, density, restitution, friction,
+//     restitution 0.8              # gravity_scale, linear_damping, angular_damping, trigger and static
+//     cloth 3 2 15 10              # size x, size z and segments of a sheet facing up
+//     pin 0 15                     # also position, rotation, mass, stiffness, damping, friction and gravity_scale
+pub struct SceneDescription {
+	pub config: PhysicsConfig,
+	pub bodies: Vec<Object>,
+	pub soft_bodies: Vec<SoftBody>
+}
 
-	//o1.angular_velocity = Vec3(0.2, 0.3, 0.5);
-	//floor.angular_velocity = Vec3(-0.05, 0.1, -0.02);
+// what's being described, finished once the next one starts
+enum Item {
+	Body { object: Box<Object>, position: Vec3, rotation: Vec3, density: Option<f32>, is_static: bool },
+	Cloth { vertices: Vec<Vec3>, indices: Vec<(u16, u16, u16)>, position: Vec3, rotation: Vec3, mass: f32, stiffness: f32, damping: f32, pins: Vec<usize>, friction: Option<f32>, gravity_scale: Option<f32> }
+}
 
 
-	(
-		vec![cube, floor],
-		vec![cube_vbuf, floor_vbuf],
-		vec![cube_ibuf, floor_ibuf]
-	)
+impl SceneDescription {
+	// build_scene and build_soft_bodies on the default config
+	pub fn builtin() -> Self {
+		Self { config: PhysicsConfig::default(), bodies: build_scene(), soft_bodies: build_soft_bodies() }
+	}
+	
+	pub fn load(path: &Path) -> Result<Self, String> {
+		let text = std::fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+		Self::parse(&text, path.parent().unwrap_or(Path::new(""))).map_err(|e| format!("{}: {}", path.display(), e))
+	}
+	
+	// directory is where relative heightfield paths start from
+	pub fn parse(text: &str, directory: &Path) -> Result<Self, String> {
+		let mut scene = Self { config: PhysicsConfig::default(), bodies: vec![], soft_bodies: vec![] };
+		let mut item = None;
+		for (number, line) in text.lines().enumerate() {
+			let line = line.split('#').next().unwrap_or("");
+			let words: Vec<&str> = line.split_whitespace().collect();
+			let Some((&keyword, args)) = words.split_first() else { continue };
+			scene.parse_line(&mut item, keyword, args, directory).map_err(|e| format!("line {}: {}", number + 1, e))?;
+		}
+		if let Some(last) = item {
+			scene.finish(last).map_err(|e| format!("at the end: {}", e))?;
+		}
+		Ok(scene)
+	}
+	
+	pub fn build_world(self) -> PhysicsWorld {
+		let mut world = PhysicsWorld::new(self.config);
+		for object in self.bodies {
+			world.add_body(object);
+		}
+		for soft_body in self.soft_bodies {
+			world.add_soft_body(soft_body);
+		}
+		world
+	}
+	
+	fn parse_line(&mut self, item: &mut Option<Item>, keyword: &str, args: &[&str], directory: &Path) -> Result<(), String> {
+		match keyword {
+			"gravity" => self.config.gravity = vector(args)?,
+			"tick_rate" => self.config.tick_rate = match number(args)? {
+				hz if hz > 0.0 => hz,
+				hz => return Err(format!("the tick rate has to be positive, not {}", hz))
+			},
+			"solver_iterations" => self.config.solver_iterations = single(args)?.parse().map_err(|e| format!("solver_iterations: {}", e))?,
+			"integrator" => self.config.integrator = Integrator::from_name(single(args)?).ok_or(format!("unknown integrator {}", args[0]))?,
+			"body" => {
+				if let Some(last) = item.take() { self.finish(last)?; }
+				*item = Some(Item::Body { object: Box::new(body_object(args, directory)?), position: Vec3(0.0, 0.0, 0.0), rotation: Vec3(0.0, 0.0, 0.0), density: None, is_static: false });
+			}
+			"cloth" => {
+				if let Some(last) = item.take() { self.finish(last)?; }
+				let [size_x, size_z, segments_x, segments_z] = numbers(args)?;
+				if size_x <= 0.0 || size_z <= 0.0 || segments_x < 1.0 || segments_z < 1.0 || (segments_x + 1.0) * (segments_z + 1.0) > u16::MAX as f32 {
+					return Err(format!("no cloth of size {} by {} in {} by {} segments", size_x, size_z, segments_x, segments_z));
+				}
+				let (vertices, indices) = SoftBody::cloth_mesh(size_x, size_z, segments_x as u16, segments_z as u16);
+				*item = Some(Item::Cloth { vertices, indices, position: Vec3(0.0, 0.0, 0.0), rotation: Vec3(0.0, 0.0, 0.0), mass: 1.0, stiffness: 400.0, damping: 1.0, pins: vec![], friction: None, gravity_scale: None });
+			}
+			_ => match item {
+				Some(item) => item.set(keyword, args)?,
+				None => return Err(format!("{} before any body or cloth", keyword))
+			}
+		}
+		Ok(())
+	}
+	
+	fn finish(&mut self, item: Item) -> Result<(), String> {
+		match item {
+			Item::Body { mut object, position, rotation, density, is_static } => {
+				object.transform = placed(position, rotation);
+				if let Some(density) = density { object.set_density(density); }
+				if is_static { object.set_static(); }
+				self.bodies.push(*object);
+			}
+			Item::Cloth { vertices, indices, position, rotation, mass, stiffness, damping, pins, friction, gravity_scale } => {
+				let mut cloth = SoftBody::new(&vertices, &indices, &placed(position, rotation), mass, stiffness, damping)?;
+				for pin in pins {
+					match pin < vertices.len() {
+						true => cloth.pin(pin),
+						false => return Err(format!("can't pin vertex {} of a cloth with {}", pin, vertices.len()))
+					}
+				}
+				if let Some(friction) = friction { cloth.friction = friction; }
+				if let Some(gravity_scale) = gravity_scale { cloth.gravity_scale = gravity_scale; }
+				self.soft_bodies.push(cloth);
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Item {
+	fn set(&mut self, keyword: &str, args: &[&str]) -> Result<(), String> {
+		match (self, keyword) {
+			(Item::Body { position, .. } | Item::Cloth { position, .. }, "position") => *position = vector(args)?,
+			(Item::Body { rotation, .. } | Item::Cloth { rotation, .. }, "rotation") => *rotation = vector(args)?,
+			(Item::Body { object, .. }, "velocity") => object.velocity = vector(args)?,
+			(Item::Body { object, .. }, "angular_velocity") => object.angular_velocity = vector(args)?,
+			(Item::Body { density, .. }, "density") => *density = match number(args)? {
+				d if d > 0.0 => Some(d),
+				d => return Err(format!("the density has to be positive, not {}, static makes a body immovable", d))
+			},
+			(Item::Body { object, .. }, "restitution") => object.restitution = number(args)?,
+			(Item::Body { object, .. }, "friction") => object.friction = number(args)?,
+			(Item::Body { object, .. }, "gravity_scale") => object.gravity_scale = number(args)?,
+			(Item::Body { object, .. }, "linear_damping") => object.linear_damping = number(args)?,
+			(Item::Body { object, .. }, "angular_damping") => object.angular_damping = number(args)?,
+			(Item::Body { object, .. }, "trigger") => { numbers::<0>(args)?; object.is_trigger = true; }
+			(Item::Body { is_static, .. }, "static") => { numbers::<0>(args)?; *is_static = true; }
+			(Item::Cloth { mass, .. }, "mass") => *mass = number(args)?,
+			(Item::Cloth { stiffness, .. }, "stiffness") => *stiffness = number(args)?,
+			(Item::Cloth { damping, .. }, "damping") => *damping = number(args)?,
+			(Item::Cloth { pins, .. }, "pin") => for arg in args {
+				pins.push(arg.parse().map_err(|e| format!("pin {}: {}", arg, e))?);
+			},
+			(Item::Cloth { friction, .. }, "friction") => *friction = Some(number(args)?),
+			(Item::Cloth { gravity_scale, .. }, "gravity_scale") => *gravity_scale = Some(number(args)?),
+			(Item::Body { .. }, _) => return Err(format!("bodies have no {}", keyword)),
+			(Item::Cloth { .. }, _) => return Err(format!("cloth has no {}", keyword))
+		}
+		Ok(())
+	}
 }
 
 
+fn body_object(args: &[&str], directory: &Path) -> Result<Object, String> {
+	let Some((&shape, args)) = args.split_first() else { return Err("a body needs a shape".to_string()) };
+	let shape = match shape {
+		"sphere" => Shape::Sphere { radius: positive(number(args)?)? },
+		"box" => {
+			let [x, y, z] = numbers(args)?;
+			Shape::Box { half_extents: Vec3(positive(x)?, positive(y)?, positive(z)?) }
+		}
+		"capsule" => {
+			let [half_height, radius] = numbers(args)?;
+			Shape::Capsule { half_height: positive(half_height)?, radius: positive(radius)? }
+		}
+		"floor" => {
+			let size = positive(number(args)?)?;
+			return Ok(Object::new(&[
+				Vec3(-size, 0.0, -size),
+				Vec3(-size, 0.0,  size),
+				Vec3( size, 0.0, -size),
+				Vec3( size, 0.0,  size),
+			], &[
+				(0, 2, 3),
+				(0, 3, 1)
+			]));
+		}
+		"heightfield" => {
+			let [path, spacing, height_scale] = match args {
+				[path, spacing, height_scale] => [*path, *spacing, *height_scale],
+				_ => return Err("a heightfield needs a path, a spacing and a height scale".to_string())
+			};
+			let [spacing, height_scale] = numbers(&[spacing, height_scale])?;
+			let path = directory.join(path);
+			let field = Heightfield::load(&path.to_string_lossy(), positive(spacing)?, height_scale).map_err(|e| format!("can't load {}: {}", path.display(), e))?;
+			return Ok(Object::from_heightfield(field));
+		}
+		other => return Err(format!("unknown shape {}", other))
+	};
+	let mut object = Object::new(&[], &[]);
+	object.set_shape(shape);
+	Ok(object)
+}
+
+// rotated about x, y and z in turn, then moved
+fn placed(position: Vec3, rotation: Vec3) -> Mat4 {
+	Mat4::identity().rotate_x(rotation.0).rotate_y(rotation.1).rotate_z(rotation.2).translate(position)
+}
+
+fn single<'a>(args: &[&'a str]) -> Result<&'a str, String> {
+	match args {
+		[arg] => Ok(arg),
+		_ => Err(format!("expected one value, got {}", args.len()))
+	}
+}
+
+fn numbers<const N: usize>(args: &[&str]) -> Result<[f32; N], String> {
+	if args.len() != N {
+		return Err(format!("expected {} numbers, got {}", N, args.len()));
+	}
+	let mut values = [0.0f32; N];
+	for (value, arg) in values.iter_mut().zip(args) {
+		*value = arg.parse().map_err(|_| format!("{} isn't a number", arg))?;
+		if !value.is_finite() {
+			return Err(format!("{} isn't finite", arg));
+		}
+	}
+	Ok(values)
+}
+
+fn number(args: &[&str]) -> Result<f32, String> {
+	numbers::<1>(args).map(|[x]| x)
+}
+
+fn vector(args: &[&str]) -> Result<Vec3, String> {
+	numbers(args).map(|[x, y, z]| Vec3(x, y, z))
+}
+
+fn positive(x: f32) -> Result<f32, String> {
+	match x > 0.0 {
+		true => Ok(x),
+		false => Err(format!("{} has to be positive", x))
+	}
+}
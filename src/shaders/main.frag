@@ -1,21 +1,53 @@
 #version 150
 
 in vec3 world_position;
-in vec3 shadowmap_position;
+in float view_depth;
 out vec4 color;
 out vec4 normal_color;
 
 uniform vec3 camera_location;
-uniform vec3 light_direction;
-uniform sampler2DShadow shadowmap_texture;
-uniform vec2 shadowmap_resolution;
+uniform sampler2DArrayShadow shadowmap_texture;
+uniform sampler2DArray shadowmap_depth;
+uniform float shadowmap_resolution;
 uniform float shadowmap_tolerance;
-uniform float dummy;
+uniform int shadow_filter; // the order of ShadowFilter, hard, kernel, noise, pcss
+uniform float shadow_filter_radius; // in texels
+uniform float shadow_penumbra_slope; // penumbra width per depth between blocker and receiver, both in shadow map units
+uniform mat4 cascade_transforms[4];
+uniform float cascade_splits[4]; // the view depth each cascade ends at
+uniform int cascade_count;
+uniform float cascade_blend;
+uniform bool show_cascades;
+
+// the lights that reach this object, the first light_count of each array
+uniform int light_count;
+uniform int light_types[8]; // directional, point, spot
+uniform vec3 light_positions[8];
+uniform vec3 light_directions[8]; // toward a directional light, the way a spot light shines
+uniform vec3 light_colors[8]; // times the intensity
+uniform float light_ranges[8];
+uniform vec3 light_attenuations[8]; // constant, linear and quadratic
+uniform vec2 light_cones[8]; // the cosines of a spot light's inner and outer angles
+uniform int light_shadows[8]; // the cube map or spot layer its shadow is in, any slot for the cascades, -1 for none
+uniform samplerCubeShadow point_shadowmaps[2];
+uniform sampler2DArrayShadow spot_shadowmap;
+uniform mat4 spot_shadow_transforms[4];
+uniform float local_shadow_tolerance; // of the distance to the light, which the local maps hold divided by its range
 
 
 const float ambient_level = 0.2;
 const vec3 specular_color = vec3(1.0, 1.0, 1.0);
 
+const vec3 cascade_colors[4] = vec3[](
+	vec3(1.0, 0.2, 0.2),
+	vec3(0.2, 1.0, 0.2),
+	vec3(0.2, 0.4, 1.0),
+	vec3(1.0, 1.0, 0.2)
+);
+
+// the widest pcss looks for blockers, in texels
+const float max_blocker_search = 32.0;
+
 
 const vec2 sample_pos[16] = vec2[](
 	vec2( 0.97484398,  0.75648379),
@@ -36,56 +68,157 @@ const vec2 sample_pos[16] = vec2[](
 	vec2( 0.14383161, -0.14100790)
 );
 
-float sample_shade_kernel_hardware(vec3 normal) {
-	float bias = max(10.0 - 10.0 * dot(normal, light_direction), 1.0) * shadowmap_tolerance;
+// the filters all take the position in the cascade's map, with the depth to compare in z, already biased
+
+float sample_shade_kernel_hardware(vec3 shadowmap_position, int cascade, float radius) {
 	float shade = 0.0;
 	for (int i = 0; i < 4; i++) {
-		shade += texture(shadowmap_texture, vec3(shadowmap_position.xy + sample_pos[i] / shadowmap_resolution, shadowmap_position.z - bias));
+		shade += texture(shadowmap_texture, vec4(shadowmap_position.xy + sample_pos[i] * radius, cascade, shadowmap_position.z));
 	}
 	if (shade >= 3.9) return 1.0;
 	for (int i = 4; i < sample_pos.length(); i++) {
-		shade += texture(shadowmap_texture, vec3(shadowmap_position.xy + sample_pos[i] / shadowmap_resolution, shadowmap_position.z - bias));
+		shade += texture(shadowmap_texture, vec4(shadowmap_position.xy + sample_pos[i] * radius, cascade, shadowmap_position.z));
 	}
 	return shade / sample_pos.length();
 }
 
+float sample_shade_hardware_smooth(vec3 shadowmap_position, int cascade) {
+	return texture(shadowmap_texture, vec4(shadowmap_position.xy, cascade, shadowmap_position.z));
+}
 
-void main() {
-	vec3 normal = normalize(cross(dFdx(world_position), dFdy(world_position)));
-	
-	float diffuse_brightness = max(dot(normal, light_direction), 0.0);
-	float specular_brightness = pow(max(dot(normal, normalize(light_direction + normalize(camera_location - world_position))), 0.0), 16.0) * diffuse_brightness;
-	
-	float shade = sample_shade_kernel_hardware(normal);
-	specular_brightness *= max(1.0 - shade*1.5, 0.0);
-	diffuse_brightness *= 1.0 - shade;
-	
-	color = vec4(mix(ambient_level, 1.0, diffuse_brightness) * abs(normal) + specular_color * specular_brightness, 1.0);
-	normal_color = vec4(normal, 1.0);
+
+float random(vec4 seed4){
+	float dot_product = dot(seed4, vec4(12.9898, 78.233, 45.164, 94.673));
+	return fract(sin(dot_product) * 43758.5453);
 }
 
+float sample_shade_noise(vec3 shadowmap_position, int cascade) {
+	int index = int(16.0 * random(gl_FragCoord.xyxy)) % 16;
+	return texture(shadowmap_texture, vec4(shadowmap_position.xy + sample_pos[index] * shadow_filter_radius / shadowmap_resolution, cascade, shadowmap_position.z));
+}
 
+float sample_shade_pcss(vec3 shadowmap_position, int cascade) {
+	// how far off whatever is in front averages, looking as wide as a blocker at the map's near side could blur
+	float search_radius = min(shadowmap_position.z * shadow_penumbra_slope, max_blocker_search / shadowmap_resolution);
+	float blocker_depth = 0.0;
+	int blockers = 0;
+	for (int i = 0; i < sample_pos.length(); i++) {
+		float depth = texture(shadowmap_depth, vec3(shadowmap_position.xy + sample_pos[i] * search_radius, cascade)).r;
+		if (depth < shadowmap_position.z) {
+			blocker_depth += depth;
+			blockers++;
+		}
+	}
+	if (blockers == 0) return 0.0;
+	
+	// sharp where the blocker touches, wider the further the light has to go past it
+	float penumbra = (shadowmap_position.z - blocker_depth / float(blockers)) * shadow_penumbra_slope;
+	return sample_shade_kernel_hardware(shadowmap_position, cascade, max(penumbra, 1.0 / shadowmap_resolution));
+}
 
+float sample_shade(vec3 normal, int cascade, vec3 light) {
+	vec3 shadowmap_position = vec3(cascade_transforms[cascade] * vec4(world_position, 1.0)) * 0.5 + 0.5;
+	shadowmap_position.z -= max(10.0 - 10.0 * dot(normal, light), 1.0) * shadowmap_tolerance;
+	switch (shadow_filter) {
+		case 0: return sample_shade_hardware_smooth(shadowmap_position, cascade);
+		case 2: return sample_shade_noise(shadowmap_position, cascade);
+		case 3: return sample_shade_pcss(shadowmap_position, cascade);
+		default: return sample_shade_kernel_hardware(shadowmap_position, cascade, shadow_filter_radius / shadowmap_resolution);
+	}
+}
 
+// the first cascade out to this depth, or cascade_count past the last one
+int find_cascade() {
+	int cascade = 0;
+	while (cascade < cascade_count && view_depth > cascade_splits[cascade]) cascade++;
+	return cascade;
+}
 
+float sample_shade_cascaded(vec3 normal, int cascade, vec3 light) {
+	if (cascade >= cascade_count) return 0.0;
+	float shade = sample_shade(normal, cascade, light);
+	
+	// over the end of a cascade fade into the next one, or out of shadow past the last
+	float start = cascade == 0 ? 0.0 : cascade_splits[cascade - 1];
+	float end = cascade_splits[cascade];
+	float blend_start = end - cascade_blend * (end - start);
+	if (view_depth > blend_start) {
+		float next = cascade + 1 < cascade_count ? sample_shade(normal, cascade + 1, light) : 0.0;
+		shade = mix(shade, next, (view_depth - blend_start) / (end - blend_start));
+	}
+	return shade;
+}
 
 
+// point and spot shadow maps hold the distance from the light over its range
+float local_reference(int light, vec3 normal, vec3 to_light, float light_distance) {
+	return light_distance / light_ranges[light] - max(10.0 - 10.0 * dot(normal, to_light), 1.0) * local_shadow_tolerance;
+}
 
-float sample_shade_hardware_smooth(vec3 normal) {
-	float bias = max(10.0 * (1.0 - dot(normal, light_direction)), 1.0) * shadowmap_tolerance;
-	return texture(shadowmap_texture, vec3(shadowmap_position.xy, shadowmap_position.z - bias));
+float sample_shade_point(int light, vec3 normal, vec3 to_light, float light_distance) {
+	vec4 position = vec4(-to_light, local_reference(light, normal, to_light, light_distance));
+	// samplers in an array can only be picked by a constant
+	switch (light_shadows[light]) {
+		case 0: return texture(point_shadowmaps[0], position);
+		case 1: return texture(point_shadowmaps[1], position);
+		default: return 0.0;
+	}
 }
 
+float sample_shade_spot(int light, vec3 normal, vec3 to_light, float light_distance) {
+	int layer = light_shadows[light];
+	vec4 projected = spot_shadow_transforms[layer] * vec4(world_position, 1.0);
+	if (projected.w <= 0.0) return 0.0;
+	vec2 shadowmap_position = projected.xy / projected.w * 0.5 + 0.5;
+	return texture(spot_shadowmap, vec4(shadowmap_position, layer, local_reference(light, normal, to_light, light_distance)));
+}
 
-float random(vec4 seed4){
-	float dot_product = dot(seed4, vec4(12.9898, 78.233, 45.164, 94.673));
-	return fract(sin(dot_product) * 43758.5453);
+// what is left of the light at this distance, smoothly nothing by its range
+float attenuate(int light, float light_distance) {
+	vec3 coefficients = light_attenuations[light];
+	float window = clamp(1.0 - pow(light_distance / light_ranges[light], 4.0), 0.0, 1.0);
+	return window * window / (coefficients.x + coefficients.y * light_distance + coefficients.z * light_distance * light_distance);
 }
 
-float sample_shade_noise(vec3 normal) {
-	float bias = max(10.0 * (1.0 - dot(normal, light_direction)), 1.0) * shadowmap_tolerance;
-	int index = int(16.0 * random(gl_FragCoord.xyxy)) % 16;
-	return texture(shadowmap_texture, vec3(shadowmap_position.xy + sample_pos[index] * 3.0 / shadowmap_resolution, shadowmap_position.z - bias));
+
+void main() {
+	vec3 normal = normalize(cross(dFdx(world_position), dFdy(world_position)));
+	vec3 view_direction = normalize(camera_location - world_position);
+	int cascade = find_cascade();
+	
+	vec3 diffuse = vec3(0.0);
+	vec3 specular = vec3(0.0);
+	for (int i = 0; i < light_count; i++) {
+		vec3 to_light = light_directions[i];
+		float light_distance = 0.0;
+		float strength = 1.0;
+		if (light_types[i] != 0) {
+			vec3 offset = light_positions[i] - world_position;
+			light_distance = length(offset);
+			to_light = offset / light_distance;
+			strength = attenuate(i, light_distance);
+			if (light_types[i] == 2) strength *= smoothstep(light_cones[i].y, light_cones[i].x, dot(-to_light, light_directions[i]));
+		}
+		
+		float diffuse_brightness = max(dot(normal, to_light), 0.0) * strength;
+		if (diffuse_brightness <= 0.0) continue;
+		float specular_brightness = pow(max(dot(normal, normalize(to_light + view_direction)), 0.0), 16.0) * diffuse_brightness;
+		
+		float shade = 0.0;
+		if (light_shadows[i] >= 0) {
+			switch (light_types[i]) {
+				case 0: shade = sample_shade_cascaded(normal, cascade, to_light); break;
+				case 1: shade = sample_shade_point(i, normal, to_light, light_distance); break;
+				default: shade = sample_shade_spot(i, normal, to_light, light_distance);
+			}
+		}
+		specular += light_colors[i] * specular_brightness * max(1.0 - shade*1.5, 0.0);
+		diffuse += light_colors[i] * diffuse_brightness * (1.0 - shade);
+	}
+	
+	color = vec4(mix(vec3(ambient_level), vec3(1.0), diffuse) * abs(normal) + specular_color * specular, 1.0);
+	if (show_cascades && cascade < cascade_count) color.rgb = mix(color.rgb, cascade_colors[cascade], 0.5);
+	normal_color = vec4(normal, 1.0);
 }
 
 
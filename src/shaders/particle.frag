@@ -0,0 +1,97 @@
+#version 150
+
+in vec2 billboard_position;
+in vec3 particle_color;
+in vec3 world_center;
+in vec4 shadowmap_position;
+out vec4 color;
+out vec4 normal_color;
+
+uniform mat4 camera_transform;
+uniform sampler2DArrayShadow shadowmap_texture;
+uniform float shadowmap_tolerance;
+
+// the same lights as main.frag, picked for the box around every particle
+uniform int light_count;
+uniform int light_types[8]; // directional, point, spot
+uniform vec3 light_positions[8];
+uniform vec3 light_directions[8]; // toward a directional light, the way a spot light shines
+uniform vec3 light_colors[8]; // times the intensity
+uniform float light_ranges[8];
+uniform vec3 light_attenuations[8]; // constant, linear and quadratic
+uniform vec2 light_cones[8]; // the cosines of a spot light's inner and outer angles
+uniform int light_shadows[8]; // the cube map or spot layer its shadow is in, any slot for the cascades, -1 for none
+uniform samplerCubeShadow point_shadowmaps[2];
+uniform sampler2DArrayShadow spot_shadowmap;
+uniform mat4 spot_shadow_transforms[4];
+uniform float local_shadow_tolerance;
+
+
+const float ambient_level = 0.2;
+
+
+// point and spot shadow maps hold the distance from the light over its range
+float sample_shade_point(int light, vec3 to_light, float light_distance) {
+	vec4 position = vec4(-to_light, light_distance / light_ranges[light] - local_shadow_tolerance);
+	// samplers in an array can only be picked by a constant
+	switch (light_shadows[light]) {
+		case 0: return texture(point_shadowmaps[0], position);
+		case 1: return texture(point_shadowmaps[1], position);
+		default: return 0.0;
+	}
+}
+
+float sample_shade_spot(int light, float light_distance) {
+	int layer = light_shadows[light];
+	vec4 projected = spot_shadow_transforms[layer] * vec4(world_center, 1.0);
+	if (projected.w <= 0.0) return 0.0;
+	vec2 position = projected.xy / projected.w * 0.5 + 0.5;
+	return texture(spot_shadowmap, vec4(position, layer, light_distance / light_ranges[light] - local_shadow_tolerance));
+}
+
+// what is left of the light at this distance, smoothly nothing by its range
+float attenuate(int light, float light_distance) {
+	vec3 coefficients = light_attenuations[light];
+	float window = clamp(1.0 - pow(light_distance / light_ranges[light], 4.0), 0.0, 1.0);
+	return window * window / (coefficients.x + coefficients.y * light_distance + coefficients.z * light_distance * light_distance);
+}
+
+
+void main() {
+	float r2 = dot(billboard_position, billboard_position);
+	if (r2 > 1.0) discard;
+	
+	// lit like a ball, the camera looks down +z
+	vec3 camera_normal = vec3(billboard_position, -sqrt(1.0 - r2));
+	vec3 normal = normalize(transpose(mat3(camera_transform)) * camera_normal);
+	
+	vec3 diffuse = vec3(0.0);
+	for (int i = 0; i < light_count; i++) {
+		vec3 to_light = light_directions[i];
+		float light_distance = 0.0;
+		float strength = 1.0;
+		if (light_types[i] != 0) {
+			vec3 offset = light_positions[i] - world_center;
+			light_distance = length(offset);
+			to_light = offset / light_distance;
+			strength = attenuate(i, light_distance);
+			if (light_types[i] == 2) strength *= smoothstep(light_cones[i].y, light_cones[i].x, dot(-to_light, light_directions[i]));
+		}
+		
+		float diffuse_brightness = max(dot(normal, to_light), 0.0) * strength;
+		if (diffuse_brightness <= 0.0) continue;
+		
+		float shade = 0.0;
+		if (light_shadows[i] >= 0) {
+			switch (light_types[i]) {
+				case 0: shade = shadowmap_position.w > 1.0 ? 0.0 : texture(shadowmap_texture, vec4(shadowmap_position.xyz, shadowmap_position.w - shadowmap_tolerance)); break;
+				case 1: shade = sample_shade_point(i, to_light, light_distance); break;
+				default: shade = sample_shade_spot(i, light_distance);
+			}
+		}
+		diffuse += light_colors[i] * diffuse_brightness * (1.0 - shade);
+	}
+	
+	color = vec4(mix(vec3(ambient_level), vec3(1.0), diffuse) * particle_color, 1.0);
+	normal_color = vec4(normal, 1.0);
+}
@@ -0,0 +1,37 @@
+#version 150
+
+in vec3 center; // world position of the particle
+in vec2 corner; // of the billboard, from -1 to 1
+in float size;
+in vec3 color;
+out vec2 billboard_position;
+out vec3 particle_color;
+out vec3 world_center; // the whole particle is lit as if it were all there
+out vec4 shadowmap_position; // with the cascade's layer in z and the depth in w, w past 1 out of every cascade
+
+uniform mat4 camera_transform;
+uniform mat4 perspective_matrix;
+uniform mat4 cascade_transforms[4];
+uniform float cascade_splits[4];
+uniform int cascade_count;
+
+void main() {
+	billboard_position = corner;
+	particle_color = color;
+	world_center = center;
+	
+	// spread out in camera space so the quad always faces the camera
+	vec3 center_position = vec3(camera_transform * vec4(center, 1.0));
+	vec3 camera_position = center_position + vec3(corner * size, 0.0);
+	gl_Position = perspective_matrix * vec4(camera_position, 1.0);
+	
+	// the whole particle takes the shade at its center, from the first cascade that reaches it
+	int cascade = 0;
+	while (cascade < cascade_count && center_position.z > cascade_splits[cascade]) cascade++;
+	if (cascade < cascade_count) {
+		vec3 position = vec3(cascade_transforms[cascade] * vec4(center, 1.0)) * 0.5 + 0.5;
+		shadowmap_position = vec4(position.xy, cascade, position.z);
+	} else {
+		shadowmap_position = vec4(0.0, 0.0, 0.0, 2.0);
+	}
+}
@@ -8,7 +8,8 @@ out vec4 color;
 //uniform sampler2D main_buffer;
 //uniform sampler2D normals_buffer;
 //uniform sampler2D depth_buffer;
-uniform sampler2D shadowmap_texture;
+uniform sampler2DArray shadowmap_texture;
+uniform int cascade_count;
 
 vec3 rainbow_gradient(float t) {
 	t = mod(t, 1.0) * 6.0;
@@ -21,6 +22,8 @@ vec3 rainbow_gradient(float t) {
 }
 
 void main() {
-	float t = texture(shadowmap_texture, screen_position).r;
+	// the cascades side by side, the nearest on the left
+	float x = screen_position.x * cascade_count;
+	float t = texture(shadowmap_texture, vec3(fract(x), screen_position.y, floor(x))).r;
 	color = vec4(rainbow_gradient(t), 1.0);
 }
\ No newline at end of file
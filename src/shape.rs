@@ -0,0 +1,178 @@
+use std::f32::consts::PI;
+
+use std::collections::BTreeSet;
+
+use crate::{heightfield::Heightfield, math_structs::{Mat3, Mat4, Vec3}, physics::{tangent_basis, Aabb}};
+
+
+// Collision shape of an object, in its model space and separate from the render mesh.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+	Sphere { radius: f32 },
+	Box { half_extents: Vec3 },
+	Capsule { half_height: f32, radius: f32 }, // along the model y axis, half_height is to the cap centers
+	ConvexHull { points: Box<[Vec3]> },
+	Mesh, // the object's own triangles, may be concave
+	Heightfield { field: Box<Heightfield> } // always static, the render mesh is only for drawing
+}
+
+// A convex piece in world space: the hull of points grown by radius.
+// Spheres are a point, capsules a segment, mesh triangles three points.
+#[derive(Clone, Debug)]
+pub struct Convex {
+	pub points: Vec<Vec3>,
+	pub radius: f32
+}
+
+
+impl Shape {
+	pub fn convex_hull(points: &[Vec3]) -> Self {
+		Self::ConvexHull { points: points.to_vec().into_boxed_slice() }
+	}
+	
+	pub fn is_convex(&self) -> bool {
+		!matches!(self, Self::Mesh | Self::Heightfield { .. })
+	}
+	
+	// Volume, center of mass and model space inertia about it at unit density, none for meshes, which integrate
+	// their render mesh instead. Hulls integrate the faces of their points.
+	pub fn mass_properties(&self) -> Option<(f32, Vec3, Mat3)> {
+		let origin = Vec3(0.0, 0.0, 0.0);
+		match *self {
+			Self::Sphere { radius } => {
+				let volume = 4.0 / 3.0 * PI * radius * radius * radius;
+				Some((volume, origin, Mat3::identity() * (0.4 * volume * radius * radius)))
+			}
+			Self::Box { half_extents: Vec3(x, y, z) } => {
+				let volume = 8.0 * x * y * z;
+				Some((volume, origin, Mat3::diagonal(Vec3(y*y + z*z, x*x + z*z, x*x + y*y) * (volume / 3.0))))
+			}
+			Self::Capsule { half_height, radius } => {
+				let (h, r) = (half_height, radius);
+				let cylinder = PI * r * r * 2.0 * h;
+				let caps = 4.0 / 3.0 * PI * r * r * r;
+				let axial = cylinder * r * r / 2.0 + caps * 0.4 * r * r;
+				let transverse = cylinder * (h * h / 3.0 + r * r / 4.0) + caps * (0.4 * r * r + h * h + 0.75 * h * r);
+				Some((cylinder + caps, origin, Mat3::diagonal(Vec3(transverse, axial, transverse))))
+			}
+			Self::Heightfield { .. } => Some((0.0, origin, Mat3::zero())),
+			Self::ConvexHull { ref points } => Some(mesh_mass_properties(hull_triangles(points).into_iter())),
+			Self::Mesh => None
+		}
+	}
+	
+	// none for meshes and heightfields, which are handled a triangle at a time
+	pub fn world_convex(&self, transform: &Mat4) -> Option<Convex> {
+		let (points, radius) = match self {
+			Self::Sphere { radius } => (vec![Vec3(0.0, 0.0, 0.0)], *radius),
+			Self::Box { half_extents: Vec3(x, y, z) } => ((0..8).map(|i| Vec3(
+				if i & 4 == 0 { -x } else { *x },
+				if i & 2 == 0 { -y } else { *y },
+				if i & 1 == 0 { -z } else { *z }
+			)).collect(), 0.0),
+			Self::Capsule { half_height, radius } => (vec![Vec3(0.0, -half_height, 0.0), Vec3(0.0, *half_height, 0.0)], *radius),
+			Self::ConvexHull { points } => (points.to_vec(), 0.0),
+			Self::Mesh | Self::Heightfield { .. } => return None
+		};
+		Some(Convex { points: points.into_iter().map(|p| p.apply_transform(transform)).collect(), radius })
+	}
+}
+
+
+// Volume, centroid and inertia about the centroid at unit density of a closed triangle mesh, summed as signed
+// tetrahedra from the origin to each triangle. Either winding works, open meshes enclose next to no volume.
+pub fn mesh_mass_properties(triangles: impl Iterator<Item = (Vec3, Vec3, Vec3)>) -> (f32, Vec3, Mat3) {
+	let canonical = Mat3([
+		[2.0, 1.0, 1.0],
+		[1.0, 2.0, 1.0],
+		[1.0, 1.0, 2.0],
+	]) * (1.0 / 120.0);
+	
+	let mut volume = 0.0;
+	let mut moment = Vec3(0.0, 0.0, 0.0);
+	let mut covariance = Mat3::zero();
+	for (a, b, c) in triangles {
+		let m = Mat3::from_columns(a, b, c);
+		let det = m.determinant();
+		volume += det / 6.0;
+		moment += (a + b + c) * (det / 24.0);
+		covariance += m.mult_mat3(&canonical).mult_mat3(&m.transpose()) * det;
+	}
+	
+	if volume.abs() < 1e-12 {
+		return (0.0, Vec3(0.0, 0.0, 0.0), Mat3::zero());
+	}
+	let centroid = moment / volume;
+	
+	// the sign of the volume is the winding's, the moments share it
+	if volume < 0.0 {
+		volume = -volume;
+		covariance = covariance * -1.0;
+	}
+	
+	// parallel axis theorem, moved from the origin to the centroid
+	let covariance = covariance - Mat3::outer(centroid, centroid) * volume;
+	(volume, centroid, Mat3::identity() * covariance.trace() - covariance)
+}
+
+
+// Outward wound triangles over the faces of the convex hull of the points. Every plane through three points
+// with the rest behind it is a face, the points on it are fanned around their middle. Points inside are
+// ignored and flat or degenerate sets have no faces. Checks every triple, which is fine for hull sized sets.
+fn hull_triangles(points: &[Vec3]) -> Vec<(Vec3, Vec3, Vec3)> {
+	let bounds = match points.is_empty() {
+		true => return Vec::new(),
+		false => Aabb::from_points(points)
+	};
+	let tolerance = 1e-5 * (bounds.max - bounds.min).length();
+	
+	let mut faces = BTreeSet::new();
+	let mut triangles = Vec::new();
+	for i in 0..points.len() {
+		for j in i + 1..points.len() {
+			for k in j + 1..points.len() {
+				let normal = (points[j] - points[i]).cross(points[k] - points[i]);
+				if normal.length() <= tolerance * tolerance { continue; }
+				let normal = normal.normalize();
+				
+				let heights = points.iter().map(|&p| (p - points[i]).dot(normal)).collect::<Vec<f32>>();
+				let normal = match (heights.iter().all(|&h| h <= tolerance), heights.iter().all(|&h| h >= -tolerance)) {
+					(true, _) => normal,
+					(false, true) => -normal,
+					(false, false) => continue
+				};
+				
+				let on_face = (0..points.len()).filter(|&l| heights[l].abs() <= tolerance).collect::<Vec<usize>>();
+				if !faces.insert(on_face.clone()) { continue; }
+				
+				let middle = on_face.iter().fold(Vec3(0.0, 0.0, 0.0), |sum, &l| sum + points[l]) / on_face.len() as f32;
+				let (t1, t2) = tangent_basis(normal);
+				let mut around = on_face.iter().map(|&l| points[l]).collect::<Vec<Vec3>>();
+				around.sort_by(|&a, &b| f32::atan2((a - middle).dot(t2), (a - middle).dot(t1)).total_cmp(&f32::atan2((b - middle).dot(t2), (b - middle).dot(t1))));
+				for pair in 0..around.len() {
+					triangles.push((middle, around[pair], around[(pair + 1) % around.len()]));
+				}
+			}
+		}
+	}
+	triangles
+}
+
+
+impl Convex {
+	pub fn support(&self, direction: Vec3) -> Vec3 {
+		let mut best = self.points[0];
+		for &p in self.points[1..].iter() {
+			if p.dot(direction) > best.dot(direction) { best = p; }
+		}
+		best
+	}
+	
+	pub fn center(&self) -> Vec3 {
+		self.points.iter().fold(Vec3(0.0, 0.0, 0.0), |sum, &p| sum + p) / self.points.len() as f32
+	}
+	
+	pub fn aabb(&self) -> Aabb {
+		Aabb::from_points(&self.points).expand(self.radius)
+	}
+}
@@ -0,0 +1,307 @@
+use std::collections::BTreeMap;
+
+use crate::{math_structs::{Mat4, Vec3}, object::Object, physics::{ContactCache, ContactEvent, ContactEventKind, ContactPoint, Manifold}};
+
+
+static MAGIC: &[u8; 4] = b"PSNP";
+static VERSION: u32 = 1;
+
+
+// Everything about a body that changes while stepping. The mesh, shape and material stay with the world.
+// Like snapshots, two are equal only if every bit of them is.
+#[derive(Copy, Clone, Debug)]
+pub struct BodyState {
+	pub transform: Mat4,
+	pub velocity: Vec3,
+	pub angular_velocity: Vec3,
+	pub force: Vec3,
+	pub torque: Vec3,
+	pub sleeping: bool,
+	pub sleep_timer: f32
+}
+
+#[derive(Clone, Debug)]
+pub struct SoftBodyState {
+	pub positions: Box<[Vec3]>,
+	pub velocities: Box<[Vec3]>
+}
+
+// The state of a world at the end of a step, see PhysicsWorld::snapshot. Restoring it into the same world
+// and stepping again gives the same results bit for bit, contacts and warm starting included.
+// Two snapshots are equal only if every bit of them is, so NaNs match and -0.0 doesn't match 0.0,
+// and to_bytes round trips exactly.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+	pub time: f64,
+	pub step_count: u64,
+	pub accumulator: f64,
+	pub bodies: Vec<BodyState>,
+	pub joint_impulses: Vec<[f32; 9]>, // in joint id order
+	pub soft_bodies: Vec<SoftBodyState>,
+	pub contacts: ContactCache
+}
+
+
+impl BodyState {
+	pub fn of(object: &Object) -> Self {
+		Self {
+			transform: object.transform,
+			velocity: object.velocity,
+			angular_velocity: object.angular_velocity,
+			force: object.force,
+			torque: object.torque,
+			sleeping: object.sleeping,
+			sleep_timer: object.sleep_timer
+		}
+	}
+	
+	pub fn apply(&self, object: &mut Object) {
+		object.transform = self.transform;
+		object.velocity = self.velocity;
+		object.angular_velocity = self.angular_velocity;
+		object.force = self.force;
+		object.torque = self.torque;
+		object.sleeping = self.sleeping;
+		object.sleep_timer = self.sleep_timer;
+	}
+}
+
+
+impl Snapshot {
+	// bodies whose state isn't the same in both, for finding where two runs went apart
+	pub fn differing_bodies(&self, other: &Snapshot) -> Vec<usize> {
+		(0..self.bodies.len().max(other.bodies.len())).filter(|&i| self.bodies.get(i) != other.bodies.get(i)).collect()
+	}
+	
+	// Little endian, with a magic number and version in front.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut w = Writer(Vec::new());
+		w.0.extend_from_slice(MAGIC);
+		w.u32(VERSION);
+		w.f64(self.time);
+		w.u64(self.step_count);
+		w.f64(self.accumulator);
+		
+		w.usize(self.bodies.len());
+		for body in self.bodies.iter() {
+			w.body(body);
+		}
+		
+		w.usize(self.joint_impulses.len());
+		for impulses in self.joint_impulses.iter() {
+			for &x in impulses.iter() {
+				w.f32(x);
+			}
+		}
+		
+		w.usize(self.soft_bodies.len());
+		for soft_body in self.soft_bodies.iter() {
+			w.soft_body(soft_body);
+		}
+		
+		for manifolds in [&self.contacts.manifolds, &self.contacts.triggers] {
+			w.usize(manifolds.len());
+			for (&(a, b), manifold) in manifolds.iter() {
+				w.usize(a);
+				w.usize(b);
+				w.usize(manifold.points.len());
+				for point in manifold.points.iter() {
+					w.u64(point.feature);
+					w.vec3(point.position);
+					w.vec3(point.normal);
+					for x in [point.separation, point.normal_impulse, point.tangent_impulse.0, point.tangent_impulse.1] {
+						w.f32(x);
+					}
+				}
+			}
+		}
+		w.usize(self.contacts.events.len());
+		for event in self.contacts.events.iter() {
+			w.0.push(match event.kind {
+				ContactEventKind::Begin => 0,
+				ContactEventKind::Persist => 1,
+				ContactEventKind::End => 2
+			});
+			w.usize(event.bodies.0);
+			w.usize(event.bodies.1);
+			w.vec3(event.point);
+			w.vec3(event.normal);
+			w.f32(event.impulse);
+			w.bool(event.trigger);
+		}
+		w.usize(self.contacts.touching.len());
+		for (&(a, b), &(point, normal, trigger)) in self.contacts.touching.iter() {
+			w.usize(a);
+			w.usize(b);
+			w.vec3(point);
+			w.vec3(normal);
+			w.bool(trigger);
+		}
+		
+		w.0
+	}
+	
+	// None if the bytes are cut short, have anything after the end, or aren't a snapshot of this version
+	pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+		let mut r = Reader(bytes);
+		if r.take(4)? != MAGIC || r.u32()? != VERSION { return None; }
+		let time = r.f64()?;
+		let step_count = r.u64()?;
+		let accumulator = r.f64()?;
+		
+		let bodies = (0..r.usize()?).map(|_| Some(BodyState {
+			transform: r.mat4()?,
+			velocity: r.vec3()?,
+			angular_velocity: r.vec3()?,
+			force: r.vec3()?,
+			torque: r.vec3()?,
+			sleeping: r.bool()?,
+			sleep_timer: r.f32()?
+		})).collect::<Option<Vec<BodyState>>>()?;
+		
+		let joint_impulses = (0..r.usize()?).map(|_| {
+			let mut impulses = [0.0; 9];
+			for x in impulses.iter_mut() {
+				*x = r.f32()?;
+			}
+			Some(impulses)
+		}).collect::<Option<Vec<[f32; 9]>>>()?;
+		
+		let soft_bodies = (0..r.usize()?).map(|_| {
+			let count = r.usize()?;
+			let positions = (0..count).map(|_| r.vec3()).collect::<Option<Box<[Vec3]>>>()?;
+			let velocities = (0..count).map(|_| r.vec3()).collect::<Option<Box<[Vec3]>>>()?;
+			Some(SoftBodyState { positions, velocities })
+		}).collect::<Option<Vec<SoftBodyState>>>()?;
+		
+		let mut read_manifolds = || (0..r.usize()?).map(|_| {
+			let pair = (r.usize()?, r.usize()?);
+			let points = (0..r.usize()?).map(|_| Some(ContactPoint {
+				feature: r.u64()?,
+				position: r.vec3()?,
+				normal: r.vec3()?,
+				separation: r.f32()?,
+				normal_impulse: r.f32()?,
+				tangent_impulse: (r.f32()?, r.f32()?)
+			})).collect::<Option<Vec<ContactPoint>>>()?;
+			Some((pair, Manifold { points }))
+		}).collect::<Option<BTreeMap<(usize, usize), Manifold>>>();
+		let manifolds = read_manifolds()?;
+		let triggers = read_manifolds()?;
+		
+		let events = (0..r.usize()?).map(|_| Some(ContactEvent {
+			kind: match r.take(1)?[0] {
+				0 => ContactEventKind::Begin,
+				1 => ContactEventKind::Persist,
+				2 => ContactEventKind::End,
+				_ => return None
+			},
+			bodies: (r.usize()?, r.usize()?),
+			point: r.vec3()?,
+			normal: r.vec3()?,
+			impulse: r.f32()?,
+			trigger: r.bool()?
+		})).collect::<Option<Vec<ContactEvent>>>()?;
+		
+		let touching = (0..r.usize()?).map(|_| Some(((r.usize()?, r.usize()?), (r.vec3()?, r.vec3()?, r.bool()?))))
+			.collect::<Option<BTreeMap<(usize, usize), (Vec3, Vec3, bool)>>>()?;
+		
+		if !r.0.is_empty() { return None; }
+		Some(Self {
+			time,
+			step_count,
+			accumulator,
+			bodies,
+			joint_impulses,
+			soft_bodies,
+			contacts: ContactCache { manifolds, triggers, events, touching }
+		})
+	}
+}
+
+// compared by their encoding, which has every bit of every float
+impl PartialEq for BodyState {
+	fn eq(&self, other: &Self) -> bool {
+		let (mut a, mut b) = (Writer(Vec::new()), Writer(Vec::new()));
+		a.body(self);
+		b.body(other);
+		a.0 == b.0
+	}
+}
+
+impl PartialEq for SoftBodyState {
+	fn eq(&self, other: &Self) -> bool {
+		let (mut a, mut b) = (Writer(Vec::new()), Writer(Vec::new()));
+		a.soft_body(self);
+		b.soft_body(other);
+		a.0 == b.0
+	}
+}
+
+impl PartialEq for Snapshot {
+	fn eq(&self, other: &Self) -> bool {
+		self.to_bytes() == other.to_bytes()
+	}
+}
+
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+	fn u32(&mut self, x: u32) { self.0.extend_from_slice(&x.to_le_bytes()); }
+	fn u64(&mut self, x: u64) { self.0.extend_from_slice(&x.to_le_bytes()); }
+	fn usize(&mut self, x: usize) { self.u64(x as u64); }
+	fn f32(&mut self, x: f32) { self.0.extend_from_slice(&x.to_le_bytes()); }
+	fn f64(&mut self, x: f64) { self.0.extend_from_slice(&x.to_le_bytes()); }
+	fn bool(&mut self, x: bool) { self.0.push(x as u8); }
+	fn vec3(&mut self, v: Vec3) { for x in [v.0, v.1, v.2] { self.f32(x); } }
+	fn mat4(&mut self, m: &Mat4) { for &x in m.0.iter().flatten() { self.f32(x); } }
+	
+	fn body(&mut self, body: &BodyState) {
+		self.mat4(&body.transform);
+		for v in [body.velocity, body.angular_velocity, body.force, body.torque] {
+			self.vec3(v);
+		}
+		self.bool(body.sleeping);
+		self.f32(body.sleep_timer);
+	}
+	
+	fn soft_body(&mut self, soft_body: &SoftBodyState) {
+		self.usize(soft_body.positions.len());
+		for &v in soft_body.positions.iter().chain(soft_body.velocities.iter()) {
+			self.vec3(v);
+		}
+	}
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+	fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+		if self.0.len() < n { return None; }
+		let (bytes, rest) = self.0.split_at(n);
+		self.0 = rest;
+		Some(bytes)
+	}
+	
+	fn u32(&mut self) -> Option<u32> { Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?)) }
+	fn u64(&mut self) -> Option<u64> { Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?)) }
+	fn usize(&mut self) -> Option<usize> { usize::try_from(self.u64()?).ok() }
+	fn f32(&mut self) -> Option<f32> { Some(f32::from_le_bytes(self.take(4)?.try_into().ok()?)) }
+	fn f64(&mut self) -> Option<f64> { Some(f64::from_le_bytes(self.take(8)?.try_into().ok()?)) }
+	fn bool(&mut self) -> Option<bool> {
+		match self.take(1)?[0] {
+			0 => Some(false),
+			1 => Some(true),
+			_ => None
+		}
+	}
+	fn vec3(&mut self) -> Option<Vec3> { Some(Vec3(self.f32()?, self.f32()?, self.f32()?)) }
+	fn mat4(&mut self) -> Option<Mat4> {
+		let mut m = [[0.0; 4]; 4];
+		for x in m.iter_mut().flatten() {
+			*x = self.f32()?;
+		}
+		Some(Mat4(m))
+	}
+}
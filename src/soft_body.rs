@@ -0,0 +1,239 @@
+use glium::{backend::Facade, index::PrimitiveType, IndexBuffer, VertexBuffer};
+
+use crate::{math_structs::{Mat4, Vec3}, narrow_phase, object::{unique_edges, Object}, physics::{point_in_triangle, Aabb, CONTACT_MARGIN, MAX_CONTACT_DEPTH, SLEEP_LINEAR_VELOCITY}, query, shape::Convex};
+
+
+#[derive(Copy, Clone, Debug)]
+pub struct Spring {
+	pub a: u16,
+	pub b: u16,
+	pub rest_length: f32,
+	pub stiffness: f32, // force per unit of stretch
+	pub damping: f32 // force per unit of stretching speed
+}
+
+// A mesh whose vertices are particles joined by a spring along every edge, solved with XPBD in substeps.
+// Positions are in world space. Particles collide with rigid bodies and push back on dynamic ones,
+// but not with each other or other soft bodies.
+#[derive(Clone, Debug)]
+pub struct SoftBody {
+	pub positions: Box<[Vec3]>,
+	pub velocities: Box<[Vec3]>,
+	pub inverse_masses: Box<[f32]>, // zero for pinned particles
+	pub springs: Box<[Spring]>,
+	pub indices: Box<[(u16, u16, u16)]>,
+	pub particle_radius: f32,
+	pub friction: f32,
+	pub gravity_scale: f32,
+	pub substeps: usize
+}
+
+enum Collider {
+	Convex(Convex),
+	Mesh(Vec<(Vec3, Vec3, Vec3, Vec3)>) // corners and outward normal of each triangle
+}
+
+
+impl SoftBody {
+	// Mass is spread evenly over the vertices and has to be positive, pin particles to hold them in place instead.
+	// The stiffness has to be positive too, and every triangle has to be made of the given vertices.
+	pub fn new(vertices: &[Vec3], indices: &[(u16, u16, u16)], transform: &Mat4, mass: f32, stiffness: f32, damping: f32) -> Result<Self, String> {
+		if !mass.is_finite() || mass <= 0.0 {
+			return Err(format!("a soft body needs a positive mass, not {}", mass));
+		}
+		if !stiffness.is_finite() || stiffness <= 0.0 {
+			return Err(format!("a soft body needs a positive stiffness, not {}", stiffness));
+		}
+		if let Some(triangle) = indices.iter().find(|&&(a, b, c)| [a, b, c].iter().any(|&v| v as usize >= vertices.len())) {
+			return Err(format!("the triangle {:?} is past the {} vertices", triangle, vertices.len()));
+		}
+		let positions = vertices.iter().map(|v| v.apply_transform(transform)).collect::<Box<[Vec3]>>();
+		let springs = unique_edges(indices).iter().map(|&(a, b)| Spring {
+			a,
+			b,
+			rest_length: (positions[a as usize] - positions[b as usize]).length(),
+			stiffness,
+			damping
+		}).collect();
+		
+		Ok(Self {
+			velocities: vec![Vec3(0.0, 0.0, 0.0); positions.len()].into_boxed_slice(),
+			inverse_masses: vec![positions.len() as f32 / mass; positions.len()].into_boxed_slice(),
+			positions,
+			springs,
+			indices: indices.to_vec().into_boxed_slice(),
+			particle_radius: 0.05,
+			friction: 0.5,
+			gravity_scale: 1.0,
+			substeps: 10
+		})
+	}
+	
+	// The vertex buffer is dynamic, write the positions into it after every step.
+	pub fn new_with_buffers(display: &impl Facade, vertices: &[Vec3], indices: &[(u16, u16, u16)], transform: &Mat4, mass: f32, stiffness: f32, damping: f32) -> Result<(Self, VertexBuffer<Vec3>, IndexBuffer<u32>), String> {
+		let soft_body = Self::new(vertices, indices, transform, mass, stiffness, damping)?;
+		let (vertex_buffer, index_buffer) = soft_body.create_buffers(display);
+		Ok((soft_body, vertex_buffer, index_buffer))
+	}
+	
+	// world space like the particles, for soft bodies made without a display
+	pub fn create_buffers(&self, display: &impl Facade) -> (VertexBuffer<Vec3>, IndexBuffer<u32>) {
+		let vertex_buffer = VertexBuffer::dynamic(display, &self.positions).unwrap();
+		let index_buffer = IndexBuffer::new(display, PrimitiveType::TrianglesList, &self.indices.iter().flat_map(|&(a, b, c)| [a as u32, b as u32, c as u32]).collect::<Vec<u32>>()).unwrap();
+		(vertex_buffer, index_buffer)
+	}
+	
+	// A flat sheet in the model xz plane, centered on the origin and facing +y, as vertices and triangles.
+	// Vertex (i, j) is at index j * (segments_x + 1) + i.
+	pub fn cloth_mesh(size_x: f32, size_z: f32, segments_x: u16, segments_z: u16) -> (Vec<Vec3>, Vec<(u16, u16, u16)>) {
+		let mut vertices = Vec::new();
+		for j in 0..=segments_z {
+			for i in 0..=segments_x {
+				vertices.push(Vec3(
+					size_x * (i as f32 / segments_x as f32 - 0.5),
+					0.0,
+					size_z * (j as f32 / segments_z as f32 - 0.5)
+				));
+			}
+		}
+		
+		let index = |i: u16, j: u16| j * (segments_x + 1) + i;
+		let mut indices = Vec::new();
+		for j in 0..segments_z {
+			for i in 0..segments_x {
+				indices.push((index(i, j), index(i + 1, j), index(i, j + 1)));
+				indices.push((index(i + 1, j), index(i + 1, j + 1), index(i, j + 1)));
+			}
+		}
+		(vertices, indices)
+	}
+	
+	// pinned particles stay where they are put
+	pub fn pin(&mut self, vertex: usize) {
+		self.inverse_masses[vertex] = 0.0;
+		self.velocities[vertex] = Vec3(0.0, 0.0, 0.0);
+	}
+	
+	// moves every particle and stops them
+	pub fn set_positions(&mut self, positions: &[Vec3]) {
+		self.positions.copy_from_slice(positions);
+		self.velocities.fill(Vec3(0.0, 0.0, 0.0));
+	}
+	
+	pub fn aabb(&self) -> Aabb {
+		Aabb::from_points(&self.positions).expand(self.particle_radius)
+	}
+	
+	
+	pub(crate) fn step(&mut self, objects: &mut [Object], gravity: Vec3, dt: f32) {
+		let h = dt / self.substeps as f32;
+		
+		// the rigid bodies don't move during the substeps, so what the particles can reach is known up front
+		let max_speed = self.velocities.iter().map(|v| v.length()).fold(0.0, f32::max) + gravity.length() * dt;
+		let bounds = self.aabb().expand(CONTACT_MARGIN + max_speed * dt);
+		let colliders = objects.iter().enumerate().filter(|(_, o)| !o.is_trigger && o.world_aabb().overlaps(&bounds)).map(|(i, o)| {
+			let collider = match o.shape.world_convex(&o.transform) {
+				Some(convex) => Collider::Convex(convex),
+				None => Collider::Mesh(query::world_triangles(o, &bounds).into_iter().filter_map(|(_, a, b, c)| {
+					let normal = (c - a).cross(b - a);
+					match normal.length_squared() > 1e-12 {
+						true => Some((a, b, c, normal.normalize())),
+						false => None
+					}
+				}).collect())
+			};
+			(i, collider)
+		}).collect::<Vec<(usize, Collider)>>();
+		
+		for _ in 0..self.substeps {
+			let previous = self.positions.clone();
+			for k in 0..self.positions.len() {
+				if self.inverse_masses[k] == 0.0 { continue; }
+				self.velocities[k] += gravity * (self.gravity_scale * h);
+				self.positions[k] += self.velocities[k] * h;
+			}
+			
+			self.solve_springs(&previous, h);
+			self.collide(objects, &colliders, &previous, h);
+			
+			for k in 0..self.positions.len() {
+				self.velocities[k] = match self.inverse_masses[k] == 0.0 {
+					true => Vec3(0.0, 0.0, 0.0),
+					false => (self.positions[k] - previous[k]) / h
+				};
+			}
+		}
+	}
+	
+	// One XPBD pass with compliance 1 / stiffness, with the damping folded into the same update.
+	fn solve_springs(&mut self, previous: &[Vec3], h: f32) {
+		for spring in self.springs.iter() {
+			let (a, b) = (spring.a as usize, spring.b as usize);
+			let (w_a, w_b) = (self.inverse_masses[a], self.inverse_masses[b]);
+			if w_a + w_b == 0.0 { continue; }
+			
+			let delta = self.positions[a] - self.positions[b];
+			let length = delta.length();
+			if length < 1e-9 { continue; }
+			let n = delta / length;
+			
+			let compliance = 1.0 / (spring.stiffness * h * h);
+			let gamma = spring.damping / (spring.stiffness * h);
+			let stretching = ((self.positions[a] - previous[a]) - (self.positions[b] - previous[b])).dot(n);
+			let lambda = (-(length - spring.rest_length) - gamma * stretching) / ((1.0 + gamma) * (w_a + w_b) + compliance);
+			
+			self.positions[a] += n * (lambda * w_a);
+			self.positions[b] -= n * (lambda * w_b);
+		}
+	}
+	
+	// Pushes particles out of the rigid bodies with friction against the surface's own motion,
+	// and hands the momentum that took to dynamic bodies.
+	fn collide(&mut self, objects: &mut [Object], colliders: &[(usize, Collider)], previous: &[Vec3], h: f32) {
+		for (k, &start) in previous.iter().enumerate() {
+			if self.inverse_masses[k] == 0.0 { continue; }
+			
+			for (i, collider) in colliders.iter() {
+				let x = self.positions[k];
+				let contact = match collider {
+					Collider::Convex(convex) => {
+						let particle = Convex { points: vec![x], radius: self.particle_radius };
+						narrow_phase::separating_normal(&particle, convex).map(|(normal, separation)| (normal, particle.radius + convex.radius - separation))
+					}
+					// like the rigid mesh contacts, the face the particle is least deep behind
+					Collider::Mesh(triangles) => triangles.iter().filter_map(|&(a, b, c, normal)| {
+						let d = (x - a).dot(normal);
+						match d < self.particle_radius && d > -MAX_CONTACT_DEPTH && point_in_triangle(x - normal * d, a, b, c) {
+							true => Some((normal, self.particle_radius - d)),
+							false => None
+						}
+					}).min_by(|p, q| p.1.total_cmp(&q.1))
+				};
+				let (normal, depth) = match contact {
+					Some(contact) if contact.1 > 0.0 => contact,
+					_ => continue
+				};
+				
+				let object = &objects[*i];
+				let r = x - object.transform.get_position();
+				let surface_motion = (object.velocity + object.angular_velocity.cross(r)) * h;
+				let mut correction = normal * depth;
+				
+				let relative = x + correction - start - surface_motion;
+				let tangential = relative - normal * relative.dot(normal);
+				let slide = tangential.length();
+				if slide > 1e-9 {
+					correction -= tangential * (f32::min(self.friction * depth, slide) / slide);
+				}
+				self.positions[k] += correction;
+				
+				// a sleeping body stays asleep under a resting cloth, the push has to be enough to get it moving
+				let impulse = -correction / (self.inverse_masses[k] * h);
+				let object = &mut objects[*i];
+				if object.is_static() || (object.sleeping && impulse.length() * object.inverse_mass < SLEEP_LINEAR_VELOCITY) { continue; }
+				object.wake();
+				object.apply_impulse(impulse, r);
+			}
+		}
+	}
+}
@@ -0,0 +1,393 @@
+use std::{cell::{Ref, RefCell}, collections::hash_map::DefaultHasher, hash::{Hash, Hasher}, sync::mpsc::{self, Receiver, Sender}};
+
+use crate::{debug::{DebugBody, DebugContact, DebugFrame}, force::ForceField, integrator::Integrator, joint::{Joint, JointId, JointSet}, math_structs::{Mat4, Vec3}, object::Object, physics::{self, Aabb, ContactCache, ContactEvent, Diagnostics, Manifold}, query::{self, BoundsTree, CastHit, QueryFilter}, shape::Shape, snapshot::{BodyState, Snapshot, SoftBodyState}, soft_body::SoftBody};
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PhysicsConfig {
+	pub gravity: Vec3,
+	pub tick_rate: f32, // fixed steps per simulated second
+	pub solver_iterations: usize,
+	pub max_steps_per_advance: usize, // time beyond this many steps is dropped so a slow machine doesn't fall further behind
+	pub parallel_narrow_phase: bool, // gives the same results as running on one thread, only faster with many pairs
+	pub integrator: Integrator
+}
+
+// Owns the bodies, joints, force fields and contact state, and steps them. Needs no window or GL context.
+// Bodies are addressed by index, and removing one shifts the bodies after it down by one.
+pub struct PhysicsWorld {
+	pub config: PhysicsConfig,
+	objects: Vec<Object>,
+	contacts: ContactCache,
+	joints: JointSet,
+	fields: Vec<Box<dyn ForceField>>,
+	soft_bodies: Vec<SoftBody>,
+	event_senders: Vec<Sender<ContactEvent>>,
+	diagnostics: Diagnostics,
+	time: f64,
+	step_count: u64,
+	accumulator: f64,
+	bounds_tree: RefCell<Option<BoundsTree>> // for the queries, none since the bodies last changed
+}
+
+
+impl Default for PhysicsConfig {
+	fn default() -> Self {
+		Self {
+			gravity: Vec3(0.0, -9.8, 0.0),
+			tick_rate: 60.0,
+			solver_iterations: 10,
+			max_steps_per_advance: 5,
+			parallel_narrow_phase: true,
+			integrator: Integrator::default()
+		}
+	}
+}
+
+
+impl PhysicsWorld {
+	pub fn new(config: PhysicsConfig) -> Self {
+		Self {
+			config,
+			objects: Vec::new(),
+			contacts: ContactCache::new(),
+			joints: JointSet::new(),
+			fields: Vec::new(),
+			soft_bodies: Vec::new(),
+			event_senders: Vec::new(),
+			diagnostics: Diagnostics::default(),
+			time: 0.0,
+			step_count: 0,
+			accumulator: 0.0,
+			bounds_tree: RefCell::new(None)
+		}
+	}
+	
+	// Runs as many fixed steps of 1 / tick_rate as fit in the elapsed wall time, carrying the remainder
+	// to the next call. Results only depend on the number of steps, never on how the time was sliced.
+	pub fn advance(&mut self, elapsed: f32) -> usize {
+		let dt = 1.0 / self.config.tick_rate;
+		self.accumulator += elapsed as f64;
+		
+		let mut steps = 0;
+		while self.accumulator >= dt as f64 && steps < self.config.max_steps_per_advance {
+			self.step(dt);
+			self.accumulator -= dt as f64;
+			steps += 1;
+		}
+		// past the limit the backlog is dropped, only the fraction of a step is carried
+		if steps == self.config.max_steps_per_advance {
+			self.accumulator %= dt as f64;
+		}
+		steps
+	}
+	
+	pub fn step(&mut self, dt: f32) {
+		physics::run(&mut self.objects, &mut self.contacts, &mut self.joints, &self.fields, &self.config, dt);
+		*self.bounds_tree.get_mut() = None;
+		for soft_body in self.soft_bodies.iter_mut() {
+			soft_body.step(&mut self.objects, self.config.gravity, dt);
+		}
+		let events = &self.contacts.events;
+		self.event_senders.retain(|sender| events.iter().all(|&event| sender.send(event).is_ok()));
+		self.diagnostics = physics::diagnostics(&self.objects, &self.contacts, self.config.gravity);
+		self.time += dt as f64;
+		self.step_count += 1;
+	}
+	
+	// simulated seconds so far
+	pub fn time(&self) -> f64 {
+		self.time
+	}
+	
+	pub fn step_count(&self) -> u64 {
+		self.step_count
+	}
+	
+	// energy, momentum and penetration as of the last step
+	pub fn diagnostics(&self) -> Diagnostics {
+		self.diagnostics
+	}
+	
+	// the same, for the bodies as they are now
+	pub fn measure(&self) -> Diagnostics {
+		physics::diagnostics(&self.objects, &self.contacts, self.config.gravity)
+	}
+	
+	// Hash of the exact bits of every body's state, equal between two runs only if they agree bit for bit.
+	pub fn state_hash(&self) -> u64 {
+		let mut hasher = DefaultHasher::new();
+		for object in self.objects.iter() {
+			let (transform, velocity, angular_velocity) = object.get_dynamic_state();
+			let velocities = [velocity.0, velocity.1, velocity.2, angular_velocity.0, angular_velocity.1, angular_velocity.2];
+			for x in transform.0.iter().flatten().chain(velocities.iter()) {
+				x.to_bits().hash(&mut hasher);
+			}
+			object.sleeping.hash(&mut hasher);
+		}
+		for soft_body in self.soft_bodies.iter() {
+			for p in soft_body.positions.iter().chain(soft_body.velocities.iter()) {
+				for x in [p.0, p.1, p.2] {
+					x.to_bits().hash(&mut hasher);
+				}
+			}
+		}
+		hasher.finish()
+	}
+	
+	
+	// Copies the state that stepping changes, which is cheap next to cloning the bodies with their meshes.
+	pub fn snapshot(&self) -> Snapshot {
+		Snapshot {
+			time: self.time,
+			step_count: self.step_count,
+			accumulator: self.accumulator,
+			bodies: self.objects.iter().map(BodyState::of).collect(),
+			joint_impulses: self.joints.iter().map(|(_, joint)| joint.impulses).collect(),
+			soft_bodies: self.soft_bodies.iter().map(|s| SoftBodyState { positions: s.positions.clone(), velocities: s.velocities.clone() }).collect(),
+			contacts: self.contacts.clone()
+		}
+	}
+	
+	// Puts the world back to when the snapshot was taken, after which stepping repeats what happened then.
+	// Only fits the world it came from, with the same bodies, joints and soft bodies in the same order.
+	// A snapshot with different counts, or contacts between bodies the world doesn't have, is refused and the
+	// world left as it was.
+	pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), String> {
+		let counts = |what: &str, world: usize, snapshot: usize| match world == snapshot {
+			true => Ok(()),
+			false => Err(format!("the snapshot has {} {} but the world has {}", snapshot, what, world))
+		};
+		counts("bodies", self.objects.len(), snapshot.bodies.len())?;
+		counts("joints", self.joints.len(), snapshot.joint_impulses.len())?;
+		counts("soft bodies", self.soft_bodies.len(), snapshot.soft_bodies.len())?;
+		for (soft_body, state) in self.soft_bodies.iter().zip(snapshot.soft_bodies.iter()) {
+			counts("soft body particles", soft_body.positions.len(), state.positions.len())?;
+			counts("soft body velocities", soft_body.velocities.len(), state.velocities.len())?;
+		}
+		snapshot.contacts.check(self.objects.len())?;
+		
+		self.time = snapshot.time;
+		self.step_count = snapshot.step_count;
+		self.accumulator = snapshot.accumulator;
+		for (object, state) in self.objects.iter_mut().zip(snapshot.bodies.iter()) {
+			state.apply(object);
+		}
+		for ((_, joint), impulses) in self.joints.iter_mut().zip(snapshot.joint_impulses.iter()) {
+			joint.impulses = *impulses;
+		}
+		for (soft_body, state) in self.soft_bodies.iter_mut().zip(snapshot.soft_bodies.iter()) {
+			soft_body.positions.copy_from_slice(&state.positions);
+			soft_body.velocities.copy_from_slice(&state.velocities);
+		}
+		self.contacts = snapshot.contacts.clone();
+		*self.bounds_tree.get_mut() = None;
+		Ok(())
+	}
+	
+	
+	pub fn add_body(&mut self, object: Object) -> usize {
+		*self.bounds_tree.get_mut() = None;
+		self.objects.push(object);
+		self.objects.len() - 1
+	}
+	
+	// also removes the joints attached to it
+	pub fn remove_body(&mut self, index: usize) -> Object {
+		self.joints.remove_body(index);
+		self.contacts.remove_body(index);
+		*self.bounds_tree.get_mut() = None;
+		self.objects.remove(index)
+	}
+	
+	pub fn bodies(&self) -> &[Object] {
+		&self.objects
+	}
+	
+	pub fn bodies_mut(&mut self) -> &mut [Object] {
+		*self.bounds_tree.get_mut() = None;
+		&mut self.objects
+	}
+	
+	pub fn body(&self, index: usize) -> Option<&Object> {
+		self.objects.get(index)
+	}
+	
+	pub fn body_mut(&mut self, index: usize) -> Option<&mut Object> {
+		*self.bounds_tree.get_mut() = None;
+		self.objects.get_mut(index)
+	}
+	
+	pub fn len(&self) -> usize {
+		self.objects.len()
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.objects.is_empty()
+	}
+	
+	pub fn dynamic_states(&self) -> Vec<(Mat4, Vec3, Vec3)> {
+		self.objects.iter().map(Object::get_dynamic_state).collect()
+	}
+	
+	// teleports every body, so the cached contacts no longer apply
+	pub fn set_dynamic_states(&mut self, states: &[(Mat4, Vec3, Vec3)]) {
+		for (object, &state) in self.objects.iter_mut().zip(states) {
+			object.set_dynamic_state(state);
+		}
+		self.contacts.clear();
+		*self.bounds_tree.get_mut() = None;
+	}
+	
+	
+	// joints are made against the current body positions, see Joint::hinge and friends
+	pub fn add_joint(&mut self, joint: Joint) -> JointId {
+		self.joints.add(joint)
+	}
+	
+	pub fn remove_joint(&mut self, id: JointId) -> Option<Joint> {
+		self.joints.remove(id)
+	}
+	
+	pub fn joints(&self) -> &JointSet {
+		&self.joints
+	}
+	
+	pub fn joints_mut(&mut self) -> &mut JointSet {
+		&mut self.joints
+	}
+	
+	
+	// soft bodies are stepped after the rigid bodies and numbered separately from them
+	pub fn add_soft_body(&mut self, soft_body: SoftBody) -> usize {
+		self.soft_bodies.push(soft_body);
+		self.soft_bodies.len() - 1
+	}
+	
+	pub fn remove_soft_body(&mut self, index: usize) -> SoftBody {
+		self.soft_bodies.remove(index)
+	}
+	
+	pub fn soft_bodies(&self) -> &[SoftBody] {
+		&self.soft_bodies
+	}
+	
+	pub fn soft_bodies_mut(&mut self) -> &mut [SoftBody] {
+		&mut self.soft_bodies
+	}
+	
+	pub fn soft_body_positions(&self) -> Vec<Vec<Vec3>> {
+		self.soft_bodies.iter().map(|s| s.positions.to_vec()).collect()
+	}
+	
+	// teleports the particles of every soft body and stops them
+	pub fn set_soft_body_positions(&mut self, positions: &[Vec<Vec3>]) {
+		for (soft_body, positions) in self.soft_bodies.iter_mut().zip(positions) {
+			soft_body.set_positions(positions);
+		}
+	}
+	
+	
+	pub fn add_field(&mut self, field: impl ForceField + 'static) {
+		self.fields.push(Box::new(field));
+	}
+	
+	pub fn clear_fields(&mut self) {
+		self.fields.clear();
+	}
+	
+	
+	// every touching pair with the lower index first, as of the last step
+	pub fn contacts(&self) -> impl Iterator<Item = ((usize, usize), &Manifold)> {
+		self.contacts.manifolds.iter().map(|(&pair, manifold)| (pair, manifold))
+	}
+	
+	pub fn contacts_of(&self, index: usize) -> impl Iterator<Item = ((usize, usize), &Manifold)> {
+		self.contacts().filter(move |&((a, b), _)| a == index || b == index)
+	}
+	
+	pub fn manifold(&self, a: usize, b: usize) -> Option<&Manifold> {
+		self.contacts.manifolds.get(&(a.min(b), a.max(b)))
+	}
+	
+	// the contact events of the last step
+	pub fn events(&self) -> &[ContactEvent] {
+		&self.contacts.events
+	}
+	
+	// Every step's events are sent here from then on, until the receiver is dropped.
+	pub fn subscribe_events(&mut self) -> Receiver<ContactEvent> {
+		let (sender, receiver) = mpsc::channel();
+		self.event_senders.push(sender);
+		receiver
+	}
+	
+	// bodies overlapping the trigger, as of the last step
+	pub fn trigger_overlaps(&self, trigger: usize) -> Vec<usize> {
+		self.contacts.triggers.keys().filter_map(|&(a, b)| match (a == trigger, b == trigger) {
+			(true, _) => Some(b),
+			(_, true) => Some(a),
+			_ => None
+		}).collect()
+	}
+	
+	// contacts of the last step and the state of every body, for drawing
+	pub fn debug_frame(&self) -> DebugFrame {
+		DebugFrame {
+			bodies: self.objects.iter().map(|o| DebugBody {
+				aabb: o.world_aabb(),
+				center: o.transform.get_position(),
+				velocity: o.velocity,
+				angular_velocity: o.angular_velocity,
+				sleeping: o.sleeping,
+				is_static: o.is_static()
+			}).collect(),
+			contacts: self.contacts.manifolds.values().flat_map(|m| m.points.iter()).map(|p| DebugContact {
+				point: p.position,
+				normal: p.normal,
+				separation: p.separation
+			}).collect()
+		}
+	}
+	
+	// Built on the first query after the bodies were stepped, added, removed or handed out mutably,
+	// then shared by the queries until that happens again.
+	fn bounds_tree(&self) -> Ref<'_, BoundsTree> {
+		if self.bounds_tree.borrow().is_none() {
+			*self.bounds_tree.borrow_mut() = Some(BoundsTree::new(&self.objects));
+		}
+		Ref::map(self.bounds_tree.borrow(), |tree| tree.as_ref().unwrap())
+	}
+	
+	// bodies whose bounds overlap the box
+	pub fn query_aabb(&self, aabb: &Aabb) -> Vec<usize> {
+		let mut bodies = self.bounds_tree().bodies_where(|bounds| bounds.overlaps(aabb)).into_iter().map(|(i, _)| i).collect::<Vec<usize>>();
+		bodies.sort();
+		bodies
+	}
+	
+	pub fn ray_cast(&self, origin: Vec3, direction: Vec3, max_distance: f32, filter: &QueryFilter) -> Option<CastHit> {
+		query::ray_cast_in(&self.objects, Some(&self.bounds_tree()), origin, direction, max_distance, filter)
+	}
+	
+	pub fn ray_cast_all(&self, origin: Vec3, direction: Vec3, max_distance: f32, filter: &QueryFilter) -> Vec<CastHit> {
+		query::ray_cast_all_in(&self.objects, Some(&self.bounds_tree()), origin, direction, max_distance, filter)
+	}
+	
+	pub fn sphere_cast(&self, center: Vec3, radius: f32, direction: Vec3, max_distance: f32, filter: &QueryFilter) -> Option<CastHit> {
+		self.shape_cast(&Shape::Sphere { radius }, &Mat4::identity().translate(center), direction, max_distance, filter)
+	}
+	
+	// the box's orientation and center come from the transform
+	pub fn box_cast(&self, transform: &Mat4, half_extents: Vec3, direction: Vec3, max_distance: f32, filter: &QueryFilter) -> Option<CastHit> {
+		self.shape_cast(&Shape::Box { half_extents }, transform, direction, max_distance, filter)
+	}
+	
+	pub fn shape_cast(&self, shape: &Shape, transform: &Mat4, direction: Vec3, max_distance: f32, filter: &QueryFilter) -> Option<CastHit> {
+		query::shape_cast_in(&self.objects, Some(&self.bounds_tree()), shape, transform, direction, max_distance, filter)
+	}
+	
+	pub fn point_overlaps(&self, point: Vec3, filter: &QueryFilter) -> Vec<usize> {
+		query::point_overlaps_in(&self.objects, Some(&self.bounds_tree()), point, filter)
+	}
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use std::path::PathBuf;
+
+use common::display;
+use gl_engine::capture::{self, Recorder};
+use glium::{Rect, Surface};
+
+
+// a fresh directory under the build's tmp dir for each test
+fn scratch(name: &str) -> PathBuf {
+	let directory = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("capture").join(name);
+	let _ = std::fs::remove_dir_all(&directory);
+	directory
+}
+
+// a frame that says which one it is in its first pixel
+fn frame(index: u8) -> image::RgbaImage {
+	image::RgbaImage::from_fn(8, 6, |x, y| image::Rgba([index, x as u8, y as u8, 255]))
+}
+
+
+#[test]
+fn records_a_numbered_sequence() {
+	let directory = scratch("sequence");
+	let mut recorder = Recorder::new(&directory, 30.0).unwrap();
+	assert!((recorder.frame_dt() - 1.0 / 30.0).abs() < 1e-7);
+	for i in 0..90 {
+		recorder.record(frame(i)).unwrap();
+	}
+	assert_eq!(recorder.frames(), 90);
+	assert_eq!(recorder.finish().unwrap(), 90);
+	
+	// everything queued got written, in order, and nothing else
+	assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 90);
+	for i in 0..90 {
+		let path = directory.join(format!("frame_{:05}.png", i));
+		let image = image::open(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e)).to_rgba8();
+		assert!(image == frame(i as u8), "{} isn't frame {}", path.display(), i);
+	}
+}
+
+#[test]
+fn keeps_an_earlier_recording() {
+	let directory = scratch("earlier");
+	let mut first = Recorder::new(&directory, 60.0).unwrap();
+	first.record(frame(0)).unwrap();
+	first.finish().unwrap();
+	assert!(Recorder::new(&directory, 60.0).is_err());
+}
+
+#[test]
+fn saves_timestamped_screenshots() {
+	let stamp = capture::timestamp();
+	// 2024-05-01_13-45-12-345
+	assert_eq!(stamp.len(), 23, "{}", stamp);
+	assert!(stamp.chars().enumerate().all(|(i, c)| match i {
+		4 | 7 | 13 | 16 | 19 => c == '-',
+		10 => c == '_',
+		_ => c.is_ascii_digit()
+	}), "{}", stamp);
+	assert!(stamp.as_str() >= "2024", "{}", stamp);
+	
+	let directory = scratch("screenshots");
+	let path = capture::save_screenshot(&frame(7), &directory).unwrap();
+	assert_eq!(path.parent().unwrap(), directory);
+	assert!(path.file_name().unwrap().to_str().unwrap().starts_with("screenshot_2"));
+	assert!(image::open(&path).unwrap().to_rgba8() == frame(7));
+}
+
+#[test]
+fn reads_back_offscreen_frames_top_row_first() {
+	let Some(display) = display() else { return; };
+	// blue with a red strip along the top
+	let image = capture::render_offscreen(&display, 16, 12, |target| {
+		target.clear_color(0.0, 0.0, 1.0, 1.0);
+		target.clear(Some(&Rect { left: 0, bottom: 10, width: 16, height: 2 }), Some((1.0, 0.0, 0.0, 1.0)), false, None, None);
+	}).unwrap();
+	assert_eq!(image.dimensions(), (16, 12));
+	assert_eq!(image.get_pixel(3, 0).0, [255, 0, 0, 255]);
+	assert_eq!(image.get_pixel(3, 11).0, [0, 0, 255, 255]);
+}
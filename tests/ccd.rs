@@ -0,0 +1,85 @@
+mod common;
+
+use common::{floor, DT};
+use gl_engine::{heightfield::Heightfield, math_structs::Vec3, object::Object, physics::Ccd, shape::Shape, world::{PhysicsConfig, PhysicsWorld}};
+
+
+fn body(shape: Shape, position: Vec3, ccd: Ccd) -> Object {
+	let mut body = common::body(shape, position);
+	body.ccd = ccd;
+	body
+}
+
+// A long bar level above the floor, turning most of the way round in one step. Its ends swing down through
+// the floor and back up on the far side, while the straight line between where they start and end stays above it.
+fn spinning_bar(ccd: Ccd) -> PhysicsWorld {
+	let mut world = PhysicsWorld::new(PhysicsConfig { gravity: Vec3(0.0, 0.0, 0.0), ..Default::default() });
+	world.add_body(floor());
+	let mut bar = body(Shape::Box { half_extents: Vec3(2.0, 0.05, 0.05) }, Vec3(0.0, 1.0, 0.0), ccd);
+	bar.angular_velocity = Vec3(0.0, 0.0, 170f32.to_radians() / DT);
+	world.add_body(bar);
+	world
+}
+
+
+#[test]
+fn spinning_bar_hits_the_floor_it_swings_through() {
+	let mut world = spinning_bar(Ccd::ConservativeAdvancement);
+	let spin = world.body(1).unwrap().angular_velocity;
+	world.step(DT);
+	
+	let impact = world.events().iter().find(|e| e.bodies == (0, 1) && e.impulse > 0.0).expect("no impact with the floor");
+	assert!(impact.point.1.abs() < 0.1 && impact.normal.1 < -0.99, "impact at {:?} along {:?}", impact.point, impact.normal);
+	assert!(world.body(1).unwrap().angular_velocity.2 < 0.5 * spin.2, "still spinning at {:?}", world.body(1).unwrap().angular_velocity);
+	
+	// what hit never got into the floor
+	let bar = world.body(1).unwrap();
+	let lowest = [-1.0, 1.0].iter().map(|&x| Vec3(2.0 * x, -0.05, 0.0).apply_transform(&bar.transform).1).fold(f32::INFINITY, f32::min);
+	assert!(lowest > -0.05, "end at {} under the floor", lowest);
+}
+
+#[test]
+fn linear_sweep_misses_the_arc() {
+	// the case above with the chords the linear sweep uses, which never come near the floor
+	let mut world = spinning_bar(Ccd::Linear);
+	let spin = world.body(1).unwrap().angular_velocity;
+	world.step(DT);
+	assert!(world.events().is_empty());
+	assert_eq!(world.body(1).unwrap().angular_velocity, spin);
+}
+
+#[test]
+fn fast_projectiles_stop_either_way() {
+	// without spin both modes catch it
+	for ccd in [Ccd::Linear, Ccd::ConservativeAdvancement] {
+		let mut world = PhysicsWorld::new(PhysicsConfig::default());
+		world.add_body(floor());
+		let mut bullet = body(Shape::Box { half_extents: Vec3(0.1, 0.1, 0.1) }, Vec3(0.0, 5.0, 0.0), ccd);
+		bullet.velocity = Vec3(3.0, -900.0, 0.0);
+		world.add_body(bullet);
+		
+		world.step(DT);
+		let position = world.body(1).unwrap().transform.get_position();
+		assert!(position.1 > 0.0, "{:?} went through to {:?}", ccd, position);
+		assert!(world.body(1).unwrap().velocity.1 > -1.0, "{:?} still going down at {:?}", ccd, world.body(1).unwrap().velocity);
+	}
+}
+
+#[test]
+fn spinners_settle_on_terrain() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(Object::from_heightfield(Heightfield::new(33, 33, 1.0, &[0.0; 33 * 33])));
+	let mut top = body(Shape::Box { half_extents: Vec3(0.8, 0.1, 0.3) }, Vec3(0.0, 2.0, 0.0), Ccd::ConservativeAdvancement);
+	top.angular_velocity = Vec3(40.0, 0.0, 25.0);
+	top.velocity = Vec3(0.0, -20.0, 0.0);
+	let top = world.add_body(top);
+	
+	for _ in 0..600 {
+		world.step(DT);
+		let diagnostics = world.diagnostics();
+		assert!(diagnostics.max_penetration < 0.02, "sank {} into the terrain", diagnostics.max_penetration);
+	}
+	let top = world.body(top).unwrap();
+	assert!(top.sleeping, "never came to rest, at {:?}", top.transform.get_position());
+	assert!(top.transform.get_position().1 > 0.05 && top.transform.get_position().1 < 0.35, "rests at {:?}", top.transform.get_position());
+}
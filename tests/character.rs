@@ -0,0 +1,148 @@
+mod common;
+
+use common::{floor, DT};
+use gl_engine::{character::CharacterController, math_structs::{Mat4, Vec3}, object::Object, shape::Shape, world::{PhysicsConfig, PhysicsWorld}};
+
+
+static GRAVITY: Vec3 = Vec3(0.0, -9.8, 0.0);
+
+
+fn static_box(half_extents: Vec3, transform: Mat4) -> Object {
+	let mut block = Object::new(&[], &[]);
+	block.set_shape(Shape::Box { half_extents });
+	block.set_static();
+	block.transform = transform;
+	block
+}
+
+// a capsule 1.6 tall standing on the floor at x, z
+fn character(x: f32, z: f32) -> CharacterController {
+	CharacterController::new(Vec3(x, 0.81, z), 0.3, 0.5)
+}
+
+fn walk(character: &mut CharacterController, world: &PhysicsWorld, velocity: Vec3, steps: usize) {
+	for _ in 0..steps {
+		character.update(world.bodies(), velocity, false, GRAVITY, DT);
+	}
+}
+
+
+#[test]
+fn walks_up_low_steps_but_not_walls() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	world.add_body(static_box(Vec3(1.0, 0.1, 2.0), Mat4::identity().translate(Vec3(2.0, 0.1, 0.0))));
+	world.add_body(static_box(Vec3(1.0, 0.5, 2.0), Mat4::identity().translate(Vec3(2.0, 0.5, 5.0))));
+	
+	let mut low = character(0.0, 0.0);
+	walk(&mut low, &world, Vec3(2.0, 0.0, 0.0), 60);
+	assert!(low.position.0 > 1.5, "stopped at {:?}", low.position);
+	assert!((low.feet_position().1 - 0.2).abs() < 0.03 && low.grounded, "feet at {:?}", low.feet_position());
+	
+	let mut high = character(0.0, 5.0);
+	walk(&mut high, &world, Vec3(2.0, 0.0, 0.0), 60);
+	assert!(high.position.0 < 1.0 - 0.3 + 0.02 && high.position.0 > 0.6, "ended at {:?}", high.position);
+	assert!(high.feet_position().1.abs() < 0.03 && high.grounded, "feet at {:?}", high.feet_position());
+}
+
+#[test]
+fn slides_along_walls() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	world.add_body(static_box(Vec3(0.5, 2.0, 5.0), Mat4::identity().translate(Vec3(1.5, 2.0, 0.0))));
+	
+	let mut walker = character(0.0, -2.0);
+	walk(&mut walker, &world, Vec3(2.0, 0.0, 2.0), 60);
+	// into the wall at 45 degrees, the part along it carries on
+	assert!(walker.position.0 < 1.0 - 0.3 + 0.001 && walker.position.0 > 0.6, "at {:?}", walker.position);
+	assert!((walker.position.2 - 0.0).abs() < 0.1, "only got to {:?}", walker.position);
+	assert!(walker.grounded);
+}
+
+#[test]
+fn stays_grounded_on_slopes() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	// a 20 degree ramp rising towards +x, with the middle of its top face 1.5 up
+	let angle = 20.0f32.to_radians();
+	let ramp = Mat4::identity().rotate_z(angle).set_position(Vec3(0.5 * angle.sin(), 1.5 - 0.5 * angle.cos(), 0.0));
+	world.add_body(static_box(Vec3(4.0, 0.5, 2.0), ramp));
+	
+	let surface = |x: f32| 1.5 + x * angle.tan();
+	let mut walker = CharacterController::new(Vec3(-2.0, surface(-2.0) + 0.81 / angle.cos(), 0.0), 0.3, 0.5);
+	for direction in [1.0, -1.0] {
+		for step in 0..60 {
+			walker.update(world.bodies(), Vec3(2.0 * direction, 0.0, 0.0), false, GRAVITY, DT);
+			assert!(walker.grounded, "left the slope at step {} at {:?}", step, walker.position);
+			assert!((walker.ground_normal.dot(Vec3(0.0, 1.0, 0.0)) - angle.cos()).abs() < 1e-3, "{:?}", walker.ground_normal);
+		}
+	}
+	assert!((walker.position.0 + 2.0).abs() < 0.1, "back at {:?}", walker.position);
+}
+
+#[test]
+fn pushes_out_of_what_it_starts_in() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	world.add_body(static_box(Vec3(1.0, 1.0, 1.0), Mat4::identity().translate(Vec3(0.0, 1.0, 0.0))));
+	
+	// overlapping the side of the box by 0.2
+	let mut stuck = character(1.1, 0.0);
+	stuck.update(world.bodies(), Vec3(0.0, 0.0, 0.0), false, GRAVITY, DT);
+	assert!(stuck.position.0 >= 1.3 - 1e-3 && stuck.position.0 < 1.4, "at {:?}", stuck.position);
+	assert!(stuck.position.2.abs() < 1e-3);
+}
+
+#[test]
+fn jumps_only_from_the_ground() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	let mut jumper = character(0.0, 0.0);
+	walk(&mut jumper, &world, Vec3(0.0, 0.0, 0.0), 5);
+	assert!(jumper.grounded);
+	
+	// held all the way up, which doesn't jump again in the air
+	let mut highest: f32 = 0.0;
+	let mut landed = None;
+	for step in 0..90 {
+		jumper.update(world.bodies(), Vec3(0.0, 0.0, 0.0), landed.is_none(), GRAVITY, DT);
+		highest = highest.max(jumper.feet_position().1);
+		if step == 0 { assert!(!jumper.grounded && jumper.velocity.1 > 0.0, "didn't take off, {:?}", jumper.velocity); }
+		if step > 0 && landed.is_none() && jumper.grounded { landed = Some(step); }
+	}
+	// up to v^2 / 2g, and back down after 2v / g
+	let peak = jumper.jump_speed * jumper.jump_speed / (2.0 * -GRAVITY.1);
+	assert!((highest - peak).abs() < 0.1, "peaked at {} instead of {}", highest, peak);
+	let airtime = 2.0 * jumper.jump_speed / -GRAVITY.1 / DT;
+	assert!(landed.is_some_and(|step| (step as f32 - airtime).abs() < 3.0), "landed at step {:?} instead of {}", landed, airtime);
+	assert!(jumper.grounded && jumper.feet_position().1.abs() < 0.03, "feet at {:?}", jumper.feet_position());
+}
+
+#[test]
+fn slides_down_slopes_steeper_than_the_limit() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	// a 60 degree ramp rising towards +x, past the 45 degree limit, with the middle of its top face 3 up
+	let angle = 60.0f32.to_radians();
+	let ramp = Mat4::identity().rotate_z(angle).set_position(Vec3(0.5 * angle.sin(), 3.0 - 0.5 * angle.cos(), 0.0));
+	world.add_body(static_box(Vec3(4.0, 0.5, 2.0), ramp));
+	
+	// resting against it, where the bottom cap touches the face
+	let surface = |x: f32| 3.0 + x * angle.tan();
+	let resting = 0.3 / angle.cos() + 0.5;
+	let mut slider = CharacterController::new(Vec3(0.0, surface(0.0) + resting + 0.01, 0.0), 0.3, 0.5);
+	assert!(!slider.is_walkable(Vec3(-angle.sin(), angle.cos(), 0.0)));
+	
+	// walking up it doesn't help
+	let mut previous = slider.position;
+	for step in 0..60 {
+		slider.update(world.bodies(), Vec3(2.0, 0.0, 0.0), false, GRAVITY, DT);
+		assert!(!slider.grounded, "stood on the slope at step {} at {:?}", step, slider.position);
+		assert!(slider.position.1 < previous.1, "climbed at step {} to {:?}", step, slider.position);
+		previous = slider.position;
+	}
+	assert!(slider.position.0 < -0.1, "only slid to {:?}", slider.position);
+	// along the face rather than falling through or away from it
+	let above = slider.position.1 - surface(slider.position.0);
+	assert!((above - resting).abs() < 0.02, "{} above the slope at {:?}", above, slider.position);
+}
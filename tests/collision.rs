@@ -0,0 +1,104 @@
+mod common;
+
+use common::{assert_matrices_near, body, DT};
+use gl_engine::{math_structs::{Mat4, Vec3}, object::Object, query::QueryFilter, shape::Shape, world::{PhysicsConfig, PhysicsWorld}};
+
+
+fn octahedron() -> Shape {
+	Shape::convex_hull(&[Vec3(1.0, 0.0, 0.0), Vec3(-1.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0), Vec3(0.0, -1.0, 0.0), Vec3(0.0, 0.0, 1.0), Vec3(0.0, 0.0, -1.0)])
+}
+
+fn cube_hull(half: f32) -> Shape {
+	Shape::convex_hull(&(0..8).map(|i| Vec3(
+		if i & 4 == 0 { -half } else { half },
+		if i & 2 == 0 { -half } else { half },
+		if i & 1 == 0 { -half } else { half }
+	)).collect::<Vec<Vec3>>())
+}
+
+// the deepest contact between the two bodies, from a step without gravity, with the static one first
+fn deepest_contact(a: Object, b: Object) -> (Vec3, f32) {
+	let mut world = PhysicsWorld::new(PhysicsConfig { gravity: Vec3(0.0, 0.0, 0.0), ..PhysicsConfig::default() });
+	let mut a = a;
+	a.set_static();
+	world.add_body(a);
+	world.add_body(b);
+	world.step(DT);
+	let manifold = world.manifold(0, 1).expect("no contact");
+	let deepest = manifold.points.iter().min_by(|p, q| p.separation.total_cmp(&q.separation)).unwrap();
+	(deepest.normal, deepest.separation)
+}
+
+
+#[test]
+fn gjk_finds_the_gap_between_hulls() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(body(octahedron(), Vec3(0.0, 0.0, 0.0)));
+	
+	// tip to tip along y, then tip to face along a diagonal
+	let start = Mat4::identity().translate(Vec3(0.0, 3.0, 0.0));
+	let hit = world.shape_cast(&octahedron(), &start, Vec3(0.0, -1.0, 0.0), 5.0, &QueryFilter::default()).unwrap();
+	assert!((hit.distance - 1.0).abs() < 1e-3, "{:?}", hit);
+	
+	let direction = Vec3(-1.0, -1.0, -1.0).normalize();
+	let start = Mat4::identity().translate(Vec3(3.0, 3.0, 3.0));
+	let hit = world.shape_cast(&Shape::Sphere { radius: 0.0 }, &start, direction, 10.0, &QueryFilter::default()).unwrap();
+	// the face x + y + z = 1 is 1 / sqrt 3 from the middle
+	assert!((hit.distance - (27.0f32.sqrt() - 1.0 / 3.0f32.sqrt())).abs() < 1e-3, "{:?}", hit);
+	assert!((hit.normal - Vec3(1.0, 1.0, 1.0).normalize()).length() < 1e-2, "{:?}", hit);
+	
+	// just outside a face, and just inside
+	assert!(world.point_overlaps(Vec3(0.34, 0.34, 0.34), &QueryFilter::default()).is_empty());
+	assert_eq!(world.point_overlaps(Vec3(0.32, 0.32, 0.32), &QueryFilter::default()), vec![0]);
+}
+
+#[test]
+fn epa_finds_the_penetration_depth() {
+	// the hulls overlap by 0.1 in y and much more across, so y is the way out
+	let (normal, separation) = deepest_contact(body(cube_hull(1.0), Vec3(0.0, 0.0, 0.0)), body(cube_hull(1.0), Vec3(0.5, 1.9, 0.3)));
+	assert!((separation + 0.1).abs() < 1e-3, "separation {}", separation);
+	assert!(normal.1.abs() > 0.999, "normal {:?}", normal);
+	
+	let (normal, separation) = deepest_contact(body(octahedron(), Vec3(0.0, 0.0, 0.0)), body(octahedron(), Vec3(1.9, 0.0, 0.0)));
+	// tip to tip they overlap by 0.1 along x, but sliding out across the faces is shallower
+	assert!((separation + 0.1 / 3.0f32.sqrt()).abs() < 1e-3, "separation {}", separation);
+	assert!(normal.0.abs() > 0.5 && normal.0.abs() < 0.6, "normal {:?}", normal);
+}
+
+#[test]
+fn box_box_picks_the_shallowest_axis() {
+	let (normal, separation) = deepest_contact(body(Shape::Box { half_extents: Vec3(1.0, 1.0, 1.0) }, Vec3(0.0, 0.0, 0.0)), body(Shape::Box { half_extents: Vec3(1.0, 1.0, 1.0) }, Vec3(0.3, -0.2, 1.85)));
+	assert!((separation + 0.15).abs() < 1e-3, "separation {}", separation);
+	assert!(normal.2.abs() > 0.999, "normal {:?}", normal);
+	
+	// turned about y, the x and z overlaps only grow
+	let mut turned = body(Shape::Box { half_extents: Vec3(0.5, 0.5, 0.5) }, Vec3(0.0, 0.0, 0.0));
+	turned.transform = Mat4::identity().rotate_y(0.7).translate(Vec3(0.2, 1.4, 0.1));
+	let (normal, separation) = deepest_contact(body(Shape::Box { half_extents: Vec3(1.0, 1.0, 1.0) }, Vec3(0.0, 0.0, 0.0)), turned);
+	assert!((separation + 0.1).abs() < 1e-3, "separation {}", separation);
+	assert!(normal.1.abs() > 0.999, "normal {:?}", normal);
+}
+
+#[test]
+fn hull_mass_comes_from_its_points() {
+	// a cube hull with a point inside it weighs the same as the box
+	let mut points = (0..8).map(|i| Vec3(
+		if i & 4 == 0 { -1.0 } else { 1.0 },
+		if i & 2 == 0 { -0.5 } else { 0.5 },
+		if i & 1 == 0 { -0.25 } else { 0.25 }
+	)).collect::<Vec<Vec3>>();
+	points.push(Vec3(0.1, 0.2, 0.0));
+	let hull = body(Shape::convex_hull(&points), Vec3(0.0, 0.0, 0.0));
+	let cube = body(Shape::Box { half_extents: Vec3(1.0, 0.5, 0.25) }, Vec3(0.0, 0.0, 0.0));
+	assert!((hull.volume - 1.0).abs() < 1e-4, "{}", hull.volume);
+	assert_matrices_near(&hull.inverse_inertia, &cube.inverse_inertia, 1e-3 * cube.inverse_inertia.trace());
+	
+	// and hulls off the origin are moved onto their center of mass like meshes
+	let offset = body(Shape::convex_hull(&[Vec3(2.0, 0.0, 0.0), Vec3(3.0, 0.0, 0.0), Vec3(2.0, 1.0, 0.0), Vec3(2.0, 0.0, 1.0)]), Vec3(0.0, 0.0, 0.0));
+	assert!((offset.mass() - 1.0 / 6.0).abs() < 1e-5, "{}", offset.mass());
+	assert!((offset.transform.get_position() - Vec3(2.25, 0.25, 0.25)).length() < 1e-5, "{:?}", offset.transform.get_position());
+	
+	// flat hulls have no volume
+	let flat = body(Shape::convex_hull(&[Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), Vec3(0.0, 0.0, 1.0), Vec3(1.0, 0.0, 1.0)]), Vec3(0.0, 0.0, 0.0));
+	assert!(flat.is_static());
+}
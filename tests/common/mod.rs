@@ -0,0 +1,53 @@
+// Fixtures shared by the integration tests, each of which only uses some of them.
+#![allow(dead_code)]
+
+use gl_engine::{headless::HeadlessDisplay, math_structs::{Mat3, Vec3}, object::Object, shape::Shape};
+
+
+pub static DT: f32 = 1.0 / 60.0;
+
+
+// a 20 by 20 meter quad facing up at y = 0, static since it encloses no volume
+pub fn floor() -> Object {
+	let floor = [Vec3(-10.0, 0.0, -10.0), Vec3(-10.0, 0.0, 10.0), Vec3(10.0, 0.0, -10.0), Vec3(10.0, 0.0, 10.0)];
+	Object::new(&floor, &[(0, 2, 3), (0, 3, 1)])
+}
+
+// a dynamic body of the shape at unit density
+pub fn body(shape: Shape, position: Vec3) -> Object {
+	let mut body = Object::new(&[], &[]);
+	body.set_shape(shape);
+	body.transform = body.transform.translate(position);
+	body
+}
+
+// a box mesh with its corners around center instead of the model origin
+pub fn box_mesh(half_extents: Vec3, center: Vec3) -> Object {
+	let Vec3(x, y, z) = half_extents;
+	let corners = [
+		Vec3(-x, -y, -z), Vec3(-x, -y, z), Vec3(-x, y, -z), Vec3(-x, y, z),
+		Vec3(x, -y, -z), Vec3(x, -y, z), Vec3(x, y, -z), Vec3(x, y, z)
+	].map(|corner| corner + center);
+	Object::new(&corners, &[(0, 2, 3), (0, 3, 1), (0, 1, 5), (0, 5, 4), (0, 4, 6), (0, 6, 2), (7, 2, 6), (7, 6, 4), (7, 4, 5), (7, 5, 1), (7, 1, 3), (7, 3, 2)])
+}
+
+// None where there is no EGL to render with, which the rendering tests then skip
+pub fn display() -> Option<HeadlessDisplay> {
+	match HeadlessDisplay::new() {
+		Ok(display) => Some(display),
+		Err(message) => {
+			eprintln!("skipping, no headless OpenGL: {}", message);
+			None
+		}
+	}
+}
+
+pub fn assert_near(value: f32, expected: f32, tolerance: f32, what: &str) {
+	assert!((value - expected).abs() <= tolerance, "{}: {} is not within {} of {}", what, value, tolerance, expected);
+}
+
+pub fn assert_matrices_near(a: &Mat3, b: &Mat3, tolerance: f32) {
+	for column in 0..3 {
+		assert!((a.column(column) - b.column(column)).length() <= tolerance, "{:?} is not {:?}", a, b);
+	}
+}
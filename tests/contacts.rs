@@ -0,0 +1,39 @@
+mod common;
+
+use common::{body, floor, DT};
+use gl_engine::{heightfield::Heightfield, math_structs::Vec3, object::Object, shape::Shape, world::{PhysicsConfig, PhysicsWorld}};
+
+
+#[test]
+fn resting_contacts_are_under_the_body() {
+	// The grid triangles next to the one the sphere is over see it too. Their contacts go straight down
+	// onto their plane rather than towards one of their corners, and then fall outside them.
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(Object::from_heightfield(Heightfield::new(9, 9, 1.0, &[0.0; 81])));
+	let sphere = world.add_body(body(Shape::Sphere { radius: 0.5 }, Vec3(0.6, 0.5, 0.4)));
+	for _ in 0..30 {
+		world.step(DT);
+	}
+	let manifold = world.manifold(0, sphere).unwrap();
+	assert!(!manifold.points.is_empty());
+	for point in manifold.points.iter() {
+		assert!((point.position - Vec3(0.6, point.position.1, 0.4)).length() < 0.01, "contact at {:?}", point.position);
+	}
+	let position = world.body(sphere).unwrap().transform.get_position();
+	assert!((position - Vec3(0.6, 0.5, 0.4)).length() < 0.01, "rolled to {:?}", position);
+}
+
+#[test]
+fn a_box_landing_flat_doesnt_spin() {
+	// too fast for contacts alone, the ccd impact takes the middle of the face that lands instead of one corner
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	let mut cube = body(Shape::Box { half_extents: Vec3(0.5, 0.5, 0.5) }, Vec3(0.0, 2.0, 0.0));
+	cube.velocity = Vec3(0.0, -90.0, 0.0);
+	let cube = world.add_body(cube);
+	world.step(DT);
+	
+	let cube = world.body(cube).unwrap();
+	assert!(cube.transform.get_position().1 > 0.4, "went through to {:?}", cube.transform.get_position());
+	assert!(cube.angular_velocity.length() < 1e-3, "spinning at {:?}", cube.angular_velocity);
+}
@@ -0,0 +1,50 @@
+use gl_engine::{debug::{DebugBody, DebugContact, DebugFrame}, math_structs::Vec3, physics::Aabb};
+
+
+fn debug_body(center: Vec3, sleeping: bool, is_static: bool) -> DebugBody {
+	DebugBody {
+		aabb: Aabb { min: center - Vec3(0.5, 0.5, 0.5), max: center + Vec3(0.5, 0.5, 0.5) },
+		center,
+		velocity: Vec3(1.0, 0.0, 0.0),
+		angular_velocity: Vec3(0.0, 2.0, 0.0),
+		sleeping,
+		is_static
+	}
+}
+
+
+#[test]
+fn lines_outline_bodies_and_contacts() {
+	let contact = DebugContact { point: Vec3(0.0, 1.0, 0.0), normal: Vec3(0.0, 1.0, 0.0), separation: -0.01 };
+	let speculative = DebugContact { separation: 0.02, ..contact };
+	let frame = DebugFrame {
+		bodies: vec![debug_body(Vec3(0.0, -1.0, 0.0), false, true), debug_body(Vec3(3.0, 1.0, 0.0), true, false), debug_body(Vec3(-3.0, 1.0, 0.0), false, false)],
+		contacts: vec![contact, speculative]
+	};
+	let lines = frame.lines();
+	
+	// a cross and a normal per contact, then the box of every body, with a cross at the center of the
+	// dynamic ones and velocity arrows on the awake one
+	assert_eq!(lines.len(), 2 * (3 + 1) + 12 + (12 + 3) + (12 + 3 + 2));
+	let (penetrating, speculative) = (lines[0].2, lines[4].2);
+	assert_ne!(penetrating, speculative);
+	assert!(lines[..4].iter().all(|line| line.2 == penetrating) && lines[4..8].iter().all(|line| line.2 == speculative));
+	assert_eq!((lines[3].0, lines[3].1), (Vec3(0.0, 1.0, 0.0), Vec3(0.0, 1.5, 0.0)));
+	
+	// the box edges run between its corners, each along one axis
+	let box_lines = &lines[8..20];
+	for &(start, end, _) in box_lines {
+		let edge = end - start;
+		assert_eq!([edge.0, edge.1, edge.2].iter().filter(|&&d| d != 0.0).count(), 1, "{:?}", edge);
+		assert!((edge.length() - 1.0).abs() < 1e-6);
+	}
+	let colors = [lines[8].2, lines[20].2, lines[35].2];
+	assert!(colors[0] != colors[1] && colors[1] != colors[2] && colors[0] != colors[2], "static, sleeping and awake share colors");
+	
+	// the arrows start at the center and show where it goes in a fraction of a second
+	let arrows = &lines[lines.len() - 2..];
+	assert!(arrows.iter().all(|&(start, _, _)| start == Vec3(-3.0, 1.0, 0.0)));
+	assert!((arrows[0].1 - arrows[0].0).normalize() == Vec3(1.0, 0.0, 0.0) && (arrows[1].1 - arrows[1].0).normalize() == Vec3(0.0, 1.0, 0.0));
+	
+	assert!(DebugFrame::default().lines().is_empty());
+}
@@ -0,0 +1,211 @@
+mod common;
+
+use common::floor;
+use gl_engine::{joint::Joint, math_structs::{Mat4, Vec3}, object::Object, shape::Shape, snapshot::{Snapshot, SoftBodyState}, soft_body::SoftBody, world::{PhysicsConfig, PhysicsWorld}};
+
+
+fn build_world() -> PhysicsWorld {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	
+	world.add_body(floor());
+	
+	let shapes = [
+		Shape::Box { half_extents: Vec3(0.5, 0.5, 0.5) },
+		Shape::Sphere { radius: 0.4 },
+		Shape::Capsule { half_height: 0.3, radius: 0.25 },
+		Shape::Box { half_extents: Vec3(0.8, 0.2, 0.4) }
+	];
+	for i in 0..12 {
+		let mut body = Object::new(&[], &[]);
+		body.set_shape(shapes[i % shapes.len()].clone());
+		body.transform = body.transform.rotate_axis(Vec3(1.0, 0.5, 0.25).normalize(), 0.3 * i as f32).translate(Vec3((i % 3) as f32 * 0.7 - 0.7, 1.0 + i as f32 * 1.1, (i % 2) as f32 * 0.3));
+		body.angular_velocity = Vec3(0.0, 0.5 * i as f32, 0.0);
+		world.add_body(body);
+	}
+	
+	let anchor = world.body(12).unwrap().transform.get_position() + Vec3(0.0, 1.0, 0.0);
+	world.add_joint(Joint::ball_socket(world.bodies(), 12, None, anchor));
+	world
+}
+
+
+#[test]
+fn identical_runs_hash_identically() {
+	let (mut first, mut second) = (build_world(), build_world());
+	for _ in 0..300 {
+		first.step(1.0 / 60.0);
+		second.step(1.0 / 60.0);
+	}
+	assert_eq!(first.state_hash(), second.state_hash());
+	assert_ne!(first.state_hash(), build_world().state_hash());
+}
+
+#[test]
+fn advance_does_not_depend_on_how_time_is_sliced() {
+	let (mut stepped, mut advanced) = (build_world(), build_world());
+	
+	// irregular wall ticks, several of them shorter than a step
+	let ticks = [0.004, 0.021, 0.0166, 0.033, 0.009, 0.05];
+	for k in 0..200 {
+		advanced.advance(ticks[k % ticks.len()]);
+	}
+	while stepped.step_count() < advanced.step_count() {
+		stepped.step(1.0 / 60.0);
+	}
+	assert_eq!(stepped.state_hash(), advanced.state_hash());
+}
+
+#[test]
+fn advance_clamps_long_stalls() {
+	let mut world = build_world();
+	let steps = world.advance(2.0);
+	assert_eq!(steps, world.config.max_steps_per_advance);
+	
+	// the stall is dropped, not paid back over the following ticks
+	assert_eq!(world.advance(0.0), 0);
+}
+
+#[test]
+fn restoring_a_snapshot_replays_identically() {
+	let mut world = build_world();
+	for _ in 0..120 {
+		world.step(1.0 / 60.0);
+	}
+	let snapshot = world.snapshot();
+	for _ in 0..120 {
+		world.step(1.0 / 60.0);
+	}
+	let (hash, end) = (world.state_hash(), world.snapshot());
+	
+	// mid pile up, so the contacts and warm started impulses have to come back too
+	world.restore(&snapshot).unwrap();
+	assert_eq!(world.snapshot(), snapshot);
+	for _ in 0..120 {
+		world.step(1.0 / 60.0);
+	}
+	assert_eq!(world.state_hash(), hash);
+	assert!(world.snapshot().differing_bodies(&end).is_empty());
+	assert!(!snapshot.differing_bodies(&end).is_empty());
+}
+
+#[test]
+fn snapshots_of_other_worlds_are_refused() {
+	let mut world = build_world();
+	world.step(1.0 / 60.0);
+	let snapshot = world.snapshot();
+	world.step(1.0 / 60.0);
+	let before = world.snapshot();
+	
+	// a body short, a joint more, and a soft body that isn't there
+	let mut fewer_bodies = snapshot.clone();
+	fewer_bodies.bodies.pop();
+	let mut more_joints = snapshot.clone();
+	more_joints.joint_impulses.push([0.0; 9]);
+	let mut extra_soft_body = snapshot.clone();
+	extra_soft_body.soft_bodies.push(SoftBodyState { positions: vec![Vec3(0.0, 0.0, 0.0)].into_boxed_slice(), velocities: vec![Vec3(0.0, 0.0, 0.0)].into_boxed_slice() });
+	for mismatched in [fewer_bodies, more_joints, extra_soft_body] {
+		assert!(world.restore(&mismatched).is_err());
+		assert_eq!(world.snapshot(), before, "a refused snapshot was partly applied");
+	}
+	
+	// soft bodies with a different number of particles
+	let (vertices, indices) = SoftBody::cloth_mesh(1.0, 1.0, 2, 2);
+	world.add_soft_body(SoftBody::new(&vertices, &indices, &Mat4::identity().translate(Vec3(5.0, 2.0, 0.0)), 1.0, 1000.0, 1.0).unwrap());
+	let mut fewer_particles = world.snapshot();
+	fewer_particles.soft_bodies[0].positions = fewer_particles.soft_bodies[0].positions[1..].to_vec().into_boxed_slice();
+	fewer_particles.soft_bodies[0].velocities = fewer_particles.soft_bodies[0].velocities[1..].to_vec().into_boxed_slice();
+	let before = world.snapshot();
+	assert!(world.restore(&fewer_particles).is_err());
+	assert_eq!(world.snapshot(), before);
+}
+
+#[test]
+fn snapshots_round_trip_through_bytes() {
+	let mut world = build_world();
+	for _ in 0..90 {
+		world.step(1.0 / 60.0);
+	}
+	let snapshot = world.snapshot();
+	let bytes = snapshot.to_bytes();
+	assert_eq!(Snapshot::from_bytes(&bytes), Some(snapshot));
+	
+	// cut short anywhere, with anything after the end, or with a broken header, it doesn't read
+	for length in (0..bytes.len()).step_by(5) {
+		assert_eq!(Snapshot::from_bytes(&bytes[..length]), None, "read {} of {} bytes", length, bytes.len());
+	}
+	assert_eq!(Snapshot::from_bytes(&[bytes.as_slice(), &[0]].concat()), None);
+	let mut corrupted = bytes.clone();
+	corrupted[0] ^= 0xff;
+	assert_eq!(Snapshot::from_bytes(&corrupted), None);
+}
+
+#[test]
+fn snapshots_with_contacts_between_missing_bodies_are_refused() {
+	let mut world = build_world();
+	for _ in 0..90 {
+		world.step(1.0 / 60.0);
+	}
+	let snapshot = world.snapshot();
+	let &(a, b) = snapshot.contacts.manifolds.keys().next().expect("nothing touching");
+	
+	// one manifold moved over to a body past the end
+	let mut missing_body = snapshot.clone();
+	let manifold = missing_body.contacts.manifolds.remove(&(a, b)).unwrap();
+	missing_body.contacts.manifolds.insert((a, world.len()), manifold);
+	// and one point with a feature no contact could have
+	let mut unknown_feature = snapshot.clone();
+	unknown_feature.contacts.manifolds.get_mut(&(a, b)).unwrap().points[0].feature = u64::MAX;
+	
+	for bad in [missing_body, unknown_feature] {
+		let before = world.snapshot();
+		assert!(world.restore(&bad).is_err());
+		assert_eq!(world.snapshot(), before, "a refused snapshot was partly applied");
+		world.step(1.0 / 60.0);
+	}
+	assert!(world.restore(&snapshot).is_ok());
+}
+
+#[test]
+fn parallel_and_serial_narrow_phase_agree() {
+	let (mut serial, mut parallel) = (build_world(), build_world());
+	serial.config.parallel_narrow_phase = false;
+	parallel.config.parallel_narrow_phase = true;
+	
+	// thrown at the floor hard enough that the ccd pass has impacts to order
+	for world in [&mut serial, &mut parallel] {
+		for i in 1..world.len() {
+			world.body_mut(i).unwrap().velocity = Vec3(0.0, -40.0 - i as f32, 0.0);
+		}
+	}
+	for _ in 0..120 {
+		serial.step(1.0 / 60.0);
+		parallel.step(1.0 / 60.0);
+	}
+	assert_eq!(serial.state_hash(), parallel.state_hash());
+}
+
+#[test]
+fn snapshots_compare_every_bit() {
+	let mut world = build_world();
+	world.step(1.0 / 60.0);
+	
+	// a NaN is the same as itself, so two runs that both went bad agree
+	let mut nan = world.snapshot();
+	nan.bodies[3].velocity.0 = f32::NAN;
+	assert_eq!(nan, nan.clone());
+	assert!(nan.differing_bodies(&nan.clone()).is_empty());
+	
+	// but the sign of a zero is a difference between them
+	let mut positive = world.snapshot();
+	positive.bodies[5].angular_velocity.2 = 0.0;
+	let mut negative = positive.clone();
+	negative.bodies[5].angular_velocity.2 = -0.0;
+	assert_ne!(positive, negative);
+	assert_eq!(positive.differing_bodies(&negative), vec![5]);
+	
+	let mut soft_positive = positive.clone();
+	soft_positive.soft_bodies.push(SoftBodyState { positions: vec![Vec3(0.0, 0.0, 0.0)].into_boxed_slice(), velocities: vec![Vec3(0.0, 0.0, 0.0)].into_boxed_slice() });
+	let mut soft_negative = soft_positive.clone();
+	soft_negative.soft_bodies[0].velocities[0].1 = -0.0;
+	assert_ne!(soft_positive.soft_bodies[0], soft_negative.soft_bodies[0]);
+}
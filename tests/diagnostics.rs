@@ -0,0 +1,100 @@
+mod common;
+
+use common::{assert_near, body, floor, DT};
+use gl_engine::{math_structs::Vec3, shape::Shape, world::{PhysicsConfig, PhysicsWorld}};
+
+
+#[test]
+fn free_fall_keeps_its_energy() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	let mut sphere = body(Shape::Sphere { radius: 0.5 }, Vec3(0.0, 10.0, 0.0));
+	sphere.velocity = Vec3(1.0, 2.0, 0.0);
+	sphere.angular_velocity = Vec3(0.0, 0.0, 3.0);
+	world.add_body(sphere);
+	
+	let start = world.measure();
+	for _ in 0..60 {
+		world.step(DT);
+		// semi implicit euler loses a little each step, but not more than a percent over a second
+		let energy = world.diagnostics().total_energy();
+		assert_near(energy, start.total_energy(), 0.01 * start.total_energy().abs(), "total energy");
+	}
+	assert_eq!(world.diagnostics().max_penetration, 0.0);
+}
+
+#[test]
+fn bouncing_never_gains_energy() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	let mut sphere = body(Shape::Sphere { radius: 0.5 }, Vec3(0.0, 3.0, 0.0));
+	sphere.restitution = 0.9;
+	world.add_body(sphere);
+	
+	let start = world.measure().total_energy();
+	let mut bounced = false;
+	for _ in 0..600 {
+		world.step(DT);
+		let diagnostics = world.diagnostics();
+		assert!(diagnostics.total_energy() <= start + 0.02 * start.abs(), "energy went up to {} from {}", diagnostics.total_energy(), start);
+		assert!(diagnostics.max_penetration < 0.05, "sank {} into the floor", diagnostics.max_penetration);
+		bounced |= world.body(1).unwrap().velocity.1 > 1.0;
+	}
+	assert!(bounced);
+}
+
+#[test]
+fn momentum_is_conserved_without_gravity() {
+	// spheres, so spinning needs no gyroscopic term and any change would come from the contacts
+	let mut world = PhysicsWorld::new(PhysicsConfig { gravity: Vec3(0.0, 0.0, 0.0), ..Default::default() });
+	let mut left = body(Shape::Sphere { radius: 0.4 }, Vec3(-3.0, 0.2, 0.0));
+	left.velocity = Vec3(4.0, 0.0, 0.5);
+	left.angular_velocity = Vec3(0.3, 1.0, 0.0);
+	let mut right = body(Shape::Sphere { radius: 0.5 }, Vec3(3.0, -0.2, 0.1));
+	right.velocity = Vec3(-2.0, 0.0, 0.0);
+	world.add_body(left);
+	world.add_body(right);
+	
+	let start = world.measure();
+	let mut collided = false;
+	for _ in 0..180 {
+		world.step(DT);
+		let diagnostics = world.diagnostics();
+		collided |= world.contacts().next().is_some();
+		assert_near((diagnostics.linear_momentum - start.linear_momentum).length(), 0.0, 1e-3 * start.linear_momentum.length(), "linear momentum");
+		assert_near((diagnostics.angular_momentum - start.angular_momentum).length(), 0.0, 1e-3 * start.angular_momentum.length(), "angular momentum");
+	}
+	assert!(collided);
+}
+
+#[test]
+fn resting_box_barely_penetrates() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	world.add_body(body(Shape::Box { half_extents: Vec3(0.5, 0.5, 0.5) }, Vec3(0.0, 1.0, 0.0)));
+	
+	for _ in 0..240 {
+		world.step(DT);
+	}
+	let diagnostics = world.diagnostics();
+	assert!(diagnostics.max_penetration < 0.01, "penetration of {}", diagnostics.max_penetration);
+	assert_near(diagnostics.kinetic_energy, 0.0, 1e-3, "kinetic energy");
+	assert_near(diagnostics.linear_momentum.length(), 0.0, 1e-2, "linear momentum");
+	// the center rests half a meter up, under one unit of mass
+	assert_near(diagnostics.potential_energy, 9.8 * 0.5, 0.05, "potential energy");
+}
+
+#[test]
+fn heavy_bodies_report_their_spin() {
+	// a 3 m cube of water, whose inverse inertia is too small to be turned back into its inertia
+	let mut world = PhysicsWorld::new(PhysicsConfig { gravity: Vec3(0.0, 0.0, 0.0), ..Default::default() });
+	let mut cube = body(Shape::Box { half_extents: Vec3(1.5, 1.5, 1.5) }, Vec3(0.0, 0.0, 0.0));
+	cube.set_density(1000.0);
+	cube.angular_velocity = Vec3(0.0, 2.0, 0.0);
+	world.add_body(cube);
+	
+	// I = m (3² + 3²) / 12 about any axis
+	let inertia = 27000.0 * 18.0 / 12.0;
+	let diagnostics = world.measure();
+	assert_near(diagnostics.kinetic_energy, 0.5 * inertia * 4.0, 1e-3 * inertia, "kinetic energy");
+	assert_near(diagnostics.angular_momentum.1, inertia * 2.0, 1e-3 * inertia, "angular momentum");
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::{body, box_mesh, floor, DT};
+use gl_engine::{math_structs::Vec3, physics::{ContactEvent, ContactEventKind}, shape::Shape, world::{PhysicsConfig, PhysicsWorld}};
+
+
+fn kinds(events: &[ContactEvent], bodies: (usize, usize)) -> Vec<ContactEventKind> {
+	events.iter().filter(|e| e.bodies == bodies).map(|e| e.kind).collect()
+}
+
+
+#[test]
+fn contacts_begin_persist_and_end() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	let sphere = world.add_body(body(Shape::Sphere { radius: 0.5 }, Vec3(1.0, 0.5, -2.0)));
+	
+	// set down on the floor, it touches from the first step on, through falling asleep
+	world.step(DT);
+	assert_eq!(kinds(world.events(), (0, sphere)), vec![ContactEventKind::Begin]);
+	let event = world.events()[0];
+	assert!(event.impulse > 0.0 && !event.trigger, "{:?}", event);
+	assert!(event.normal.1 < -0.99 && (event.point - Vec3(1.0, 0.0, -2.0)).length() < 0.05, "{:?}", event);
+	for step in 0..60 {
+		world.step(DT);
+		assert_eq!(kinds(world.events(), (0, sphere)), vec![ContactEventKind::Persist], "at step {}", step);
+	}
+	assert!(world.body(sphere).unwrap().sleeping);
+	
+	// lifted off, it ends once and then nothing more is said about the pair
+	let (transform, _, _) = world.body(sphere).unwrap().get_dynamic_state();
+	world.body_mut(sphere).unwrap().set_dynamic_state((transform.translate(Vec3(0.0, 3.0, 0.0)), Vec3(0.0, 0.0, 0.0), Vec3(0.0, 0.0, 0.0)));
+	world.step(DT);
+	assert_eq!(kinds(world.events(), (0, sphere)), vec![ContactEventKind::End]);
+	world.step(DT);
+	assert!(kinds(world.events(), (0, sphere)).is_empty());
+}
+
+#[test]
+fn triggers_report_bodies_all_the_way_through_them() {
+	// a sphere drifting down through a mesh trigger much deeper than contacts reach
+	let mut world = PhysicsWorld::new(PhysicsConfig { gravity: Vec3(0.0, 0.0, 0.0), ..PhysicsConfig::default() });
+	let mut volume = box_mesh(Vec3(2.0, 2.0, 2.0), Vec3(0.0, 0.0, 0.0));
+	volume.is_trigger = true;
+	volume.set_static();
+	let volume = world.add_body(volume);
+	let mut sphere = body(Shape::Sphere { radius: 0.25 }, Vec3(0.0, 3.0, 0.0));
+	sphere.velocity = Vec3(0.0, -2.0, 0.0);
+	let sphere = world.add_body(sphere);
+	
+	let mut history = Vec::new();
+	for _ in 0..240 {
+		world.step(DT);
+		let height = world.body(sphere).unwrap().transform.get_position().1;
+		for kind in kinds(world.events(), (volume, sphere)) {
+			history.push((kind, height));
+		}
+		assert!(world.events().iter().all(|e| e.trigger && e.impulse == 0.0));
+	}
+	assert_eq!(world.body(sphere).unwrap().velocity, Vec3(0.0, -2.0, 0.0), "the trigger got in the way");
+	
+	let begins = history.iter().filter(|(kind, _)| *kind == ContactEventKind::Begin).collect::<Vec<_>>();
+	let ends = history.iter().filter(|(kind, _)| *kind == ContactEventKind::End).collect::<Vec<_>>();
+	assert_eq!(begins.len(), 1, "{:?}", history);
+	assert_eq!(ends.len(), 1, "{:?}", history);
+	// it starts as the sphere reaches the top and ends as it leaves the bottom, overlapping all the way between
+	assert!((begins[0].1 - 2.25).abs() < 0.1, "began at {}", begins[0].1);
+	assert!((ends[0].1 + 2.25).abs() < 0.1, "ended at {}", ends[0].1);
+	let persists = history.iter().filter(|(kind, _)| *kind == ContactEventKind::Persist).count();
+	assert_eq!(persists + 2, history.len());
+	assert!(persists as f32 > 4.4 / (2.0 * DT), "only persisted {} steps", persists);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+
+use common::{floor, DT};
+use gl_engine::{force::{Buoyancy, ForceField, PointAttractor, WindVolume}, math_structs::Vec3, object::Object, physics::Aabb, shape::Shape, world::{PhysicsConfig, PhysicsWorld}};
+
+
+// a box left to fall asleep on the floor
+fn sleeping_box() -> (PhysicsWorld, usize) {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	let mut cube = Object::new(&[], &[]);
+	cube.set_shape(Shape::Box { half_extents: Vec3(0.5, 0.5, 0.5) });
+	cube.transform = cube.transform.translate(Vec3(0.0, 0.5, 0.0));
+	cube.friction = 0.2;
+	let cube = world.add_body(cube);
+	for _ in 0..120 {
+		world.step(DT);
+	}
+	assert!(world.body(cube).unwrap().sleeping, "never fell asleep");
+	(world, cube)
+}
+
+
+#[test]
+fn fields_wake_the_bodies_they_push() {
+	let (mut world, cube) = sleeping_box();
+	world.add_field(WindVolume { bounds: Aabb { min: Vec3(-5.0, 0.0, -5.0), max: Vec3(5.0, 5.0, 5.0) }, velocity: Vec3(20.0, 0.0, 0.0), drag: 2.0 });
+	for _ in 0..60 {
+		world.step(DT);
+	}
+	let cube = world.body(cube).unwrap();
+	assert!(!cube.sleeping);
+	assert!(cube.transform.get_position().0 > 0.5, "only blown to {:?}", cube.transform.get_position());
+}
+
+#[test]
+fn bodies_held_against_a_field_fall_back_asleep() {
+	// pulled into the floor, which holds it where it is
+	let (mut world, cube) = sleeping_box();
+	world.add_field(PointAttractor { position: Vec3(0.0, -5.0, 0.0), strength: 10.0, min_distance: 0.1 });
+	world.step(DT);
+	assert!(world.body(cube).unwrap().sleeping);
+	assert!((world.body(cube).unwrap().transform.get_position() - Vec3(0.0, 0.5, 0.0)).length() < 0.01);
+}
+
+// some fields, counting the times they push on the body while it's awake
+struct Watched(Vec<Box<dyn ForceField>>, Arc<AtomicUsize>);
+
+impl ForceField for Watched {
+	fn apply(&self, object: &mut Object, gravity: Vec3) {
+		if !object.sleeping { self.1.fetch_add(1, Ordering::Relaxed); }
+		for field in self.0.iter() {
+			field.apply(object, gravity);
+		}
+	}
+}
+
+#[test]
+fn gentle_fields_leave_resting_bodies_asleep() {
+	// in shallow water that lifts a fifth of its weight, and pulled a little towards a point off to the side
+	let (mut world, cube) = sleeping_box();
+	let awake = Arc::new(AtomicUsize::new(0));
+	world.add_field(Watched(vec![
+		Box::new(Buoyancy { surface_height: 2.0, fluid_density: 0.2, linear_drag: 1.0, angular_drag: 1.0 }),
+		Box::new(PointAttractor { position: Vec3(8.0, 0.5, 0.0), strength: 20.0, min_distance: 0.1 })
+	], awake.clone()));
+	for _ in 0..120 {
+		world.step(DT);
+	}
+	assert_eq!(awake.load(Ordering::Relaxed), 0, "woken by the fields");
+	assert!(world.body(cube).unwrap().sleeping);
+}
+
+#[test]
+fn gravity_scale_and_damping_change_the_fall() {
+	let mut world = PhysicsWorld::new(PhysicsConfig { gravity: Vec3(0.0, -10.0, 0.0), ..PhysicsConfig::default() });
+	for i in 0..3 {
+		let mut ball = Object::new(&[], &[]);
+		ball.set_shape(Shape::Sphere { radius: 0.5 });
+		ball.transform = ball.transform.translate(Vec3(3.0 * i as f32, 10.0, 0.0));
+		world.add_body(ball);
+	}
+	world.body_mut(1).unwrap().gravity_scale = 0.5;
+	world.body_mut(2).unwrap().linear_damping = 1.0;
+	for _ in 0..60 {
+		world.step(DT);
+	}
+	let speeds = world.bodies().iter().map(|o| -o.velocity.1).collect::<Vec<f32>>();
+	assert!((speeds[0] - 10.0).abs() < 1e-3, "fell at {}", speeds[0]);
+	assert!((speeds[1] - 5.0).abs() < 1e-3, "half gravity fell at {}", speeds[1]);
+	assert!(speeds[2] > 5.0 && speeds[2] < 7.0, "damped fall at {}", speeds[2]);
+}
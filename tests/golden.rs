@@ -0,0 +1,213 @@
+// Renders fixed scenes from fixed camera poses and compares them with the reference images in tests/golden.
+// A pixel counts as changed when any channel is more than CHANNEL_TOLERANCE off, and a render fails when more than
+// PIXEL_TOLERANCE of its pixels changed. That absorbs rounding differences between drivers, not a changed shader.
+// Failed renders go next to a diff image in the build's tmp dir, the path is in the failure message.
+//
+//     UPDATE_GOLDEN=1 cargo test --test golden
+//
+// writes the references from the current renders instead, for when a change to the look is meant.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use common::{box_mesh, display, floor};
+use gl_engine::{headless::HeadlessDisplay, light::{Light, LightKind}, math_structs::{Mat4, Vec3}, object::Object, render::{Camera, Renderer, ShadowConfig, ShadowFilter}};
+use image::RgbaImage;
+
+
+static WIDTH: u32 = 256;
+static HEIGHT: u32 = 192;
+static CHANNEL_TOLERANCE: u8 = 24;
+static PIXEL_TOLERANCE: f32 = 0.005;
+
+
+struct View {
+	camera: Camera,
+	post_process: bool,
+	show_shadowmap: bool,
+	show_cascades: bool,
+	shadows: ShadowConfig,
+	lights: Vec<Light>
+}
+
+
+// a floor with a tall block, a cube turned on its edge against it and a low slab in front, all casting shadows
+fn blocks() -> Vec<Object> {
+	blocks_at(Vec3(0.0, 0.0, 0.0))
+}
+
+fn blocks_at(offset: Vec3) -> Vec<Object> {
+	let mut floor = floor();
+	floor.transform = floor.transform.translate(offset);
+	vec![
+		floor,
+		Object { transform: Mat4::identity().rotate_y(0.3).translate(Vec3(-1.5, 2.0, 1.0) + offset), ..box_mesh(Vec3(0.6, 2.0, 0.6), Vec3(0.0, 0.0, 0.0)) },
+		Object { transform: Mat4::identity().rotate_z(0.6).rotate_y(-0.5).translate(Vec3(1.2, 1.2, 0.0) + offset), ..box_mesh(Vec3(1.0, 1.0, 1.0), Vec3(0.0, 0.0, 0.0)) },
+		Object { transform: Mat4::identity().rotate_y(0.1).translate(Vec3(0.0, 0.25, -2.5) + offset), ..box_mesh(Vec3(1.5, 0.25, 0.8), Vec3(0.0, 0.0, 0.0)) }
+	]
+}
+
+fn render(display: &HeadlessDisplay, objects: &[Object], view: &View) -> RgbaImage {
+	let (vertex_buffers, index_buffers): (Vec<_>, Vec<_>) = objects.iter().map(|o| o.create_buffers(display)).unzip();
+	let mut renderer = Renderer::new(display, WIDTH, HEIGHT, 75.0, 0.01, 1000.0);
+	renderer.shadowmap.show_cascades = view.show_cascades;
+	renderer.shadowmap.set_config(display, view.shadows);
+	display.capture(WIDTH, HEIGHT, |target| {
+		renderer.render(display, target, &view.camera, objects, &vertex_buffers, &index_buffers, &[], &[], &[], None, view.post_process, view.show_shadowmap, &view.lights);
+	}).unwrap()
+}
+
+// the changed pixels in red over a dimmed copy of the reference
+fn diff_image(reference: &RgbaImage, image: &RgbaImage) -> (RgbaImage, usize) {
+	let mut changed = 0;
+	let diff = RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| {
+		let (a, b) = (reference.get_pixel(x, y).0, image.get_pixel(x, y).0);
+		let difference = (0..3).map(|c| a[c].abs_diff(b[c])).max().unwrap();
+		match difference > CHANNEL_TOLERANCE {
+			true => {
+				changed += 1;
+				image::Rgba([128 + difference / 2, 0, 0, 255])
+			}
+			false => {
+				let gray = ((a[0] as u32 + a[1] as u32 + a[2] as u32) / 12) as u8;
+				image::Rgba([gray, gray, gray, 255])
+			}
+		}
+	});
+	(diff, changed)
+}
+
+fn check(name: &str, image: &RgbaImage) {
+	let reference_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(format!("{}.png", name));
+	if std::env::var_os("UPDATE_GOLDEN").is_some() {
+		image.save(&reference_path).unwrap();
+		eprintln!("wrote {}", reference_path.display());
+		return;
+	}
+	
+	let failures = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+	std::fs::create_dir_all(&failures).unwrap();
+	let actual_path = failures.join(format!("{}.png", name));
+	let reference = match image::open(&reference_path) {
+		Ok(reference) => reference.to_rgba8(),
+		Err(e) => {
+			image.save(&actual_path).unwrap();
+			panic!("no reference for {} ({}), the render is at {}. UPDATE_GOLDEN=1 writes it", name, e, actual_path.display());
+		}
+	};
+	assert_eq!(reference.dimensions(), image.dimensions(), "{} changed size", name);
+	
+	let (diff, changed) = diff_image(&reference, image);
+	let fraction = changed as f32 / (WIDTH * HEIGHT) as f32;
+	if fraction > PIXEL_TOLERANCE {
+		let diff_path = failures.join(format!("{}.diff.png", name));
+		image.save(&actual_path).unwrap();
+		diff.save(&diff_path).unwrap();
+		panic!("{} has {:.2}% of its pixels changed, the render is at {} and the difference at {}", name, 100.0 * fraction, actual_path.display(), diff_path.display());
+	}
+}
+
+
+fn sun() -> Light {
+	Light::new(LightKind::Directional { direction: Vec3(1.0, 2.0, 0.0).normalize() }, Vec3(1.0, 1.0, 1.0), 1.0)
+}
+
+fn overview(post_process: bool) -> View {
+	View { camera: Camera { position: Vec3(3.0, 5.0, -9.0), horizontal_angle: 0.3, vertical_angle: -0.35 }, post_process, show_shadowmap: false, show_cascades: false, shadows: ShadowConfig::default(), lights: vec![sun()] }
+}
+
+// down at the foot of the tall block, where its shadow runs from where it stands to a long way off
+fn shadow_close_up(filter: ShadowFilter) -> View {
+	let camera = Camera { position: Vec3(-0.5, 1.5, -3.5), horizontal_angle: -0.4, vertical_angle: -0.45 };
+	View { camera, shadows: ShadowConfig { filter, ..Default::default() }, ..overview(false) }
+}
+
+#[test]
+fn post_processing_off() {
+	let Some(display) = display() else { return; };
+	check("post_processing_off", &render(&display, &blocks(), &overview(false)));
+}
+
+#[test]
+fn post_processing_on() {
+	let Some(display) = display() else { return; };
+	check("post_processing_on", &render(&display, &blocks(), &overview(true)));
+}
+
+#[test]
+fn shadow_map() {
+	// the depth the light sees, drawn over the whole screen whatever the camera
+	let Some(display) = display() else { return; };
+	check("shadow_map", &render(&display, &blocks(), &View { show_shadowmap: true, ..overview(false) }));
+}
+
+#[test]
+fn hard_shadows() {
+	let Some(display) = display() else { return; };
+	check("hard_shadows", &render(&display, &blocks(), &shadow_close_up(ShadowFilter::Hard)));
+}
+
+#[test]
+fn kernel_shadows() {
+	let Some(display) = display() else { return; };
+	check("kernel_shadows", &render(&display, &blocks(), &shadow_close_up(ShadowFilter::Kernel)));
+}
+
+#[test]
+fn noise_shadows() {
+	let Some(display) = display() else { return; };
+	check("noise_shadows", &render(&display, &blocks(), &shadow_close_up(ShadowFilter::Noise)));
+}
+
+#[test]
+fn soft_shadows() {
+	// pcss, sharp where the block meets the floor and blurring out away from it
+	let Some(display) = display() else { return; };
+	check("soft_shadows", &render(&display, &blocks(), &shadow_close_up(ShadowFilter::Pcss)));
+}
+
+#[test]
+fn cascades() {
+	// each cascade tinted its own color, on a floor big enough to run out past the last
+	let Some(display) = display() else { return; };
+	let mut objects = blocks();
+	objects[0].transform = Mat4::identity().scale(8.0);
+	let view = View { camera: Camera { position: Vec3(0.0, 2.5, -9.0), horizontal_angle: 0.0, vertical_angle: -0.1 }, show_cascades: true, ..overview(false) };
+	check("cascades", &render(&display, &objects, &view));
+}
+
+#[test]
+fn shadows_away_from_the_origin() {
+	// the cascades follow the camera, so blocks far out still cast shadows
+	let Some(display) = display() else { return; };
+	let offset = Vec3(120.0, 0.0, -80.0);
+	let mut view = overview(false);
+	view.camera.position += offset;
+	check("shadows_away_from_the_origin", &render(&display, &blocks_at(offset), &view));
+}
+
+#[test]
+fn point_light() {
+	// a lamp between the blocks and no sun, so the shadows fan out away from it across the floor
+	let Some(display) = display() else { return; };
+	let lamp = Light { range: 12.0, ..Light::new(LightKind::Point { position: Vec3(0.0, 1.2, -0.6) }, Vec3(1.0, 0.9, 0.7), 3.0) };
+	check("point_light", &render(&display, &blocks(), &View { lights: vec![lamp], ..overview(false) }));
+}
+
+#[test]
+fn spot_light() {
+	// a spot from up and behind the camera onto the cube, dark outside its cone and with the cube's shadow inside it
+	let Some(display) = display() else { return; };
+	let position = Vec3(4.0, 7.0, -6.0);
+	let spot = Light::new(LightKind::Spot { position, direction: Vec3(1.2, 0.5, 0.0) - position, inner_angle: 0.3, outer_angle: 0.4 }, Vec3(1.0, 1.0, 1.0), 12.0);
+	check("spot_light", &render(&display, &blocks(), &View { lights: vec![spot], ..overview(false) }));
+}
+
+#[test]
+fn outlines() {
+	// close up where the blocks overlap, so there are edges of both depth and normals against each other
+	let Some(display) = display() else { return; };
+	let view = View { camera: Camera { position: Vec3(0.5, 2.5, -4.5), horizontal_angle: 0.0, vertical_angle: -0.3 }, post_process: true, show_shadowmap: false, show_cascades: false, shadows: ShadowConfig::default(), lights: vec![sun()] };
+	check("outlines", &render(&display, &blocks(), &view));
+}
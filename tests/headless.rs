@@ -0,0 +1,121 @@
+mod common;
+
+use common::{box_mesh, display, floor};
+use gl_engine::{headless::HeadlessDisplay, light::{Light, LightKind}, math_structs::Vec3, particles::Particle, render::{Camera, Renderer, ShadowConfig, ShadowFilter}, shape::Shape};
+use glium::GlObject;
+
+
+static WIDTH: u32 = 160;
+static HEIGHT: u32 = 120;
+
+
+// the one light there was before there was a list of them
+fn sun() -> Vec<Light> {
+	vec![Light::new(LightKind::Directional { direction: Vec3(1.0, 2.0, 0.0).normalize() }, Vec3(1.0, 1.0, 1.0), 1.0)]
+}
+
+// a cube on a floor, seen from above and to the side
+fn render(display: &HeadlessDisplay, post_process: bool) -> image::RgbaImage {
+	let mut cube = box_mesh(Vec3(1.0, 1.0, 1.0), Vec3(0.0, 0.0, 0.0));
+	cube.set_shape(Shape::Box { half_extents: Vec3(1.0, 1.0, 1.0) });
+	cube.transform = cube.transform.rotate_y(0.4).translate(Vec3(0.0, 1.0, 0.0));
+	let objects = vec![floor(), cube];
+	let (vertex_buffers, index_buffers): (Vec<_>, Vec<_>) = objects.iter().map(|o| o.create_buffers(display)).unzip();
+	
+	let camera = Camera { position: Vec3(0.0, 4.0, -8.0), horizontal_angle: 0.0, vertical_angle: -0.4 };
+	let mut renderer = Renderer::new(display, WIDTH, HEIGHT, 75.0, 0.01, 1000.0);
+	display.capture(WIDTH, HEIGHT, |target| {
+		renderer.render(display, target, &camera, &objects, &vertex_buffers, &index_buffers, &[], &[], &[], None, post_process, false, &sun());
+	}).unwrap()
+}
+
+fn brightness(image: &image::RgbaImage, x: u32, y: u32) -> u32 {
+	let pixel = image.get_pixel(x, y).0;
+	pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32
+}
+
+
+#[test]
+fn renders_the_scene_offscreen() {
+	let Some(display) = display() else { return; };
+	let image = render(&display, false);
+	assert_eq!(image.dimensions(), (WIDTH, HEIGHT));
+	
+	// the sky is cleared to black, the floor and cube below the horizon are lit
+	let sky = (0..WIDTH).map(|x| brightness(&image, x, 0)).max().unwrap();
+	assert_eq!(sky, 0, "something drawn in the sky");
+	let lit = (0..WIDTH).flat_map(|x| (HEIGHT / 2..HEIGHT).map(move |y| (x, y))).filter(|&(x, y)| brightness(&image, x, y) > 0).count();
+	assert!(lit > (WIDTH * HEIGHT / 4) as usize, "only {} pixels lit", lit);
+	assert!(brightness(&image, WIDTH / 2, HEIGHT * 3 / 4) > 0, "nothing in the middle of the view");
+}
+
+#[test]
+fn renders_the_same_twice() {
+	let Some(display) = display() else { return; };
+	for post_process in [false, true] {
+		assert!(render(&display, post_process) == render(&display, post_process), "two renders with post processing {} differ", post_process);
+	}
+}
+
+#[test]
+fn round_trips_through_png() {
+	let Some(display) = display() else { return; };
+	let image = render(&display, true);
+	let mut bytes = std::io::Cursor::new(Vec::new());
+	image.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+	let decoded = image::load_from_memory_with_format(bytes.get_ref(), image::ImageFormat::Png).unwrap().to_rgba8();
+	assert!(decoded == image);
+}
+
+#[test]
+fn shadow_config_sizes_the_shadow_map() {
+	let Some(display) = display() else { return; };
+	let mut renderer = Renderer::new(&display, WIDTH, HEIGHT, 75.0, 0.01, 1000.0);
+	let texture = &renderer.shadowmap.texture;
+	assert_eq!((texture.dimensions(), texture.array_size()), ((2048, 2048), 4));
+	
+	// only a new size or number of cascades needs a new texture
+	let id = renderer.shadowmap.texture.get_id();
+	renderer.shadowmap.set_config(&display, ShadowConfig { filter: ShadowFilter::Pcss, bias_factor: 2.0, ..Default::default() });
+	assert_eq!(renderer.shadowmap.texture.get_id(), id);
+	assert_eq!(renderer.shadowmap.config().filter, ShadowFilter::Pcss);
+	
+	renderer.shadowmap.set_config(&display, ShadowConfig { resolution: 512, cascade_count: 2, ..*renderer.shadowmap.config() });
+	let texture = &renderer.shadowmap.texture;
+	assert_eq!((texture.dimensions(), texture.array_size()), ((512, 512), 2));
+	assert_eq!(renderer.shadowmap.config().filter, ShadowFilter::Pcss);
+	
+	// the point and spot maps go by their own resolution
+	let (id, spot_id) = (renderer.shadowmap.texture.get_id(), renderer.shadowmap.spot_texture.get_id());
+	renderer.shadowmap.set_config(&display, ShadowConfig { local_resolution: 256, ..*renderer.shadowmap.config() });
+	assert_eq!(renderer.shadowmap.texture.get_id(), id);
+	assert_ne!(renderer.shadowmap.spot_texture.get_id(), spot_id);
+	assert_eq!(renderer.shadowmap.spot_texture.dimensions(), (256, 256));
+	assert!(renderer.shadowmap.point_textures.iter().all(|cubemap| cubemap.dimensions() == 256));
+	
+	// and it draws with what it was given
+	let camera = Camera { position: Vec3(0.0, 4.0, -8.0), horizontal_angle: 0.0, vertical_angle: -0.4 };
+	display.capture(WIDTH, HEIGHT, |target| {
+		renderer.render(&display, target, &camera, &[], &[], &[], &[], &[], &[], None, false, false, &sun());
+	}).unwrap();
+	assert_eq!(renderer.shadowmap.cascades.len(), 2);
+}
+
+#[test]
+fn particles_are_lit_by_point_lights() {
+	let Some(display) = display() else { return; };
+	let camera = Camera { position: Vec3(0.0, 0.0, -5.0), horizontal_angle: 0.0, vertical_angle: 0.0 };
+	let particles = [Particle { position: Vec3(0.0, 0.0, 0.0), velocity: Vec3(0.0, 0.0, 0.0), age: 0.0, lifetime: 1.0, size: 0.5, color: Vec3(1.0, 1.0, 1.0) }];
+	let mut renderer = Renderer::new(&display, WIDTH, HEIGHT, 75.0, 0.01, 1000.0);
+	let mut render = |lights: &[Light]| display.capture(WIDTH, HEIGHT, |target| {
+		renderer.render(&display, target, &camera, &[], &[], &[], &[], &[], &particles, None, false, false, lights);
+	}).unwrap().get_pixel(WIDTH / 2, HEIGHT / 2).0;
+	
+	// with no sun at all, a red glow between the particle and the camera tints only its red
+	let dark = render(&[]);
+	let glow = Light { range: 8.0, ..Light::new(LightKind::Point { position: Vec3(0.0, 0.0, -1.5) }, Vec3(1.0, 0.0, 0.0), 1.5) };
+	let lit = render(&[glow]);
+	assert!(dark[0] > 0, "the particle wasn't drawn");
+	assert!(lit[0] > dark[0] + 50, "{:?} is no redder than {:?}", lit, dark);
+	assert_eq!((lit[1], lit[2]), (dark[1], dark[2]));
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use common::{assert_near, body, display, DT};
+use gl_engine::{heightfield::Heightfield, math_structs::Vec3, object::Object, query::QueryFilter, shape::Shape, world::{PhysicsConfig, PhysicsWorld}};
+
+
+// a 33 by 33 grid a meter apart of gentle rolling hills, steep enough that the slope matters
+fn hills() -> Heightfield {
+	let heights = (0..33 * 33).map(|i| {
+		let (column, row) = ((i % 33) as f32, (i / 33) as f32);
+		0.5 * (column * 0.3).sin() + 0.4 * (row * 0.2).cos()
+	}).collect::<Vec<f32>>();
+	Heightfield::new(33, 33, 1.0, &heights)
+}
+
+
+#[test]
+fn flat_field_holds_bodies_at_its_height() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(Object::from_heightfield(Heightfield::new(9, 9, 1.0, &[0.5; 81])));
+	let sphere = world.add_body(body(Shape::Sphere { radius: 0.5 }, Vec3(-1.5, 3.0, 0.0)));
+	let cube = world.add_body(body(Shape::Box { half_extents: Vec3(0.5, 0.5, 0.5) }, Vec3(1.5, 3.0, 0.5)));
+	
+	for _ in 0..240 {
+		world.step(DT);
+	}
+	assert_near(world.body(sphere).unwrap().transform.get_position().1, 1.0, 0.02, "sphere height");
+	assert_near(world.body(cube).unwrap().transform.get_position().1, 1.0, 0.02, "box height");
+	assert!(world.diagnostics().max_penetration < 0.01, "penetration of {}", world.diagnostics().max_penetration);
+	assert!(world.body(sphere).unwrap().sleeping && world.body(cube).unwrap().sleeping);
+}
+
+#[test]
+fn sphere_comes_to_rest_on_the_surface() {
+	let field = hills();
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(Object::from_heightfield(field.clone()));
+	let sphere = world.add_body(body(Shape::Sphere { radius: 0.5 }, Vec3(2.0, 4.0, -3.0)));
+	
+	let mut touched = false;
+	for _ in 0..600 {
+		world.step(DT);
+		touched |= world.manifold(0, sphere).is_some();
+		assert!(world.diagnostics().max_penetration < 0.05, "sank {} into the terrain", world.diagnostics().max_penetration);
+	}
+	assert!(touched);
+	
+	// wherever it rolled to, it sits on the surface under it
+	let position = world.body(sphere).unwrap().transform.get_position();
+	let ground = field.height_at(position.0, position.2).unwrap();
+	assert!(position.1 > ground && position.1 < ground + 0.55, "{} over ground at {}", position.1, ground);
+}
+
+#[test]
+fn fast_sphere_does_not_tunnel() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(Object::from_heightfield(hills()));
+	let mut bullet = body(Shape::Sphere { radius: 0.1 }, Vec3(0.3, 5.0, 0.7));
+	// twenty meters a step, far past the size of a cell
+	bullet.velocity = Vec3(0.0, -1200.0, 0.0);
+	let bullet = world.add_body(bullet);
+	
+	for _ in 0..10 {
+		world.step(DT);
+	}
+	assert!(world.body(bullet).unwrap().transform.get_position().1 > -1.5, "went through to {:?}", world.body(bullet).unwrap().transform.get_position());
+}
+
+#[test]
+fn rays_hit_at_the_sampled_height() {
+	let field = hills();
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(Object::from_heightfield(field.clone()));
+	
+	for (x, z) in [(0.0, 0.0), (3.3, -7.1), (-12.6, 10.9), (15.5, 15.5)] {
+		let ground = field.height_at(x, z).unwrap();
+		let hit = world.ray_cast(Vec3(x, 10.0, z), Vec3(0.0, -1.0, 0.0), 100.0, &QueryFilter::default()).expect("missed the terrain");
+		assert_near(hit.point.1, ground, 1e-3, "hit height");
+		assert!(hit.normal.1 > 0.5, "normal {:?} doesn't face up", hit.normal);
+	}
+	
+	// a slanted ray that crosses many cells before it comes down
+	let (origin, direction) = (Vec3(-15.0, 2.0, -15.0), Vec3(1.0, -0.1, 1.0).normalize());
+	let (distance, _) = field.ray_cast(origin, direction, 100.0).expect("missed the terrain");
+	let point = origin + direction * distance;
+	assert_near(point.1, field.height_at(point.0, point.2).unwrap(), 1e-3, "slanted hit height");
+	
+	// off the edge or pointing away misses
+	assert!(field.ray_cast(Vec3(20.0, 10.0, 0.0), Vec3(0.0, -1.0, 0.0), 100.0).is_none());
+	assert!(field.ray_cast(Vec3(0.0, 10.0, 0.0), Vec3(0.0, 1.0, 0.0), 100.0).is_none());
+}
+
+#[test]
+fn loads_from_grayscale_png() {
+	let mut pixels = image::ImageBuffer::<image::Luma<u16>, Vec<u16>>::new(3, 2);
+	for (x, y, pixel) in pixels.enumerate_pixels_mut() {
+		pixel.0[0] = ((x + 3 * y) * u16::MAX as u32 / 5) as u16;
+	}
+	let mut bytes = std::io::Cursor::new(Vec::new());
+	pixels.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+	
+	let field = Heightfield::from_png(bytes.get_ref(), 2.0, 10.0).unwrap();
+	assert_eq!((field.columns, field.rows), (3, 2));
+	for (i, &height) in field.heights.iter().enumerate() {
+		assert_near(height, 2.0 * i as f32, 1e-3, "height");
+	}
+	assert_eq!(field.point(0, 0), Vec3(-2.0, 0.0, -1.0));
+	assert_eq!(field.triangle_count(), 4);
+	assert!(Heightfield::from_png(b"not a png", 1.0, 1.0).is_err());
+}
+
+#[test]
+fn fields_past_16_bit_indices_load_and_draw() {
+	// 300 by 300 points, more than a 16 bit index reaches
+	let pixels = image::ImageBuffer::<image::Luma<u16>, Vec<u16>>::from_pixel(300, 300, image::Luma([u16::MAX / 2]));
+	let mut bytes = std::io::Cursor::new(Vec::new());
+	pixels.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+	let field = Heightfield::from_png(bytes.get_ref(), 0.1, 2.0).unwrap();
+	
+	let (vertices, indices) = field.mesh();
+	assert_eq!(vertices.len(), 300 * 300);
+	assert_eq!(indices.len(), field.triangle_count());
+	assert_eq!(indices.iter().map(|&(a, b, c)| a.max(b).max(c)).max(), Some(300 * 300 - 1));
+	
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(Object::from_heightfield(field));
+	let sphere = world.add_body(body(Shape::Sphere { radius: 0.5 }, Vec3(3.0, 3.0, -2.0)));
+	for _ in 0..180 {
+		world.step(DT);
+	}
+	assert_near(world.body(sphere).unwrap().transform.get_position().1, 1.5, 0.02, "sphere height");
+	
+	if let Some(display) = display() {
+		let (vertex_buffer, index_buffer) = world.body(0).unwrap().create_buffers(&display);
+		assert_eq!((vertex_buffer.len(), index_buffer.len()), (300 * 300, 3 * 2 * 299 * 299));
+	}
+}
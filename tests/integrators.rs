@@ -0,0 +1,121 @@
+use std::f32::consts::PI;
+
+use gl_engine::{force::PointAttractor, integrator::Integrator, math_structs::Vec3, object::Object, shape::Shape, world::{PhysicsConfig, PhysicsWorld}};
+
+
+static INTEGRATORS: [Integrator; 3] = [Integrator::SemiImplicitEuler, Integrator::VelocityVerlet, Integrator::Rk4];
+
+
+fn weightless(integrator: Integrator) -> PhysicsWorld {
+	PhysicsWorld::new(PhysicsConfig { gravity: Vec3(0.0, 0.0, 0.0), integrator, ..Default::default() })
+}
+
+// A small ball on a circular orbit of radius one around an attractor, taking a second per orbit.
+// Returns how far from its start it ends up after the given number of orbits, and the worst radius on the way.
+fn orbit(integrator: Integrator, orbits: usize) -> (f32, f32) {
+	let mut world = weightless(integrator);
+	world.add_field(PointAttractor { position: Vec3(0.0, 0.0, 0.0), strength: 4.0 * PI * PI, min_distance: 0.01 });
+	let mut ball = Object::new(&[], &[]);
+	ball.set_shape(Shape::Sphere { radius: 0.05 });
+	ball.transform = ball.transform.translate(Vec3(1.0, 0.0, 0.0));
+	ball.velocity = Vec3(0.0, 0.0, 2.0 * PI);
+	world.add_body(ball);
+	
+	let mut worst_radius = 0.0f32;
+	for _ in 0..60 * orbits {
+		world.step(1.0 / 60.0);
+		let radius = world.body(0).unwrap().transform.get_position().length();
+		worst_radius = worst_radius.max((radius - 1.0).abs());
+	}
+	((world.body(0).unwrap().transform.get_position() - Vec3(1.0, 0.0, 0.0)).length(), worst_radius)
+}
+
+// A flat box spun about one of its axes with a little wobble. Returns the worst relative change in energy and
+// angular momentum, the energy at the end relative to the start, and the least the axis lined up with the momentum.
+fn spin(integrator: Integrator, axis: Vec3, seconds: f32) -> (f32, f32, f32, f32) {
+	let mut world = weightless(integrator);
+	let mut racket = Object::new(&[], &[]);
+	racket.set_shape(Shape::Box { half_extents: Vec3(0.5, 0.3, 0.1) });
+	racket.angular_velocity = axis * 5.0 + Vec3(0.01, 0.01, 0.01);
+	world.add_body(racket);
+	
+	let start = world.measure();
+	let (mut energy_error, mut momentum_error, mut alignment) = (0.0f32, 0.0f32, 1.0f32);
+	for _ in 0..(60.0 * seconds) as usize {
+		world.step(1.0 / 60.0);
+		let diagnostics = world.diagnostics();
+		energy_error = energy_error.max((diagnostics.kinetic_energy / start.kinetic_energy - 1.0).abs());
+		momentum_error = momentum_error.max((diagnostics.angular_momentum - start.angular_momentum).length() / start.angular_momentum.length());
+		let turned_axis = axis.apply_rotation(&world.body(0).unwrap().transform);
+		alignment = alignment.min(turned_axis.dot(start.angular_momentum.normalize()));
+	}
+	(energy_error, momentum_error, world.diagnostics().kinetic_energy / start.kinetic_energy, alignment)
+}
+
+
+#[test]
+fn orbits_get_more_accurate_with_order() {
+	let [euler, verlet, rk4] = INTEGRATORS.map(|integrator| orbit(integrator, 5));
+	assert!(euler.0 > verlet.0 && verlet.0 > rk4.0, "{:?} {:?} {:?}", euler, verlet, rk4);
+	assert!(euler.1 > verlet.1 && verlet.1 > rk4.1, "{:?} {:?} {:?}", euler, verlet, rk4);
+	assert!(rk4.0 < 1e-3 && rk4.1 < 1e-4, "rk4 drifted {:?}", rk4);
+	assert!(verlet.1 < 0.01, "verlet drifted {:?}", verlet);
+}
+
+#[test]
+fn symplectic_orbits_stay_bounded() {
+	// their radius wobbles but doesn't wander off, however long they go
+	for (integrator, tolerance) in [(Integrator::SemiImplicitEuler, 0.07), (Integrator::VelocityVerlet, 0.01)] {
+		let (_, worst_radius) = orbit(integrator, 30);
+		assert!(worst_radius < tolerance, "{:?} got {} off the orbit", integrator, worst_radius);
+	}
+}
+
+#[test]
+fn spinning_about_the_middle_axis_flips() {
+	for integrator in INTEGRATORS {
+		let (_, _, _, alignment) = spin(integrator, Vec3(0.0, 1.0, 0.0), 10.0);
+		assert!(alignment < -0.9, "{:?} never flipped, got down to {}", integrator, alignment);
+	}
+}
+
+#[test]
+fn spinning_about_the_major_axis_is_stable() {
+	for integrator in INTEGRATORS {
+		let (_, _, _, alignment) = spin(integrator, Vec3(0.0, 0.0, 1.0), 10.0);
+		assert!(alignment > 0.99, "{:?} wobbled off to {}", integrator, alignment);
+	}
+}
+
+#[test]
+fn tumbling_keeps_energy_and_momentum() {
+	let axis = Vec3(0.0, 1.0, 0.0);
+	for integrator in [Integrator::VelocityVerlet, Integrator::Rk4] {
+		let (energy_error, momentum_error, _, _) = spin(integrator, axis, 10.0);
+		assert!(energy_error < 0.005 && momentum_error < 0.005, "{:?} changed energy by {} and momentum by {}", integrator, energy_error, momentum_error);
+	}
+	
+	// the implicit gyroscopic step can only take energy away, never add it
+	let (_, momentum_error, energy, _) = spin(Integrator::SemiImplicitEuler, axis, 10.0);
+	assert!(energy <= 1.0 && energy > 0.75, "energy went to {} of the start", energy);
+	assert!(momentum_error < 0.15, "momentum changed by {}", momentum_error);
+}
+
+#[test]
+fn heavy_bodies_keep_spinning() {
+	// a 3 m block of water, whose inverse inertia is too small to be turned back into its inertia
+	let mut world = weightless(Integrator::VelocityVerlet);
+	let mut block = Object::new(&[], &[]);
+	block.set_shape(Shape::Box { half_extents: Vec3(1.5, 1.5, 1.0) });
+	block.set_density(1000.0);
+	block.angular_velocity = Vec3(0.01, 0.01, 2.0);
+	world.add_body(block);
+	
+	let start = world.measure();
+	for _ in 0..120 {
+		world.step(1.0 / 60.0);
+	}
+	let end = world.measure();
+	assert!((end.angular_momentum - start.angular_momentum).length() < 0.005 * start.angular_momentum.length(), "momentum went from {:?} to {:?}", start.angular_momentum, end.angular_momentum);
+	assert!((end.kinetic_energy / start.kinetic_energy - 1.0).abs() < 0.005, "energy went from {} to {}", start.kinetic_energy, end.kinetic_energy);
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use common::{assert_matrices_near, body, DT};
+use gl_engine::{joint::Joint, math_structs::{Mat3, Vec3}, shape::Shape, world::{PhysicsConfig, PhysicsWorld}};
+
+
+#[test]
+fn ball_socket_holds_a_pendulum_at_its_length() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	let anchor = Vec3(0.0, 5.0, 0.0);
+	let bob = world.add_body(body(Shape::Sphere { radius: 0.25 }, Vec3(2.0, 5.0, 0.0)));
+	world.add_joint(Joint::ball_socket(world.bodies(), bob, None, anchor));
+	
+	let mut lowest = f32::INFINITY;
+	for _ in 0..180 {
+		world.step(DT);
+		let position = world.body(bob).unwrap().transform.get_position();
+		let length = (position - anchor).length();
+		assert!((length - 2.0).abs() < 0.05, "pendulum stretched to {}", length);
+		lowest = lowest.min(position.1);
+	}
+	// it swung through the bottom rather than hanging in place
+	assert!(lowest < 3.1, "lowest point {}", lowest);
+}
+
+#[test]
+fn hinge_limit_stops_the_rotation() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	// a bar sticking out along x, hinged about z at its end, would fall to hang straight down without the limit
+	let bar = world.add_body(body(Shape::Box { half_extents: Vec3(1.0, 0.1, 0.1) }, Vec3(1.0, 5.0, 0.0)));
+	let joint = world.add_joint(Joint::hinge(world.bodies(), bar, None, Vec3(0.0, 5.0, 0.0), Vec3(0.0, 0.0, 1.0)).with_limits(-0.5, 0.5));
+	
+	for _ in 0..180 {
+		world.step(DT);
+		let angle = world.joints().get(joint).unwrap().hinge_angle(world.bodies()).unwrap();
+		assert!((-0.55..=0.55).contains(&angle), "hinge at {}", angle);
+	}
+	let angle = world.joints().get(joint).unwrap().hinge_angle(world.bodies()).unwrap();
+	assert!((angle.abs() - 0.5).abs() < 0.05, "came to rest at {} instead of on the limit", angle);
+	let position = world.body(bar).unwrap().transform.get_position();
+	assert!(position.1 > 4.0, "fell past the limit to {:?}", position);
+}
+
+#[test]
+fn hinge_motor_reaches_its_speed() {
+	let mut world = PhysicsWorld::new(PhysicsConfig { gravity: Vec3(0.0, 0.0, 0.0), ..PhysicsConfig::default() });
+	let wheel = world.add_body(body(Shape::Sphere { radius: 0.5 }, Vec3(0.0, 1.0, 0.0)));
+	world.add_joint(Joint::hinge(world.bodies(), wheel, None, Vec3(0.0, 1.0, 0.0), Vec3(0.0, 1.0, 0.0)).with_motor(3.0, 10.0));
+	
+	for _ in 0..120 {
+		world.step(DT);
+	}
+	// the world is b, so the wheel turns the other way relative to it
+	let spin = world.body(wheel).unwrap().angular_velocity;
+	assert!((spin.1 + 3.0).abs() < 0.05, "spinning at {:?}", spin);
+	assert!(spin.0.abs() < 1e-3 && spin.2.abs() < 1e-3, "wobbling at {:?}", spin);
+}
+
+#[test]
+fn removed_joints_stop_holding() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	let bob = world.add_body(body(Shape::Sphere { radius: 0.25 }, Vec3(0.0, 4.0, 0.0)));
+	let joint = world.add_joint(Joint::ball_socket(world.bodies(), bob, None, Vec3(0.0, 5.0, 0.0)));
+	for _ in 0..10 {
+		world.step(DT);
+	}
+	assert!((world.body(bob).unwrap().transform.get_position() - Vec3(0.0, 4.0, 0.0)).length() < 0.02);
+	
+	assert!(world.remove_joint(joint).is_some());
+	assert!(world.joints().is_empty());
+	for _ in 0..30 {
+		world.step(DT);
+	}
+	assert!(world.body(bob).unwrap().transform.get_position().1 < 3.0, "still hanging at {:?}", world.body(bob).unwrap().transform.get_position());
+}
+
+#[test]
+fn slider_only_moves_along_its_axis_and_stops_at_its_limits() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	let block = world.add_body(body(Shape::Box { half_extents: Vec3(0.3, 0.3, 0.3) }, Vec3(0.0, 5.0, 0.0)));
+	world.add_joint(Joint::slider(world.bodies(), block, None, Vec3(0.0, 5.0, 0.0), Vec3(0.0, 1.0, 0.0)).with_limits(-1.0, 1.0));
+	// thrown sideways and spun, neither of which the slider lets through
+	world.body_mut(block).unwrap().velocity = Vec3(3.0, 0.0, -2.0);
+	world.body_mut(block).unwrap().angular_velocity = Vec3(1.0, 2.0, 0.5);
+	
+	for _ in 0..120 {
+		world.step(DT);
+		let position = world.body(block).unwrap().transform.get_position();
+		assert!(position.0.abs() < 0.02 && position.2.abs() < 0.02, "left the axis to {:?}", position);
+		assert!((3.95..=6.05).contains(&position.1), "slid past the limits to {:?}", position);
+	}
+	let object = world.body(block).unwrap();
+	assert_matrices_near(&object.transform.get_rotation(), &Mat3::identity(), 0.02);
+	assert!((object.transform.get_position().1 - 4.0).abs() < 0.05, "came to rest at {:?} instead of on the lower limit", object.transform.get_position());
+}
+
+#[test]
+fn fixed_joint_holds_the_relative_pose_under_load() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	// a post hanging from a ball socket with an arm welded on sideways, whose weight twists the weld as they swing
+	let post = world.add_body(body(Shape::Box { half_extents: Vec3(0.1, 1.0, 0.1) }, Vec3(0.0, 4.0, 0.0)));
+	let arm = world.add_body(body(Shape::Box { half_extents: Vec3(0.8, 0.1, 0.1) }, Vec3(0.9, 3.2, 0.0)));
+	world.add_joint(Joint::ball_socket(world.bodies(), post, None, Vec3(0.0, 5.0, 0.0)));
+	world.add_joint(Joint::fixed(world.bodies(), post, Some(arm)));
+	
+	let pose = |world: &PhysicsWorld| {
+		let (post, arm) = (world.body(post).unwrap().transform, world.body(arm).unwrap().transform);
+		let rotation = post.get_rotation().transpose();
+		(rotation.mult_vec3(arm.get_position() - post.get_position()), rotation.mult_mat3(&arm.get_rotation()))
+	};
+	let (offset, rotation) = pose(&world);
+	let mut tipped: f32 = 0.0;
+	for _ in 0..180 {
+		world.step(DT);
+		let (now_offset, now_rotation) = pose(&world);
+		assert!((now_offset - offset).length() < 0.03, "arm moved to {:?} from {:?}", now_offset, offset);
+		assert_matrices_near(&now_rotation, &rotation, 0.03);
+		tipped = tipped.max(world.body(post).unwrap().transform.get_rotation().column(1).dot(Vec3(0.0, 1.0, 0.0)).clamp(-1.0, 1.0).acos());
+	}
+	// the arm's weight swung the pair round, so the weld was actually loaded
+	assert!(tipped > 0.2, "only tipped {} radians", tipped);
+}
+
+#[test]
+fn distance_joint_holds_its_length_while_swinging() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	// a double pendulum of two distance joints, both links starting level
+	let first = world.add_body(body(Shape::Sphere { radius: 0.2 }, Vec3(1.5, 5.0, 0.0)));
+	let second = world.add_body(body(Shape::Sphere { radius: 0.2 }, Vec3(3.0, 5.0, 0.0)));
+	world.add_joint(Joint::distance(world.bodies(), first, None, Vec3(1.5, 5.0, 0.0), Vec3(0.0, 5.0, 0.0)));
+	world.add_joint(Joint::distance(world.bodies(), first, Some(second), Vec3(1.5, 5.0, 0.0), Vec3(3.0, 5.0, 0.0)));
+	
+	let mut lowest = f32::INFINITY;
+	for _ in 0..180 {
+		world.step(DT);
+		let (a, b) = (world.body(first).unwrap().transform.get_position(), world.body(second).unwrap().transform.get_position());
+		assert!(((a - Vec3(0.0, 5.0, 0.0)).length() - 1.5).abs() < 0.05, "first link stretched to {}", (a - Vec3(0.0, 5.0, 0.0)).length());
+		assert!(((b - a).length() - 1.5).abs() < 0.05, "second link stretched to {}", (b - a).length());
+		lowest = lowest.min(b.1);
+	}
+	assert!(lowest < 3.0, "lowest point {}", lowest);
+}
@@ -0,0 +1,110 @@
+use gl_engine::{light::{self, Light, LightKind, ShadowSlot, MAX_LIGHTS_PER_OBJECT, MAX_POINT_SHADOWS}, math_structs::{Mat4, Vec3}, physics::Aabb};
+
+
+fn white(kind: LightKind, intensity: f32) -> Light {
+	Light::new(kind, Vec3(1.0, 1.0, 1.0), intensity)
+}
+
+fn point(position: Vec3, intensity: f32) -> Light {
+	white(LightKind::Point { position }, intensity)
+}
+
+fn sun() -> Light {
+	white(LightKind::Directional { direction: Vec3(0.0, 1.0, 0.0) }, 1.0)
+}
+
+// where the point lands in the image, and its w
+fn project(transform: &Mat4, p: Vec3) -> (f32, f32, f32) {
+	let m = transform.0;
+	let clip = |row: usize| m[0][row] * p.0 + m[1][row] * p.1 + m[2][row] * p.2 + m[3][row];
+	let w = clip(3);
+	(clip(0) / w, clip(1) / w, w)
+}
+
+
+#[test]
+fn light_fades_out_by_its_range() {
+	let lamp = Light { range: 10.0, ..point(Vec3(0.0, 0.0, 0.0), 1.0) };
+	assert!((lamp.attenuation_at(0.0) - 1.0).abs() < 1e-6);
+	let falloff = (0..=20).map(|i| lamp.attenuation_at(i as f32 * 0.5)).collect::<Vec<f32>>();
+	assert!(falloff.windows(2).all(|pair| pair[1] < pair[0]), "{:?}", falloff);
+	assert_eq!(lamp.attenuation_at(10.0), 0.0);
+	assert_eq!(lamp.attenuation_at(25.0), 0.0);
+	assert_eq!(sun().attenuation_at(1000.0), 1.0);
+}
+
+#[test]
+fn objects_get_the_lights_that_reach_them() {
+	let bounds = Aabb { min: Vec3(-1.0, 0.0, -1.0), max: Vec3(1.0, 2.0, 1.0) };
+	let lights = [
+		point(Vec3(30.0, 1.0, 0.0), 5.0), // out of range
+		point(Vec3(4.0, 1.0, 0.0), 1.0),
+		sun(),
+		point(Vec3(0.0, 3.0, 0.0), 1.0), // closest
+		point(Vec3(0.0, 1.0, 0.0), 0.0) // inside, but dark
+	];
+	assert_eq!(light::select_lights(&lights, Some(&bounds)), vec![2, 3, 1]);
+	
+	// no more than the shader takes, strongest first
+	let many = (0..20).map(|i| point(Vec3(2.0 + 0.3 * i as f32, 1.0, 0.0), 1.0)).collect::<Vec<Light>>();
+	assert_eq!(light::select_lights(&many, Some(&bounds)), (0..MAX_LIGHTS_PER_OBJECT).collect::<Vec<usize>>());
+	assert_eq!(light::select_lights(&many, None).len(), MAX_LIGHTS_PER_OBJECT);
+}
+
+#[test]
+fn shadow_maps_go_to_the_first_lights_that_cast_them() {
+	let spot = white(LightKind::Spot { position: Vec3(0.0, 5.0, 0.0), direction: Vec3(0.0, -1.0, 0.0), inner_angle: 0.3, outer_angle: 0.5 }, 1.0);
+	let lights = [
+		Light { casts_shadows: false, ..sun() },
+		sun(),
+		sun(),
+		point(Vec3(0.0, 1.0, 0.0), 1.0),
+		spot,
+		point(Vec3(0.0, 2.0, 0.0), 1.0),
+		point(Vec3(0.0, 3.0, 0.0), 1.0)
+	];
+	let slots = light::assign_shadow_slots(&lights);
+	assert_eq!(slots, vec![ShadowSlot::None, ShadowSlot::Cascades, ShadowSlot::None, ShadowSlot::Cube(0), ShadowSlot::Spot(0), ShadowSlot::Cube(1), ShadowSlot::None]);
+	assert_eq!(MAX_POINT_SHADOWS, 2);
+}
+
+#[test]
+fn cube_faces_match_the_gl_lookup() {
+	// the face the gl picks for each direction, and where on it the direction lands by the spec's table
+	let center = Vec3(2.0, -1.0, 3.0);
+	let faces = light::cube_face_transforms(center);
+	let cases = [
+		(Vec3(1.0, 0.5, 0.25), 0, (-0.25, -0.5)),
+		(Vec3(-1.0, 0.5, 0.25), 1, (0.25, -0.5)),
+		(Vec3(0.5, 1.0, 0.25), 2, (0.5, 0.25)),
+		(Vec3(0.5, -1.0, 0.25), 3, (0.5, -0.25)),
+		(Vec3(0.5, 0.25, 1.0), 4, (0.5, -0.25)),
+		(Vec3(0.5, 0.25, -1.0), 5, (-0.5, -0.25))
+	];
+	for (direction, face, expected) in cases {
+		let (x, y, w) = project(&faces[face], center + direction * 3.0);
+		assert!((x - expected.0).abs() < 1e-5 && (y - expected.1).abs() < 1e-5, "face {} put {:?} at {:?}", face, direction, (x, y));
+		assert!((w - 3.0).abs() < 1e-5, "face {} w {}", face, w);
+		// and the opposite face has it behind
+		assert!(project(&faces[face ^ 1], center + direction * 3.0).2 < 0.0);
+	}
+}
+
+#[test]
+fn spot_shadow_map_fits_the_cone() {
+	let (position, direction, angle) = (Vec3(1.0, 6.0, -2.0), Vec3(0.3, -1.0, 0.2), 0.4f32);
+	let transform = light::spot_shadow_transform(position, direction, angle);
+	let forward = direction.normalize();
+	let (x, y, w) = project(&transform, position + forward * 5.0);
+	assert!(x.abs() < 1e-5 && y.abs() < 1e-5 && (w - 5.0).abs() < 1e-4, "{:?}", (x, y, w));
+	
+	// anything on the edge of the cone lands on the edge of the map
+	let across = forward.cross(Vec3(1.0, 0.0, 0.0)).normalize();
+	let up = forward.cross(across);
+	for turn in 0..16 {
+		let turn = turn as f32 * std::f32::consts::PI / 8.0;
+		let edge = forward * angle.cos() + (across * turn.cos() + up * turn.sin()) * angle.sin();
+		let (x, y, _) = project(&transform, position + edge * 4.0);
+		assert!(((x * x + y * y).sqrt() - 1.0).abs() < 1e-4, "{:?} at {}", (x, y), turn);
+	}
+}
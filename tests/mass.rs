@@ -0,0 +1,53 @@
+mod common;
+
+use common::{assert_matrices_near, box_mesh, floor, DT};
+use gl_engine::{math_structs::Vec3, object::Object, world::{PhysicsConfig, PhysicsWorld}};
+
+
+fn tetrahedron(offset: Vec3) -> Object {
+	let corners = [Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0), Vec3(0.0, 0.0, 1.0)].map(|corner| corner + offset);
+	Object::new(&corners, &[(0, 1, 3), (0, 2, 1), (0, 3, 2), (1, 2, 3)])
+}
+
+
+#[test]
+fn meshes_are_moved_onto_their_center_of_mass() {
+	let center = Vec3(3.0, 1.0, -2.0);
+	let offset = box_mesh(Vec3(1.0, 0.5, 0.25), center);
+	let centered = box_mesh(Vec3(1.0, 0.5, 0.25), Vec3(0.0, 0.0, 0.0));
+	
+	assert!((offset.transform.get_position() - center).length() < 1e-5, "{:?}", offset.transform.get_position());
+	assert!((offset.volume - 1.0).abs() < 1e-5, "{}", offset.volume);
+	assert_matrices_near(&offset.inverse_inertia, &centered.inverse_inertia, 1e-4);
+	
+	// the mesh is still where it was in the world
+	for (vertex, original) in offset.vertices.iter().zip(centered.vertices.iter()) {
+		assert!((vertex.apply_transform(&offset.transform) - (*original + center)).length() < 1e-5);
+	}
+}
+
+#[test]
+fn inertia_is_about_the_centroid() {
+	// a tetrahedron's centroid is the mean of its corners, and its inertia about it doesn't depend on where it is
+	let near = tetrahedron(Vec3(0.0, 0.0, 0.0));
+	let far = tetrahedron(Vec3(5.0, -3.0, 2.0));
+	assert!((near.transform.get_position() - Vec3(0.25, 0.25, 0.25)).length() < 1e-5, "{:?}", near.transform.get_position());
+	assert!((far.transform.get_position() - Vec3(5.25, -2.75, 2.25)).length() < 1e-4, "{:?}", far.transform.get_position());
+	assert!((near.mass() - 1.0 / 6.0).abs() < 1e-5 && (far.mass() - 1.0 / 6.0).abs() < 1e-5);
+	assert_matrices_near(&near.inverse_inertia, &far.inverse_inertia, 1e-2 * near.inverse_inertia.trace());
+}
+
+#[test]
+fn an_offset_mesh_rests_where_it_is_put() {
+	// with the origin off to the side as its center of mass it would tip over the edge of its own base
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	let block = world.add_body(box_mesh(Vec3(0.5, 0.5, 0.5), Vec3(2.0, 0.5, 0.0)));
+	for _ in 0..120 {
+		world.step(DT);
+	}
+	let block = world.body(block).unwrap();
+	let position = block.transform.get_position();
+	assert!((position - Vec3(2.0, 0.5, 0.0)).length() < 0.02, "moved to {:?}", position);
+	assert!(Vec3(0.0, 1.0, 0.0).apply_rotation(&block.transform).1 > 0.999, "tipped over");
+}
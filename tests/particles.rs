@@ -0,0 +1,154 @@
+mod common;
+
+use common::floor;
+use gl_engine::{math_structs::{Mat4, Vec3}, particles::{Emitter, EmitterShape, ParticleSystem}};
+
+
+fn mesh_emitter(vertices: &[Vec3], indices: &[(u16, u16, u16)]) -> Emitter {
+	let shape = EmitterShape::MeshSurface { vertices: vertices.to_vec().into_boxed_slice(), indices: indices.to_vec().into_boxed_slice() };
+	let mut emitter = Emitter::new(shape, Mat4::identity().translate(Vec3(0.0, 2.0, 0.0)));
+	emitter.rate = 100.0;
+	emitter.outward_speed = 1.0;
+	emitter
+}
+
+// one that only bursts, from a point at the given height
+fn point_emitter(height: f32, velocity: Vec3) -> Emitter {
+	let mut emitter = Emitter::new(EmitterShape::Point, Mat4::identity().translate(Vec3(0.0, height, 0.0)));
+	emitter.velocity = velocity;
+	emitter
+}
+
+
+#[test]
+fn mesh_surfaces_spawn_on_their_triangles() {
+	let mut system = ParticleSystem::new();
+	let square = [Vec3(-1.0, 0.0, -1.0), Vec3(-1.0, 0.0, 1.0), Vec3(1.0, 0.0, -1.0), Vec3(1.0, 0.0, 1.0)];
+	system.add_emitter(mesh_emitter(&square, &[(0, 2, 3), (0, 3, 1)]));
+	system.burst(0, 200);
+	assert_eq!(system.particles.len(), 200);
+	for particle in system.particles.iter() {
+		let Vec3(x, y, z) = particle.position;
+		assert!(x.abs() <= 1.0 && z.abs() <= 1.0 && (y - 2.0).abs() < 1e-6, "{:?}", particle.position);
+		assert!((particle.velocity - Vec3(0.0, 1.0, 0.0)).length() < 1e-5, "{:?}", particle.velocity);
+	}
+	// both halves of the square get some
+	assert!(system.particles.iter().any(|p| p.position.0 > p.position.2) && system.particles.iter().any(|p| p.position.0 < p.position.2));
+}
+
+#[test]
+fn meshes_without_area_spawn_nothing() {
+	let mut system = ParticleSystem::new();
+	system.add_emitter(mesh_emitter(&[], &[]));
+	system.add_emitter(mesh_emitter(&[Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), Vec3(2.0, 0.0, 0.0)], &[(0, 1, 2)]));
+	system.burst(0, 10);
+	system.burst(1, 10);
+	system.update(0.5, Vec3(0.0, -9.8, 0.0), &[]);
+	assert!(system.particles.is_empty());
+}
+
+#[test]
+fn particles_die_when_their_lifetime_is_up() {
+	let mut system = ParticleSystem::new();
+	let mut emitter = point_emitter(0.0, Vec3(0.0, 0.0, 0.0));
+	emitter.lifetime = (0.5, 1.5);
+	system.add_emitter(emitter);
+	system.burst(0, 100);
+	assert!(system.particles.iter().all(|p| (0.5..=1.5).contains(&p.lifetime)));
+	
+	let mut alive = system.particles.len();
+	for step in 0..16 {
+		system.update(0.1, Vec3(0.0, 0.0, 0.0), &[]);
+		assert!(system.particles.len() <= alive);
+		assert!(system.particles.iter().all(|p| p.age < p.lifetime), "a particle outlived its lifetime");
+		alive = system.particles.len();
+		// a second in, the ones that had less are gone and the ones with more are still there
+		if step == 9 { assert!(alive > 0 && alive < 100, "{} left after a second", alive); }
+	}
+	assert!(system.particles.is_empty());
+}
+
+#[test]
+fn gravity_and_drag_change_the_velocity_as_integrated() {
+	let gravity = Vec3(0.0, -10.0, 0.0);
+	let dt = 0.01;
+	
+	// without drag the velocity goes up evenly and each step moves by the velocity at its end
+	let mut falling = ParticleSystem::new();
+	falling.add_emitter(point_emitter(10.0, Vec3(2.0, 0.0, 0.0)));
+	falling.burst(0, 1);
+	for _ in 0..100 {
+		falling.update(dt, gravity, &[]);
+	}
+	let particle = falling.particles[0];
+	assert!((particle.velocity - Vec3(2.0, -10.0, 0.0)).length() < 1e-3, "{:?}", particle.velocity);
+	let drop = 10.0 * dt * dt * (100.0 * 101.0 / 2.0);
+	assert!((particle.position - Vec3(2.0, 10.0 - drop, 0.0)).length() < 1e-3, "{:?}", particle.position);
+	
+	// drag alone takes away the same fraction every second
+	let mut slowing = ParticleSystem::new();
+	slowing.drag = 0.5;
+	slowing.add_emitter(point_emitter(0.0, Vec3(4.0, 0.0, 0.0)));
+	slowing.burst(0, 1);
+	for _ in 0..100 {
+		slowing.update(dt, Vec3(0.0, 0.0, 0.0), &[]);
+	}
+	let speed = slowing.particles[0].velocity.0;
+	assert!((speed - 4.0 * (-0.5f32).exp()).abs() < 1e-3, "slowed to {}", speed);
+}
+
+#[test]
+fn emitting_stops_at_max_particles() {
+	let mut system = ParticleSystem::new();
+	system.max_particles = 50;
+	let mut emitter = point_emitter(0.0, Vec3(0.0, 0.0, 0.0));
+	emitter.rate = 1000.0;
+	emitter.lifetime = (10.0, 10.0);
+	system.add_emitter(emitter);
+	system.update(0.5, Vec3(0.0, 0.0, 0.0), &[]);
+	assert_eq!(system.particles.len(), 50);
+	system.burst(0, 10);
+	system.update(0.5, Vec3(0.0, 0.0, 0.0), &[]);
+	assert_eq!(system.particles.len(), 50);
+}
+
+#[test]
+fn colliding_particles_bounce_off_bodies() {
+	let objects = [floor()];
+	let mut system = ParticleSystem::new();
+	(system.collide, system.restitution, system.friction) = (true, 0.5, 0.2);
+	system.add_emitter(point_emitter(1.0, Vec3(1.0, -5.0, 0.0)));
+	system.burst(0, 1);
+	
+	let mut bounced = None;
+	for step in 0..30 {
+		system.update(0.01, Vec3(0.0, 0.0, 0.0), &objects);
+		let particle = system.particles[0];
+		assert!(particle.position.1 > 0.0, "went through the floor to {:?}", particle.position);
+		if bounced.is_none() && particle.velocity.1 > 0.0 { bounced = Some((step, particle.velocity)); }
+	}
+	// it got there after 0.2 seconds, and came back up at half the speed, sliding a little slower
+	let (step, velocity) = bounced.expect("never bounced");
+	assert_eq!(step, 19);
+	assert!((velocity - Vec3(0.8, 2.5, 0.0)).length() < 1e-4, "{:?}", velocity);
+	
+	// without collide it carries straight on
+	let mut passing = ParticleSystem::new();
+	passing.add_emitter(point_emitter(1.0, Vec3(1.0, -5.0, 0.0)));
+	passing.burst(0, 1);
+	for _ in 0..30 {
+		passing.update(0.01, Vec3(0.0, 0.0, 0.0), &objects);
+	}
+	assert!(passing.particles[0].position.1 < 0.0);
+}
+
+#[test]
+fn bursts_from_unknown_emitters_spawn_nothing() {
+	let mut system = ParticleSystem::new();
+	system.burst(0, 10);
+	system.add_emitter(point_emitter(0.0, Vec3(0.0, 0.0, 0.0)));
+	system.burst(3, 10);
+	assert!(system.particles.is_empty());
+	system.burst(0, 10);
+	assert_eq!(system.particles.len(), 10);
+}
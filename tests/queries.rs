@@ -0,0 +1,131 @@
+mod common;
+
+use common::{body, floor};
+use gl_engine::{math_structs::{Mat4, Vec3}, query::{self, QueryFilter}, shape::Shape, world::{PhysicsConfig, PhysicsWorld}};
+
+
+// spheres along x, added far ones first so the order they're visited in isn't the order they were added
+fn row_of_spheres() -> PhysicsWorld {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	for x in [9.0, 3.0, 6.0] {
+		world.add_body(body(Shape::Sphere { radius: 0.5 }, Vec3(x, 0.0, 0.0)));
+	}
+	world
+}
+
+
+#[test]
+fn ray_cast_all_hits_everything_in_line_nearest_first() {
+	let mut world = row_of_spheres();
+	let hits = world.ray_cast_all(Vec3(0.0, 0.0, 0.0), Vec3(2.0, 0.0, 0.0), 20.0, &QueryFilter::default());
+	assert_eq!(hits.iter().map(|hit| hit.body).collect::<Vec<usize>>(), vec![1, 2, 0]);
+	for (hit, distance) in hits.iter().zip([2.5, 5.5, 8.5]) {
+		assert!((hit.distance - distance).abs() < 1e-3 && hit.normal.0 < -0.99, "{:?}", hit);
+	}
+	
+	// cut short, filtered, and past a trigger
+	assert_eq!(world.ray_cast_all(Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), 6.0, &QueryFilter::default()).len(), 2);
+	assert_eq!(world.ray_cast_all(Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), 20.0, &QueryFilter { exclude: Some(2), ..QueryFilter::default() }).len(), 2);
+	world.body_mut(1).unwrap().is_trigger = true;
+	assert_eq!(world.ray_cast(Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), 20.0, &QueryFilter::default()).unwrap().body, 2);
+	assert_eq!(world.ray_cast(Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), 20.0, &QueryFilter { include_triggers: true, ..QueryFilter::default() }).unwrap().body, 1);
+}
+
+#[test]
+fn shape_casts_stop_at_the_nearest_body() {
+	let world = row_of_spheres();
+	let hit = world.sphere_cast(Vec3(0.0, 0.0, 0.0), 0.25, Vec3(1.0, 0.0, 0.0), 20.0, &QueryFilter::default()).unwrap();
+	assert_eq!(hit.body, 1);
+	assert!((hit.distance - 2.25).abs() < 1e-3, "{:?}", hit);
+	
+	// a box passing just over the spheres, then low enough to clip the first
+	let transform = Mat4::identity().translate(Vec3(0.0, 1.1, 0.0));
+	let hit = world.box_cast(&transform, Vec3(0.5, 0.5, 0.5), Vec3(1.0, 0.0, 0.0), 20.0, &QueryFilter::default());
+	assert!(hit.is_none(), "{:?}", hit);
+	let transform = Mat4::identity().translate(Vec3(0.0, 0.9, 0.0));
+	let hit = world.box_cast(&transform, Vec3(0.5, 0.5, 0.5), Vec3(1.0, 0.0, 0.0), 20.0, &QueryFilter::default()).unwrap();
+	assert_eq!(hit.body, 1);
+	
+	// the same as casting against each body on its own and keeping the nearest
+	for (i, &start) in [Vec3(0.0, 0.3, 0.2), Vec3(4.5, -0.4, 0.0), Vec3(12.0, 0.2, 0.1)].iter().enumerate() {
+		let direction = match i { 2 => Vec3(-1.0, 0.0, 0.0), _ => Vec3(1.0, 0.0, 0.0) };
+		let hit = world.sphere_cast(start, 0.3, direction, 20.0, &QueryFilter::default());
+		let nearest = (0..3).filter_map(|body| {
+			let mut alone = PhysicsWorld::new(PhysicsConfig::default());
+			alone.add_body(world.body(body).unwrap().clone());
+			alone.sphere_cast(start, 0.3, direction, 20.0, &QueryFilter::default()).map(|hit| (hit.distance, body))
+		}).min_by(|a, b| a.0.total_cmp(&b.0));
+		assert_eq!(hit.map(|hit| (hit.distance, hit.body)), nearest, "from {:?}", start);
+	}
+}
+
+#[test]
+fn zero_directions_hit_nothing() {
+	let world = row_of_spheres();
+	let nowhere = Vec3(0.0, 0.0, 0.0);
+	assert!(world.ray_cast(Vec3(3.0, 0.0, 0.0), nowhere, 20.0, &QueryFilter::default()).is_none());
+	assert!(world.ray_cast_all(Vec3(3.0, 0.0, 0.0), nowhere, 20.0, &QueryFilter::default()).is_empty());
+	assert!(world.sphere_cast(Vec3(0.0, 0.0, 0.0), 0.25, nowhere, 20.0, &QueryFilter::default()).is_none());
+	assert!(world.shape_cast(&Shape::Box { half_extents: Vec3(0.5, 0.5, 0.5) }, &Mat4::identity(), nowhere, 20.0, &QueryFilter::default()).is_none());
+}
+
+#[test]
+fn unlimited_casts_still_hit() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(body(Shape::Sphere { radius: 0.5 }, Vec3(40.0, 0.0, 0.0)));
+	world.add_body(body(Shape::Box { half_extents: Vec3(1.0, 1.0, 1.0) }, Vec3(0.0, 0.0, -30.0)));
+	let filter = QueryFilter::default();
+	
+	let hit = world.ray_cast(Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), f32::INFINITY, &filter).unwrap();
+	assert!(hit.body == 0 && (hit.distance - 39.5).abs() < 1e-3, "{:?}", hit);
+	let hit = world.ray_cast(Vec3(0.0, 0.0, 0.0), Vec3(0.0, 0.0, -1.0), f32::INFINITY, &filter).unwrap();
+	assert!(hit.body == 1 && (hit.distance - 29.0).abs() < 1e-3, "{:?}", hit);
+	assert_eq!(world.ray_cast_all(Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), f32::INFINITY, &filter).len(), 1);
+	
+	let hit = world.sphere_cast(Vec3(0.0, 0.0, 0.0), 0.25, Vec3(0.0, 0.0, -1.0), f32::INFINITY, &filter).unwrap();
+	assert!(hit.body == 1 && (hit.distance - 28.75).abs() < 1e-3, "{:?}", hit);
+	assert!(world.ray_cast(Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0), f32::INFINITY, &filter).is_none());
+}
+
+#[test]
+fn world_queries_find_the_same_as_looking_at_every_body() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	for i in 0..60 {
+		let shape = match i % 2 { 0 => Shape::Sphere { radius: 0.4 }, _ => Shape::Box { half_extents: Vec3(0.3, 0.5, 0.2) } };
+		world.add_body(body(shape, Vec3((i % 6) as f32 * 1.7 - 4.0, 0.5 + (i / 20) as f32 * 1.3, (i / 6 % 4) as f32 * 1.9 - 3.0)));
+	}
+	let filter = QueryFilter::default();
+	
+	for i in 0..40 {
+		let angle = i as f32 * 0.61;
+		let origin = Vec3(8.0 * angle.cos(), 1.0 + (i % 3) as f32, 8.0 * angle.sin());
+		let direction = Vec3(-angle.cos(), -0.1 * (i % 4) as f32, -angle.sin() + 0.05 * (i % 5) as f32);
+		assert_eq!(world.ray_cast(origin, direction, 20.0, &filter), query::ray_cast(world.bodies(), origin, direction, 20.0, &filter));
+		assert_eq!(world.ray_cast_all(origin, direction, 20.0, &filter), query::ray_cast_all(world.bodies(), origin, direction, 20.0, &filter));
+		let transform = Mat4::identity().translate(origin);
+		assert_eq!(world.sphere_cast(origin, 0.3, direction, 20.0, &filter), query::shape_cast(world.bodies(), &Shape::Sphere { radius: 0.3 }, &transform, direction, 20.0, &filter));
+		assert_eq!(world.point_overlaps(origin * 0.3, &filter), query::point_overlaps(world.bodies(), origin * 0.3, &filter));
+	}
+}
+
+#[test]
+fn world_queries_see_bodies_that_were_moved_or_added() {
+	let mut world = row_of_spheres();
+	let filter = QueryFilter::default();
+	assert_eq!(world.ray_cast(Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), 20.0, &filter).unwrap().body, 1);
+	
+	world.body_mut(1).unwrap().transform = Mat4::identity().translate(Vec3(0.0, 5.0, 0.0));
+	assert_eq!(world.ray_cast(Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), 20.0, &filter).unwrap().body, 2);
+	let added = world.add_body(body(Shape::Sphere { radius: 0.5 }, Vec3(1.0, 0.0, 0.0)));
+	assert_eq!(world.ray_cast(Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), 20.0, &filter).unwrap().body, added);
+	
+	// and bodies that moved into the ray's way over a step
+	world.remove_body(added);
+	let moving = world.body_mut(1).unwrap();
+	moving.transform = Mat4::identity().translate(Vec3(1.5, 0.0, 5.0));
+	moving.velocity = Vec3(0.0, 0.0, -300.0);
+	assert_eq!(world.ray_cast(Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), 20.0, &filter).unwrap().body, 2);
+	world.step(1.0 / 60.0);
+	assert_eq!(world.ray_cast(Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), 20.0, &filter).unwrap().body, 1);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::path::Path;
+
+use common::assert_near;
+use gl_engine::{integrator::Integrator, math_structs::Vec3, scene::SceneDescription, shape::Shape};
+
+
+#[test]
+fn a_scene_file_sets_the_config_bodies_and_cloth() {
+	let scene = SceneDescription::parse("
+		gravity 0 -5 0   # the moon, nearly
+		solver_iterations 4
+		integrator verlet
+		body box 1 0.5 2
+		position 1 2 3
+		rotation 0 1.5707964 0
+		velocity 0 0 -1
+		density 2
+		friction 0.9
+		body capsule 0.5 0.25
+		static
+		trigger
+		cloth 2 2 2 2
+		mass 0.9
+		pin 0 8
+	", Path::new("")).unwrap();
+	
+	assert_eq!(scene.config.gravity, Vec3(0.0, -5.0, 0.0));
+	assert_eq!(scene.config.solver_iterations, 4);
+	assert_eq!(scene.config.integrator, Integrator::VelocityVerlet);
+	assert_eq!(scene.bodies.len(), 2);
+	
+	let block = &scene.bodies[0];
+	assert!(matches!(block.shape, Shape::Box { half_extents: Vec3(1.0, 0.5, 2.0) }));
+	assert_eq!(block.transform.get_position(), Vec3(1.0, 2.0, 3.0));
+	// turned a quarter about y, the box's x axis points along -z
+	let x_axis = block.transform.get_rotation().mult_vec3(Vec3(1.0, 0.0, 0.0));
+	assert_near(x_axis.2, -1.0, 1e-6, "x axis z");
+	assert_eq!(block.velocity, Vec3(0.0, 0.0, -1.0));
+	assert_near(1.0 / block.inverse_mass, 2.0 * 8.0, 1e-4, "box mass");
+	assert_eq!(block.friction, 0.9);
+	
+	let post = &scene.bodies[1];
+	assert_eq!(post.inverse_mass, 0.0);
+	assert!(post.is_trigger);
+	
+	let cloth = &scene.soft_bodies[0];
+	assert_eq!(cloth.positions.len(), 9);
+	assert_eq!(cloth.inverse_masses.iter().filter(|&&m| m == 0.0).count(), 2);
+	assert_near(cloth.inverse_masses[4], 9.0 / 0.9, 1e-4, "cloth particle inverse mass");
+}
+
+#[test]
+fn a_bad_line_is_refused_with_its_number() {
+	let refused = |text: &str| SceneDescription::parse(text, Path::new("")).err().unwrap();
+	assert_eq!(refused("body sphere 1\n\nposition 1 2"), "line 3: expected 3 numbers, got 2");
+	assert_eq!(refused("position 1 2 3"), "line 1: position before any body or cloth");
+	assert_eq!(refused("body cone 1"), "line 1: unknown shape cone");
+	assert_eq!(refused("body sphere 1\nmass 2"), "line 2: bodies have no mass");
+	assert_eq!(refused("integrator leapfrog"), "line 1: unknown integrator leapfrog");
+	assert_eq!(refused("cloth 1 1 2 2\npin 9"), "at the end: can't pin vertex 9 of a cloth with 9");
+}
@@ -0,0 +1,97 @@
+use gl_engine::{math_structs::Vec3, render::{split_distances, Camera, ShadowCascade}};
+
+
+static RESOLUTION: u32 = 1024;
+static F: f32 = 1.303; // 75 degrees up and down
+static ASPECT_RATIO: f32 = 0.75;
+
+
+fn light() -> Vec3 {
+	Vec3(0.4, 2.0, 0.3).normalize()
+}
+
+// the corners of the view between near and far, in world space
+fn slice_corners(camera: &Camera, near: f32, far: f32) -> Vec<Vec3> {
+	let m = camera.get_transform().0;
+	let axes = [Vec3(m[0][0], m[1][0], m[2][0]), Vec3(m[0][1], m[1][1], m[2][1]), Vec3(m[0][2], m[1][2], m[2][2])];
+	[near, far].iter().flat_map(|&depth| [(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)].map(|(x, y)| {
+		camera.position + axes[0] * (x * depth / (F * ASPECT_RATIO)) + axes[1] * (y * depth / F) + axes[2] * depth
+	})).collect()
+}
+
+fn cameras() -> Vec<Camera> {
+	(0..24).map(|i| {
+		let i = i as f32;
+		Camera { position: Vec3(7.0 * (1.3 * i).sin(), 2.0 + i, 40.0 * (0.7 * i).cos()), horizontal_angle: 0.9 * i, vertical_angle: 0.6 * (2.1 * i).sin() }
+	}).collect()
+}
+
+
+#[test]
+fn splits_run_from_near_to_far() {
+	let even = split_distances(0.1, 40.0, 4, 0.0);
+	for (split, expected) in even.iter().zip([10.075, 20.05, 30.025, 40.0]) {
+		assert!((split - expected).abs() < 1e-3, "{:?}", even);
+	}
+	let geometric = split_distances(0.1, 40.0, 4, 1.0);
+	for pair in geometric.windows(2) {
+		assert!((pair[1] / pair[0] - 400f32.powf(0.25)).abs() < 1e-3, "{:?}", geometric);
+	}
+	for lambda in [0.0, 0.3, 0.6, 1.0] {
+		let splits = split_distances(0.1, 40.0, 3, lambda);
+		assert!(splits[0] > 0.1 && splits.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", splits);
+		assert!((splits[2] - 40.0).abs() < 1e-3, "{:?}", splits);
+	}
+}
+
+#[test]
+fn cascades_cover_their_slice() {
+	let splits = split_distances(0.01, 60.0, 4, 0.6);
+	for camera in cameras() {
+		for (&near, &far) in std::iter::once(&0.01).chain(splits.iter()).zip(splits.iter()) {
+			let cascade = ShadowCascade::fit(&camera, light(), near, far, F, ASPECT_RATIO, RESOLUTION);
+			assert_eq!(cascade.split_distance, far);
+			for corner in slice_corners(&camera, near, far) {
+				let Vec3(x, y, z) = corner.apply_transform(&cascade.transform);
+				assert!(x.abs() <= 1.0 && y.abs() <= 1.0 && z.abs() <= 1.0001, "{:?} outside the cascade from {} to {} at {:?}", corner, near, far, Vec3(x, y, z));
+			}
+		}
+	}
+}
+
+#[test]
+fn depth_grows_away_from_the_light() {
+	let camera = &cameras()[3];
+	let cascade = ShadowCascade::fit(camera, light(), 1.0, 10.0, F, ASPECT_RATIO, RESOLUTION);
+	let point = camera.position + Vec3(1.0, -2.0, 3.0);
+	let (near, far) = ((point + light()).apply_transform(&cascade.transform), (point - light()).apply_transform(&cascade.transform));
+	let here = point.apply_transform(&cascade.transform);
+	assert!(near.2 < here.2 && here.2 < far.2, "{} {} {}", near.2, here.2, far.2);
+	// and straight along the light doesn't move across the map
+	assert!((near.0 - here.0).abs() < 1e-4 && (near.1 - here.1).abs() < 1e-4);
+}
+
+#[test]
+fn cascades_hold_still_as_the_camera_moves() {
+	// turning in place keeps the size, and moving shifts the map by whole texels
+	let mut camera = Camera { position: Vec3(3.0, 4.0, -9.0), horizontal_angle: 0.3, vertical_angle: -0.2 };
+	let first = ShadowCascade::fit(&camera, light(), 5.0, 15.0, F, ASPECT_RATIO, RESOLUTION);
+	let texel = 2.0 / RESOLUTION as f32;
+	let marker = Vec3(1.0, 0.0, 2.0);
+	let start = marker.apply_transform(&first.transform);
+	
+	for i in 1..40 {
+		let i = i as f32;
+		camera.horizontal_angle += 0.37;
+		camera.vertical_angle = -0.2 + 0.3 * (0.5 * i).sin();
+		camera.position += Vec3(0.013 * i, 0.007, -0.021 * i);
+		let cascade = ShadowCascade::fit(&camera, light(), 5.0, 15.0, F, ASPECT_RATIO, RESOLUTION);
+		assert!((cascade.radius - first.radius).abs() < 1e-4 * first.radius, "radius {} became {}", first.radius, cascade.radius);
+		
+		let moved = marker.apply_transform(&cascade.transform);
+		for (a, b) in [(start.0, moved.0), (start.1, moved.1)] {
+			let texels = (b - a) / texel;
+			assert!((texels - texels.round()).abs() < 0.02, "moved {} texels", texels);
+		}
+	}
+}
@@ -0,0 +1,71 @@
+use std::{path::PathBuf, process::Command};
+
+
+static BOUNCE: &str = "
+# a ball dropped onto a floor next to a hanging sheet
+tick_rate 120
+integrator rk4
+
+body floor 5
+body sphere 0.5
+position 0 2 0
+restitution 0
+
+cloth 1 1 4 4
+position 3 2 0
+pin 0 4
+";
+
+fn scene_file(name: &str, text: &str) -> PathBuf {
+	let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+	std::fs::write(&path, text).unwrap();
+	path
+}
+
+// the last position written for the body
+fn last_position(output: &str, body: usize) -> [f32; 3] {
+	let key = format!("\"record\":\"body\",\"body\":{},\"position\":[", body);
+	let line = output.lines().rev().find(|line| line.contains(&key)).unwrap();
+	let start = line.find(&key).unwrap() + key.len();
+	let numbers: Vec<f32> = line[start..].split(']').next().unwrap().split(',').map(|x| x.parse().unwrap()).collect();
+	[numbers[0], numbers[1], numbers[2]]
+}
+
+
+#[test]
+fn a_scene_file_runs_end_to_end() {
+	let path = scene_file("bounce.scene", BOUNCE);
+	let run = Command::new(env!("CARGO_BIN_EXE_simulate")).args(["--scene", path.to_str().unwrap(), "--ticks", "240"]).output().unwrap();
+	let (stdout, stderr) = (String::from_utf8(run.stdout).unwrap(), String::from_utf8(run.stderr).unwrap());
+	assert!(run.status.success(), "{}", stderr);
+	
+	assert!(stderr.contains("ticks: 240 at 120 Hz, 2.000 s simulated"), "{}", stderr);
+	assert!(stderr.contains("bodies: 2, soft bodies: 1"), "{}", stderr);
+	assert_eq!(stdout.lines().filter(|line| line.contains("\"record\":\"body\"")).count(), 2 * 240);
+	assert!(stdout.contains("\"record\":\"begin\",\"bodies\":[0,1]"));
+	
+	// the ball has landed on the floor and stayed there
+	let [x, y, z] = last_position(&stdout, 1);
+	assert!(x.abs() < 1e-3 && z.abs() < 1e-3);
+	assert!((y - 0.5).abs() < 0.02, "the ball is at {}", y);
+}
+
+#[test]
+fn command_line_options_override_the_scene_file() {
+	let path = scene_file("override.scene", BOUNCE);
+	let run = Command::new(env!("CARGO_BIN_EXE_simulate")).args(["--scene", path.to_str().unwrap(), "--ticks", "30", "--tick-rate", "30", "--no-soft-bodies"]).output().unwrap();
+	let stderr = String::from_utf8(run.stderr).unwrap();
+	assert!(run.status.success(), "{}", stderr);
+	assert!(stderr.contains("ticks: 30 at 30 Hz, 1.000 s simulated"), "{}", stderr);
+	assert!(stderr.contains("bodies: 2, soft bodies: 0"), "{}", stderr);
+}
+
+#[test]
+fn a_bad_scene_file_is_refused_with_its_line() {
+	let path = scene_file("bad.scene", "body sphere 0.5\nposition 0 one 0\n");
+	let run = Command::new(env!("CARGO_BIN_EXE_simulate")).args(["--scene", path.to_str().unwrap()]).output().unwrap();
+	let stderr = String::from_utf8(run.stderr).unwrap();
+	assert_eq!(run.status.code(), Some(1));
+	assert!(stderr.contains("bad.scene: line 2: one isn't a number"), "{}", stderr);
+	assert!(run.stdout.is_empty());
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use common::{body, floor, DT};
+use gl_engine::{math_structs::{Mat4, Vec3}, shape::Shape, soft_body::SoftBody, world::{PhysicsConfig, PhysicsWorld}};
+
+
+// A particle of 1 kg hanging a meter under two pinned ones by springs of the given stiffness.
+// Returns how far each spring is stretched once it has settled.
+fn hanging_stretch(stiffness: f32) -> f32 {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	let vertices = [Vec3(-0.5, 0.0, 0.0), Vec3(0.5, 0.0, 0.0), Vec3(0.0, -1.0, 0.0)];
+	let mut triangle = SoftBody::new(&vertices, &[(0, 1, 2)], &Mat4::identity(), 3.0, stiffness, 0.2 * stiffness.sqrt()).unwrap();
+	triangle.pin(0);
+	triangle.pin(1);
+	world.add_soft_body(triangle);
+	for _ in 0..300 {
+		world.step(DT);
+	}
+	
+	let positions = &world.soft_bodies()[0].positions;
+	let stretches = [0, 1].map(|pin| (positions[2] - positions[pin]).length() - 1.25f32.sqrt());
+	assert!((stretches[0] - stretches[1]).abs() < 1e-4, "{:?}", stretches);
+	assert!(world.soft_bodies()[0].velocities[2].length() < 1e-3, "still moving");
+	stretches[0]
+}
+
+
+#[test]
+fn springs_hold_their_load_at_the_stretch_their_stiffness_gives() {
+	// the two springs share the weight along their slant, so each stretches by m g L / 2 k
+	for stiffness in [500.0, 2000.0, 8000.0] {
+		let expected = 9.8 * 1.25f32.sqrt() / (2.0 * stiffness);
+		let stretch = hanging_stretch(stiffness);
+		assert!((stretch - expected).abs() < 0.05 * expected, "stiffness {} stretched {} instead of {}", stiffness, stretch, expected);
+	}
+}
+
+#[test]
+fn pinned_particles_stay_put() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	let (vertices, indices) = SoftBody::cloth_mesh(2.0, 2.0, 4, 4);
+	let mut cloth = SoftBody::new(&vertices, &indices, &Mat4::identity().translate(Vec3(0.0, 3.0, 0.0)), 1.0, 2000.0, 5.0).unwrap();
+	cloth.pin(0);
+	cloth.pin(4);
+	let pinned = (cloth.positions[0], cloth.positions[4]);
+	world.add_soft_body(cloth);
+	for _ in 0..120 {
+		world.step(DT);
+	}
+	let cloth = &world.soft_bodies()[0];
+	assert_eq!((cloth.positions[0], cloth.positions[4]), pinned);
+	assert!(cloth.positions[24].1 < 1.5, "the far edge only fell to {:?}", cloth.positions[24]);
+	assert!(cloth.positions.iter().all(|p| p.0.is_finite() && p.1.is_finite() && p.2.is_finite()));
+}
+
+#[test]
+fn soft_bodies_without_mass_or_stiffness_or_with_stray_triangles_are_refused() {
+	let (vertices, indices) = SoftBody::cloth_mesh(1.0, 1.0, 2, 2);
+	let new = |mass: f32, stiffness: f32, indices: &[(u16, u16, u16)]| SoftBody::new(&vertices, indices, &Mat4::identity(), mass, stiffness, 1.0);
+	assert!(new(1.0, 1000.0, &indices).is_ok());
+	assert!(new(0.0, 1000.0, &indices).unwrap_err().contains("positive mass"));
+	assert!(new(f32::NAN, 1000.0, &indices).unwrap_err().contains("positive mass"));
+	assert!(new(1.0, -5.0, &indices).unwrap_err().contains("positive stiffness"));
+	assert!(new(1.0, 1000.0, &[(0, 1, 9)]).unwrap_err().contains("past the 9 vertices"));
+}
+
+#[test]
+fn cloth_dropped_on_a_box_comes_to_rest_on_top() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	let mut block = body(Shape::Box { half_extents: Vec3(0.5, 0.5, 0.5) }, Vec3(0.0, 0.5, 0.0));
+	block.set_static();
+	world.add_body(block);
+	let (vertices, indices) = SoftBody::cloth_mesh(2.0, 2.0, 10, 10);
+	world.add_soft_body(SoftBody::new(&vertices, &indices, &Mat4::identity().translate(Vec3(0.0, 2.0, 0.0)), 1.0, 2000.0, 5.0).unwrap());
+	
+	for step in 0..240 {
+		world.step(DT);
+		for p in world.soft_bodies()[0].positions.iter() {
+			let inside = p.0.abs() < 0.45 && p.2.abs() < 0.45 && p.1 < 0.95;
+			assert!(!inside && p.1 > -0.01, "a particle went through at step {}: {:?}", step, p);
+		}
+	}
+	let cloth = &world.soft_bodies()[0];
+	// the edges hang down the sides towards the floor
+	assert!(cloth.positions[0].1 < 0.8, "corner only hangs to {:?}", cloth.positions[0]);
+	// the part on the lid has settled, the loose corners can still sway a little
+	let on_lid = cloth.positions.iter().zip(cloth.velocities.iter()).filter(|(p, _)| p.0.abs() < 0.45 && p.2.abs() < 0.45).collect::<Vec<_>>();
+	assert!(on_lid.len() >= 16, "only {} particles stayed on the lid", on_lid.len());
+	for (p, v) in on_lid {
+		assert!(v.length() < 0.01, "still moving at {:?} on the lid at {:?}", v, p);
+		assert!((p.1 - 1.0 - cloth.particle_radius).abs() < 0.03, "{:?} isn't lying on the lid", p);
+	}
+}
+
+#[test]
+fn rigid_bodies_bounce_off_a_net() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	let ball = world.add_body(body(Shape::Sphere { radius: 0.3 }, Vec3(0.0, 4.0, 0.0)));
+	// a sheet held by its whole rim, like a trampoline
+	let (vertices, indices) = SoftBody::cloth_mesh(2.0, 2.0, 10, 10);
+	let mut net = SoftBody::new(&vertices, &indices, &Mat4::identity().translate(Vec3(0.0, 2.0, 0.0)), 1.0, 4000.0, 2.0).unwrap();
+	for j in 0..=10 {
+		for i in 0..=10 {
+			if i == 0 || i == 10 || j == 0 || j == 10 { net.pin(j * 11 + i); }
+		}
+	}
+	world.add_soft_body(net);
+	
+	let (mut lowest, mut pushed_back) = (f32::INFINITY, false);
+	for _ in 0..180 {
+		world.step(DT);
+		let ball = world.body(ball).unwrap();
+		lowest = lowest.min(ball.transform.get_position().1);
+		// it was falling at over 6 m/s when it got there
+		pushed_back |= ball.transform.get_position().1 < 2.5 && ball.velocity.1 > 0.5;
+	}
+	assert!(pushed_back, "the net never threw the ball back up");
+	assert!(lowest > 1.0, "sagged through to {}", lowest);
+	let position = world.body(ball).unwrap().transform.get_position();
+	assert!(position.1 > 1.0 && position.1 < 2.5, "ended up at {:?} rather than in the net", position);
+}
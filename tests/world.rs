@@ -0,0 +1,65 @@
+mod common;
+
+use common::{body, floor, DT};
+use gl_engine::{joint::Joint, math_structs::Vec3, shape::Shape, world::{PhysicsConfig, PhysicsWorld}};
+
+
+#[test]
+fn advance_runs_whole_steps_and_carries_the_rest() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(body(Shape::Sphere { radius: 0.5 }, Vec3(0.0, 5.0, 0.0)));
+	assert_eq!(world.advance(0.045), 2);
+	assert_eq!(world.advance(0.01), 1);
+	assert_eq!(world.step_count(), 3);
+	assert!((world.time() - 3.0 / 60.0).abs() < 1e-6);
+}
+
+#[test]
+fn a_long_stall_runs_the_most_steps_allowed_and_drops_the_rest() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(body(Shape::Sphere { radius: 0.5 }, Vec3(0.0, 5.0, 0.0)));
+	assert_eq!(world.advance(1.0), PhysicsConfig::default().max_steps_per_advance);
+	// the next frame runs its own step and nothing to catch up
+	assert_eq!(world.advance(DT), 1);
+	assert_eq!(world.advance(0.0), 0);
+	assert_eq!(world.step_count(), PhysicsConfig::default().max_steps_per_advance as u64 + 1);
+}
+
+#[test]
+fn a_stack_comes_to_rest_on_the_floor() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	let boxes = (0..3).map(|i| world.add_body(body(Shape::Box { half_extents: Vec3(0.5, 0.5, 0.5) }, Vec3(0.0, 0.5 + 1.05 * i as f32, 0.0)))).collect::<Vec<usize>>();
+	for _ in 0..240 {
+		world.step(DT);
+	}
+	for (i, &index) in boxes.iter().enumerate() {
+		let cube = world.body(index).unwrap();
+		assert!((cube.transform.get_position() - Vec3(0.0, 0.5 + i as f32, 0.0)).length() < 0.02, "box {} at {:?}", i, cube.transform.get_position());
+		assert!(cube.sleeping, "box {} is still awake", i);
+	}
+	assert!(world.diagnostics().max_penetration < 0.01);
+}
+
+#[test]
+fn removing_a_body_shifts_the_ones_after_it() {
+	let mut world = PhysicsWorld::new(PhysicsConfig::default());
+	world.add_body(floor());
+	world.add_body(body(Shape::Sphere { radius: 0.5 }, Vec3(-3.0, 0.5, 0.0)));
+	let hanging = world.add_body(body(Shape::Sphere { radius: 0.25 }, Vec3(3.0, 3.0, 0.0)));
+	world.add_joint(Joint::ball_socket(world.bodies(), hanging, None, Vec3(3.0, 4.0, 0.0)));
+	world.step(DT);
+	
+	let removed = world.remove_body(1);
+	assert_eq!(removed.transform.get_position().0, -3.0);
+	assert_eq!(world.bodies().len(), 2);
+	assert!(world.contacts_of(1).all(|((a, b), _)| a < 2 && b < 2));
+	
+	// the joint moved down with its body and still holds it
+	let (_, joint) = world.joints().iter().next().unwrap();
+	assert_eq!(joint.a, 1);
+	for _ in 0..60 {
+		world.step(DT);
+	}
+	assert!((world.body(1).unwrap().transform.get_position() - Vec3(3.0, 3.0, 0.0)).length() < 0.05);
+}